@@ -0,0 +1,191 @@
+//! Generates the block registry from the vanilla `blocks.json` data report, so that the state IDs
+//! match the ones of the client, and the item registry from the `registries.json` report. Both
+//! are committed in `fixtures/reports`.
+//!
+//! Vanilla numbers the states of a block from its first state ID, going through the values of
+//! its properties in the order of their names, the last property changing the fastest. The
+//! generated code only keeps the properties, the first and the default state of each block, and
+//! the build fails if the report does not follow that numbering.
+
+use std::collections::BTreeMap;
+use std::env;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::Deserialize;
+
+/// See `fixtures/reports/README.md`.
+const REPORT: &str = "fixtures/reports/blocks.json";
+const REGISTRIES_REPORT: &str = "fixtures/reports/registries.json";
+
+#[derive(Deserialize)]
+struct ReportRegistry {
+    entries: BTreeMap<String, ReportEntry>,
+}
+
+#[derive(Deserialize)]
+struct ReportEntry {
+    protocol_id: usize,
+}
+
+#[derive(Deserialize)]
+struct ReportBlock {
+    #[serde(default)]
+    properties: BTreeMap<String, Vec<String>>,
+    states: Vec<ReportState>,
+}
+
+#[derive(Deserialize)]
+struct ReportState {
+    id: u32,
+    #[serde(default)]
+    default: bool,
+    #[serde(default)]
+    properties: BTreeMap<String, String>,
+}
+
+struct Block {
+    name: String,
+    properties: Vec<(String, Vec<String>)>,
+    min_state: u32,
+    default_state: u32,
+}
+
+/// The state ID of some property values, from the first state of the block.
+fn state_offset(properties: &[(String, Vec<String>)], values: &BTreeMap<String, String>) -> u32 {
+    properties.iter().fold(0, |offset, (name, possible)| {
+        let value = &values[name];
+        let index = possible
+            .iter()
+            .position(|v| v == value)
+            .unwrap_or_else(|| panic!("Unknown value {value} of the property {name}"));
+        offset * possible.len() as u32 + index as u32
+    })
+}
+
+fn read_block(name: String, report: ReportBlock) -> Block {
+    // The names are the sorted keys, like in vanilla.
+    let properties: Vec<_> = report.properties.into_iter().collect();
+    let min_state = report.states.iter().map(|state| state.id).min().unwrap();
+    let mut default_state = None;
+    let count = properties
+        .iter()
+        .map(|(_, values)| values.len())
+        .product::<usize>();
+    assert_eq!(count, report.states.len(), "Missing states for {name}");
+    for state in &report.states {
+        assert_eq!(
+            state.id - min_state,
+            state_offset(&properties, &state.properties),
+            "The states of {name} are not in the vanilla order"
+        );
+        if state.default {
+            default_state = Some(state.id);
+        }
+    }
+    Block {
+        default_state: default_state.unwrap_or_else(|| panic!("No default state for {name}")),
+        name,
+        properties,
+        min_state,
+    }
+}
+
+fn generate(mut blocks: Vec<Block>, source: &Path) -> String {
+    blocks.sort_by_key(|block| block.min_state);
+    let mut next_state = 0;
+    for block in &blocks {
+        assert_eq!(
+            block.min_state, next_state,
+            "Missing states before {}",
+            block.name
+        );
+        next_state += block
+            .properties
+            .iter()
+            .map(|(_, values)| values.len() as u32)
+            .product::<u32>();
+    }
+
+    let mut out = format!("// Generated by build.rs from {}.\n\n", source.display());
+    writeln!(out, "pub static BLOCKS: [Block; {}] = [", blocks.len()).unwrap();
+    for block in &blocks {
+        write!(out, "    Block {{ name: {:?}, properties: &[", block.name).unwrap();
+        for (name, values) in &block.properties {
+            write!(out, "Property {{ name: {name:?}, values: &{values:?} }}, ").unwrap();
+        }
+        writeln!(
+            out,
+            "], min_state: {}, default_state: {} }},",
+            block.min_state, block.default_state
+        )
+        .unwrap();
+    }
+    writeln!(out, "];\n").unwrap();
+
+    let mut by_name: Vec<_> = (0..blocks.len()).collect();
+    by_name.sort_by_key(|index| &blocks[*index].name);
+    writeln!(out, "/// The indices of `BLOCKS`, sorted by name.").unwrap();
+    writeln!(
+        out,
+        "static BY_NAME: [u16; {}] = {by_name:?};",
+        blocks.len()
+    )
+    .unwrap();
+    out
+}
+
+/// The names of the items, in the order of their IDs.
+fn generate_items(registry: ReportRegistry, source: &Path) -> String {
+    let mut items = vec![None; registry.entries.len()];
+    for (name, entry) in registry.entries {
+        let item = items
+            .get_mut(entry.protocol_id)
+            .unwrap_or_else(|| panic!("Missing items before {name}"));
+        assert!(
+            item.is_none(),
+            "Two items have the ID {}",
+            entry.protocol_id
+        );
+        *item = Some(name);
+    }
+    let items: Vec<_> = items.into_iter().flatten().collect();
+
+    let mut out = format!("// Generated by build.rs from {}.\n\n", source.display());
+    writeln!(out, "/// The items, in the order of their IDs.").unwrap();
+    writeln!(
+        out,
+        "pub static ITEMS: [&str; {}] = {items:?};",
+        items.len()
+    )
+    .unwrap();
+    out
+}
+
+fn main() {
+    let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
+
+    let path = Path::new(REPORT);
+    println!("cargo:rerun-if-changed={}", path.display());
+    let report = fs::read_to_string(path).expect("Failed to read the block report");
+    let report: BTreeMap<String, ReportBlock> =
+        serde_json::from_str(&report).expect("Invalid block report");
+    let blocks = report
+        .into_iter()
+        .map(|(name, block)| read_block(name, block))
+        .collect();
+    fs::write(out_dir.join("blocks.rs"), generate(blocks, path))
+        .expect("Failed to write the block registry");
+
+    let path = Path::new(REGISTRIES_REPORT);
+    println!("cargo:rerun-if-changed={}", path.display());
+    let report = fs::read_to_string(path).expect("Failed to read the registries report");
+    let mut registries: BTreeMap<String, ReportRegistry> =
+        serde_json::from_str(&report).expect("Invalid registries report");
+    let items = registries
+        .remove("minecraft:item")
+        .expect("No item registry in the registries report");
+    fs::write(out_dir.join("items.rs"), generate_items(items, path))
+        .expect("Failed to write the item registry");
+}
@@ -0,0 +1,567 @@
+//! The types of the arguments that commands take, and how they are read from the input.
+
+use std::fmt;
+
+use super::selector::EntitySelector;
+use crate::config::Gamemode;
+use crate::consts;
+use crate::net::online::ONLINE_PLAYERS;
+use crate::packet::data_types::varint;
+use crate::player::Profile;
+use crate::world::item::ITEMS;
+
+/// A cursor over a command's input, like Brigadier's `StringReader`.
+#[derive(Clone, Debug)]
+pub struct StringReader<'a> {
+    input: &'a str,
+    cursor: usize,
+}
+
+impl<'a> StringReader<'a> {
+    pub fn new(input: &'a str) -> Self {
+        Self { input, cursor: 0 }
+    }
+
+    /// Returns the position of the cursor in the input, in bytes.
+    pub fn cursor(&self) -> usize {
+        self.cursor
+    }
+
+    /// Returns what has not been read yet.
+    pub fn remaining(&self) -> &'a str {
+        &self.input[self.cursor..]
+    }
+
+    /// Returns whether there is something left to read.
+    pub fn can_read(&self) -> bool {
+        self.cursor < self.input.len()
+    }
+
+    /// Returns the next character without reading it.
+    pub fn peek(&self) -> Option<char> {
+        self.remaining().chars().next()
+    }
+
+    /// Skips the whitespace at the cursor.
+    pub fn skip_whitespace(&mut self) {
+        let remaining = self.remaining();
+        self.cursor += remaining.len() - remaining.trim_start().len();
+    }
+
+    /// Reads the next character.
+    pub fn read_char(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.cursor += c.len_utf8();
+        Some(c)
+    }
+
+    /// Reads until one of `delimiters`, which is not read.
+    pub fn read_until(&mut self, delimiters: &[char]) -> &'a str {
+        let remaining = self.remaining();
+        let end = remaining.find(delimiters).unwrap_or(remaining.len());
+        self.cursor += end;
+        &remaining[..end]
+    }
+
+    /// Reads until the next whitespace.
+    pub fn read_word(&mut self) -> &'a str {
+        let remaining = self.remaining();
+        let end = remaining
+            .find(char::is_whitespace)
+            .unwrap_or(remaining.len());
+        self.cursor += end;
+        &remaining[..end]
+    }
+
+    /// Reads a string which is either a word, or surrounded by double quotes.
+    /// Inside quotes, `\"` and `\\` are escaped characters.
+    pub fn read_string(&mut self) -> Result<String, String> {
+        if self.peek() != Some('"') {
+            return Ok(self.read_word().to_string());
+        }
+
+        let mut result = String::new();
+        let mut escaped = false;
+        for (i, c) in self.remaining().char_indices().skip(1) {
+            match c {
+                _ if escaped => {
+                    if c != '"' && c != '\\' {
+                        return Err(format!("Invalid escape sequence '\\{c}' in quoted string"));
+                    }
+                    result.push(c);
+                    escaped = false;
+                }
+                '\\' => escaped = true,
+                '"' => {
+                    self.cursor += i + 1;
+                    return Ok(result);
+                }
+                _ => result.push(c),
+            }
+        }
+
+        Err("Unclosed quoted string".to_string())
+    }
+
+    /// Reads everything left.
+    pub fn read_remaining(&mut self) -> &'a str {
+        let remaining = self.remaining();
+        self.cursor = self.input.len();
+        remaining
+    }
+}
+
+/// The type of an argument, which decides how it is parsed.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgumentType {
+    /// A single word.
+    Word,
+    Integer {
+        min: Option<i32>,
+        max: Option<i32>,
+    },
+    Bool,
+    /// A player name, resolved to their profile before the command is executed.
+    GameProfile,
+    Gamemode,
+    /// The rest of the input, as a chat message.
+    Message,
+    /// A player name, a UUID or a target selector.
+    Entity {
+        single: bool,
+        players_only: bool,
+    },
+    /// The coordinates of a block, which may be relative (`~`) or local (`^`).
+    BlockPos,
+    /// A dimension, like `minecraft:the_nether`.
+    Dimension,
+    /// An item, like `minecraft:stone`.
+    Item,
+}
+
+/// The value of a parsed argument.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ArgumentValue {
+    String(String),
+    Integer(i32),
+    Bool(bool),
+    /// The name given for a `GameProfile` argument, until it is resolved.
+    PlayerName(String),
+    Profile(Profile),
+    Gamemode(Gamemode),
+    Selector(EntitySelector),
+    BlockPos([Coordinate; 3]),
+    /// A resource location, with its namespace.
+    ResourceLocation(String),
+}
+
+/// One coordinate of a position, as typed in a command.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Coordinate {
+    Absolute(f64),
+    /// `~`: an offset from the executor's position.
+    Relative(f64),
+    /// `^`: an offset along the executor's rotation.
+    Local(f64),
+}
+
+impl Coordinate {
+    fn parse(reader: &mut StringReader) -> Result<Self, String> {
+        let word = reader.read_word();
+        let (prefix, number) = match word.chars().next() {
+            Some(c @ ('~' | '^')) => (Some(c), &word[1..]),
+            _ => (None, word),
+        };
+
+        let value = match number {
+            "" if prefix.is_some() => 0.0,
+            _ => number
+                .parse::<f64>()
+                .ok()
+                .filter(|n| n.is_finite())
+                .ok_or_else(|| format!("Invalid coordinate '{word}'"))?,
+        };
+
+        match prefix {
+            Some('~') => Ok(Self::Relative(value)),
+            Some('^') => Ok(Self::Local(value)),
+            _ if number.contains('.') => Err(format!(
+                "Invalid coordinate '{word}', block positions are integers"
+            )),
+            _ => Ok(Self::Absolute(value)),
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        matches!(self, Self::Local(_))
+    }
+
+    /// Returns the coordinate, relative to `origin` if needed.
+    /// Local coordinates need a rotation, so they are `None`.
+    pub fn resolve(&self, origin: f64) -> Option<f64> {
+        match self {
+            Self::Absolute(value) => Some(*value),
+            Self::Relative(offset) => Some(origin + offset),
+            Self::Local(_) => None,
+        }
+    }
+}
+
+/// Returns whether `c` may be part of a resource location.
+fn is_resource_location_char(c: char) -> bool {
+    c.is_ascii_lowercase() || c.is_ascii_digit() || matches!(c, '_' | '-' | '.' | '/' | ':')
+}
+
+/// Parses a resource location, adding the 'minecraft' namespace if it is missing.
+pub fn parse_resource_location(text: &str) -> Result<String, String> {
+    let invalid = || format!("Invalid resource location '{text}'");
+    if text.is_empty() || !text.chars().all(is_resource_location_char) {
+        return Err(invalid());
+    }
+
+    let (namespace, path) = text.split_once(':').unwrap_or(("minecraft", text));
+    let namespace = if namespace.is_empty() {
+        "minecraft"
+    } else {
+        namespace
+    };
+    if path.is_empty() || path.contains(':') || namespace.contains('/') {
+        return Err(invalid());
+    }
+
+    Ok(format!("{namespace}:{path}"))
+}
+
+impl ArgumentType {
+    /// An integer argument within `min..=max`.
+    pub fn integer(min: i32, max: i32) -> Self {
+        Self::Integer {
+            min: Some(min),
+            max: Some(max),
+        }
+    }
+
+    /// Reads an argument of this type at the cursor of `reader`.
+    /// On error, returns the message shown to the user.
+    pub fn parse(&self, reader: &mut StringReader) -> Result<ArgumentValue, String> {
+        match self {
+            Self::Word => Ok(ArgumentValue::String(reader.read_word().to_string())),
+            Self::Integer { min, max } => {
+                let word = reader.read_word();
+                let value = word
+                    .parse::<i32>()
+                    .map_err(|_| format!("Invalid integer '{word}'"))?;
+                if let Some(min) = min.filter(|min| value < *min) {
+                    return Err(format!(
+                        "Integer must not be less than {min}, found {value}"
+                    ));
+                }
+                if let Some(max) = max.filter(|max| value > *max) {
+                    return Err(format!(
+                        "Integer must not be more than {max}, found {value}"
+                    ));
+                }
+                Ok(ArgumentValue::Integer(value))
+            }
+            Self::Bool => match reader.read_word() {
+                "true" => Ok(ArgumentValue::Bool(true)),
+                "false" => Ok(ArgumentValue::Bool(false)),
+                word => Err(format!(
+                    "Invalid boolean, expected 'true' or 'false' but found '{word}'"
+                )),
+            },
+            Self::GameProfile => Ok(ArgumentValue::PlayerName(reader.read_word().to_string())),
+            Self::Gamemode => {
+                let word = reader.read_word();
+                Gamemode::from_name(word)
+                    .map(ArgumentValue::Gamemode)
+                    .ok_or_else(|| format!("Unknown game mode: {word}"))
+            }
+            Self::Message => Ok(ArgumentValue::String(reader.read_remaining().to_string())),
+            Self::Entity {
+                single,
+                players_only,
+            } => EntitySelector::parse(reader, *single, *players_only).map(ArgumentValue::Selector),
+            Self::BlockPos => {
+                let mut coordinates = [Coordinate::Absolute(0.0); 3];
+                for (i, coordinate) in coordinates.iter_mut().enumerate() {
+                    if i > 0 {
+                        if reader.peek() != Some(' ') {
+                            return Err("Incomplete position, expected 3 coordinates".to_string());
+                        }
+                        reader.read_char();
+                    }
+                    *coordinate = Coordinate::parse(reader)?;
+                }
+
+                let local = coordinates.iter().filter(|c| c.is_local()).count();
+                if local != 0 && local != 3 {
+                    return Err("Cannot mix world & local coordinates (everything must either use ^ or not)".to_string());
+                }
+                Ok(ArgumentValue::BlockPos(coordinates))
+            }
+            Self::Dimension => {
+                let location = parse_resource_location(reader.read_word())?;
+                if !consts::minecraft::DIMENSIONS.contains(&location.as_str()) {
+                    return Err(format!("Unknown dimension '{location}'"));
+                }
+                Ok(ArgumentValue::ResourceLocation(location))
+            }
+            Self::Item => {
+                let location = parse_resource_location(reader.read_word())?;
+                if !ITEMS.contains(&location.as_str()) {
+                    return Err(format!("Unknown item '{location}'"));
+                }
+                Ok(ArgumentValue::ResourceLocation(location))
+            }
+        }
+    }
+
+    /// Returns the values starting with `prefix` that could complete the argument.
+    pub fn suggest(&self, prefix: &str) -> Vec<String> {
+        let player_names = || ONLINE_PLAYERS.list().into_iter().map(|p| p.name);
+
+        let candidates: Vec<String> = match self {
+            Self::Bool => vec!["true".to_string(), "false".to_string()],
+            Self::Gamemode => Gamemode::ALL.iter().map(|g| g.name().to_string()).collect(),
+            Self::GameProfile => player_names().collect(),
+            Self::Entity { players_only, .. } => {
+                let mut selectors = vec!["@a", "@p", "@r", "@s"];
+                if !players_only {
+                    selectors.extend(["@e", "@n"]);
+                }
+                selectors
+                    .into_iter()
+                    .map(str::to_string)
+                    .chain(player_names())
+                    .collect()
+            }
+            Self::BlockPos => vec!["~ ~ ~".to_string()],
+            Self::Dimension => consts::minecraft::DIMENSIONS
+                .iter()
+                .map(|d| d.to_string())
+                .collect(),
+            Self::Item => ITEMS.iter().map(|item| item.to_string()).collect(),
+            _ => Vec::new(),
+        };
+
+        let mut matches: Vec<String> = candidates
+            .into_iter()
+            .filter(|candidate| {
+                let path = candidate.strip_prefix("minecraft:").unwrap_or(candidate);
+                starts_with_ignore_case(candidate, prefix) || starts_with_ignore_case(path, prefix)
+            })
+            .collect();
+        matches.sort_unstable_by_key(|m| m.to_lowercase());
+        matches
+    }
+
+    /// Returns whether the clients should ask the server for suggestions, instead of
+    /// completing the argument themselves.
+    pub fn asks_server(&self) -> bool {
+        matches!(self, Self::GameProfile | Self::Entity { .. })
+    }
+
+    /// Returns whether the argument reads the rest of the input, so nothing can follow it.
+    pub fn is_greedy(&self) -> bool {
+        matches!(self, Self::Message)
+    }
+
+    /// Writes the parser of the argument for the Declare Commands packet: its ID in the
+    /// 'minecraft:command_argument_type' registry, then its properties.
+    pub fn write_parser(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Self::Bool => buffer.extend(varint::write(0)),
+            Self::Integer { min, max } => {
+                buffer.extend(varint::write(3));
+                let flags = u8::from(min.is_some()) | u8::from(max.is_some()) << 1;
+                buffer.push(flags);
+                for bound in [min, max].into_iter().flatten() {
+                    buffer.extend_from_slice(&bound.to_be_bytes());
+                }
+            }
+            // brigadier:string, with 0 for a single word.
+            Self::Word => buffer.extend([5, 0]),
+            Self::Entity {
+                single,
+                players_only,
+            } => {
+                buffer.extend(varint::write(6));
+                buffer.push(u8::from(*single) | u8::from(*players_only) << 1);
+            }
+            Self::GameProfile => buffer.extend(varint::write(7)),
+            Self::BlockPos => buffer.extend(varint::write(8)),
+            Self::Item => buffer.extend(varint::write(14)),
+            Self::Message => buffer.extend(varint::write(19)),
+            Self::Dimension => buffer.extend(varint::write(40)),
+            Self::Gamemode => buffer.extend(varint::write(41)),
+        }
+    }
+}
+
+/// Returns whether `text` starts with `prefix`, ignoring ASCII case.
+pub fn starts_with_ignore_case(text: &str, prefix: &str) -> bool {
+    text.get(..prefix.len())
+        .is_some_and(|start| start.eq_ignore_ascii_case(prefix))
+}
+
+impl fmt::Display for ArgumentValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::String(s) | Self::PlayerName(s) => write!(f, "{s}"),
+            Self::Integer(i) => write!(f, "{i}"),
+            Self::Bool(b) => write!(f, "{b}"),
+            Self::Profile(profile) => write!(f, "{}", profile.name),
+            Self::Gamemode(gamemode) => write!(f, "{}", gamemode.name()),
+            Self::Selector(selector) => write!(f, "{selector:?}"),
+            Self::BlockPos(coordinates) => write!(f, "{coordinates:?}"),
+            Self::ResourceLocation(location) => write!(f, "{location}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_string_reader() {
+        let mut reader = StringReader::new("add  \"quoted \\\"name\\\"\" rest of it");
+        assert_eq!(reader.read_word(), "add");
+        reader.skip_whitespace();
+        assert_eq!(reader.read_string().unwrap(), "quoted \"name\"");
+        reader.skip_whitespace();
+        assert_eq!(reader.read_remaining(), "rest of it");
+        assert!(!reader.can_read());
+
+        assert!(StringReader::new("\"unclosed").read_string().is_err());
+        assert!(StringReader::new("\"bad \\n\"").read_string().is_err());
+    }
+
+    #[test]
+    fn test_integer_ranges() {
+        let kind = ArgumentType::integer(0, 4);
+        let parse = |input: &str| kind.parse(&mut StringReader::new(input));
+
+        assert_eq!(parse("3"), Ok(ArgumentValue::Integer(3)));
+        assert!(parse("5").is_err());
+        assert!(parse("-1").is_err());
+        assert!(parse("three").is_err());
+
+        let unbounded = ArgumentType::Integer {
+            min: None,
+            max: None,
+        };
+        assert_eq!(
+            unbounded.parse(&mut StringReader::new("-2147483648")),
+            Ok(ArgumentValue::Integer(i32::MIN))
+        );
+    }
+
+    #[test]
+    fn test_gamemode_and_bool() {
+        let parse = |kind: ArgumentType, input: &str| kind.parse(&mut StringReader::new(input));
+
+        assert_eq!(
+            parse(ArgumentType::Gamemode, "creative"),
+            Ok(ArgumentValue::Gamemode(Gamemode::CREATIVE))
+        );
+        assert!(parse(ArgumentType::Gamemode, "hardcore").is_err());
+        assert_eq!(
+            parse(ArgumentType::Bool, "true"),
+            Ok(ArgumentValue::Bool(true))
+        );
+        assert!(parse(ArgumentType::Bool, "yes").is_err());
+    }
+
+    #[test]
+    fn test_block_pos() {
+        let parse = |input: &str| ArgumentType::BlockPos.parse(&mut StringReader::new(input));
+
+        assert_eq!(
+            parse("1 ~ ~-2.5"),
+            Ok(ArgumentValue::BlockPos([
+                Coordinate::Absolute(1.0),
+                Coordinate::Relative(0.0),
+                Coordinate::Relative(-2.5)
+            ]))
+        );
+        assert!(parse("^ ^1 ^").is_ok());
+        assert!(parse("^ ~ ^").is_err());
+        assert!(parse("1 2").is_err());
+        assert!(parse("1.5 2 3").is_err());
+        assert_eq!(Coordinate::Relative(-2.5).resolve(10.0), Some(7.5));
+    }
+
+    #[test]
+    fn test_resource_location() {
+        assert_eq!(parse_resource_location("stone").unwrap(), "minecraft:stone");
+        assert_eq!(
+            parse_resource_location("copper:blocks/vent").unwrap(),
+            "copper:blocks/vent"
+        );
+        assert_eq!(parse_resource_location(":air").unwrap(), "minecraft:air");
+        assert!(parse_resource_location("Stone").is_err());
+        assert!(parse_resource_location("a:b:c").is_err());
+        assert!(parse_resource_location("minecraft:").is_err());
+
+        let parse_item = |input: &str| ArgumentType::Item.parse(&mut StringReader::new(input));
+        assert_eq!(
+            parse_item("stone"),
+            Ok(ArgumentValue::ResourceLocation(
+                "minecraft:stone".to_string()
+            ))
+        );
+        assert!(parse_item("minecraft:not_an_item").is_err());
+    }
+
+    #[test]
+    fn test_parsers() {
+        let encode = |kind: ArgumentType| {
+            let mut buffer = Vec::new();
+            kind.write_parser(&mut buffer);
+            buffer
+        };
+
+        assert_eq!(
+            encode(ArgumentType::integer(0, 4)),
+            [3, 3, 0, 0, 0, 0, 0, 0, 0, 4]
+        );
+        assert_eq!(
+            encode(ArgumentType::Integer {
+                min: None,
+                max: Some(1)
+            }),
+            [3, 2, 0, 0, 0, 1]
+        );
+        assert_eq!(
+            encode(ArgumentType::Entity {
+                single: false,
+                players_only: true
+            }),
+            [6, 2]
+        );
+        assert_eq!(encode(ArgumentType::Item), [14]);
+        assert_eq!(encode(ArgumentType::Gamemode), [41]);
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(
+            ArgumentType::Gamemode.suggest("s"),
+            ["spectator", "survival"]
+        );
+        assert_eq!(ArgumentType::Gamemode.suggest("CR"), ["creative"]);
+        assert_eq!(
+            ArgumentType::Dimension.suggest("the"),
+            ["minecraft:the_end", "minecraft:the_nether"]
+        );
+        assert_eq!(
+            ArgumentType::Item.suggest("minecraft:polished_d"),
+            ["minecraft:polished_deepslate", "minecraft:polished_diorite"]
+        );
+        assert_eq!(ArgumentType::Item.suggest("bedr"), ["minecraft:bedrock"]);
+        assert_eq!(ArgumentType::Bool.suggest(""), ["false", "true"]);
+        assert!(ArgumentType::integer(0, 1).suggest("").is_empty());
+    }
+}
@@ -0,0 +1,286 @@
+//! ban, ban-ip, tempban, tempban-ip, pardon, pardon-ip, banlist and kick.
+
+use chrono::{DateTime, FixedOffset, Local};
+
+use super::save_list;
+use crate::commands::arguments::ArgumentType;
+use crate::commands::dispatcher::{
+    argument, literal, Command, CommandContext, CommandError, CommandRegistry,
+};
+use crate::consts::disconnect_reasons;
+use crate::net::online::ONLINE_PLAYERS;
+use crate::user_lists::bans::{
+    self, BanInfo, IpBan, IpRange, PlayerBan, BANNED_IPS, BANNED_PLAYERS,
+};
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        Command::new("ban")
+            .description("Bans a player")
+            .permission(3)
+            .syntax(vec![argument("targets", ArgumentType::GameProfile)], ban)
+            .syntax(
+                vec![
+                    argument("targets", ArgumentType::GameProfile),
+                    argument("reason", ArgumentType::Message),
+                ],
+                ban,
+            ),
+    );
+    registry.register(
+        Command::new("ban-ip")
+            .description("Bans an IP address, a range or the address of an online player")
+            .permission(3)
+            .syntax(vec![argument("target", ArgumentType::Word)], ban_ip)
+            .syntax(
+                vec![
+                    argument("target", ArgumentType::Word),
+                    argument("reason", ArgumentType::Message),
+                ],
+                ban_ip,
+            ),
+    );
+    let duration = || argument("duration", ArgumentType::Word);
+    registry.register(
+        Command::new("tempban")
+            .description("Bans a player for a duration like '7d'")
+            .permission(3)
+            .syntax(
+                vec![argument("targets", ArgumentType::GameProfile), duration()],
+                ban,
+            )
+            .syntax(
+                vec![
+                    argument("targets", ArgumentType::GameProfile),
+                    duration(),
+                    argument("reason", ArgumentType::Message),
+                ],
+                ban,
+            ),
+    );
+    registry.register(
+        Command::new("tempban-ip")
+            .description("Bans an IP address or a range for a duration like '7d'")
+            .permission(3)
+            .syntax(
+                vec![argument("target", ArgumentType::Word), duration()],
+                ban_ip,
+            )
+            .syntax(
+                vec![
+                    argument("target", ArgumentType::Word),
+                    duration(),
+                    argument("reason", ArgumentType::Message),
+                ],
+                ban_ip,
+            ),
+    );
+    registry.register(
+        Command::new("pardon")
+            .description("Unbans a player")
+            .permission(3)
+            .syntax(vec![argument("targets", ArgumentType::Word)], pardon),
+    );
+    registry.register(
+        Command::new("pardon-ip")
+            .description("Unbans an IP address or a range")
+            .permission(3)
+            .syntax(vec![argument("target", ArgumentType::Word)], pardon_ip),
+    );
+    registry.register(
+        Command::new("banlist")
+            .description("Lists the bans")
+            .permission(3)
+            .syntax(vec![], |ctx| banlist(ctx, true, true))
+            .syntax(vec![literal("players")], |ctx| banlist(ctx, true, false))
+            .syntax(vec![literal("ips")], |ctx| banlist(ctx, false, true)),
+    );
+    let targets = || {
+        argument(
+            "targets",
+            ArgumentType::Entity {
+                single: false,
+                players_only: true,
+            },
+        )
+    };
+    registry.register(
+        Command::new("kick")
+            .description("Disconnects players from the server")
+            .permission(3)
+            .syntax(vec![targets()], kick)
+            .syntax(
+                vec![targets(), argument("reason", ArgumentType::Message)],
+                kick,
+            ),
+    );
+}
+
+/// The reason of kicks made without one.
+const DEFAULT_KICK_REASON: &str = "Kicked by an operator";
+
+/// Returns when the ban expires, given the `duration` argument of tempban and tempban-ip, and
+/// its reason.
+fn ban_details(
+    ctx: &CommandContext,
+) -> Result<(Option<DateTime<FixedOffset>>, Option<String>), CommandError> {
+    let expires = match ctx.has("duration") {
+        true => {
+            let duration = ctx.string("duration")?;
+            let duration = bans::parse_duration(duration).ok_or_else(|| {
+                CommandError::Failed(format!(
+                    "Invalid duration '{duration}', expected e.g. 30m, 12h, 7d or 2w"
+                ))
+            })?;
+            Some(Local::now().fixed_offset() + duration)
+        }
+        false => None,
+    };
+    let reason = ctx.string("reason").ok().map(str::to_string);
+    Ok((expires, reason))
+}
+
+/// ban <targets> [<reason>], tempban <targets> <duration> [<reason>]
+fn ban(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let profile = ctx.profile("targets")?.clone();
+
+    if BANNED_PLAYERS.get(&profile.uuid).is_some() {
+        return Err(CommandError::Failed(
+            "Nothing changed. The player is already banned".to_string(),
+        ));
+    }
+
+    let (expires, reason) = ban_details(ctx)?;
+    let ban = PlayerBan {
+        uuid: profile.uuid,
+        name: profile.name,
+        info: BanInfo::new(&ctx.source.name, reason.as_deref(), expires),
+    };
+
+    BANNED_PLAYERS.add(ban.clone());
+    ctx.source
+        .send(format!("Banned {}: {}", ban.name, ban.info.reason));
+    save_list(ctx, "player ban list", || BANNED_PLAYERS.save());
+    ONLINE_PLAYERS.kick(&ban.uuid, &ban.info.message(disconnect_reasons::BANNED));
+    Ok(())
+}
+
+/// ban-ip <ip|range|name> [<reason>], tempban-ip <ip|range|name> <duration> [<reason>]
+fn ban_ip(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let target = ctx.string("target")?;
+    let ip: IpRange = match target.parse() {
+        Ok(ip) => ip,
+        Err(_) => match ONLINE_PLAYERS.get_by_name(target) {
+            Some(player) => player.addr.ip().into(),
+            None => {
+                return Err(CommandError::Failed(
+                    "Invalid IP address or unknown player".to_string(),
+                ))
+            }
+        },
+    };
+
+    if BANNED_IPS.entries().iter().any(|ban| ban.ip == ip) {
+        return Err(CommandError::Failed(
+            "Nothing changed. That IP is already banned".to_string(),
+        ));
+    }
+
+    let (expires, reason) = ban_details(ctx)?;
+    let ban = IpBan {
+        ip,
+        info: BanInfo::new(&ctx.source.name, reason.as_deref(), expires),
+    };
+
+    BANNED_IPS.add(ban.clone());
+    ctx.source
+        .send(format!("Banned IP {ip}: {}", ban.info.reason));
+    save_list(ctx, "IP ban list", || BANNED_IPS.save());
+    for player in ONLINE_PLAYERS.list() {
+        if ip.contains(player.addr.ip()) {
+            ONLINE_PLAYERS.kick(
+                &player.uuid,
+                &ban.info.message(disconnect_reasons::KICKED_IP_BANNED),
+            );
+        }
+    }
+    Ok(())
+}
+
+/// pardon <targets>. The name is looked up in the ban list, so this works offline.
+fn pardon(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let name = ctx.string("targets")?;
+    let not_banned =
+        || CommandError::Failed("Nothing changed. The player isn't banned".to_string());
+
+    let ban = BANNED_PLAYERS.get_by_name(name).ok_or_else(not_banned)?;
+    if !BANNED_PLAYERS.remove(&ban.uuid) {
+        return Err(not_banned());
+    }
+    ctx.source.send(format!("Unbanned {}", ban.name));
+    save_list(ctx, "player ban list", || BANNED_PLAYERS.save());
+    Ok(())
+}
+
+/// pardon-ip <ip|range>
+fn pardon_ip(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let ip: IpRange = ctx
+        .string("target")?
+        .parse()
+        .map_err(|_| CommandError::Failed("Invalid IP address".to_string()))?;
+
+    if !BANNED_IPS.remove(&ip.to_string()) {
+        return Err(CommandError::Failed(
+            "Nothing changed. That IP isn't banned".to_string(),
+        ));
+    }
+    ctx.source.send(format!("Unbanned IP {ip}"));
+    save_list(ctx, "IP ban list", || BANNED_IPS.save());
+    Ok(())
+}
+
+/// banlist [ips|players]
+fn banlist(ctx: &mut CommandContext, players: bool, ips: bool) -> Result<(), CommandError> {
+    let mut lines: Vec<String> = Vec::new();
+
+    if players {
+        for ban in BANNED_PLAYERS.entries() {
+            lines.push(format!(
+                "{} was banned by {}: {}",
+                ban.name, ban.info.source, ban.info.reason
+            ));
+        }
+    }
+    if ips {
+        for ban in BANNED_IPS.entries() {
+            lines.push(format!(
+                "{} was banned by {}: {}",
+                ban.ip, ban.info.source, ban.info.reason
+            ));
+        }
+    }
+
+    if lines.is_empty() {
+        ctx.source.send("There are no bans");
+        return Ok(());
+    }
+
+    ctx.source
+        .send(format!("There are {} ban(s):", lines.len()));
+    for line in lines {
+        ctx.source.send(line);
+    }
+    Ok(())
+}
+
+/// kick <targets> [<reason>]
+fn kick(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let players = ctx.players("targets")?;
+    let reason = ctx.string("reason").unwrap_or(DEFAULT_KICK_REASON);
+
+    for player in players {
+        ONLINE_PLAYERS.kick(&player.uuid, reason);
+        ctx.source.send(format!("Kicked {}: {reason}", player.name));
+    }
+    Ok(())
+}
@@ -0,0 +1,37 @@
+//! The commands the server comes with, one module per topic.
+
+mod bans;
+mod ops;
+mod server;
+mod teleport;
+mod whitelist;
+mod world;
+
+use super::dispatcher::{CommandContext, CommandRegistry};
+use crate::user_lists::UserListError;
+
+/// Returns a registry holding every built-in command.
+pub fn registry() -> CommandRegistry {
+    let mut registry = CommandRegistry::new();
+    server::register(&mut registry);
+    ops::register(&mut registry);
+    whitelist::register(&mut registry);
+    bans::register(&mut registry);
+    teleport::register(&mut registry);
+    world::register(&mut registry);
+    registry
+}
+
+/// Saves a user list once the command is done, telling its executor if that failed. The list in
+/// memory keeps the change, and is saved again with the next one.
+fn save_list(
+    ctx: &mut CommandContext,
+    what: &'static str,
+    save: fn() -> Result<(), UserListError>,
+) {
+    ctx.save(move |source| {
+        if let Err(e) = save() {
+            source.send_error(format!("Failed to save the {what}, error: {e}"));
+        }
+    });
+}
@@ -0,0 +1,73 @@
+//! op and deop.
+
+use super::save_list;
+use crate::commands::arguments::ArgumentType;
+use crate::commands::dispatcher::{
+    argument, Command, CommandContext, CommandError, CommandRegistry,
+};
+use crate::config;
+use crate::user_lists::ops::{OpChange, OperatorEntry, LEVELS, OPERATORS};
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        Command::new("op")
+            .description("Makes a player a server operator")
+            .permission(3)
+            .syntax(vec![argument("targets", ArgumentType::GameProfile)], op),
+    );
+    // The name is looked up in the operator list, so this works offline.
+    registry.register(
+        Command::new("deop")
+            .description("Removes a player from the server operators")
+            .permission(3)
+            .syntax(vec![argument("targets", ArgumentType::Word)], deop),
+    );
+}
+
+/// Makes a player a server operator, with the level set by 'op-permission-level'.
+fn op(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let profile = ctx.profile("targets")?;
+    let name = &profile.name;
+
+    let level = config::Settings::new().op_permission_level;
+    if !LEVELS.contains(&level) {
+        return Err(CommandError::Failed(format!(
+            "'op-permission-level' is {level}, it must be between 1 and 4"
+        )));
+    }
+    let entry = OperatorEntry {
+        uuid: profile.uuid.clone(),
+        name: name.to_string(),
+        level,
+        bypasses_player_limit: false,
+    };
+
+    match OPERATORS.add(entry) {
+        Ok(OpChange::Added | OpChange::Updated) => {
+            ctx.source.send(format!("Made {name} a server operator"));
+            save_list(ctx, "operator list", || OPERATORS.save());
+            Ok(())
+        }
+        Ok(OpChange::Unchanged) => Err(CommandError::Failed(
+            "Nothing changed. The player already is an operator".to_string(),
+        )),
+        Err(e) => Err(CommandError::Failed(format!(
+            "Failed to make {name} a server operator, error: {e}"
+        ))),
+    }
+}
+
+fn deop(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let name = ctx.string("targets")?;
+    let not_an_operator =
+        || CommandError::Failed("Nothing changed. The player is not an operator".to_string());
+
+    let entry = OPERATORS.get_by_name(name).ok_or_else(not_an_operator)?;
+    if !OPERATORS.remove(&entry.uuid) {
+        return Err(not_an_operator());
+    }
+    ctx.source
+        .send(format!("Made {} no longer a server operator", entry.name));
+    save_list(ctx, "operator list", || OPERATORS.save());
+    Ok(())
+}
@@ -0,0 +1,89 @@
+//! help, list, save-all and stop.
+
+use crate::commands::arguments::ArgumentType;
+use crate::commands::dispatcher::{
+    argument, Command, CommandContext, CommandError, CommandRegistry,
+};
+use crate::commands::COMMANDS;
+use crate::config;
+use crate::net::online::ONLINE_PLAYERS;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        Command::new("help")
+            .alias("?")
+            .description("Shows the usage of the commands")
+            .syntax(vec![], help)
+            .syntax(vec![argument("command", ArgumentType::Word)], help_command),
+    );
+    registry.register(
+        Command::new("list")
+            .description("Lists the online players")
+            .syntax(vec![], list),
+    );
+    registry.register(
+        Command::new("save-all")
+            .description("Saves the server to the disk")
+            .permission(4)
+            .syntax(vec![], save_all),
+    );
+    registry.register(
+        Command::new("stop")
+            .description("Stops the server")
+            .permission(4)
+            .syntax(vec![], stop),
+    );
+}
+
+/// help: the usage of every command the executor may use.
+fn help(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    for command in COMMANDS.available(ctx.source.permission_level) {
+        for line in command.usage() {
+            ctx.source.send(line);
+        }
+    }
+    Ok(())
+}
+
+/// help <command>
+fn help_command(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let name = ctx.string("command")?;
+    let command = COMMANDS
+        .get(name)
+        .filter(|c| c.permission_level <= ctx.source.permission_level)
+        .ok_or_else(|| CommandError::Failed(format!("Unknown command: {name}")))?;
+
+    for line in command.usage() {
+        ctx.source.send(line);
+    }
+    Ok(())
+}
+
+fn list(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let mut names: Vec<String> = ONLINE_PLAYERS.list().into_iter().map(|p| p.name).collect();
+    names.sort_unstable_by_key(|name| name.to_lowercase());
+
+    ctx.source.send(format!(
+        "There are {} of a max of {} players online: {}",
+        names.len(),
+        config::Settings::new().max_players,
+        names.join(", ")
+    ));
+    Ok(())
+}
+
+fn save_all(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    ctx.source.send("Saving the game (this may take a moment!)");
+    // The user lists are saved by the commands changing them.
+    ctx.save(|source| match crate::world::save() {
+        Ok(()) => source.send("Saved the game"),
+        Err(e) => source.send_error(format!("Failed to save the game: {e}")),
+    });
+    Ok(())
+}
+
+fn stop(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    ctx.source.send("Stopping the server");
+    crate::tick::request_stop();
+    Ok(())
+}
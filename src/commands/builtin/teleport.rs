@@ -0,0 +1,136 @@
+//! teleport, also known as tp. Like `execute in`, `in <dimension>` takes the players to another
+//! dimension, where their relative coordinates are scaled like through a portal.
+
+use crate::commands::arguments::{ArgumentType, Coordinate};
+use crate::commands::dispatcher::{
+    argument, literal, Command, CommandContext, CommandError, CommandRegistry,
+};
+use crate::net::online::{OnlinePlayer, ONLINE_PLAYERS};
+use crate::world;
+use crate::world::dimension::{DimensionType, Location};
+
+/// The players can't go farther than this from the origin, horizontally.
+const MAX_HORIZONTAL: f64 = 30_000_000.0;
+const MAX_VERTICAL: f64 = 20_000_000.0;
+
+pub fn register(registry: &mut CommandRegistry) {
+    let targets = || {
+        argument(
+            "targets",
+            ArgumentType::Entity {
+                single: false,
+                players_only: true,
+            },
+        )
+    };
+    let location = || argument("location", ArgumentType::BlockPos);
+    let dimension = || argument("dimension", ArgumentType::Dimension);
+    registry.register(
+        Command::new("teleport")
+            .alias("tp")
+            .description("Teleports players, also to another dimension")
+            .permission(2)
+            .syntax(vec![location()], teleport_self)
+            .syntax(vec![location(), literal("in"), dimension()], teleport_self)
+            .syntax(vec![targets(), location()], teleport)
+            .syntax(
+                vec![targets(), location(), literal("in"), dimension()],
+                teleport,
+            ),
+    );
+}
+
+/// teleport <location> [in <dimension>]: the executor, who must be a player.
+fn teleport_self(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let player = ctx
+        .source
+        .uuid
+        .as_deref()
+        .and_then(|uuid| ONLINE_PLAYERS.get(uuid))
+        .ok_or_else(|| {
+            CommandError::Failed("A player is required to run this command here".to_string())
+        })?;
+    teleport_players(ctx, vec![player])
+}
+
+/// teleport <targets> <location> [in <dimension>]
+fn teleport(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let players = ctx.players("targets")?;
+    teleport_players(ctx, players)
+}
+
+fn teleport_players(
+    ctx: &mut CommandContext,
+    players: Vec<OnlinePlayer>,
+) -> Result<(), CommandError> {
+    let dimension = match ctx.has("dimension") {
+        true => {
+            let id = ctx.resource_location("dimension")?;
+            let dimension = world::dimension(id)
+                .ok_or_else(|| CommandError::Failed(format!("The dimension {id} is not loaded")))?;
+            Some(dimension.kind)
+        }
+        false => None,
+    };
+    let coordinates = ctx.block_pos("location")?;
+
+    let mut locations = Vec::with_capacity(players.len());
+    for player in &players {
+        let location = resolve(&coordinates, &player.location, dimension)?;
+        locations.push((player, location));
+    }
+    for (player, location) in &locations {
+        ONLINE_PLAYERS.teleport(&player.uuid, location.clone());
+    }
+
+    let (player, Location { x, y, z, .. }) = &locations[0];
+    match locations.len() {
+        1 => ctx.source.send(format!(
+            "Teleported {} to {x:.6}, {y:.6}, {z:.6}",
+            player.name
+        )),
+        count => ctx.source.send(format!(
+            "Teleported {count} entities to {x:.6}, {y:.6}, {z:.6}"
+        )),
+    }
+    Ok(())
+}
+
+/// Where a player goes: the coordinates are relative to where they are, scaled if they go to
+/// another dimension. The absolute ones are at the middle of the block.
+fn resolve(
+    coordinates: &[Coordinate; 3],
+    origin: &Location,
+    dimension: Option<&'static DimensionType>,
+) -> Result<Location, CommandError> {
+    let from = DimensionType::get(origin.dimension);
+    let to = dimension.or(from);
+    let scale = match (from, to) {
+        (Some(from), Some(to)) => from.coordinate_scale / to.coordinate_scale,
+        _ => 1.0,
+    };
+    let resolve = |coordinate: &Coordinate, origin: f64, middle: f64| match coordinate {
+        Coordinate::Absolute(value) => Some(value + middle),
+        coordinate => coordinate.resolve(origin),
+    };
+    let (Some(x), Some(y), Some(z)) = (
+        resolve(&coordinates[0], origin.x * scale, 0.5),
+        resolve(&coordinates[1], origin.y, 0.0),
+        resolve(&coordinates[2], origin.z * scale, 0.5),
+    ) else {
+        return Err(CommandError::Failed(
+            "Local coordinates are not supported yet".to_string(),
+        ));
+    };
+    if x.abs() > MAX_HORIZONTAL || z.abs() > MAX_HORIZONTAL || y.abs() > MAX_VERTICAL {
+        return Err(CommandError::Failed(
+            "Invalid position for teleport".to_string(),
+        ));
+    }
+    Ok(Location {
+        dimension: to.map_or(origin.dimension, |kind| kind.id),
+        x,
+        y,
+        z,
+    })
+}
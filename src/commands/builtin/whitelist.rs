@@ -0,0 +1,121 @@
+//! whitelist (on|off|add <targets>|remove <targets>|list|reload)
+
+use std::path::Path;
+
+use super::save_list;
+use crate::commands::arguments::ArgumentType;
+use crate::commands::dispatcher::{
+    argument, literal, Command, CommandContext, CommandError, CommandRegistry,
+};
+use crate::user_lists::whitelist::{self, WhitelistEntry, WHITELIST};
+use crate::{config, consts};
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        Command::new("whitelist")
+            .description("Manages the players allowed to join the server")
+            .permission(3)
+            .syntax(vec![literal("on")], |ctx| set_whitelist(ctx, true))
+            .syntax(vec![literal("off")], |ctx| set_whitelist(ctx, false))
+            .syntax(vec![literal("list")], list)
+            .syntax(vec![literal("reload")], reload)
+            .syntax(
+                vec![
+                    literal("add"),
+                    argument("targets", ArgumentType::GameProfile),
+                ],
+                add,
+            )
+            .syntax(
+                vec![literal("remove"), argument("targets", ArgumentType::Word)],
+                remove,
+            ),
+    );
+}
+
+/// Turns the whitelist on or off by changing 'white-list' in the 'server.properties' file.
+fn set_whitelist(ctx: &mut CommandContext, enabled: bool) -> Result<(), CommandError> {
+    if WHITELIST.is_enabled() == enabled {
+        return Err(CommandError::Failed(
+            match enabled {
+                true => "Whitelist is already turned on",
+                false => "Whitelist is already turned off",
+            }
+            .to_string(),
+        ));
+    }
+
+    WHITELIST.set_enabled(enabled);
+    ctx.save(move |source| {
+        let path = Path::new(consts::filepaths::PROPERTIES);
+        if let Err(e) = config::set_property(path, "white-list", &enabled.to_string()) {
+            source.send_error(format!("Failed to save the whitelist setting, error: {e}"));
+        }
+    });
+
+    if enabled {
+        ctx.source.send("Whitelist is now turned on");
+        whitelist::enforce();
+    } else {
+        ctx.source.send("Whitelist is now turned off");
+    }
+    Ok(())
+}
+
+fn list(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let names: Vec<String> = WHITELIST.entries().into_iter().map(|e| e.name).collect();
+    if names.is_empty() {
+        ctx.source.send("There are no whitelisted players");
+    } else {
+        ctx.source.send(format!(
+            "There are {} whitelisted player(s): {}",
+            names.len(),
+            names.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+fn reload(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    WHITELIST
+        .reload()
+        .map_err(|e| CommandError::Failed(format!("Failed to reload the whitelist, error: {e}")))?;
+
+    ctx.source.send("Reloaded the whitelist");
+    whitelist::enforce();
+    Ok(())
+}
+
+fn add(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let profile = ctx.profile("targets")?;
+    let name = profile.name.clone();
+
+    let entry = WhitelistEntry {
+        uuid: profile.uuid.clone(),
+        name: name.clone(),
+    };
+    if !WHITELIST.add(entry) {
+        return Err(CommandError::Failed(
+            "Player is already whitelisted".to_string(),
+        ));
+    }
+    ctx.source.send(format!("Added {name} to the whitelist"));
+    save_list(ctx, "whitelist", || WHITELIST.save());
+    Ok(())
+}
+
+/// The name is looked up in the whitelist, so this works offline.
+fn remove(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let name = ctx.string("targets")?;
+    let not_whitelisted = || CommandError::Failed("Player is not whitelisted".to_string());
+
+    let entry = WHITELIST.get_by_name(name).ok_or_else(not_whitelisted)?;
+    if !WHITELIST.remove(&entry.uuid) {
+        return Err(not_whitelisted());
+    }
+    ctx.source
+        .send(format!("Removed {} from the whitelist", entry.name));
+    whitelist::enforce();
+    save_list(ctx, "whitelist", || WHITELIST.save());
+    Ok(())
+}
@@ -0,0 +1,49 @@
+//! defaultgamemode, which changes the world's 'level.dat'.
+
+use crate::commands::arguments::ArgumentType;
+use crate::commands::dispatcher::{
+    argument, Command, CommandContext, CommandError, CommandRegistry,
+};
+use crate::world::level::LevelData;
+use crate::world::LEVEL;
+
+pub fn register(registry: &mut CommandRegistry) {
+    registry.register(
+        Command::new("defaultgamemode")
+            .description("Sets the game mode of the new players")
+            .permission(2)
+            .syntax(
+                vec![argument("gamemode", ArgumentType::Gamemode)],
+                default_gamemode,
+            ),
+    );
+}
+
+/// Runs `f` on the world's metadata, failing if the world is not loaded yet.
+fn with_level<T>(f: impl FnOnce(&mut LevelData) -> T) -> Result<T, CommandError> {
+    match LEVEL.lock().unwrap().as_mut() {
+        Some(level) => Ok(f(level)),
+        None => Err(CommandError::Failed(
+            "The world is not loaded yet".to_string(),
+        )),
+    }
+}
+
+/// defaultgamemode <gamemode>
+fn default_gamemode(ctx: &mut CommandContext) -> Result<(), CommandError> {
+    let gamemode = ctx.gamemode("gamemode")?;
+    let previous = with_level(|level| {
+        let previous = level.gamemode();
+        level.game_type = gamemode.id();
+        previous
+    })?;
+    if previous == gamemode {
+        return Err(CommandError::Failed(format!(
+            "Nothing changed. The default game mode is already {}",
+            gamemode.name()
+        )));
+    }
+    ctx.source
+        .send(format!("The default game mode is now {}", gamemode.name()));
+    Ok(())
+}
@@ -1,64 +1,138 @@
-use std::{thread, time::Duration};
+//! The server console. When stdin is a terminal, commands are typed in a line editor with
+//! history and Tab completion; otherwise, lines are read as they come, e.g. from a pipe.
+
+use std::io::IsTerminal;
+use std::thread;
 
-use colored::Colorize;
 use log::{debug, info, warn};
+use rustyline::completion::Completer;
+use rustyline::error::ReadlineError;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::Validator;
+use rustyline::{Context, Editor, Helper};
 use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::sync::mpsc::{self, UnboundedSender};
 
-use crate::{consts, fs_manager, player};
+use super::source::{CommandSource, MAX_PERMISSION_LEVEL};
+use super::COMMANDS;
+use crate::{logging, tick};
 
-// Asynchronously handles user input. It never returns
+/// The prompt of the line editor.
+const PROMPT: &str = "> ";
 
-// TODO: IMPLEMENT COMMANDS SEPARATELY FROM THIS FUNCTION, otherwise the code will just be as good as a dumpster fire
-// TODO: use the 'Command Pattern' and command handlers
+// Asynchronously handles user input. It never returns
+// The commands are parsed here, and queued to be executed on the main loop.
 pub async fn handle_input() -> ! {
+    let (sender, mut receiver) = mpsc::unbounded_channel();
+
+    if std::io::stdin().is_terminal() {
+        // The line editor blocks, so it gets its own thread.
+        thread::spawn(move || read_interactive(sender));
+    } else {
+        tokio::spawn(read_lines(sender));
+    }
+
+    while let Some(line) = receiver.recv().await {
+        let input = line.trim();
+        if input.is_empty() {
+            continue;
+        }
+        debug!("you entered: {input}");
+
+        // Executed on the main loop, the output goes to the console.
+        super::submit(tick::tasks(), input, CommandSource::console()).await;
+    }
+
+    // There is nothing left to read.
+    std::future::pending().await
+}
+
+/// Reads lines from stdin until EOF.
+async fn read_lines(sender: UnboundedSender<String>) {
     let mut reader = BufReader::new(tokio::io::stdin());
     let mut buffer = String::new();
 
     loop {
         buffer.clear();
-        if let Ok(bytes_read) = reader.read_line(&mut buffer).await {
-            if bytes_read == 0 {
-                continue; // EOF
+        match reader.read_line(&mut buffer).await {
+            Ok(0) => return,
+            Ok(_) => {
+                if sender.send(buffer.clone()).is_err() {
+                    return;
+                }
+            }
+            Err(e) => {
+                warn!("Failed to read the console input: {e}");
+                return;
             }
         }
+    }
+}
 
-        debug!("you entered: {buffer}");
-        // Debug/test logic down here
-
-        if buffer.trim().to_lowercase() == "stop" {
-            let content = "Server will stop in few second…";
-            warn!("{}", content.red().bold());
-            thread::sleep(Duration::from_secs(1));
-            crate::gracefully_exit(-1000);
+/// Reads lines with the line editor until EOF.
+fn read_interactive(sender: UnboundedSender<String>) {
+    let mut editor = match Editor::<ConsoleHelper, DefaultHistory>::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            warn!("Failed to start the console line editor: {e}");
+            return;
         }
-        //made a server operator (level 4)
-
-        if buffer.trim().to_lowercase().starts_with("op") {
-            let mut parts = buffer.split_whitespace();
-            parts.next();
-
-            if let Some(element) = parts.next() {
-                let uuid = match player::get_uuid(element).await {
-                    Ok(body) => body,
-                    Err(_) => String::from("not found"),
-                };
-                let content = match fs_manager::write_ops_json(
-                    &consts::filepaths::OPERATORS,
-                    uuid.as_str(),
-                    element,
-                    4,
-                    true,
-                ) {
-                    Ok(_) => format!("Made {} a server operator.", element),
-                    Err(e) => format!(
-                        "Failed to make {} as a server operator, error: {} ",
-                        element, e
-                    ),
-                };
-                info!("{}", content);
-            } else {
-                warn!("Missing one argument: op <-")
+    };
+    editor.set_helper(Some(ConsoleHelper));
+    match editor.create_external_printer() {
+        Ok(printer) => logging::set_console_printer(printer),
+        Err(e) => warn!("Failed to print the logs above the console prompt: {e}"),
+    }
+
+    loop {
+        match editor.readline(PROMPT) {
+            Ok(line) => {
+                if let Err(e) = editor.add_history_entry(line.as_str()) {
+                    debug!("Failed to add to the console history: {e}");
+                }
+                if sender.send(line).is_err() {
+                    return;
+                }
+            }
+            // The terminal is in raw mode, so Ctrl+C does not raise SIGINT.
+            Err(ReadlineError::Interrupted) => {
+                info!("Received Ctrl+C, shutting down...");
+                crate::gracefully_exit(0);
+            }
+            Err(ReadlineError::Eof) => return,
+            Err(e) => {
+                warn!("Failed to read the console input: {e}");
+                return;
             }
         }
     }
 }
+
+/// Completes the commands typed in the line editor.
+struct ConsoleHelper;
+
+impl Completer for ConsoleHelper {
+    type Candidate = String;
+
+    fn complete(
+        &self,
+        line: &str,
+        pos: usize,
+        _ctx: &Context<'_>,
+    ) -> rustyline::Result<(usize, Vec<String>)> {
+        let suggestions = COMMANDS.suggest(&line[..pos], MAX_PERMISSION_LEVEL);
+        Ok((suggestions.start, suggestions.matches))
+    }
+}
+
+impl Hinter for ConsoleHelper {
+    type Hint = String;
+}
+
+impl Highlighter for ConsoleHelper {}
+
+impl Validator for ConsoleHelper {}
+
+impl Helper for ConsoleHelper {}
@@ -0,0 +1,705 @@
+//! The command registry. Every command declares its syntaxes, and the input is parsed against
+//! them before the matching handler is executed.
+
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+use super::arguments::{
+    starts_with_ignore_case, ArgumentType, ArgumentValue, Coordinate, StringReader,
+};
+use super::selector::EntitySelector;
+use super::source::CommandSource;
+use crate::config::Gamemode;
+use crate::net::online::OnlinePlayer;
+use crate::player::{self, Profile};
+
+/// The function executing a command, once its arguments were parsed.
+pub type Handler = fn(&mut CommandContext) -> Result<(), CommandError>;
+
+/// What a handler leaves to be done once it returned, like writing a file.
+pub type Save = Box<dyn FnOnce(&mut CommandSource) + Send>;
+
+/// One element of a command's syntax, after its name.
+#[derive(Clone, Debug)]
+pub enum Part {
+    /// A fixed word, like 'add' in 'whitelist add <targets>'.
+    Literal(&'static str),
+    Argument {
+        name: &'static str,
+        kind: ArgumentType,
+    },
+}
+
+/// A fixed word in a command's syntax.
+pub fn literal(word: &'static str) -> Part {
+    Part::Literal(word)
+}
+
+/// An argument in a command's syntax.
+pub fn argument(name: &'static str, kind: ArgumentType) -> Part {
+    Part::Argument { name, kind }
+}
+
+impl Part {
+    fn usage(&self) -> String {
+        match self {
+            Self::Literal(word) => word.to_string(),
+            Self::Argument { name, .. } => format!("<{name}>"),
+        }
+    }
+}
+
+/// One way to call a command, and its handler.
+pub struct Syntax {
+    pub parts: Vec<Part>,
+    pub handler: Handler,
+}
+
+/// A command that can be registered.
+pub struct Command {
+    pub name: &'static str,
+    pub aliases: Vec<&'static str>,
+    pub description: &'static str,
+    /// The permission level required to execute the command, from 0 to 4.
+    pub permission_level: u8,
+    pub syntaxes: Vec<Syntax>,
+}
+
+impl Command {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            aliases: Vec::new(),
+            description: "",
+            permission_level: 0,
+            syntaxes: Vec::new(),
+        }
+    }
+
+    pub fn alias(mut self, alias: &'static str) -> Self {
+        self.aliases.push(alias);
+        self
+    }
+
+    pub fn description(mut self, description: &'static str) -> Self {
+        self.description = description;
+        self
+    }
+
+    pub fn permission(mut self, level: u8) -> Self {
+        self.permission_level = level;
+        self
+    }
+
+    /// Adds a way to call the command. `parts` may be empty for a command without arguments.
+    ///
+    /// # Panics
+    /// If an argument reading the rest of the input is not the last part.
+    pub fn syntax(mut self, parts: Vec<Part>, handler: Handler) -> Self {
+        let greedy = parts.iter().position(|part| match part {
+            Part::Argument { kind, .. } => kind.is_greedy(),
+            Part::Literal(_) => false,
+        });
+        assert!(
+            greedy.is_none_or(|i| i + 1 == parts.len()),
+            "/{}: nothing can follow a greedy argument",
+            self.name
+        );
+        self.syntaxes.push(Syntax { parts, handler });
+        self
+    }
+
+    /// Returns whether the command is called `name`, or has it as an alias.
+    pub fn is_named(&self, name: &str) -> bool {
+        self.name.eq_ignore_ascii_case(name)
+            || self.aliases.iter().any(|a| a.eq_ignore_ascii_case(name))
+    }
+
+    /// Returns one line per syntax, like '/whitelist add <targets>'.
+    pub fn usage(&self) -> Vec<String> {
+        self.syntaxes
+            .iter()
+            .map(|syntax| {
+                let mut line = format!("/{}", self.name);
+                for part in &syntax.parts {
+                    line.push(' ');
+                    line.push_str(&part.usage());
+                }
+                line
+            })
+            .collect()
+    }
+
+    /// Finds how the token being typed at the end of the input could be completed, if the input
+    /// before it matches `syntax`. Returns where the token starts, and the completions.
+    fn suggest_syntax(
+        &self,
+        syntax: &Syntax,
+        mut reader: StringReader,
+    ) -> Option<(usize, Vec<String>)> {
+        for part in &syntax.parts {
+            reader.skip_whitespace();
+
+            let mut attempt = reader.clone();
+            let parsed = match part {
+                Part::Literal(word) => attempt.read_word().eq_ignore_ascii_case(word),
+                Part::Argument { kind, .. } => kind.parse(&mut attempt).is_ok(),
+            };
+            if attempt.can_read() {
+                if !parsed {
+                    return None;
+                }
+                reader = attempt;
+                continue;
+            }
+
+            // This part reaches the end of the input, so it is the one being typed.
+            let prefix = reader.remaining();
+            let matches = match part {
+                Part::Literal(word) => starts_with_ignore_case(word, prefix)
+                    .then(|| word.to_string())
+                    .into_iter()
+                    .collect(),
+                Part::Argument { kind, .. } => kind.suggest(prefix),
+            };
+            return Some((reader.cursor(), matches));
+        }
+
+        None
+    }
+
+    /// Parses the input following the command's name against one of its syntaxes.
+    fn parse_syntax(
+        &self,
+        syntax: &Syntax,
+        mut reader: StringReader,
+    ) -> Result<HashMap<&'static str, ArgumentValue>, Mismatch> {
+        let mut arguments = HashMap::new();
+
+        for (matched, part) in syntax.parts.iter().enumerate() {
+            let mismatch = |message: String| Mismatch {
+                matched,
+                trailing: false,
+                message,
+            };
+
+            reader.skip_whitespace();
+            if !reader.can_read() {
+                return Err(mismatch(match part {
+                    Part::Literal(_) => "Incomplete command".to_string(),
+                    Part::Argument { name, .. } => format!("Missing argument <{name}>"),
+                }));
+            }
+
+            match part {
+                Part::Literal(word) => {
+                    let read = reader.read_word();
+                    if !read.eq_ignore_ascii_case(word) {
+                        return Err(mismatch(format!("Incorrect argument '{read}'")));
+                    }
+                }
+                Part::Argument { name, kind } => {
+                    let value = kind.parse(&mut reader).map_err(mismatch)?;
+                    arguments.insert(*name, value);
+                }
+            }
+        }
+
+        reader.skip_whitespace();
+        if reader.can_read() {
+            return Err(Mismatch {
+                matched: syntax.parts.len(),
+                trailing: true,
+                message: format!("Unexpected '{}'", reader.remaining()),
+            });
+        }
+
+        Ok(arguments)
+    }
+}
+
+/// Why the input did not match a syntax.
+struct Mismatch {
+    /// The number of parts of the syntax that matched, to report the closest syntax.
+    matched: usize,
+    /// Whether every part matched but some input was left, which explains the error less than
+    /// a part that failed at the same position.
+    trailing: bool,
+    message: String,
+}
+
+/// The completions of the token at the end of an input.
+#[derive(Debug, Default, PartialEq)]
+pub struct Suggestions {
+    /// Where the token starts in the input, in bytes.
+    pub start: usize,
+    pub matches: Vec<String>,
+}
+
+/// A command whose input was parsed, ready to be executed.
+pub struct ParsedCommand {
+    handler: Handler,
+    arguments: HashMap<&'static str, ArgumentValue>,
+}
+
+impl ParsedCommand {
+    /// Finds the profiles of the players named in `GameProfile` arguments.
+    pub async fn resolve(&mut self) -> Result<(), CommandError> {
+        for value in self.arguments.values_mut() {
+            if let ArgumentValue::PlayerName(name) = value {
+                let profile = player::get_profile(name)
+                    .await
+                    .map_err(|e| CommandError::Failed(e.to_string()))?;
+                *value = ArgumentValue::Profile(profile);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Executes the command's handler. Returns the saves it queued, see `CommandContext::save`.
+    pub fn execute(self, source: &mut CommandSource) -> Result<Vec<Save>, CommandError> {
+        let mut context = CommandContext {
+            source,
+            arguments: self.arguments,
+            saves: Vec::new(),
+        };
+        (self.handler)(&mut context)?;
+        Ok(context.saves)
+    }
+}
+
+/// What a handler is given: the executor and the arguments.
+pub struct CommandContext<'a> {
+    pub source: &'a mut CommandSource,
+    arguments: HashMap<&'static str, ArgumentValue>,
+    saves: Vec<Save>,
+}
+
+impl CommandContext<'_> {
+    /// Queues `save` to be run once the handler returned, with the executor to report to. Outside
+    /// of the tests, it runs on the I/O thread, so that the main loop never waits for the disk.
+    /// The saves of a handler that fails are dropped, so handlers change nothing before failing.
+    pub fn save(&mut self, save: impl FnOnce(&mut CommandSource) + Send + 'static) {
+        self.saves.push(Box::new(save));
+    }
+
+    /// Returns whether an optional argument was given.
+    pub fn has(&self, name: &str) -> bool {
+        self.arguments.contains_key(name)
+    }
+
+    fn get(&self, name: &str) -> Result<&ArgumentValue, CommandError> {
+        self.arguments
+            .get(name)
+            .ok_or_else(|| CommandError::MissingArgument(name.to_string()))
+    }
+
+    pub fn string(&self, name: &str) -> Result<&str, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::String(s) | ArgumentValue::PlayerName(s) => Ok(s),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    pub fn integer(&self, name: &str) -> Result<i32, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::Integer(i) => Ok(*i),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    pub fn bool(&self, name: &str) -> Result<bool, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::Bool(b) => Ok(*b),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    pub fn profile(&self, name: &str) -> Result<&Profile, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::Profile(profile) => Ok(profile),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    pub fn gamemode(&self, name: &str) -> Result<Gamemode, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::Gamemode(gamemode) => Ok(*gamemode),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    pub fn selector(&self, name: &str) -> Result<&EntitySelector, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::Selector(selector) => Ok(selector),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    /// Returns the online players selected by an entity argument, failing if there are none.
+    pub fn players(&self, name: &str) -> Result<Vec<OnlinePlayer>, CommandError> {
+        let players = self.selector(name)?.select(self.source.uuid.as_deref());
+        if players.is_empty() {
+            return Err(CommandError::Failed("No player was found".to_string()));
+        }
+        Ok(players)
+    }
+
+    pub fn block_pos(&self, name: &str) -> Result<[Coordinate; 3], CommandError> {
+        match self.get(name)? {
+            ArgumentValue::BlockPos(coordinates) => Ok(*coordinates),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+
+    pub fn resource_location(&self, name: &str) -> Result<&str, CommandError> {
+        match self.get(name)? {
+            ArgumentValue::ResourceLocation(location) => Ok(location),
+            _ => Err(CommandError::MissingArgument(name.to_string())),
+        }
+    }
+}
+
+/// Every command the server knows.
+#[derive(Default)]
+pub struct CommandRegistry {
+    commands: Vec<Command>,
+}
+
+impl CommandRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers a command. A command with the same name replaces the previous one.
+    pub fn register(&mut self, command: Command) {
+        self.commands.retain(|c| c.name != command.name);
+        self.commands.push(command);
+    }
+
+    /// Returns a command given its name or one of its aliases, ignoring case.
+    pub fn get(&self, name: &str) -> Option<&Command> {
+        self.commands.iter().find(|c| c.is_named(name))
+    }
+
+    /// Returns the commands that can be executed with the given permission level.
+    pub fn available(&self, permission_level: u8) -> impl Iterator<Item = &Command> {
+        self.commands
+            .iter()
+            .filter(move |c| c.permission_level <= permission_level)
+    }
+
+    /// Parses `input`, with or without a leading slash, without executing it.
+    pub fn parse(&self, input: &str, permission_level: u8) -> Result<ParsedCommand, CommandError> {
+        let input = input.trim();
+        let input = input.strip_prefix('/').unwrap_or(input);
+
+        let mut reader = StringReader::new(input);
+        let name = reader.read_word();
+        let command = self
+            .get(name)
+            .ok_or_else(|| CommandError::Unknown(name.to_string()))?;
+
+        if command.permission_level > permission_level {
+            return Err(CommandError::PermissionDenied(command.name.to_string()));
+        }
+
+        let mut mismatches = Vec::new();
+        for syntax in &command.syntaxes {
+            match command.parse_syntax(syntax, reader.clone()) {
+                Ok(arguments) => {
+                    return Ok(ParsedCommand {
+                        handler: syntax.handler,
+                        arguments,
+                    });
+                }
+                Err(mismatch) => mismatches.push(mismatch),
+            }
+        }
+
+        // The first syntax that went the furthest explains the error, and the usage only shows
+        // the syntaxes that went as far.
+        let Some(closest) = mismatches
+            .iter()
+            .rev()
+            .max_by_key(|m| (m.matched, !m.trailing))
+        else {
+            return Err(CommandError::Syntax {
+                message: "Incorrect command".to_string(),
+                usage: command.usage().join("\n"),
+            });
+        };
+        let usage: Vec<String> = command
+            .usage()
+            .into_iter()
+            .zip(&mismatches)
+            .filter(|(_, m)| m.matched == closest.matched)
+            .map(|(line, _)| line)
+            .collect();
+        Err(CommandError::Syntax {
+            message: closest.message.clone(),
+            usage: usage.join("\n"),
+        })
+    }
+
+    /// Returns the completions of the token at the end of `input`, with or without a leading
+    /// slash, among the commands available with the given permission level.
+    pub fn suggest(&self, input: &str, permission_level: u8) -> Suggestions {
+        let offset = usize::from(input.starts_with('/'));
+        let mut reader = StringReader::new(&input[offset..]);
+        let name = reader.read_word();
+
+        // Still typing the command's name.
+        if !reader.can_read() {
+            let mut matches: Vec<String> = self
+                .available(permission_level)
+                .flat_map(|c| std::iter::once(c.name).chain(c.aliases.iter().copied()))
+                .filter(|n| starts_with_ignore_case(n, name))
+                .map(str::to_string)
+                .collect();
+            matches.sort_unstable();
+            return Suggestions {
+                start: offset,
+                matches,
+            };
+        }
+
+        let Some(command) = self
+            .get(name)
+            .filter(|c| c.permission_level <= permission_level)
+        else {
+            return Suggestions::default();
+        };
+
+        // The syntaxes sharing a start suggest for the same token.
+        let mut suggestions = Suggestions::default();
+        for syntax in &command.syntaxes {
+            if let Some((start, matches)) = command.suggest_syntax(syntax, reader.clone()) {
+                let start = start + offset;
+                if start > suggestions.start {
+                    suggestions = Suggestions {
+                        start,
+                        matches: Vec::new(),
+                    };
+                }
+                if start == suggestions.start {
+                    suggestions.matches.extend(matches);
+                }
+            }
+        }
+        suggestions.matches.sort_unstable();
+        suggestions.matches.dedup();
+        suggestions
+    }
+
+    /// Parses `input` and resolves its arguments, ready to be executed.
+    pub async fn prepare(
+        &self,
+        input: &str,
+        permission_level: u8,
+    ) -> Result<ParsedCommand, CommandError> {
+        let mut command = self.parse(input, permission_level)?;
+        command.resolve().await?;
+        Ok(command)
+    }
+
+    /// Parses and executes `input` as `source`, on the current thread.
+    #[cfg(test)]
+    pub async fn dispatch(
+        &self,
+        input: &str,
+        source: &mut CommandSource,
+    ) -> Result<(), CommandError> {
+        let mut command = self.parse(input, source.permission_level)?;
+        command.resolve().await?;
+        for save in command.execute(source)? {
+            save(source);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum CommandError {
+    #[error("Unknown command: {0}")]
+    Unknown(String),
+    #[error("You do not have permission to use /{0}")]
+    PermissionDenied(String),
+    #[error("{message}, usage:\n{usage}")]
+    Syntax { message: String, usage: String },
+    /// Returned by handlers whose command could not be carried out.
+    #[error("{0}")]
+    Failed(String),
+    /// A handler asked for an argument its syntax does not have.
+    #[error("The command has no argument <{0}>")]
+    MissingArgument(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::source::CapturedOutput;
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register(Command::new("say").alias("broadcast").syntax(
+            vec![argument("message", ArgumentType::Message)],
+            |ctx| {
+                let message = format!("[{}] {}", ctx.source.name, ctx.string("message")?);
+                ctx.source.send(message);
+                Ok(())
+            },
+        ));
+        registry.register(Command::new("add").syntax(
+            vec![
+                argument("a", ArgumentType::integer(0, 100)),
+                argument("b", ArgumentType::integer(0, 100)),
+            ],
+            |ctx| {
+                let sum = ctx.integer("a")? + ctx.integer("b")?;
+                ctx.source.send(sum.to_string());
+                Ok(())
+            },
+        ));
+        registry.register(
+            Command::new("switch")
+                .permission(3)
+                .syntax(vec![literal("on")], |ctx| {
+                    ctx.source.send("on");
+                    Ok(())
+                })
+                .syntax(vec![literal("off")], |_| {
+                    Err(CommandError::Failed("Already off".to_string()))
+                }),
+        );
+        registry.register(
+            Command::new("rule")
+                .permission(2)
+                .syntax(vec![literal("speed")], |ctx| {
+                    ctx.source.send("speed");
+                    Ok(())
+                })
+                .syntax(
+                    vec![
+                        literal("speed"),
+                        argument("value", ArgumentType::integer(0, 10)),
+                    ],
+                    |_| Ok(()),
+                )
+                .syntax(vec![literal("other")], |_| Ok(())),
+        );
+        registry
+    }
+
+    fn run(input: &str, permission_level: u8) -> (Result<(), CommandError>, Vec<String>) {
+        let output = CapturedOutput::default();
+        let mut source = CommandSource::new("tester", permission_level, output.clone());
+        let result = registry()
+            .parse(input, permission_level)
+            .and_then(|command| command.execute(&mut source))
+            .map(|_| ());
+        (result, output.lines())
+    }
+
+    #[test]
+    fn test_dispatch() {
+        assert_eq!(
+            run("say hello  world", 0),
+            (Ok(()), vec!["[tester] hello  world".to_string()])
+        );
+        assert_eq!(run("/BROADCAST hi", 0).1, vec!["[tester] hi"]);
+        assert_eq!(run("add 2 40", 0).1, vec!["42"]);
+        assert_eq!(run("switch on", 4).1, vec!["on"]);
+        assert_eq!(
+            run("switch off", 4).0,
+            Err(CommandError::Failed("Already off".to_string()))
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "nothing can follow a greedy argument")]
+    fn test_greedy_argument_is_last() {
+        Command::new("bad").syntax(
+            vec![argument("message", ArgumentType::Message), literal("end")],
+            |_| Ok(()),
+        );
+    }
+
+    #[test]
+    fn test_unknown_and_permission() {
+        assert_eq!(
+            run("sayy hi", 0).0,
+            Err(CommandError::Unknown("sayy".to_string()))
+        );
+        // Only whole names match.
+        assert!(matches!(
+            run("switchon", 4).0,
+            Err(CommandError::Unknown(_))
+        ));
+        assert_eq!(
+            run("switch on", 2).0,
+            Err(CommandError::PermissionDenied("switch".to_string()))
+        );
+        assert_eq!(registry().available(0).count(), 2);
+    }
+
+    #[test]
+    fn test_usage_errors() {
+        let syntax_error = |input: &str| match run(input, 4).0 {
+            Err(CommandError::Syntax { message, usage }) => (message, usage),
+            other => panic!("expected a syntax error for '{input}', got {other:?}"),
+        };
+
+        assert_eq!(
+            syntax_error("add 1"),
+            (
+                "Missing argument <b>".to_string(),
+                "/add <a> <b>".to_string()
+            )
+        );
+        assert_eq!(
+            syntax_error("add 1 101").0,
+            "Integer must not be more than 100, found 101"
+        );
+        assert_eq!(syntax_error("add 1 2 3").0, "Unexpected '3'");
+        assert_eq!(syntax_error("say").0, "Missing argument <message>");
+
+        let (message, usage) = syntax_error("switch maybe");
+        assert_eq!(message, "Incorrect argument 'maybe'");
+        assert_eq!(usage, "/switch on\n/switch off");
+        assert_eq!(syntax_error("switch").0, "Incomplete command");
+
+        // The failing argument explains more than the syntax without it.
+        let (message, usage) = syntax_error("rule speed 11");
+        assert_eq!(message, "Integer must not be more than 10, found 11");
+        assert_eq!(usage, "/rule speed\n/rule speed <value>");
+        assert_eq!(run("rule SPEED", 4), (Ok(()), vec!["speed".to_string()]));
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let registry = registry();
+        let suggest = |input: &str, level: u8| {
+            let suggestions = registry.suggest(input, level);
+            (suggestions.start, suggestions.matches)
+        };
+
+        assert_eq!(
+            suggest("/s", 4),
+            (1, vec!["say".to_string(), "switch".to_string()])
+        );
+        // Commands above the permission level are hidden.
+        assert_eq!(suggest("s", 0), (0, vec!["say".to_string()]));
+        assert_eq!(
+            suggest("switch ", 4),
+            (7, vec!["off".to_string(), "on".to_string()])
+        );
+        assert_eq!(suggest("/switch o", 4).0, 8);
+        assert_eq!(suggest("switch of", 4).1, ["off"]);
+        assert!(suggest("switch maybe ", 4).1.is_empty());
+        assert!(suggest("add 1 ", 4).1.is_empty());
+        assert!(suggest("unknown ", 4).1.is_empty());
+    }
+}
@@ -0,0 +1,239 @@
+//! The Brigadier command graph, which the clients use to highlight and complete commands.
+//! It is built from the registry for a permission level, and sent in the Declare Commands packet.
+//! See https://wiki.vg/Command_Data
+
+use super::arguments::ArgumentType;
+use super::dispatcher::{CommandRegistry, Part};
+use crate::packet::data_types::{string, varint};
+
+/// The node types, in the 2 lowest bits of a node's flags.
+const ROOT: u8 = 0;
+const LITERAL: u8 = 1;
+const ARGUMENT: u8 = 2;
+/// Set when the command can be executed once this node is reached.
+const EXECUTABLE: u8 = 0x04;
+/// Set when the node redirects to another one, like an alias to its command.
+const HAS_REDIRECT: u8 = 0x08;
+/// Set when the argument has a suggestions type.
+const HAS_SUGGESTIONS_TYPE: u8 = 0x10;
+
+/// The suggestions type making the clients send Command Suggestions Requests.
+const ASK_SERVER: &str = "minecraft:ask_server";
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum NodeKind {
+    Root,
+    Literal(&'static str),
+    Argument {
+        name: &'static str,
+        kind: ArgumentType,
+    },
+}
+
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub kind: NodeKind,
+    pub executable: bool,
+    /// The indices of the children in the graph.
+    pub children: Vec<usize>,
+    pub redirect: Option<usize>,
+}
+
+impl Node {
+    fn new(kind: NodeKind) -> Self {
+        Self {
+            kind,
+            executable: false,
+            children: Vec::new(),
+            redirect: None,
+        }
+    }
+}
+
+/// The commands available at a permission level, as a tree of nodes. The root is the first node.
+#[derive(Debug)]
+pub struct CommandGraph {
+    nodes: Vec<Node>,
+}
+
+impl CommandGraph {
+    /// Builds the graph of the commands of `registry` which can be executed with
+    /// `permission_level`. Syntaxes starting the same way share their nodes, and aliases are
+    /// literals redirecting to their command.
+    pub fn build(registry: &CommandRegistry, permission_level: u8) -> Self {
+        let mut graph = Self {
+            nodes: vec![Node::new(NodeKind::Root)],
+        };
+
+        for command in registry.available(permission_level) {
+            let command_node = graph.child(0, NodeKind::Literal(command.name));
+
+            for syntax in &command.syntaxes {
+                let mut node = command_node;
+                for part in &syntax.parts {
+                    let kind = match part {
+                        Part::Literal(word) => NodeKind::Literal(word),
+                        Part::Argument { name, kind } => NodeKind::Argument {
+                            name,
+                            kind: kind.clone(),
+                        },
+                    };
+                    node = graph.child(node, kind);
+                }
+                graph.nodes[node].executable = true;
+            }
+
+            for alias in &command.aliases {
+                let alias_node = graph.child(0, NodeKind::Literal(alias));
+                graph.nodes[alias_node].redirect = Some(command_node);
+            }
+        }
+
+        graph
+    }
+
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    /// Returns the child of `parent` of the given kind, adding it if needed.
+    fn child(&mut self, parent: usize, kind: NodeKind) -> usize {
+        let existing = self.nodes[parent]
+            .children
+            .iter()
+            .copied()
+            .find(|&child| self.nodes[child].kind == kind);
+        if let Some(child) = existing {
+            return child;
+        }
+
+        self.nodes.push(Node::new(kind));
+        let child = self.nodes.len() - 1;
+        self.nodes[parent].children.push(child);
+        child
+    }
+
+    /// Encodes the payload of the Declare Commands packet: the nodes, then the index of the root.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut payload = varint::write(self.nodes.len() as i32);
+
+        for node in &self.nodes {
+            let mut flags = match node.kind {
+                NodeKind::Root => ROOT,
+                NodeKind::Literal(_) => LITERAL,
+                NodeKind::Argument { .. } => ARGUMENT,
+            };
+            if node.executable {
+                flags |= EXECUTABLE;
+            }
+            if node.redirect.is_some() {
+                flags |= HAS_REDIRECT;
+            }
+            if matches!(&node.kind, NodeKind::Argument { kind, .. } if kind.asks_server()) {
+                flags |= HAS_SUGGESTIONS_TYPE;
+            }
+            payload.push(flags);
+
+            payload.extend(varint::write(node.children.len() as i32));
+            for &child in &node.children {
+                payload.extend(varint::write(child as i32));
+            }
+            if let Some(redirect) = node.redirect {
+                payload.extend(varint::write(redirect as i32));
+            }
+
+            match &node.kind {
+                NodeKind::Root => {}
+                NodeKind::Literal(name) => payload.extend(string::write(name)),
+                NodeKind::Argument { name, kind } => {
+                    payload.extend(string::write(name));
+                    kind.write_parser(&mut payload);
+                    if kind.asks_server() {
+                        payload.extend(string::write(ASK_SERVER));
+                    }
+                }
+            }
+        }
+
+        payload.extend(varint::write(0));
+        payload
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::dispatcher::{argument, literal, Command};
+
+    fn registry() -> CommandRegistry {
+        let mut registry = CommandRegistry::new();
+        registry.register(
+            Command::new("say")
+                .alias("tell")
+                .syntax(vec![argument("message", ArgumentType::Message)], |_| Ok(())),
+        );
+        registry.register(
+            Command::new("time")
+                .permission(2)
+                .syntax(vec![literal("query")], |_| Ok(()))
+                .syntax(
+                    vec![
+                        literal("set"),
+                        argument("time", ArgumentType::integer(0, 24000)),
+                    ],
+                    |_| Ok(()),
+                )
+                .syntax(vec![literal("set"), literal("day")], |_| Ok(())),
+        );
+        registry
+    }
+
+    #[test]
+    fn test_shared_nodes() {
+        let graph = CommandGraph::build(&registry(), 4);
+        let nodes = graph.nodes();
+
+        // root, say, <message>, tell, time, query, set, <time>, day
+        assert_eq!(nodes.len(), 9);
+        assert_eq!(nodes[0].children, [1, 3, 4]);
+        assert_eq!(nodes[3].redirect, Some(1));
+        assert_eq!(nodes[4].children, [5, 6]);
+        assert_eq!(nodes[6].children, [7, 8]);
+        assert!(!nodes[4].executable && !nodes[6].executable);
+        assert!(nodes[7].executable && nodes[8].executable);
+    }
+
+    #[test]
+    fn test_encode() {
+        // Without the permission level for /time.
+        let graph = CommandGraph::build(&registry(), 0);
+
+        #[rustfmt::skip]
+        let expected = [
+            4, // Node count
+            0x00, 2, 1, 3, // Root, children 1 and 3
+            0x01, 1, 2, 3, b's', b'a', b'y', // Literal 'say', child 2
+            0x06, 0, 7, b'm', b'e', b's', b's', b'a', b'g', b'e', 19, // Executable argument, minecraft:message
+            0x09, 0, 1, 4, b't', b'e', b'l', b'l', // Literal 'tell', redirecting to 1
+            0, // Root index
+        ];
+        assert_eq!(graph.encode(), expected);
+    }
+
+    #[test]
+    fn test_ask_server() {
+        let mut registry = CommandRegistry::new();
+        registry.register(
+            Command::new("op").syntax(vec![argument("targets", ArgumentType::GameProfile)], |_| {
+                Ok(())
+            }),
+        );
+
+        let payload = CommandGraph::build(&registry, 0).encode();
+        let mut expected = vec![0x16, 0, 7];
+        expected.extend(b"targets");
+        expected.push(7);
+        expected.extend(string::write(ASK_SERVER));
+        assert!(payload.windows(expected.len()).any(|w| w == expected));
+    }
+}
@@ -1,9 +1,172 @@
+//! This module is about the server's commands: how they are declared, parsed and executed.
+
+pub mod arguments;
+mod builtin;
 mod command_line;
+pub mod dispatcher;
+pub mod graph;
+pub mod selector;
+pub mod source;
+
+use std::sync::mpsc::{self, Sender};
+use std::thread;
+
+use dispatcher::{CommandRegistry, ParsedCommand};
+use once_cell::sync::Lazy;
+use source::CommandSource;
+use tokio::sync::oneshot;
+
+use crate::tick::{Task, TaskQueue};
+
+/// Every command of the server.
+pub static COMMANDS: Lazy<CommandRegistry> = Lazy::new(builtin::registry);
+
+/// Runs the saves of the commands one after the other, so that the main loop never waits for the
+/// disk.
+static IO_WORKER: Lazy<Sender<Task>> = Lazy::new(|| {
+    let (sender, receiver) = mpsc::channel::<Task>();
+    thread::Builder::new()
+        .name("IO-Worker".to_string())
+        .spawn(move || receiver.into_iter().for_each(|task| task()))
+        .expect("Failed to start the I/O thread");
+    sender
+});
 
-// TODO: I'll need to implement the 'Command Pattern' here.
-// TODO: I'll also need to implement a sort of queue that stores all received commands.
+/// Parses `input` on the current task, then queues it to be executed on `tasks`, the main
+/// loop's outside of the tests, after the commands submitted before it. What the command saves is
+/// then handed to the I/O thread. Errors are sent to `source`.
+/// The returned receiver completes once the command and its saves are done, or it was rejected.
+pub async fn submit(
+    tasks: &TaskQueue,
+    input: &str,
+    source: CommandSource,
+) -> oneshot::Receiver<()> {
+    let (done, receiver) = oneshot::channel();
+
+    match COMMANDS.prepare(input, source.permission_level).await {
+        Ok(command) => tasks.push(move || execute(command, source, done)),
+        Err(e) => {
+            source.send_error(e.to_string());
+            let _ = done.send(());
+        }
+    }
+
+    receiver
+}
+
+fn execute(command: ParsedCommand, mut source: CommandSource, done: oneshot::Sender<()>) {
+    match command.execute(&mut source) {
+        Ok(saves) if !saves.is_empty() => {
+            // The thread lives as long as the server.
+            let _ = IO_WORKER.send(Box::new(move || {
+                for save in saves {
+                    save(&mut source);
+                }
+                let _ = done.send(());
+            }));
+            return;
+        }
+        Ok(_) => {}
+        Err(e) => source.send_error(e.to_string()),
+    }
+    let _ = done.send(());
+}
 
 // Initializes the listening for cli commands
 pub async fn listen_console_commands() {
     tokio::spawn(command_line::handle_input());
 }
+
+#[cfg(test)]
+mod tests {
+    use super::source::{CapturedOutput, CommandSource};
+    use super::*;
+
+    #[tokio::test]
+    async fn test_help() {
+        let output = CapturedOutput::default();
+        let mut player = CommandSource::new("player", 0, output.clone());
+
+        COMMANDS.dispatch("help", &mut player).await.unwrap();
+        let lines = output.lines();
+        assert!(lines.contains(&"/list".to_string()));
+        // Operator commands are hidden from regular players.
+        assert!(!lines.iter().any(|line| line.starts_with("/op ")));
+
+        let output = CapturedOutput::default();
+        let mut console = CommandSource::new("console", 4, output.clone());
+        COMMANDS
+            .dispatch("/? whitelist", &mut console)
+            .await
+            .unwrap();
+        assert!(output
+            .lines()
+            .contains(&"/whitelist add <targets>".to_string()));
+    }
+
+    #[tokio::test]
+    async fn test_op_requires_whole_name() {
+        let mut console = CommandSource::new("console", 4, CapturedOutput::default());
+        assert!(COMMANDS
+            .dispatch("operator Notch", &mut console)
+            .await
+            .is_err());
+        assert!(matches!(
+            COMMANDS.parse("op", 4),
+            Err(dispatcher::CommandError::Syntax { .. })
+        ));
+    }
+
+    #[test]
+    fn test_player_selectors() {
+        assert!(COMMANDS.parse("kick @a[name=!Notch] Too loud", 3).is_ok());
+        match COMMANDS.parse("kick @e", 3) {
+            Err(dispatcher::CommandError::Syntax { message, .. }) => {
+                assert!(message.starts_with("Only players may be affected"))
+            }
+            _ => panic!("@e should be refused"),
+        }
+    }
+
+    #[test]
+    fn test_ban_durations() {
+        assert!(COMMANDS.parse("tempban Notch 7d Griefing", 3).is_ok());
+        assert!(COMMANDS.parse("tempban-ip 10.0.0.0/8 12h", 3).is_ok());
+        assert!(COMMANDS.parse("tempban Notch", 3).is_err());
+        // Without tempban, the whole message is the reason.
+        assert!(COMMANDS.parse("ban Notch 5m of griefing", 3).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_submit() {
+        let tasks = TaskQueue::new();
+        let console = |output: &CapturedOutput| CommandSource::new("console", 4, output.clone());
+
+        // Errors are reported right away.
+        let output = CapturedOutput::default();
+        let done = submit(&tasks, "unknown", console(&output)).await;
+        assert!(done.await.is_ok());
+        assert_eq!(output.lines().len(), 1);
+
+        // Valid commands wait for the main loop.
+        let output = CapturedOutput::default();
+        let mut done = submit(&tasks, "help list", console(&output)).await;
+        assert!(done.try_recv().is_err());
+        assert_eq!(tasks.run_pending(), 1);
+        assert!(done.await.is_ok());
+        assert_eq!(output.lines(), ["/list"]);
+
+        // What they save is then written on the I/O thread.
+        let output = CapturedOutput::default();
+        let done = submit(&tasks, "save-all", console(&output)).await;
+        assert_eq!(tasks.run_pending(), 1);
+        assert!(done.await.is_ok());
+        assert_eq!(
+            output.lines(),
+            [
+                "Saving the game (this may take a moment!)",
+                "Saved the game"
+            ]
+        );
+    }
+}
@@ -0,0 +1,228 @@
+//! Entity selectors: a player name, a UUID, or a target selector like `@a[name=Notch]`.
+//! As the server has no entities but players yet, selectors only ever select online players.
+
+use rand::seq::SliceRandom;
+
+use super::arguments::StringReader;
+use crate::net::online::{OnlinePlayer, ONLINE_PLAYERS};
+use crate::packet::data_types::uuid;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum EntitySelector {
+    Name(String),
+    /// A hyphenated UUID.
+    Uuid(String),
+    /// A target selector: `@p`, `@r`, `@a`, `@e`, `@s` or `@n`, and its arguments.
+    Variable {
+        variable: char,
+        arguments: Vec<(String, String)>,
+    },
+}
+
+impl EntitySelector {
+    /// Reads a selector. `single` refuses selectors which may select several entities, and
+    /// `players_only` refuses selectors which may select other entities than players.
+    pub fn parse(
+        reader: &mut StringReader,
+        single: bool,
+        players_only: bool,
+    ) -> Result<Self, String> {
+        if reader.peek() != Some('@') {
+            let word = reader.read_word();
+            if let Some(uuid) = uuid::from_str(word) {
+                return Ok(Self::Uuid(uuid::to_string(uuid)));
+            }
+            if word.is_empty() || word.len() > 16 {
+                return Err("Invalid name or UUID".to_string());
+            }
+            return Ok(Self::Name(word.to_string()));
+        }
+
+        reader.read_char();
+        let variable = match reader.read_char() {
+            Some(c @ ('p' | 'r' | 'a' | 'e' | 's' | 'n')) => c,
+            Some(c) => return Err(format!("Unknown selector type '@{c}'")),
+            None => return Err("Missing selector type".to_string()),
+        };
+
+        let arguments = if reader.peek() == Some('[') {
+            parse_arguments(reader)?
+        } else {
+            Vec::new()
+        };
+
+        let selector = Self::Variable {
+            variable,
+            arguments,
+        };
+        if single && !selector.is_single() {
+            return Err(
+                "Only one entity is allowed, but the provided selector allows more than one"
+                    .to_string(),
+            );
+        }
+        if players_only && !selector.is_players_only() {
+            return Err("Only players may be affected by this command, but the provided selector includes entities".to_string());
+        }
+
+        Ok(selector)
+    }
+
+    fn argument(&self, key: &str) -> Option<&str> {
+        match self {
+            Self::Variable { arguments, .. } => arguments
+                .iter()
+                .find(|(k, _)| k == key)
+                .map(|(_, v)| v.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Returns whether the selector selects one entity at most.
+    pub fn is_single(&self) -> bool {
+        match self {
+            Self::Variable { variable, .. } => {
+                matches!(variable, 'p' | 'r' | 's' | 'n') || self.argument("limit") == Some("1")
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns whether the selector selects players only.
+    pub fn is_players_only(&self) -> bool {
+        match self {
+            Self::Variable { variable, .. } => {
+                !matches!(variable, 'e' | 'n')
+                    || matches!(self.argument("type"), Some("player" | "minecraft:player"))
+            }
+            _ => true,
+        }
+    }
+
+    /// Returns the online players selected. `executor` is the UUID of the player executing
+    /// the command, if it is a player, which `@s` selects.
+    ///
+    /// Only the `name` and `limit` arguments are applied. As players have no position yet, `@p`
+    /// and `@n` select the first player by name.
+    pub fn select(&self, executor: Option<&str>) -> Vec<OnlinePlayer> {
+        let mut players = ONLINE_PLAYERS.list();
+        players.sort_unstable_by_key(|p| p.name.to_lowercase());
+
+        let variable = match self {
+            Self::Name(name) => {
+                players.retain(|p| p.name.eq_ignore_ascii_case(name));
+                return players;
+            }
+            Self::Uuid(uuid) => {
+                players.retain(|p| &p.uuid == uuid);
+                return players;
+            }
+            Self::Variable { variable, .. } => *variable,
+        };
+
+        if variable == 's' {
+            players.retain(|p| Some(p.uuid.as_str()) == executor);
+        }
+        if let Some(name) = self.argument("name") {
+            match name.strip_prefix('!') {
+                Some(name) => players.retain(|p| !p.name.eq_ignore_ascii_case(name)),
+                None => players.retain(|p| p.name.eq_ignore_ascii_case(name)),
+            }
+        }
+        if variable == 'r' {
+            players.shuffle(&mut rand::thread_rng());
+        }
+
+        let limit = match variable {
+            'p' | 'r' | 's' | 'n' => 1,
+            _ => usize::MAX,
+        };
+        let limit = self
+            .argument("limit")
+            .and_then(|limit| limit.parse().ok())
+            .unwrap_or(limit);
+        players.truncate(limit);
+        players
+    }
+}
+
+/// Reads the arguments of a target selector, like `[name=Notch,limit=1]`.
+fn parse_arguments(reader: &mut StringReader) -> Result<Vec<(String, String)>, String> {
+    reader.read_char();
+    let mut arguments = Vec::new();
+
+    loop {
+        reader.skip_whitespace();
+        if reader.peek() == Some(']') {
+            reader.read_char();
+            return Ok(arguments);
+        }
+
+        let key = reader.read_until(&['=', ',', ']']).trim().to_string();
+        if key.is_empty() || reader.read_char() != Some('=') {
+            return Err("Expected an argument like key=value in the selector".to_string());
+        }
+
+        reader.skip_whitespace();
+        let value = if reader.peek() == Some('"') {
+            reader.read_string()?
+        } else {
+            reader.read_until(&[',', ']']).trim().to_string()
+        };
+        arguments.push((key, value));
+
+        reader.skip_whitespace();
+        match reader.read_char() {
+            Some(',') => {}
+            Some(']') => return Ok(arguments),
+            _ => return Err("Expected end of options".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &str, single: bool, players_only: bool) -> Result<EntitySelector, String> {
+        EntitySelector::parse(&mut StringReader::new(input), single, players_only)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            parse("Notch", true, true),
+            Ok(EntitySelector::Name("Notch".to_string()))
+        );
+        assert_eq!(
+            parse("069a79f4-44e9-4726-a5be-fca90e38aaf5", true, true),
+            Ok(EntitySelector::Uuid(
+                "069a79f4-44e9-4726-a5be-fca90e38aaf5".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("@a[name=\"jeb_\", limit=2]", false, true),
+            Ok(EntitySelector::Variable {
+                variable: 'a',
+                arguments: vec![
+                    ("name".to_string(), "jeb_".to_string()),
+                    ("limit".to_string(), "2".to_string())
+                ],
+            })
+        );
+
+        assert!(parse("@x", false, false).is_err());
+        assert!(parse("@a[name=Notch", false, false).is_err());
+        assert!(parse("@a[=Notch]", false, false).is_err());
+    }
+
+    #[test]
+    fn test_restrictions() {
+        assert!(parse("@a", true, true).is_err());
+        assert!(parse("@a[limit=1]", true, true).is_ok());
+        assert!(parse("@p", true, true).is_ok());
+        assert!(parse("@e", false, true).is_err());
+        assert!(parse("@e[type=player]", false, true).is_ok());
+        assert!(parse("@e", false, false).is_ok());
+    }
+}
@@ -0,0 +1,112 @@
+//! Who executes a command, and where its output goes.
+
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+
+use crate::net::online::ONLINE_PLAYERS;
+
+/// The name of the console, e.g. in the ban lists.
+pub const CONSOLE_NAME: &str = "Server";
+
+/// The highest permission level, which the console has.
+pub const MAX_PERMISSION_LEVEL: u8 = 4;
+
+/// Where the feedback of a command is sent.
+pub trait CommandOutput: Send {
+    fn send(&self, message: &str);
+
+    /// Sends why a command failed.
+    fn send_error(&self, message: &str) {
+        self.send(message);
+    }
+}
+
+/// Logs the feedback, for commands typed in the console.
+pub struct ConsoleOutput;
+
+impl CommandOutput for ConsoleOutput {
+    fn send(&self, message: &str) {
+        info!("{message}");
+    }
+
+    fn send_error(&self, message: &str) {
+        warn!("{message}");
+    }
+}
+
+/// Shows the feedback to an online player, as system messages.
+pub struct PlayerOutput {
+    pub uuid: String,
+}
+
+impl CommandOutput for PlayerOutput {
+    fn send(&self, message: &str) {
+        ONLINE_PLAYERS.send_message(&self.uuid, message);
+    }
+}
+
+/// Keeps the feedback, so that it can be read once the command was executed.
+#[derive(Clone, Default)]
+pub struct CapturedOutput(Arc<Mutex<Vec<String>>>);
+
+impl CapturedOutput {
+    /// Returns the lines of feedback received so far.
+    pub fn lines(&self) -> Vec<String> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+impl CommandOutput for CapturedOutput {
+    fn send(&self, message: &str) {
+        self.0.lock().unwrap().push(message.to_string());
+    }
+}
+
+/// The executor of a command.
+pub struct CommandSource {
+    pub name: String,
+    /// The UUID of the executor, when it is a player.
+    pub uuid: Option<String>,
+    pub permission_level: u8,
+    output: Box<dyn CommandOutput>,
+}
+
+impl CommandSource {
+    pub fn new(name: &str, permission_level: u8, output: impl CommandOutput + 'static) -> Self {
+        Self {
+            name: name.to_string(),
+            uuid: None,
+            permission_level,
+            output: Box::new(output),
+        }
+    }
+
+    /// A player, whose permission level is their operator level.
+    pub fn player(
+        uuid: &str,
+        name: &str,
+        permission_level: u8,
+        output: impl CommandOutput + 'static,
+    ) -> Self {
+        Self {
+            uuid: Some(uuid.to_string()),
+            ..Self::new(name, permission_level, output)
+        }
+    }
+
+    /// The server console, which may execute every command.
+    pub fn console() -> Self {
+        Self::new(CONSOLE_NAME, MAX_PERMISSION_LEVEL, ConsoleOutput)
+    }
+
+    /// Sends feedback to the executor.
+    pub fn send(&self, message: impl AsRef<str>) {
+        self.output.send(message.as_ref());
+    }
+
+    /// Tells the executor why their command failed.
+    pub fn send_error(&self, message: impl AsRef<str>) {
+        self.output.send_error(message.as_ref());
+    }
+}
@@ -1,14 +1,15 @@
 //! This module is the interface between the server.properties file. Querying for server settings.
-// !TODO generator_settings
 // !Todo text-filtering-config
 // use dot_properties::{read_properties, Properties};
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufReader;
 use std::io::{Error, ErrorKind};
-use std::net::Ipv4Addr;
+use std::net::{Ipv4Addr, SocketAddr};
 use std::path::Path;
 
 use read_properties::Properties;
+
+use crate::world::region::Compression;
 pub mod read_properties;
 //use std::sync::Arc;
 
@@ -28,17 +29,100 @@ pub mod read_properties;
 /// println!("{max_players}");
 /// ```
 ///
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Difficulty {
+    PEACEFUL,
     EASY,
     NORMAL,
     HARD,
 }
+
+impl Difficulty {
+    /// Returns the ID of the difficulty, in 'level.dat' and in packets.
+    pub fn id(&self) -> i8 {
+        match self {
+            Self::PEACEFUL => 0,
+            Self::EASY => 1,
+            Self::NORMAL => 2,
+            Self::HARD => 3,
+        }
+    }
+
+    pub fn from_id(id: i8) -> Option<Self> {
+        match id {
+            0 => Some(Self::PEACEFUL),
+            1 => Some(Self::EASY),
+            2 => Some(Self::NORMAL),
+            3 => Some(Self::HARD),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum Gamemode {
     ADVENTURE,
     SURVIVAL,
     CREATIVE,
     SPECTATOR,
 }
+
+impl Gamemode {
+    pub const ALL: [Self; 4] = [
+        Self::SURVIVAL,
+        Self::CREATIVE,
+        Self::ADVENTURE,
+        Self::SPECTATOR,
+    ];
+
+    /// Returns the gamemode given its name, like in the 'server.properties' file and in commands.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_lowercase().as_str() {
+            "survival" => Some(Self::SURVIVAL),
+            "creative" => Some(Self::CREATIVE),
+            "adventure" => Some(Self::ADVENTURE),
+            "spectator" => Some(Self::SPECTATOR),
+            _ => None,
+        }
+    }
+
+    /// Returns the name of the gamemode, like in the 'server.properties' file and in commands.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SURVIVAL => "survival",
+            Self::CREATIVE => "creative",
+            Self::ADVENTURE => "adventure",
+            Self::SPECTATOR => "spectator",
+        }
+    }
+
+    /// Returns the ID of the gamemode, in 'level.dat' and in packets.
+    pub fn id(&self) -> i32 {
+        match self {
+            Self::SURVIVAL => 0,
+            Self::CREATIVE => 1,
+            Self::ADVENTURE => 2,
+            Self::SPECTATOR => 3,
+        }
+    }
+
+    pub fn from_id(id: i32) -> Option<Self> {
+        Self::ALL.into_iter().find(|gamemode| gamemode.id() == id)
+    }
+}
+
+/// Returns the seed of a 'level-seed', which is either a number or hashed like a Java string.
+pub fn parse_seed(seed: &str) -> i64 {
+    seed.parse::<i64>().unwrap_or_else(|_| {
+        // String.hashCode: s[0]*31^(n-1) + ... + s[n-1], over the UTF-16 code units.
+        seed.encode_utf16()
+            .fold(0i32, |hash, unit| {
+                hash.wrapping_mul(31).wrapping_add(unit.into())
+            })
+            .into()
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum WorlPreset {
     NORMAL,
     FLAT,
@@ -48,78 +132,136 @@ pub enum WorlPreset {
 }
 
 pub struct Settings {
-    enable_jmx_monitoring: bool,
-    rcon_port: u16,
-    level_seed: Option<i64>,
+    pub accepts_transfers: bool,
+    pub enable_jmx_monitoring: bool,
+    pub rcon_port: u16,
+    pub level_seed: Option<i64>,
     pub gamemode: Gamemode,
     enable_command_block: bool,
-    enable_query: bool,
+    pub enable_query: bool,
     enforce_secure_profile: bool,
-    level_name: Option<String>,
-    motd: Option<String>,
-    query_port: u16,
+    pub level_name: Option<String>,
+    pub motd: Option<String>,
+    pub query_port: u16,
     pvp: bool,
-    generate_structures: bool,
+    pub generate_structures: bool,
     max_chained_neighbor_updates: Option<i32>,
-    difficulty: Difficulty,
+    pub difficulty: Difficulty,
     network_compression_threshold: i32,
     max_tick_time: i64,
     require_resource_pack: bool,
     use_native_transport: bool,
-    max_players: u32,
+    pub max_players: u32,
     online_mode: bool,
     enable_status: bool,
     allow_flight: bool,
     initial_disabled_packs: Option<String>,
-    broadcast_rcon_to_ops: bool,
-    view_distance: u8,
+    pub broadcast_rcon_to_ops: bool,
+    pub view_distance: u8,
     pub server_ip: Option<Ipv4Addr>,
     resource_pack_prompt: Option<String>,
-    allow_nether: bool,
+    pub allow_nether: bool,
     pub server_port: u16,
-    enable_rcon: bool,
+    pub enable_rcon: bool,
     sync_chunk_writes: bool,
-    op_permission_level: u8,
+    pub op_permission_level: u8,
     prevent_proxy_connections: bool,
-    hide_online_players: bool,
+    pub hide_online_players: bool,
     resource_pack: Option<String>,
     entity_broadcast_range_percentage: u8,
-    simulation_distance: u8,
-    rcon_password: Option<String>,
+    pub simulation_distance: u8,
+    pub rcon_password: Option<String>,
     player_idle_timeout: i32,
     force_gamemode: bool,
     rate_limit: u32,
-    hardcore: bool,
-    white_list: bool,
+    pub hardcore: bool,
+    pub white_list: bool,
     broadcast_console_to_ops: bool,
     spawn_npcs: bool,
     spawn_animals: bool,
     log_ips: bool,
     function_permission_level: u8,
     initial_enabled_packs: String,
-    level_type: WorlPreset,
+    pub level_type: WorlPreset,
     spawn_monsters: bool,
-    enforce_whitelist: bool,
+    pub enforce_whitelist: bool,
     spawn_protection: u16,
     resource_pack_sha1: Option<String>,
     max_world_size: u32,
-    //generator_settings:todo!(),
+    /// How the chunks are compressed in the region files.
+    pub region_file_compression: Compression,
+    /// Serves the metrics over HTTP, see `metrics`.
+    pub enable_metrics: bool,
+    pub metrics_port: u16,
+    /// Where the management interface listens, see `management`.
+    pub management_port: u16,
+    /// The JSON settings of the superflat worlds.
+    pub generator_settings: String,
     //text_filtering_config:todo!(),
 }
 
-fn read(filepath: &Path) -> std::io::Result<Properties> {
+pub fn read(filepath: &Path) -> std::io::Result<Properties> {
     let file = File::open(filepath)?;
     let mut reader = BufReader::new(file);
     return read_properties::read_properties(&mut reader)
         .map_err(|e| Error::new(ErrorKind::Other, e.to_string()));
 }
 
+/// Reads a property of 'server.properties' without loading the settings, e.g. before the file
+/// is created. Returns `None` if the file or the property does not exist.
+pub fn read_property(key: &str) -> Option<String> {
+    let properties = read(Path::new(crate::consts::filepaths::PROPERTIES)).ok()?;
+    properties.get_property(key).ok().map(str::to_string)
+}
+
+/// Changes the value of a property in a properties file, keeping every other line untouched.
+/// The property is appended if it is not in the file yet.
+pub fn set_property(filepath: &Path, key: &str, value: &str) -> std::io::Result<()> {
+    let content = fs::read_to_string(filepath)?;
+    let mut found = false;
+
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            let is_comment =
+                line.trim_start().starts_with('#') || line.trim_start().starts_with('!');
+            match line.split_once('=') {
+                Some((field, _)) if !is_comment && field.trim() == key => {
+                    found = true;
+                    format!("{key}={value}")
+                }
+                _ => line.to_string(),
+            }
+        })
+        .collect();
+
+    if !found {
+        lines.push(format!("{key}={value}"));
+    }
+
+    fs::write(filepath, lines.join("\n") + "\n")
+}
+
 impl Settings {
     pub fn new() -> Self {
-        let config_file = read(Path::new(crate::consts::filepaths::PROPERTIES))
-            .expect("Error reading {server.properties} file");
+        Self::from_file(Path::new(crate::consts::filepaths::PROPERTIES))
+    }
+
+    /// The address to listen on with `port`: 'server-ip', or every interface if it is not set.
+    pub fn bind_address(&self, port: u16) -> SocketAddr {
+        SocketAddr::from((self.server_ip.unwrap_or(Ipv4Addr::UNSPECIFIED), port))
+    }
+
+    /// Reads the settings of a properties file, which must have every property.
+    pub fn from_file(path: &Path) -> Self {
+        let config_file = read(path).expect("Error reading {server.properties} file");
 
         Self {
+            accepts_transfers: config_file
+                .get_property("accepts-transfers")
+                .unwrap()
+                .parse::<bool>()
+                .unwrap(),
             enable_jmx_monitoring: config_file
                 .get_property("enable-jmx-monitoring")
                 .unwrap()
@@ -132,9 +274,14 @@ impl Settings {
                 .unwrap(),
             level_seed: match config_file.get_property("level-seed").unwrap() {
                 "" => None,
-                s => Some(s.parse::<i64>().unwrap()),
+                s => Some(parse_seed(s)),
             },
-            gamemode: match config_file.get_property("gamemode").unwrap().to_lowercase().as_str() {
+            gamemode: match config_file
+                .get_property("gamemode")
+                .unwrap()
+                .to_lowercase()
+                .as_str()
+            {
                 "creative" => Gamemode::CREATIVE,
                 "survival" => Gamemode::SURVIVAL,
                 "spectator" => Gamemode::SPECTATOR,
@@ -187,6 +334,7 @@ impl Settings {
                 s => Some(s.parse::<i32>().unwrap()),
             },
             difficulty: match config_file.get_property("difficulty").unwrap() {
+                "peaceful" => Difficulty::PEACEFUL,
                 "normal" => Difficulty::NORMAL,
                 "easy" => Difficulty::EASY,
                 "hard" => Difficulty::HARD,
@@ -366,12 +514,18 @@ impl Settings {
                 .parse::<String>()
                 .unwrap(),
             // level-type and also be "minecraft\:normal"
-            level_type: match config_file.get_property("level-type").unwrap() {
+            // The default file has 'minecraft\:normal', with the colon escaped.
+            level_type: match config_file
+                .get_property("level-type")
+                .unwrap()
+                .replace("\\:", ":")
+                .trim_start_matches("minecraft:")
+            {
                 "normal" => WorlPreset::NORMAL,
                 "flat" => WorlPreset::FLAT,
                 "large_biomes" => WorlPreset::LARGEBIOMES,
-                "amplified" => WorlPreset::SINGLEBIOMESURFACE,
-                "single_biome_surface" => WorlPreset::AMPLIFIED,
+                "amplified" => WorlPreset::AMPLIFIED,
+                "single_biome_surface" => WorlPreset::SINGLEBIOMESURFACE,
                 _ => WorlPreset::NORMAL, // default value
             },
             spawn_monsters: config_file
@@ -398,9 +552,69 @@ impl Settings {
                 .unwrap()
                 .parse::<u32>()
                 .unwrap(),
-            //generator_settings: todo!(),
+            region_file_compression: config_file
+                .get_property("region-file-compression")
+                .ok()
+                .and_then(Compression::from_property)
+                .unwrap_or(Compression::Zlib),
+            // Not vanilla properties, so they may be missing.
+            enable_metrics: config_file
+                .get_property("enable-metrics")
+                .ok()
+                .and_then(|s| s.parse::<bool>().ok())
+                .unwrap_or(false),
+            metrics_port: config_file
+                .get_property("metrics.port")
+                .ok()
+                .and_then(|s| s.parse::<u16>().ok())
+                .unwrap_or(9225),
+            management_port: config_file
+                .get_property("management.port")
+                .ok()
+                .and_then(|s| s.parse::<u16>().ok())
+                .unwrap_or(9226),
+            generator_settings: config_file
+                .get_property("generator-settings")
+                .unwrap_or("{}")
+                .to_string(),
             //text_filtering_config: todo!(),
         }
     }
     //fn gamemode_to_enum(inp)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::NamedTempFile;
+
+    #[test]
+    fn test_set_property() -> std::io::Result<()> {
+        let file = NamedTempFile::new()?;
+        fs::write(
+            file.path(),
+            "# white-list=comment\nmotd=A Minecraft Server\nwhite-list=false\n",
+        )?;
+
+        set_property(file.path(), "white-list", "true")?;
+        set_property(file.path(), "enforce-whitelist", "true")?;
+
+        assert_eq!(
+            fs::read_to_string(file.path())?,
+            "# white-list=comment\nmotd=A Minecraft Server\nwhite-list=true\nenforce-whitelist=true\n"
+        );
+
+        let properties = read(file.path())?;
+        assert_eq!(properties.get_property("white-list").unwrap(), "true");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_seed() {
+        assert_eq!(parse_seed("-123"), -123);
+        // Like "hello".hashCode() in Java.
+        assert_eq!(parse_seed("hello"), 99_162_322);
+        assert_eq!(parse_seed("a much longer seed"), -1_044_084_140);
+    }
+}
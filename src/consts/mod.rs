@@ -6,6 +6,15 @@
 pub mod minecraft {
     pub const VERSION: &'static str = "1.21.1"; //upgrade to 1.21.1 cuz wiki.vg is up to date
     pub const PROTOCOL_VERSION: usize = 767;
+    /// The version of the saved data, in the chunks and in 'level.dat'.
+    pub const DATA_VERSION: i32 = 3955;
+
+    /// The dimensions of the vanilla worlds.
+    pub const DIMENSIONS: [&str; 3] = [
+        "minecraft:overworld",
+        "minecraft:the_nether",
+        "minecraft:the_end",
+    ];
 }
 
 /// Server logging messages.
@@ -41,6 +50,22 @@ pub mod messages {
     }
 }
 
+/// Reasons shown to the players when they are disconnected from the server.
+pub mod disconnect_reasons {
+    pub const NOT_WHITELISTED: &str = "You are not white-listed on this server!";
+    pub const BANNED: &str = "You are banned from this server.";
+    pub const IP_BANNED: &str = "Your IP address is banned from this server.";
+    pub const KICKED_IP_BANNED: &str = "You have been IP banned from this server.";
+    pub const DUPLICATE_LOGIN: &str = "You logged in from another location";
+    pub const INVALID_NAME: &str = "Invalid characters in username";
+    pub const TRANSFERS_DISABLED: &str = "Server does not accept transfers";
+    /// Followed by the version of the server.
+    pub const INCOMPATIBLE: &str = "Incompatible client! Please use";
+    /// Followed by the version of the server.
+    pub const MISSING_CORE_PACK: &str = "Missing the vanilla data pack of Minecraft";
+    pub const TIMED_OUT: &str = "Timed out";
+}
+
 /// Module used to store file paths relative to the server binary.
 pub mod filepaths {
     /// server.properties file, used to store server settings.
@@ -51,13 +76,8 @@ pub mod filepaths {
     pub const BANNED_IP: &'static str = "banned-ips.json";
     pub const BANNED_PLAYERS: &'static str = "banned-players.json";
     pub const USERCACHE: &'static str = "usercache.json";
-    pub const SESSION: &'static str = "session.lock";
 }
 pub mod folderpath {
-    pub const WORLDS_DIRECTORY: &'static str = "world/";
-    pub const THE_END: &'static str = "world/DIM1/";
-    pub const NETHER: &'static str = "world/DIM-1/";
-    pub const OVERWORLD: &'static str = "world/region/";
     pub const LOGS: &'static str = "logs/";
 }
 
@@ -85,6 +105,7 @@ bug-report-link=
 difficulty=easy
 enable-command-block=false
 enable-jmx-monitoring=false
+enable-metrics=false
 enable-query=false
 enable-rcon=false
 enable-status=true
@@ -103,11 +124,14 @@ initial-enabled-packs=vanilla
 level-name=world
 level-seed=
 level-type=minecraft\:normal
+log-format=text
 log-ips=true
+management.port=9226
 max-chained-neighbor-updates=1000000
 max-players=20
 max-tick-time=60000
 max-world-size=29999984
+metrics.port=9225
 motd=A Minecraft Server
 network-compression-threshold=256
 online-mode=true
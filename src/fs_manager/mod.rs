@@ -1,15 +1,14 @@
-use std::fs::{self, File, OpenOptions};
+use std::fs::{self, File};
 use std::io::{self, BufRead};
 use std::path::Path;
-use std::vec;
+use std::sync::Mutex;
+pub mod session_lock;
 mod utils;
-use crate::{consts, gracefully_exit};
+use crate::world::paths::WorldPaths;
+use crate::{config, consts, gracefully_exit, world};
 use colored::Colorize;
 use log::{error, info, warn};
-use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
-use std::io::Read;
-use std::io::Write;
+use session_lock::{SessionLock, SessionLockError};
 
 // Initializes the server's required files and directories
 pub fn init() -> std::io::Result<()> {
@@ -69,6 +68,13 @@ fn check_eula() -> io::Result<bool> {
 }
 
 pub fn clean_file() -> () {
+    // Read before 'server.properties' is removed.
+    let world = config::read(Path::new(consts::filepaths::PROPERTIES))
+        .ok()
+        .and_then(|properties| {
+            let name = properties.get_property("level-name").ok()?;
+            WorldPaths::new(name).ok()
+        });
     match fs::remove_file(consts::filepaths::EULA) {
         Ok(_) => info!("File delete."),
         Err(e) => info!("Error when delete file: {}", e),
@@ -89,10 +95,6 @@ pub fn clean_file() -> () {
         Ok(_) => info!("File delete."),
         Err(e) => info!("Error when delete file: {}", e),
     }
-    match fs::remove_file(consts::filepaths::SESSION) {
-        Ok(_) => info!("File delete."),
-        Err(e) => info!("Error when delete file: {}", e),
-    }
     match fs::remove_file(consts::filepaths::USERCACHE) {
         Ok(_) => info!("File delete."),
         Err(e) => info!("Error when delete file: {}", e),
@@ -106,21 +108,28 @@ pub fn clean_file() -> () {
         Ok(_) => info!("File delete."),
         Err(e) => info!("Error when delete file: {}", e),
     }
-    match fs::remove_dir(consts::folderpath::NETHER) {
-        Ok(_) => info!("File delete."),
-        Err(e) => info!("Error when delete file: {}", e),
-    }
-    match fs::remove_dir(consts::folderpath::OVERWORLD) {
-        Ok(_) => info!("File delete."),
-        Err(e) => info!("Error when delete file: {}", e),
-    }
-    match fs::remove_dir(consts::folderpath::THE_END) {
-        Ok(_) => info!("File delete."),
-        Err(e) => info!("Error when delete file: {}", e),
-    }
-    match fs::remove_dir(consts::folderpath::WORLDS_DIRECTORY) {
-        Ok(_) => info!("File delete."),
-        Err(e) => info!("Error when delete file: {}", e),
+    // Only the empty world directories are removed.
+    if let Some(world) = world {
+        match fs::remove_file(world.root().join(session_lock::FILENAME)) {
+            Ok(_) => info!("File delete."),
+            Err(e) => info!("Error when delete file: {}", e),
+        }
+        for dimension in consts::minecraft::DIMENSIONS {
+            for dir in [
+                world.region(dimension),
+                world.entities(dimension),
+                world.poi(dimension),
+                world.data(dimension),
+            ] {
+                let _ = fs::remove_dir(dir);
+            }
+            let _ = fs::remove_dir(world.dimension(dimension));
+        }
+        let _ = fs::remove_dir(world.playerdata());
+        match fs::remove_dir(world.root()) {
+            Ok(_) => info!("File delete."),
+            Err(e) => info!("Error when delete file: {}", e),
+        }
     }
 }
 
@@ -149,14 +158,6 @@ pub fn create_other_files() {
             e
         ),
     }
-    match utils::create_file_nn(Path::new(consts::filepaths::SESSION)) {
-        Ok(_) => info!("Created file {}", consts::filepaths::SESSION),
-        Err(e) => info!(
-            "Failed to create the file {} as error:{}",
-            consts::filepaths::SESSION,
-            e
-        ),
-    }
     match utils::create_file_nn(Path::new(consts::filepaths::USERCACHE)) {
         Ok(_) => info!("Created file {}", consts::filepaths::USERCACHE),
         Err(e) => info!(
@@ -184,79 +185,39 @@ pub fn create_dirs() {
         ),
     }
 
-    match utils::create_dir(Path::new(consts::folderpath::WORLDS_DIRECTORY)) {
-        Ok(_) => info!("No existing world data, creating new world"),
-        Err(e) => info!(
-            "Failed to create dir{} as error: {}",
-            consts::folderpath::WORLDS_DIRECTORY,
-            e
-        ),
-    }
-
-    match utils::create_dir(Path::new(consts::folderpath::OVERWORLD)) {
-        Ok(_) => info!("Created dir{}", consts::folderpath::OVERWORLD),
-        Err(e) => info!(
-            "Failed to create dir{} as error: {}",
-            consts::folderpath::OVERWORLD,
+    let world = world::paths();
+    match world.create_dirs() {
+        Ok(_) => info!("Created the dirs of the world {}", world.root().display()),
+        Err(e) => error!(
+            "Failed to create the dirs of the world {} as error: {}",
+            world.root().display(),
             e
         ),
     }
-
-    match utils::create_dir(Path::new(consts::folderpath::THE_END)) {
-        Ok(_) => info!("Created dir{}", consts::folderpath::THE_END),
-        Err(e) => info!(
-            "Failed to create dir{} as error: {}",
-            consts::folderpath::THE_END,
-            e
-        ),
-    }
-
-    match utils::create_dir(Path::new(consts::folderpath::NETHER)) {
-        Ok(_) => info!("Created dir{}", consts::folderpath::NETHER),
-        Err(e) => info!(
-            "Failed to create dir{} as error: {}",
-            consts::folderpath::NETHER,
-            e
-        ),
-    }
-}
-#[derive(Serialize, Deserialize)]
-struct Player {
-    uuid: String,
-    name: String,
-    level: u8,
-    bypassesplayerlimit: bool,
 }
 
-pub fn write_ops_json(
-    filename: &str,
-    uuid: &str,
-    name: &str,
-    level: u8,
-    bypasses_player_limit: bool,
-) -> std::io::Result<()> {
-    let mut file = OpenOptions::new()
-        .read(true)
-        .write(true)
-        .create(true)
-        .open(consts::filepaths::OPERATORS)?;
+/// The lock held on the world directory while the server runs.
+static SESSION_LOCK: Mutex<Option<SessionLock>> = Mutex::new(None);
 
-    let mut content = String::new();
-    file.read_to_string(&mut content)?;
+/// Locks the world directory, so that no other server can open it.
+/// Fails if another process already holds the lock.
+pub fn lock_world() -> Result<(), SessionLockError> {
+    let lock = SessionLock::acquire(world::paths().root())?;
+    info!("Locked the world with '{}'", lock.path().to_string_lossy());
+    *SESSION_LOCK.lock().unwrap() = Some(lock);
+    Ok(())
+}
 
-    let mut json_data: Vec<Value> = if content.trim().is_empty() {
-        vec![]
-    } else {
-        serde_json::from_str(&content)?
+/// Releases the lock on the world directory, if it is held.
+pub fn unlock_world() {
+    let lock = match SESSION_LOCK.lock() {
+        Ok(mut lock) => lock.take(),
+        Err(_) => return,
     };
-    let new_object = json!({
-        "name": name,
-        "uuid": uuid,
-        "level": level,
-        "bypassesPlayerLimit": bypasses_player_limit
-    });
-    json_data.push(new_object);
-    file.set_len(0)?;
-    file.write_all(serde_json::to_string_pretty(&json_data)?.as_bytes());
-    Ok(())
+
+    if let Some(lock) = lock {
+        if let Err(e) = lock.release() {
+            warn!("Failed to release the world lock: {e}");
+        }
+    }
 }
@@ -0,0 +1,108 @@
+//! The 'session.lock' file of the world directory. It is locked by the server for as long as it
+//! runs, so that two servers never open the same world.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use fs4::FileExt;
+use thiserror::Error;
+
+/// The name of the lock file, inside the world directory.
+pub const FILENAME: &str = "session.lock";
+
+/// A held lock on a world directory. The lock is released when this is dropped, or when the
+/// process exits.
+#[derive(Debug)]
+pub struct SessionLock {
+    file: File,
+    path: PathBuf,
+}
+
+impl SessionLock {
+    /// Locks the world in `world_dir`, failing if another process already holds the lock.
+    pub fn acquire(world_dir: &Path) -> Result<Self, SessionLockError> {
+        let path = world_dir.join(FILENAME);
+        let mut file = OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)?;
+
+        match file.try_lock_exclusive() {
+            Ok(()) => {}
+            Err(e) if e.kind() == fs4::lock_contended_error().kind() => {
+                return Err(SessionLockError::AlreadyLocked(path));
+            }
+            Err(e) => return Err(e.into()),
+        }
+
+        // Like vanilla, the file only contains a snowman.
+        file.set_len(0)?;
+        file.write_all("☃".as_bytes())?;
+        file.flush()?;
+
+        Ok(Self { file, path })
+    }
+
+    /// Returns the path of the lock file.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Releases the lock.
+    pub fn release(self) -> io::Result<()> {
+        FileExt::unlock(&self.file)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum SessionLockError {
+    #[error("'{}' is already locked by another process, is another server running on this world?", .0.to_string_lossy())]
+    AlreadyLocked(PathBuf),
+    #[error("Failed to lock the world: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::thread;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_second_lock_fails() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let world_dir = dir.path().to_path_buf();
+
+        let lock = SessionLock::acquire(&world_dir)?;
+        assert_eq!(fs::read_to_string(lock.path())?, "☃");
+
+        // A second attempt, like another server starting on the same world.
+        let second_dir = world_dir.clone();
+        let second = thread::spawn(move || SessionLock::acquire(&second_dir))
+            .join()
+            .unwrap();
+        assert!(matches!(second, Err(SessionLockError::AlreadyLocked(_))));
+
+        // Once released, the world can be locked again.
+        lock.release()?;
+        let third = thread::spawn(move || SessionLock::acquire(&world_dir))
+            .join()
+            .unwrap();
+        assert!(third.is_ok());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_missing_world_directory() {
+        let dir = TempDir::new().unwrap();
+        let missing = dir.path().join("missing");
+        assert!(matches!(
+            SessionLock::acquire(&missing),
+            Err(SessionLockError::Io(_))
+        ));
+    }
+}
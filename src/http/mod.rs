@@ -0,0 +1,163 @@
+//! A minimal HTTP/1.1 server for the monitoring endpoints: one request per connection, and the
+//! request bodies are ignored.
+
+use std::future::Future;
+use std::io;
+
+use log::{debug, warn};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::net::TcpListener;
+
+use crate::logging;
+
+/// The largest request head read, the rest is ignored.
+const MAX_HEAD_SIZE: usize = 8192;
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Request {
+    pub method: String,
+    /// The path, without the query string.
+    pub path: String,
+}
+
+#[derive(Debug)]
+pub struct Response {
+    pub status: u16,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    pub fn ok(content_type: &'static str, body: String) -> Self {
+        Self {
+            status: 200,
+            content_type,
+            body,
+        }
+    }
+
+    pub fn json(body: serde_json::Value) -> Self {
+        Self::ok("application/json", body.to_string())
+    }
+
+    pub fn not_found() -> Self {
+        Self {
+            status: 404,
+            content_type: "text/plain",
+            body: "Not Found".to_string(),
+        }
+    }
+
+    fn reason(&self) -> &'static str {
+        match self.status {
+            200 => "OK",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            _ => "",
+        }
+    }
+}
+
+/// Answers every connection with `handler` until the server stops.
+pub async fn serve<F, Fut>(listener: TcpListener, handler: F)
+where
+    F: Fn(Request) -> Fut + Clone + Send + Sync + 'static,
+    Fut: Future<Output = Response> + Send,
+{
+    loop {
+        let (mut socket, addr) = match listener.accept().await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Failed to accept an HTTP connection: {e}");
+                continue;
+            }
+        };
+
+        let handler = handler.clone();
+        tokio::spawn(async move {
+            let result = async {
+                let request = read_request(&mut socket).await?;
+                write_response(&mut socket, &handler(request).await).await
+            };
+            if let Err(e) = result.await {
+                debug!(
+                    addr:% = addr;
+                    "Failed to answer the HTTP request from {}: {e}",
+                    logging::ip(addr)
+                );
+            }
+        });
+    }
+}
+
+/// Reads the request line and the headers.
+pub async fn read_request(reader: &mut (impl AsyncRead + Unpin)) -> io::Result<Request> {
+    let mut head = Vec::new();
+    let mut buf = [0; 1024];
+    while !head.windows(4).any(|w| w == b"\r\n\r\n") && head.len() < MAX_HEAD_SIZE {
+        let n = reader.read(&mut buf).await?;
+        if n == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..n]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split(' ');
+    let (Some(method), Some(target)) = (request_line.next(), request_line.next()) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "Malformed HTTP request line",
+        ));
+    };
+    let path = target.split('?').next().unwrap_or_default();
+
+    Ok(Request {
+        method: method.to_string(),
+        path: path.to_string(),
+    })
+}
+
+/// Writes the response, then closes the connection.
+pub async fn write_response(
+    writer: &mut (impl AsyncWrite + Unpin),
+    response: &Response,
+) -> io::Result<()> {
+    let head = format!(
+        "HTTP/1.1 {} {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        response.status,
+        response.reason(),
+        response.content_type,
+        response.body.len()
+    );
+    writer.write_all(head.as_bytes()).await?;
+    writer.write_all(response.body.as_bytes()).await?;
+    writer.shutdown().await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_request_and_response() {
+        let mut reader = &b"GET /metrics?x=1 HTTP/1.1\r\nHost: localhost\r\n\r\n"[..];
+        assert_eq!(
+            read_request(&mut reader).await.unwrap(),
+            Request {
+                method: "GET".to_string(),
+                path: "/metrics".to_string()
+            }
+        );
+        assert!(read_request(&mut &b"\r\n\r\n"[..]).await.is_err());
+
+        let mut output = Vec::new();
+        write_response(&mut output, &Response::not_found())
+            .await
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "HTTP/1.1 404 Not Found\r\nContent-Type: text/plain\r\nContent-Length: 9\r\nConnection: close\r\n\r\nNot Found"
+        );
+    }
+}
@@ -0,0 +1,160 @@
+//! The log file, 'logs/latest.log'. Like vanilla, the previous one is archived when the server
+//! starts and at midnight, gzipped as 'logs/YYYY-MM-DD-N.log.gz' where N counts the archives of
+//! the day, starting at 1.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Local, NaiveDate};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+/// The file being written to, in the logs directory.
+pub const LATEST: &str = "latest.log";
+
+pub struct LogFile {
+    dir: PathBuf,
+    writer: BufWriter<File>,
+    /// The day of the lines in the file.
+    date: NaiveDate,
+}
+
+impl LogFile {
+    /// Archives the previous log file if there is one, and starts a new one.
+    pub fn open(dir: &Path, now: DateTime<Local>) -> io::Result<Self> {
+        fs::create_dir_all(dir)?;
+
+        let latest = dir.join(LATEST);
+        if let Ok(metadata) = fs::metadata(&latest) {
+            let modified: DateTime<Local> = metadata.modified()?.into();
+            archive(dir, modified.date_naive())?;
+        }
+
+        Ok(Self {
+            dir: dir.to_path_buf(),
+            writer: BufWriter::new(create(&latest)?),
+            date: now.date_naive(),
+        })
+    }
+
+    /// Writes a line, first archiving the file if `now` is on another day than its lines.
+    pub fn write_line(&mut self, now: DateTime<Local>, line: &str) -> io::Result<()> {
+        if now.date_naive() != self.date {
+            self.writer.flush()?;
+            archive(&self.dir, self.date)?;
+            self.writer = BufWriter::new(create(&self.dir.join(LATEST))?);
+            self.date = now.date_naive();
+        }
+
+        writeln!(self.writer, "{line}")?;
+        // Lines should not be lost if the server crashes.
+        self.writer.flush()
+    }
+}
+
+fn create(path: &Path) -> io::Result<File> {
+    OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .open(path)
+}
+
+/// Gzips 'latest.log' into the first free archive name of `date`, and removes it.
+fn archive(dir: &Path, date: NaiveDate) -> io::Result<PathBuf> {
+    let latest = dir.join(LATEST);
+    let archive = (1..)
+        .map(|n| dir.join(format!("{}-{n}.log.gz", date.format("%Y-%m-%d"))))
+        .find(|path| !path.exists())
+        .unwrap();
+
+    let mut encoder = GzEncoder::new(File::create(&archive)?, Compression::default());
+    io::copy(&mut File::open(&latest)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(latest)?;
+
+    Ok(archive)
+}
+
+/// Removes the ANSI escape sequences, like colours, from a line.
+pub fn strip_ansi(line: &str) -> String {
+    let mut result = String::with_capacity(line.len());
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        if c != '\x1b' {
+            result.push(c);
+            continue;
+        }
+        // Control Sequence Introducer: parameters until a final byte in '@'..='~'.
+        if chars.next() == Some('[') {
+            for c in chars.by_ref() {
+                if ('@'..='~').contains(&c) {
+                    break;
+                }
+            }
+        }
+    }
+
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+    use tempfile::TempDir;
+
+    fn read_archive(path: &Path) -> String {
+        let mut content = String::new();
+        GzDecoder::new(File::open(path).unwrap())
+            .read_to_string(&mut content)
+            .unwrap();
+        content
+    }
+
+    #[test]
+    fn test_rotation() -> io::Result<()> {
+        let dir = TempDir::new()?;
+        let day = |d| Local.with_ymd_and_hms(2024, 9, d, 12, 0, 0).unwrap();
+
+        let mut file = LogFile::open(dir.path(), day(1))?;
+        file.write_line(day(1), "first day")?;
+        assert_eq!(fs::read_to_string(dir.path().join(LATEST))?, "first day\n");
+
+        // Midnight.
+        file.write_line(day(2), "second day")?;
+        assert_eq!(
+            read_archive(&dir.path().join("2024-09-01-1.log.gz")),
+            "first day\n"
+        );
+        assert_eq!(fs::read_to_string(dir.path().join(LATEST))?, "second day\n");
+
+        // Two restarts on the same day.
+        let date = day(2).date_naive();
+        assert_eq!(
+            archive(dir.path(), date)?,
+            dir.path().join("2024-09-02-1.log.gz")
+        );
+        fs::write(dir.path().join(LATEST), "restarted")?;
+        assert_eq!(
+            archive(dir.path(), date)?,
+            dir.path().join("2024-09-02-2.log.gz")
+        );
+        assert!(!dir.path().join(LATEST).exists());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_strip_ansi() {
+        assert_eq!(
+            strip_ansi("\x1b[1;92m[ SERVER STARTED ]\x1b[0m done"),
+            "[ SERVER STARTED ] done"
+        );
+        assert_eq!(strip_ansi("no colours"), "no colours");
+    }
+}
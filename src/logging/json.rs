@@ -0,0 +1,106 @@
+//! The JSON format of the console, one object per line for the log shippers:
+//! `{"timestamp":...,"level":"INFO","target":...,"thread":...,"message":...,"fields":{...}}`.
+//! The fields are the key-values of the records, like `info!(addr:% = addr; "...")`.
+
+use chrono::{DateTime, Local, SecondsFormat};
+use log::kv::{self, Key, Value, VisitSource};
+use log::Record;
+use serde_json::{json, Map};
+
+use super::{file, IP_WITHHELD};
+
+/// The fields holding IP addresses, withheld when 'log-ips' is false.
+const IP_FIELDS: [&str; 2] = ["addr", "ip"];
+
+/// Formats a record as a JSON object.
+pub fn record(
+    now: DateTime<Local>,
+    thread: Option<&str>,
+    record: &Record,
+    log_ips: bool,
+) -> serde_json::Value {
+    let mut fields = Fields {
+        fields: Map::new(),
+        log_ips,
+    };
+    // Visiting only fails when the visitor does.
+    let _ = record.key_values().visit(&mut fields);
+
+    json!({
+        "timestamp": now.to_rfc3339_opts(SecondsFormat::Millis, false),
+        "level": record.level().as_str(),
+        "target": record.target(),
+        "thread": thread.unwrap_or("unnamed"),
+        "message": file::strip_ansi(&record.args().to_string()),
+        "fields": fields.fields,
+    })
+}
+
+struct Fields {
+    fields: Map<String, serde_json::Value>,
+    log_ips: bool,
+}
+
+impl<'kvs> VisitSource<'kvs> for Fields {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), kv::Error> {
+        let key = key.as_str();
+        let value = if !self.log_ips && IP_FIELDS.contains(&key) {
+            json!(IP_WITHHELD)
+        } else if let Some(n) = value.to_i64() {
+            json!(n)
+        } else if let Some(n) = value.to_u64() {
+            json!(n)
+        } else if let Some(b) = value.to_bool() {
+            json!(b)
+        } else {
+            json!(value.to_string())
+        };
+
+        self.fields.insert(key.to_string(), value);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use log::Level;
+
+    #[test]
+    fn test_record() {
+        let now = Local.with_ymd_and_hms(2024, 9, 1, 8, 5, 3).unwrap();
+        let fields: [(&str, Value); 3] = [
+            ("addr", Value::from_display(&"127.0.0.1:50000")),
+            ("uuid", Value::from("069a79f4-44e9-4726-a5be-fca90e38aaf5")),
+            ("packet_id", Value::from(0x2B)),
+        ];
+        let record = Record::builder()
+            .level(Level::Info)
+            .target("copper_server::net::connection")
+            .args(format_args!("Notch logged in"))
+            .key_values(&fields)
+            .build();
+
+        let value = super::record(now, Some("main"), &record, true);
+        assert_eq!(value["level"], "INFO");
+        assert_eq!(value["target"], "copper_server::net::connection");
+        assert_eq!(value["thread"], "main");
+        assert_eq!(value["message"], "Notch logged in");
+        assert!(value["timestamp"]
+            .as_str()
+            .unwrap()
+            .starts_with("2024-09-01T08:05:03.000"));
+        assert_eq!(
+            value["fields"],
+            json!({
+                "addr": "127.0.0.1:50000",
+                "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+                "packet_id": 43,
+            })
+        );
+
+        let value = super::record(now, Some("main"), &record, false);
+        assert_eq!(value["fields"]["addr"], IP_WITHHELD);
+    }
+}
@@ -1,26 +1,191 @@
-use env_logger::Builder;
-use log::LevelFilter;
+//! The logging of the server: to the console with colours, and to 'logs/latest.log' in the
+//! vanilla format, `[HH:MM:SS] [thread/LEVEL]: message`.
+//! The level is set with the `RUST_LOG` environment variable, like `RUST_LOG=debug`, and is
+//! `info` by default.
+//! The console can also log JSON objects instead, see `json`, with the `--json-logs` flag or
+//! 'log-format=json' in 'server.properties'.
+
+mod file;
+mod json;
+
+use std::fmt::Display;
+use std::io::{self, IsTerminal, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, Local};
+use env_logger::{Builder, Env, Target, WriteStyle};
+use file::LogFile;
+use log::{LevelFilter, Log, Metadata, Record};
+use rustyline::ExternalPrinter;
+
+use crate::{config, consts};
+
+/// Replaces the IP addresses when 'log-ips' is false, like vanilla.
+pub const IP_WITHHELD: &str = "<ip address withheld>";
+
+/// Whether IP addresses may be logged, from 'log-ips'.
+static LOG_IPS: AtomicBool = AtomicBool::new(true);
+
+/// Prints the console lines above the prompt of the line editor, once it runs.
+static CONSOLE_PRINTER: Mutex<Option<Box<dyn ExternalPrinter + Send>>> = Mutex::new(None);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// For people, with colours.
+    Text,
+    /// One JSON object per line.
+    Json,
+}
+
+/// Returns an IP address to log, or a placeholder if 'log-ips' is false.
+pub fn ip(addr: impl Display) -> String {
+    if LOG_IPS.load(Ordering::Relaxed) {
+        addr.to_string()
+    } else {
+        IP_WITHHELD.to_string()
+    }
+}
 
 /// Initializes the logging for the whole application
-pub fn init(log_level: LevelFilter) {
-    let mut builder = Builder::new();
+/// It runs before the settings are loaded, so it reads the properties it needs by itself.
+pub fn init() {
+    let json_flag = std::env::args().any(|arg| arg == "--json-logs");
+    let format = if json_flag || config::read_property("log-format").as_deref() == Some("json") {
+        LogFormat::Json
+    } else {
+        LogFormat::Text
+    };
+    let log_ips = config::read_property("log-ips").is_none_or(|value| value != "false");
+    LOG_IPS.store(log_ips, Ordering::Relaxed);
+
+    let mut builder = Builder::from_env(Env::default().default_filter_or("info"));
+    // The console line editor logs every key press when debugging.
+    builder.filter_module("rustyline", LevelFilter::Warn);
+    builder.target(Target::Pipe(Box::new(Console)));
+    // The colours are only written to terminals, unless `RUST_LOG_STYLE` says otherwise.
+    if std::env::var_os("RUST_LOG_STYLE").is_none() && io::stderr().is_terminal() {
+        builder.write_style(WriteStyle::Always);
+    }
+    let console = builder.build();
 
-    // TODO: Customize logging format. Making the logging level the right color is time consuming.
+    let (file, file_error) = match LogFile::open(Path::new(consts::folderpath::LOGS), Local::now())
+    {
+        Ok(file) => (Some(Mutex::new(file)), None),
+        Err(e) => (None, Some(e)),
+    };
 
-    //builder.format(|buf, record| {
-    //    writeln!(
-    //        buf,
-    //        "[{}] [{}] [{}] {}",
-    //        chrono::Local::now().format("%Y-%m-%d %H:%M:%S"),
-    //        record.level(),
-    //        record.target(),
-    //        record.args()
-    //    )
-    //});
+    log::set_max_level(console.filter());
+    log::set_boxed_logger(Box::new(Logger {
+        console,
+        format,
+        file,
+    }))
+    .expect("The logger is only initialized once");
 
-    // And add use::io::Write; for the above code.
+    if let Some(e) = file_error {
+        log::warn!("Failed to open the log file, logging to the console only: {e}");
+    }
+}
+
+/// Makes the console lines go through the line editor, so they do not break the line being
+/// typed.
+pub fn set_console_printer(printer: impl ExternalPrinter + Send + 'static) {
+    *CONSOLE_PRINTER.lock().unwrap() = Some(Box::new(printer));
+}
+
+/// Writes to the line editor's printer if there is one, to stderr otherwise.
+struct Console;
+
+impl Write for Console {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match CONSOLE_PRINTER.lock().unwrap().as_mut() {
+            Some(printer) => {
+                printer
+                    .print(String::from_utf8_lossy(buf).into_owned())
+                    .map_err(io::Error::other)?;
+                Ok(buf.len())
+            }
+            None => io::stderr().write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        io::stderr().flush()
+    }
+}
+
+/// Logs to the console and to the log file.
+struct Logger {
+    /// Filters the records, and formats them in the text format.
+    console: env_logger::Logger,
+    format: LogFormat,
+    file: Option<Mutex<LogFile>>,
+}
+
+impl Log for Logger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.console.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.console.matches(record) {
+            return;
+        }
+        let now = Local::now();
+        let thread = std::thread::current();
+
+        match self.format {
+            LogFormat::Text => self.console.log(record),
+            LogFormat::Json => {
+                let line =
+                    json::record(now, thread.name(), record, LOG_IPS.load(Ordering::Relaxed));
+                // In one write, as the line editor prints each write on its own line.
+                let _ = Console.write_all(format!("{line}\n").as_bytes());
+            }
+        }
+
+        if let Some(file) = &self.file {
+            let line = file_line(now, thread.name(), record);
+            // There is nowhere to report the failure but the console, which already has it.
+            let _ = file.lock().unwrap().write_line(now, &line);
+        }
+    }
+
+    fn flush(&self) {
+        self.console.flush();
+    }
+}
+
+/// Formats a record like vanilla, without colours.
+fn file_line(now: DateTime<Local>, thread: Option<&str>, record: &Record) -> String {
+    file::strip_ansi(&format!(
+        "[{}] [{}/{}]: {}",
+        now.format("%H:%M:%S"),
+        thread.unwrap_or("unnamed"),
+        record.level(),
+        record.args()
+    ))
+}
 
-    builder.filter_level(log_level);
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use log::Level;
 
-    builder.init();
+    #[test]
+    fn test_file_line() {
+        let now = Local.with_ymd_and_hms(2024, 9, 1, 8, 5, 3).unwrap();
+        let line = file_line(
+            now,
+            Some("main"),
+            &Record::builder()
+                .level(Level::Info)
+                .args(format_args!("\x1b[1m[ SERVER STARTED ]\x1b[0m"))
+                .build(),
+        );
+        assert_eq!(line, "[08:05:03] [main/INFO]: [ SERVER STARTED ]");
+    }
 }
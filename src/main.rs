@@ -4,13 +4,23 @@ mod commands;
 mod config;
 mod consts;
 mod fs_manager;
+mod http;
 mod logging;
+mod management;
+mod metrics;
+mod nbt;
 mod net;
 mod packet;
 mod player;
+mod query;
+mod rcon;
 mod slp;
+mod tick;
 mod time;
+mod user_lists;
+mod world;
 use std::env::{self};
+use std::net::SocketAddr;
 
 use config::Gamemode;
 use consts::messages;
@@ -23,6 +33,8 @@ async fn main() {
 
     if arguments.len() > 1 {
         match arguments[1].as_str() {
+            // Read by the logging.
+            "--json-logs" => {}
             "-remove_files" | "--remove" => {
                 clean_file();
                 info!("All files have been removed.");
@@ -49,13 +61,13 @@ async fn main() {
         gracefully_exit(-1);
     }
 
-    info!("{}", *messages::SERVER_SHUTDOWN);
+    gracefully_exit(0);
 }
 
 /// Logic that must executes as early as possibe
 async fn early_init() -> Result<(), Box<dyn std::error::Error>> {
     // This must executes as early as possible
-    logging::init(log::LevelFilter::Debug);
+    logging::init();
 
     info!("{}", *messages::SERVER_STARTING);
 
@@ -79,8 +91,12 @@ fn init() -> Result<(), Box<dyn std::error::Error>> {
 
     // Makes sure server files are initialized and valid.
     fs_manager::init()?;
+    world::init_paths(&config::Settings::new())?;
     fs_manager::create_dirs();
+    fs_manager::lock_world()?;
     fs_manager::create_other_files();
+    user_lists::init(&config::Settings::new());
+    world::load(&config::Settings::new())?;
     let gamemode1 = match config::Settings::new().gamemode {
         Gamemode::SURVIVAL => "Survival",
         Gamemode::ADVENTURE => "Adventure",
@@ -104,14 +120,113 @@ async fn start() -> Result<(), Box<dyn std::error::Error>> {
     );
     info!("{}", *messages::SERVER_STARTED);
 
-    net::listen().await.map_err(|e| {
-        error!("Failed to listen for packets: {e}");
-        e
-    })?;
+    tokio::spawn(async {
+        if let Err(e) = net::listen().await {
+            error!("Failed to listen for packets: {e}");
+            gracefully_exit(-1);
+        }
+    });
+
+    start_rcon().await;
+    start_query().await;
+    start_metrics().await;
+    start_management().await;
+
+    // Runs until the server is stopped, e.g. by the stop command.
+    tick::run().await;
 
     Ok(())
 }
 
+/// Starts the RCON server if 'enable-rcon' is set.
+async fn start_rcon() {
+    let config = config::Settings::new();
+    if !config.enable_rcon {
+        return;
+    }
+
+    let address = config.bind_address(config.rcon_port);
+    let password = config.rcon_password.unwrap_or_default();
+    let server = rcon::RconServer::bind(
+        address,
+        &password,
+        config.broadcast_rcon_to_ops,
+        tick::tasks(),
+    );
+    match server.await {
+        Ok(server) => {
+            info!("RCON running on {address}");
+            tokio::spawn(server.run());
+        }
+        Err(e) => warn!("Failed to start the RCON server: {e}"),
+    }
+}
+
+/// Starts the query server if 'enable-query' is set.
+async fn start_query() {
+    let config = config::Settings::new();
+    if !config.enable_query {
+        return;
+    }
+
+    let address = config.bind_address(config.query_port);
+    let info = query::QueryInfo {
+        motd: config
+            .motd
+            .unwrap_or_else(|| "A Minecraft Server".to_string()),
+        map: config.level_name.unwrap_or_else(|| "world".to_string()),
+        max_players: config.max_players,
+        host_ip: match config.server_ip {
+            Some(ip) => ip.to_string(),
+            None => "0.0.0.0".to_string(),
+        },
+        host_port: config.server_port,
+        hide_online_players: config.hide_online_players,
+    };
+    match query::QueryServer::bind(address, info).await {
+        Ok(server) => {
+            info!("Query running on {address}");
+            tokio::spawn(server.run());
+        }
+        Err(e) => warn!("Failed to start the query server: {e}"),
+    }
+}
+
+/// Starts serving the metrics if 'enable-metrics' is set.
+async fn start_metrics() {
+    let config = config::Settings::new();
+    if !config.enable_metrics {
+        return;
+    }
+
+    let address = config.bind_address(config.metrics_port);
+    match metrics::MetricsServer::bind(address).await {
+        Ok(server) => {
+            info!("Metrics served on http://{address}/metrics");
+            tokio::spawn(server.run());
+        }
+        Err(e) => warn!("Failed to start the metrics server: {e}"),
+    }
+}
+
+/// Starts the management interface if 'enable-jmx-monitoring' is set.
+async fn start_management() {
+    let config = config::Settings::new();
+    if !config.enable_jmx_monitoring {
+        return;
+    }
+
+    // Only local tools may use it, since it has no authentication.
+    let address = SocketAddr::from(([127, 0, 0, 1], config.management_port));
+    match management::ManagementServer::bind(address, tick::tasks()).await {
+        Ok(server) => {
+            info!("Management interface running on http://{address}");
+            tokio::spawn(server.run());
+        }
+        Err(e) => warn!("Failed to start the management interface: {e}"),
+    }
+}
+
 /// Sets up a behavior when the user executes CTRL + C.
 fn init_ctrlc_handler() -> Result<(), Box<dyn std::error::Error>> {
     ctrlc::set_handler(move || {
@@ -148,6 +263,11 @@ pub fn gracefully_exit(code: i32) -> ! {
         warn!("{}", messages::server_shutdown_code(code));
     }
 
+    if let Err(e) = world::save() {
+        error!("Failed to save the world: {e}");
+    }
+    fs_manager::unlock_world();
+
     // Well, for now it's not "gracefully" exiting.
     std::process::exit(code);
 }
@@ -0,0 +1,142 @@
+//! The management interface enabled by 'enable-jmx-monitoring'. Vanilla exposes the tick times
+//! over JMX, here they are served as JSON over HTTP on the loopback interface, with some
+//! controls, on 'management.port':
+//! - `GET /`: the average tick time in milliseconds and the last tick times in nanoseconds,
+//!   named like the attributes of the vanilla MBean, and the online players.
+//! - `POST /save-all`: saves the server, and returns the output of the command.
+
+use std::io;
+use std::net::SocketAddr;
+
+use serde_json::json;
+use tokio::net::TcpListener;
+
+use crate::commands;
+use crate::commands::source::{CapturedOutput, CommandSource, CONSOLE_NAME, MAX_PERMISSION_LEVEL};
+use crate::http::{self, Request, Response};
+use crate::net::online::ONLINE_PLAYERS;
+use crate::tick::{self, TaskQueue};
+
+pub struct ManagementServer {
+    listener: TcpListener,
+    /// Where the commands are queued, the main loop's outside of the tests.
+    tasks: &'static TaskQueue,
+}
+
+impl ManagementServer {
+    pub async fn bind(addr: SocketAddr, tasks: &'static TaskQueue) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            tasks,
+        })
+    }
+
+    #[cfg(test)]
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Answers the requests until the server stops.
+    pub async fn run(self) {
+        let tasks = self.tasks;
+        http::serve(self.listener, move |request| handle(tasks, request)).await
+    }
+}
+
+async fn handle(tasks: &TaskQueue, request: Request) -> Response {
+    match (request.method.as_str(), request.path.as_str()) {
+        ("GET", "/") => Response::json(status()),
+        ("POST", "/save-all") => {
+            Response::json(json!({ "output": execute(tasks, "save-all").await }))
+        }
+        (_, "/" | "/save-all") => Response {
+            status: 405,
+            content_type: "text/plain",
+            body: "Method Not Allowed".to_string(),
+        },
+        _ => Response::not_found(),
+    }
+}
+
+fn status() -> serde_json::Value {
+    let mut players = ONLINE_PLAYERS.list();
+    players.sort_unstable_by_key(|player| player.name.to_lowercase());
+    let players: Vec<_> = players
+        .into_iter()
+        .map(|player| json!({ "name": player.name, "uuid": player.uuid }))
+        .collect();
+
+    json!({
+        "averageTickTime": tick::average_tick_time(),
+        "tickTimes": tick::tick_times(),
+        "players": players,
+    })
+}
+
+/// Executes a command as the console on the main loop, and returns its output.
+async fn execute(tasks: &TaskQueue, command: &str) -> Vec<String> {
+    let output = CapturedOutput::default();
+    let source = CommandSource::new(CONSOLE_NAME, MAX_PERMISSION_LEVEL, output.clone());
+    // The sender is only dropped without sending if the server stops first.
+    let _ = commands::submit(tasks, command, source).await.await;
+    output.lines()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    /// Sends a request, and returns the whole response.
+    async fn request(addr: SocketAddr, method: &str, path: &str) -> String {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        let request = format!("{method} {path} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        stream.write_all(request.as_bytes()).await.unwrap();
+
+        let mut response = String::new();
+        tokio::io::AsyncReadExt::read_to_string(&mut stream, &mut response)
+            .await
+            .unwrap();
+        response
+    }
+
+    fn body(response: &str) -> serde_json::Value {
+        let (_, body) = response.split_once("\r\n\r\n").unwrap();
+        serde_json::from_str(body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_endpoints() {
+        let tasks = Box::leak(Box::default());
+        tokio::spawn(tick::run_tasks(tasks));
+        let server = ManagementServer::bind("127.0.0.1:0".parse().unwrap(), tasks)
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let response = request(addr, "GET", "/").await;
+        assert!(response.starts_with("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\n"));
+        let status = body(&response);
+        assert!(status["averageTickTime"].is_f64());
+        assert_eq!(
+            status["tickTimes"].as_array().unwrap().len(),
+            tick::TICK_TIMES_SAMPLES
+        );
+        assert!(status["players"].is_array());
+
+        let response = request(addr, "POST", "/save-all").await;
+        assert_eq!(
+            body(&response),
+            json!({ "output": ["Saving the game (this may take a moment!)", "Saved the game"] })
+        );
+
+        assert!(request(addr, "GET", "/save-all")
+            .await
+            .starts_with("HTTP/1.1 405"));
+        assert!(request(addr, "GET", "/unknown")
+            .await
+            .starts_with("HTTP/1.1 404"));
+    }
+}
@@ -0,0 +1,283 @@
+//! Metrics about the server, in the Prometheus text format. They are atomic counters updated
+//! where things happen, cheap enough to always be collected, and served over HTTP on
+//! 'metrics.port' when 'enable-metrics' is set.
+
+use std::fmt::Write;
+use std::io;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+use once_cell::sync::Lazy;
+use tokio::net::TcpListener;
+
+use crate::http::{self, Response};
+use crate::net::online::ONLINE_PLAYERS;
+use crate::net::ConnectionState;
+
+/// The metrics of the server.
+pub static METRICS: Lazy<Metrics> = Lazy::new(Metrics::default);
+
+/// Packets are counted by ID up to this one excluded, which is above every vanilla ID.
+const PACKET_IDS: usize = 0x80;
+
+/// The upper bounds of the tick duration histogram buckets, in seconds.
+const TICK_BUCKETS: [f64; 9] = [0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5];
+
+/// The packets and their bytes, by state and ID.
+struct PacketCounters {
+    packets: [[AtomicU64; PACKET_IDS]; ConnectionState::ALL.len()],
+    bytes: [[AtomicU64; PACKET_IDS]; ConnectionState::ALL.len()],
+}
+
+impl Default for PacketCounters {
+    fn default() -> Self {
+        Self {
+            packets: std::array::from_fn(|_| std::array::from_fn(|_| AtomicU64::new(0))),
+            bytes: std::array::from_fn(|_| std::array::from_fn(|_| AtomicU64::new(0))),
+        }
+    }
+}
+
+impl PacketCounters {
+    fn add(&self, state: ConnectionState, id: i32, bytes: usize) {
+        let Some(index) = usize::try_from(id).ok().filter(|&id| id < PACKET_IDS) else {
+            return;
+        };
+        self.packets[state as usize][index].fetch_add(1, Ordering::Relaxed);
+        self.bytes[state as usize][index].fetch_add(bytes as u64, Ordering::Relaxed);
+    }
+
+    /// Writes the packet and byte counters, leaving out the packets never seen.
+    fn render(&self, output: &mut String, direction: &str) {
+        let counters = [("packets", &self.packets), ("bytes", &self.bytes)];
+        for (unit, values) in counters {
+            let name = format!("copper_{unit}_{direction}_total");
+            let _ = writeln!(output, "# TYPE {name} counter");
+            for state in ConnectionState::ALL {
+                for (id, value) in values[state as usize].iter().enumerate() {
+                    let value = value.load(Ordering::Relaxed);
+                    if value > 0 {
+                        let _ = writeln!(
+                            output,
+                            "{name}{{state=\"{}\",id=\"{id:#04x}\"}} {value}",
+                            state.name()
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// A histogram of tick durations.
+struct TickHistogram {
+    /// The number of ticks per bucket, not cumulative.
+    buckets: [AtomicU64; TICK_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+impl Default for TickHistogram {
+    fn default() -> Self {
+        Self {
+            buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            sum_micros: AtomicU64::new(0),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    connections: [AtomicI64; ConnectionState::ALL.len()],
+    received: PacketCounters,
+    sent: PacketCounters,
+    ticks: TickHistogram,
+    /// The bits of an `f64`.
+    tps: AtomicU64,
+    loaded_chunks: AtomicI64,
+}
+
+impl Metrics {
+    /// Counts a new connection, in the handshake state.
+    pub fn connection_opened(&self) {
+        self.connections[ConnectionState::Handshake as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_changed_state(&self, from: ConnectionState, to: ConnectionState) {
+        self.connections[from as usize].fetch_sub(1, Ordering::Relaxed);
+        self.connections[to as usize].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn connection_closed(&self, state: ConnectionState) {
+        self.connections[state as usize].fetch_sub(1, Ordering::Relaxed);
+    }
+
+    /// Counts a packet received from a client, `bytes` including its length prefix.
+    pub fn packet_received(&self, state: ConnectionState, id: i32, bytes: usize) {
+        self.received.add(state, id, bytes);
+    }
+
+    /// Counts a packet sent to a client, `bytes` including its length prefix.
+    pub fn packet_sent(&self, state: ConnectionState, id: i32, bytes: usize) {
+        self.sent.add(state, id, bytes);
+    }
+
+    /// Records how long a tick took.
+    pub fn tick_finished(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = TICK_BUCKETS
+            .iter()
+            .position(|&bound| seconds <= bound)
+            .unwrap_or(TICK_BUCKETS.len());
+        self.ticks.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.ticks
+            .sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    /// Sets the number of ticks done in the last second.
+    pub fn set_tps(&self, tps: f64) {
+        self.tps.store(tps.to_bits(), Ordering::Relaxed);
+    }
+
+    pub fn add_loaded_chunks(&self, count: i64) {
+        self.loaded_chunks.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// Writes every metric in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut output = String::new();
+
+        let _ = writeln!(output, "# TYPE copper_connections gauge");
+        for state in ConnectionState::ALL {
+            let _ = writeln!(
+                output,
+                "copper_connections{{state=\"{}\"}} {}",
+                state.name(),
+                self.connections[state as usize].load(Ordering::Relaxed)
+            );
+        }
+
+        self.received.render(&mut output, "received");
+        self.sent.render(&mut output, "sent");
+
+        let _ = writeln!(output, "# TYPE copper_tick_duration_seconds histogram");
+        let mut count = 0;
+        for (i, bucket) in self.ticks.buckets.iter().enumerate() {
+            count += bucket.load(Ordering::Relaxed);
+            let bound = match TICK_BUCKETS.get(i) {
+                Some(bound) => bound.to_string(),
+                None => "+Inf".to_string(),
+            };
+            let _ = writeln!(
+                output,
+                "copper_tick_duration_seconds_bucket{{le=\"{bound}\"}} {count}"
+            );
+        }
+        let sum = self.ticks.sum_micros.load(Ordering::Relaxed) as f64 / 1_000_000.0;
+        let _ = writeln!(output, "copper_tick_duration_seconds_sum {sum}");
+        let _ = writeln!(output, "copper_tick_duration_seconds_count {count}");
+
+        let gauges = [
+            (
+                "copper_tps",
+                f64::from_bits(self.tps.load(Ordering::Relaxed)),
+            ),
+            ("copper_online_players", ONLINE_PLAYERS.count() as f64),
+            (
+                "copper_loaded_chunks",
+                self.loaded_chunks.load(Ordering::Relaxed) as f64,
+            ),
+        ];
+        for (name, value) in gauges {
+            let _ = writeln!(output, "# TYPE {name} gauge\n{name} {value}");
+        }
+
+        if let Some(bytes) = resident_memory() {
+            let _ = writeln!(
+                output,
+                "# TYPE copper_resident_memory_bytes gauge\ncopper_resident_memory_bytes {bytes}"
+            );
+        }
+
+        output
+    }
+}
+
+/// Returns the memory used by the server, on Linux.
+#[cfg(target_os = "linux")]
+fn resident_memory() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    // SAFETY: sysconf only reads a configuration value.
+    let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) };
+    Some(pages * u64::try_from(page_size).ok()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn resident_memory() -> Option<u64> {
+    None
+}
+
+/// Serves the metrics over HTTP, on every path.
+pub struct MetricsServer {
+    listener: TcpListener,
+}
+
+impl MetricsServer {
+    pub async fn bind(addr: SocketAddr) -> io::Result<Self> {
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+        })
+    }
+
+    /// Answers the scrapes until the server stops.
+    pub async fn run(self) {
+        http::serve(self.listener, |_| async {
+            Response::ok("text/plain; version=0.0.4", METRICS.render())
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let metrics = Metrics::default();
+        metrics.connection_opened();
+        metrics.connection_changed_state(ConnectionState::Handshake, ConnectionState::Status);
+        metrics.packet_received(ConnectionState::Status, 0x00, 3);
+        metrics.packet_received(ConnectionState::Status, 0x00, 3);
+        metrics.packet_sent(ConnectionState::Status, 0x01, 10);
+        metrics.tick_finished(Duration::from_millis(3));
+        metrics.tick_finished(Duration::from_millis(40));
+        metrics.tick_finished(Duration::from_secs(5));
+        metrics.set_tps(19.5);
+
+        let output = metrics.render();
+        for line in [
+            "copper_connections{state=\"handshake\"} 0",
+            "copper_connections{state=\"status\"} 1",
+            "copper_packets_received_total{state=\"status\",id=\"0x00\"} 2",
+            "copper_bytes_received_total{state=\"status\",id=\"0x00\"} 6",
+            "copper_packets_sent_total{state=\"status\",id=\"0x01\"} 1",
+            "copper_bytes_sent_total{state=\"status\",id=\"0x01\"} 10",
+            "copper_tick_duration_seconds_bucket{le=\"0.005\"} 1",
+            "copper_tick_duration_seconds_bucket{le=\"0.025\"} 1",
+            "copper_tick_duration_seconds_bucket{le=\"0.05\"} 2",
+            "copper_tick_duration_seconds_bucket{le=\"2.5\"} 2",
+            "copper_tick_duration_seconds_bucket{le=\"+Inf\"} 3",
+            "copper_tick_duration_seconds_sum 5.043",
+            "copper_tick_duration_seconds_count 3",
+            "copper_tps 19.5",
+            "copper_loaded_chunks 0",
+        ] {
+            assert!(output.lines().any(|l| l == line), "missing {line}");
+        }
+        // Packets never seen are left out.
+        assert!(!output.contains("state=\"play\",id="));
+    }
+}
@@ -0,0 +1,570 @@
+//! The binary format of NBT: big-endian numbers, and strings in modified UTF-8, prefixed by their
+//! length in bytes as an unsigned short.
+//! - In files, the root is a compound with a name, usually empty, and the whole file may be
+//!   gzipped or zlib compressed. The server writes them gzipped, like vanilla.
+//! - In packets, since 1.20.2, the root has no name and may be any tag.
+//!
+//! The lengths are checked against the data left before anything is allocated, so a few bytes
+//! cannot make the server allocate gigabytes.
+
+use std::io::{Read, Write};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::GzEncoder;
+
+use super::{ids, Compound, NbtError, Tag, MAX_DEPTH};
+
+/// The largest NBT file once decompressed, so a small file cannot make the server allocate
+/// gigabytes.
+pub const MAX_FILE_SIZE: usize = 32 * 1024 * 1024;
+
+/// Reads a named root compound, returning its name, the compound and the number of bytes read.
+pub fn read_named(data: &[u8]) -> Result<(String, Compound, usize), NbtError> {
+    let mut reader = Reader::new(data);
+    let id = reader.u8()?;
+    if id != ids::COMPOUND {
+        return Err(NbtError::InvalidRoot(id));
+    }
+    let name = reader.string()?;
+    let compound = reader.compound()?;
+    Ok((name, compound, reader.position))
+}
+
+/// Writes a named root compound.
+pub fn write_named(name: &str, compound: &Compound) -> Result<Vec<u8>, NbtError> {
+    let mut result = vec![ids::COMPOUND];
+    write_string(&mut result, name)?;
+    write_compound(&mut result, compound, 0)?;
+    Ok(result)
+}
+
+/// Reads a nameless root tag, as sent in packets, returning it and the number of bytes read.
+/// The root is None when it is an End tag, which is how packets send "nothing".
+#[cfg(test)]
+pub fn read_network(data: &[u8]) -> Result<(Option<Tag>, usize), NbtError> {
+    let mut reader = Reader::new(data);
+    let id = reader.u8()?;
+    if id == ids::END {
+        return Ok((None, reader.position));
+    }
+    let tag = reader.payload(id)?;
+    Ok((Some(tag), reader.position))
+}
+
+/// Writes a nameless root tag, as sent in packets.
+pub fn write_network(tag: &Tag) -> Result<Vec<u8>, NbtError> {
+    let mut result = vec![tag.id()];
+    write_payload(&mut result, tag, 0)?;
+    Ok(result)
+}
+
+/// Reads an NBT file, uncompressed, gzipped or zlib compressed, returning the name of the root
+/// and the root compound.
+pub fn read_file(data: &[u8]) -> Result<(String, Compound), NbtError> {
+    let decompressed;
+    let data = match data {
+        [0x1F, 0x8B, ..] => {
+            decompressed = decompress(GzDecoder::new(data))?;
+            &decompressed
+        }
+        [0x78, ..] => {
+            decompressed = decompress(ZlibDecoder::new(data))?;
+            &decompressed
+        }
+        _ => data,
+    };
+
+    let (name, compound, _) = read_named(data)?;
+    Ok((name, compound))
+}
+
+/// Reads at most `MAX_FILE_SIZE` bytes from a decoder.
+fn decompress(decoder: impl Read) -> Result<Vec<u8>, NbtError> {
+    let mut data = Vec::new();
+    decoder
+        .take(MAX_FILE_SIZE as u64 + 1)
+        .read_to_end(&mut data)?;
+    if data.len() > MAX_FILE_SIZE {
+        return Err(NbtError::FileTooLarge);
+    }
+    Ok(data)
+}
+
+/// Writes a gzipped NBT file with a named root compound.
+pub fn write_file(name: &str, compound: &Compound) -> Result<Vec<u8>, NbtError> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    encoder.write_all(&write_named(name, compound)?)?;
+    Ok(encoder.finish()?)
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+    /// How many lists and compounds the reader is in.
+    depth: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self {
+            data,
+            position: 0,
+            depth: 0,
+        }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], NbtError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(NbtError::UnexpectedEnd)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], NbtError> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, NbtError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    /// Reads the length of an array or a list, whose elements take at least `element_size`
+    /// bytes each.
+    fn length(&mut self, element_size: usize) -> Result<usize, NbtError> {
+        let len = i32::from_be_bytes(self.array()?);
+        let remaining = self.data.len() - self.position;
+        match usize::try_from(len) {
+            Ok(n) if n.saturating_mul(element_size) <= remaining => Ok(n),
+            _ => Err(NbtError::InvalidLength(len)),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, NbtError> {
+        let len = u16::from_be_bytes(self.array()?);
+        decode_mutf8(self.take(len.into())?)
+    }
+
+    /// Reads the elements of an array of `N`-byte numbers.
+    fn numbers<const N: usize, T>(
+        &mut self,
+        from_bytes: fn([u8; N]) -> T,
+    ) -> Result<Vec<T>, NbtError> {
+        let len = self.length(N)?;
+        Ok(self
+            .take(len * N)?
+            .chunks_exact(N)
+            .map(|chunk| from_bytes(chunk.try_into().unwrap()))
+            .collect())
+    }
+
+    /// Enters a list or a compound.
+    fn enter(&mut self) -> Result<(), NbtError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(NbtError::TooDeep);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    // The recursive functions are kept small, since the stack may only have room for a few
+    // hundred bytes per level in debug builds.
+    fn payload(&mut self, id: u8) -> Result<Tag, NbtError> {
+        match id {
+            ids::LIST => self.list().map(Tag::List),
+            ids::COMPOUND => self.compound().map(Tag::Compound),
+            _ => self.primitive(id),
+        }
+    }
+
+    /// Reads a tag that is not a list nor a compound.
+    fn primitive(&mut self, id: u8) -> Result<Tag, NbtError> {
+        Ok(match id {
+            ids::BYTE => Tag::Byte(i8::from_be_bytes(self.array()?)),
+            ids::SHORT => Tag::Short(i16::from_be_bytes(self.array()?)),
+            ids::INT => Tag::Int(i32::from_be_bytes(self.array()?)),
+            ids::LONG => Tag::Long(i64::from_be_bytes(self.array()?)),
+            ids::FLOAT => Tag::Float(f32::from_be_bytes(self.array()?)),
+            ids::DOUBLE => Tag::Double(f64::from_be_bytes(self.array()?)),
+            ids::BYTE_ARRAY => Tag::ByteArray(self.numbers(i8::from_be_bytes)?),
+            ids::STRING => Tag::String(self.string()?),
+            ids::INT_ARRAY => Tag::IntArray(self.numbers(i32::from_be_bytes)?),
+            ids::LONG_ARRAY => Tag::LongArray(self.numbers(i64::from_be_bytes)?),
+            _ => return Err(NbtError::InvalidTagType(id)),
+        })
+    }
+
+    fn list(&mut self) -> Result<Vec<Tag>, NbtError> {
+        self.enter()?;
+        let id = self.u8()?;
+        let len = self.length(min_payload_size(id)?)?;
+        if id == ids::END && len > 0 {
+            return Err(NbtError::InvalidTagType(id));
+        }
+        let mut tags = Vec::with_capacity(len);
+        for _ in 0..len {
+            tags.push(self.payload(id)?);
+        }
+        self.depth -= 1;
+        Ok(tags)
+    }
+
+    fn compound(&mut self) -> Result<Compound, NbtError> {
+        self.enter()?;
+        let mut compound = Compound::new();
+        loop {
+            let id = self.u8()?;
+            if id == ids::END {
+                self.depth -= 1;
+                return Ok(compound);
+            }
+            let name = self.string()?;
+            let tag = self.payload(id)?;
+            compound.insert(name, tag);
+        }
+    }
+}
+
+/// The smallest size of a tag of type `id` without its type and name, to check list lengths.
+fn min_payload_size(id: u8) -> Result<usize, NbtError> {
+    Ok(match id {
+        // Lists of End tags must be empty, checked after the length.
+        ids::END => 0,
+        ids::BYTE | ids::COMPOUND => 1,
+        ids::SHORT | ids::STRING => 2,
+        ids::INT | ids::FLOAT | ids::BYTE_ARRAY | ids::INT_ARRAY | ids::LONG_ARRAY => 4,
+        ids::LIST => 5,
+        ids::LONG | ids::DOUBLE => 8,
+        _ => return Err(NbtError::InvalidTagType(id)),
+    })
+}
+
+/// Writes a tag in `depth` lists and compounds.
+fn write_payload(out: &mut Vec<u8>, tag: &Tag, depth: usize) -> Result<(), NbtError> {
+    match tag {
+        Tag::List(tags) => write_list(out, tags, depth),
+        Tag::Compound(compound) => write_compound(out, compound, depth),
+        tag => write_primitive(out, tag),
+    }
+}
+
+fn write_list(out: &mut Vec<u8>, tags: &[Tag], depth: usize) -> Result<(), NbtError> {
+    if depth >= MAX_DEPTH {
+        return Err(NbtError::TooDeep);
+    }
+    let id = tags.first().map_or(ids::END, Tag::id);
+    if tags.iter().any(|tag| tag.id() != id) {
+        return Err(NbtError::MixedList);
+    }
+    out.push(id);
+    out.extend_from_slice(&(tags.len() as i32).to_be_bytes());
+    for tag in tags {
+        write_payload(out, tag, depth + 1)?;
+    }
+    Ok(())
+}
+
+/// Writes a tag that is not a list nor a compound.
+fn write_primitive(out: &mut Vec<u8>, tag: &Tag) -> Result<(), NbtError> {
+    match tag {
+        Tag::Byte(value) => out.extend_from_slice(&value.to_be_bytes()),
+        Tag::Short(value) => out.extend_from_slice(&value.to_be_bytes()),
+        Tag::Int(value) => out.extend_from_slice(&value.to_be_bytes()),
+        Tag::Long(value) => out.extend_from_slice(&value.to_be_bytes()),
+        Tag::Float(value) => out.extend_from_slice(&value.to_be_bytes()),
+        Tag::Double(value) => out.extend_from_slice(&value.to_be_bytes()),
+        Tag::ByteArray(values) => {
+            out.extend_from_slice(&(values.len() as i32).to_be_bytes());
+            out.extend(values.iter().map(|&value| value as u8));
+        }
+        Tag::String(value) => write_string(out, value)?,
+        Tag::List(_) | Tag::Compound(_) => unreachable!("Written by write_payload"),
+        Tag::IntArray(values) => {
+            out.extend_from_slice(&(values.len() as i32).to_be_bytes());
+            values
+                .iter()
+                .for_each(|value| out.extend_from_slice(&value.to_be_bytes()));
+        }
+        Tag::LongArray(values) => {
+            out.extend_from_slice(&(values.len() as i32).to_be_bytes());
+            values
+                .iter()
+                .for_each(|value| out.extend_from_slice(&value.to_be_bytes()));
+        }
+    }
+    Ok(())
+}
+
+fn write_compound(out: &mut Vec<u8>, compound: &Compound, depth: usize) -> Result<(), NbtError> {
+    if depth >= MAX_DEPTH {
+        return Err(NbtError::TooDeep);
+    }
+    for (name, tag) in compound {
+        out.push(tag.id());
+        write_string(out, name)?;
+        write_payload(out, tag, depth + 1)?;
+    }
+    out.push(ids::END);
+    Ok(())
+}
+
+fn write_string(out: &mut Vec<u8>, value: &str) -> Result<(), NbtError> {
+    let bytes = encode_mutf8(value);
+    let len = u16::try_from(bytes.len()).map_err(|_| NbtError::StringTooLong(bytes.len()))?;
+    out.extend_from_slice(&len.to_be_bytes());
+    out.extend_from_slice(&bytes);
+    Ok(())
+}
+
+/// Encodes a string in Java's modified UTF-8: NUL takes two bytes, and the characters outside
+/// of the BMP are encoded as surrogate pairs of three bytes each.
+pub fn encode_mutf8(value: &str) -> Vec<u8> {
+    if !value.chars().any(|c| c == '\0' || c.len_utf8() == 4) {
+        return value.as_bytes().to_vec();
+    }
+
+    let mut result = Vec::with_capacity(value.len() + 2);
+    for unit in value.encode_utf16() {
+        match unit {
+            0x01..=0x7F => result.push(unit as u8),
+            0x00 | 0x80..=0x7FF => {
+                result.push(0xC0 | (unit >> 6) as u8);
+                result.push(0x80 | (unit & 0x3F) as u8);
+            }
+            _ => {
+                result.push(0xE0 | (unit >> 12) as u8);
+                result.push(0x80 | ((unit >> 6) & 0x3F) as u8);
+                result.push(0x80 | (unit & 0x3F) as u8);
+            }
+        }
+    }
+    result
+}
+
+/// Decodes a string in Java's modified UTF-8. Unpaired surrogates are rejected since Rust
+/// strings cannot hold them.
+pub fn decode_mutf8(bytes: &[u8]) -> Result<String, NbtError> {
+    // Without 4-byte sequences or encoded surrogates, it is plain UTF-8.
+    if !bytes.iter().any(|&b| b >= 0xF0) {
+        if let Ok(value) = std::str::from_utf8(bytes) {
+            return Ok(value.to_string());
+        }
+    }
+
+    fn continuation(iter: &mut impl Iterator<Item = u16>) -> Result<u16, NbtError> {
+        match iter.next() {
+            Some(b) if b & 0xC0 == 0x80 => Ok(b & 0x3F),
+            _ => Err(NbtError::InvalidString),
+        }
+    }
+
+    let mut units = Vec::with_capacity(bytes.len());
+    let mut iter = bytes.iter().map(|&b| u16::from(b));
+    while let Some(b) = iter.next() {
+        let unit = match b {
+            0x00..=0x7F => b,
+            0xC0..=0xDF => ((b & 0x1F) << 6) | continuation(&mut iter)?,
+            0xE0..=0xEF => {
+                let high = continuation(&mut iter)?;
+                ((b & 0x0F) << 12) | (high << 6) | continuation(&mut iter)?
+            }
+            _ => return Err(NbtError::InvalidString),
+        };
+        units.push(unit);
+    }
+
+    String::from_utf16(&units).map_err(|_| NbtError::InvalidString)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::random_tag;
+    use flate2::write::ZlibEncoder;
+    use rand::Rng;
+
+    fn sample() -> Compound {
+        Compound::from([
+            ("name".to_string(), Tag::from("Bananrama")),
+            ("health".to_string(), Tag::Float(20.0)),
+            (
+                "pos".to_string(),
+                Tag::List(vec![Tag::Double(0.5), Tag::Double(64.0)]),
+            ),
+            ("heights".to_string(), Tag::LongArray(vec![1, -1])),
+        ])
+    }
+
+    #[test]
+    fn test_named() {
+        let data = write_named(
+            "hello world",
+            &Compound::from([("name".to_string(), Tag::from("Bananrama"))]),
+        )
+        .unwrap();
+        // The "hello world" example of the specification.
+        assert_eq!(
+            data,
+            b"\x0a\x00\x0bhello world\x08\x00\x04name\x00\x09Bananrama\x00"
+        );
+        let (name, compound, len) = read_named(&data).unwrap();
+        assert_eq!(name, "hello world");
+        assert_eq!(compound["name"], Tag::from("Bananrama"));
+        assert_eq!(len, data.len());
+
+        assert!(matches!(
+            read_named(&[ids::INT, 0, 0, 0, 0, 0, 1]),
+            Err(NbtError::InvalidRoot(ids::INT))
+        ));
+    }
+
+    #[test]
+    fn test_network() {
+        let data = write_network(&Tag::from("Kicked")).unwrap();
+        assert_eq!(data, b"\x08\x00\x06Kicked");
+        assert_eq!(
+            read_network(&data).unwrap(),
+            (Some(Tag::from("Kicked")), data.len())
+        );
+        assert_eq!(read_network(&[ids::END]).unwrap(), (None, 1));
+    }
+
+    #[test]
+    fn test_file_compression() {
+        let data = write_file("", &sample()).unwrap();
+        assert_eq!(&data[..2], [0x1F, 0x8B]);
+        assert_eq!(read_file(&data).unwrap(), (String::new(), sample()));
+
+        let named = write_named("", &sample()).unwrap();
+        assert_eq!(read_file(&named).unwrap(), (String::new(), sample()));
+        let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&named).unwrap();
+        let zlib = encoder.finish().unwrap();
+        assert_eq!(read_file(&zlib).unwrap(), (String::new(), sample()));
+    }
+
+    #[test]
+    fn test_file_size_limit() {
+        // A few kilobytes of zeros, which would be more than the limit once decompressed.
+        let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::best());
+        let zeros = vec![0; 1024 * 1024];
+        for _ in 0..=MAX_FILE_SIZE / zeros.len() {
+            encoder.write_all(&zeros).unwrap();
+        }
+        let data = encoder.finish().unwrap();
+        assert!(data.len() < 64 * 1024);
+        assert!(matches!(read_file(&data), Err(NbtError::FileTooLarge)));
+    }
+
+    #[test]
+    fn test_random_round_trips() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let tag = random_tag(&mut rng, 4);
+            let data = write_network(&tag).unwrap();
+            assert_eq!(read_network(&data).unwrap(), (Some(tag), data.len()));
+        }
+    }
+
+    #[test]
+    fn test_truncated_and_garbage() {
+        let data = write_named("", &sample()).unwrap();
+        for len in 0..data.len() {
+            assert!(read_named(&data[..len]).is_err());
+        }
+
+        // Random bytes must fail cleanly, never panic nor allocate wildly.
+        let mut rng = rand::thread_rng();
+        for _ in 0..2000 {
+            let mut data = vec![ids::COMPOUND, 0, 0];
+            data.extend((0..rng.gen_range(0..64)).map(|_| rng.gen::<u8>()));
+            let _ = read_named(&data);
+        }
+    }
+
+    #[test]
+    fn test_malicious_lengths() {
+        // A byte array claiming 2 GB.
+        let data = [ids::BYTE_ARRAY, 0x7F, 0xFF, 0xFF, 0xFF, 1, 2];
+        assert!(matches!(
+            read_network(&data),
+            Err(NbtError::InvalidLength(i32::MAX))
+        ));
+        // A negative length.
+        let data = [ids::INT_ARRAY, 0xFF, 0xFF, 0xFF, 0xFF];
+        assert!(matches!(
+            read_network(&data),
+            Err(NbtError::InvalidLength(-1))
+        ));
+        // A list of a billion compounds, each taking at least one byte.
+        let data = [ids::LIST, ids::COMPOUND, 0x40, 0, 0, 0, 0];
+        assert!(matches!(
+            read_network(&data),
+            Err(NbtError::InvalidLength(0x4000_0000))
+        ));
+        // A list of End tags that is not empty.
+        let data = [ids::LIST, ids::END, 0, 0, 0, 3];
+        assert!(read_network(&data).is_err());
+        // An unknown tag type.
+        assert!(matches!(
+            read_network(&[13]),
+            Err(NbtError::InvalidTagType(13))
+        ));
+    }
+
+    #[test]
+    fn test_depth() {
+        // Lists of lists.
+        let nested = |depth: usize| {
+            let mut data = vec![ids::LIST];
+            for _ in 1..depth {
+                data.extend_from_slice(&[ids::LIST, 0, 0, 0, 1]);
+            }
+            data.extend_from_slice(&[ids::END, 0, 0, 0, 0]);
+            data
+        };
+        assert!(read_network(&nested(MAX_DEPTH)).is_ok());
+        assert!(matches!(
+            read_network(&nested(MAX_DEPTH + 1)),
+            Err(NbtError::TooDeep)
+        ));
+
+        // Compounds of compounds, written and read.
+        let mut tag = Tag::Compound(Compound::new());
+        for _ in 0..MAX_DEPTH - 1 {
+            tag = Tag::Compound(Compound::from([(String::new(), tag)]));
+        }
+        let data = write_network(&tag).unwrap();
+        assert!(read_network(&data).is_ok());
+        let tag = Tag::Compound(Compound::from([(String::new(), tag)]));
+        assert!(matches!(write_network(&tag), Err(NbtError::TooDeep)));
+        // The same data with another level, written by hand.
+        let mut data = data;
+        data.splice(1..1, [ids::COMPOUND, 0, 0]);
+        data.push(ids::END);
+        assert!(matches!(read_network(&data), Err(NbtError::TooDeep)));
+    }
+
+    #[test]
+    fn test_mutf8() {
+        for value in ["plain", "é and ü", "nul \0 inside", "🍌 bananas", "日本語"] {
+            assert_eq!(decode_mutf8(&encode_mutf8(value)).unwrap(), value);
+        }
+        assert_eq!(encode_mutf8("\0"), [0xC0, 0x80]);
+        // U+1F34C as the surrogate pair D83C DF4C.
+        assert_eq!(encode_mutf8("🍌"), [0xED, 0xA0, 0xBC, 0xED, 0xBD, 0x8C]);
+        // An unpaired surrogate, and a 4-byte UTF-8 sequence.
+        assert!(decode_mutf8(&[0xED, 0xA0, 0xBC]).is_err());
+        assert!(decode_mutf8("🍌".as_bytes()).is_err());
+
+        assert!(matches!(
+            write_network(&Tag::String("a".repeat(65536))),
+            Err(NbtError::StringTooLong(65536))
+        ));
+        assert!(matches!(
+            write_network(&Tag::List(vec![Tag::Byte(1), Tag::Int(1)])),
+            Err(NbtError::MixedList)
+        ));
+    }
+}
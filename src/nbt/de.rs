@@ -0,0 +1,375 @@
+//! Converts tags to Rust values with serde, the reverse of `ser`.
+//! The numbers convert to any type they fit in, and bytes to booleans.
+
+use serde::de::value::{MapAccessDeserializer, MapDeserializer, SeqDeserializer};
+use serde::de::{
+    self, DeserializeOwned, Deserializer, IntoDeserializer, MapAccess, SeqAccess, Visitor,
+};
+use serde::forward_to_deserialize_any;
+use serde::Deserialize;
+
+use super::ser::{BYTE_ARRAY_TOKEN, INT_ARRAY_TOKEN, LONG_ARRAY_TOKEN};
+use super::{Compound, NbtError, Tag};
+
+/// The name of the newtype struct requested by the `Deserialize` of `Tag`. Tags deserialize
+/// their arrays as a map with the token of the array as the only key, so that they are kept
+/// as arrays rather than turned into lists.
+const TAG_TOKEN: &str = "$copper::nbt::Tag";
+
+/// Converts a tag to a value.
+pub fn from_tag<T: DeserializeOwned>(tag: Tag) -> Result<T, NbtError> {
+    T::deserialize(tag)
+}
+
+/// Tags deserialize from themselves, or from any self-describing format.
+impl<'de> Deserialize<'de> for Tag {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_newtype_struct(TAG_TOKEN, TagVisitor)
+    }
+}
+
+struct TagVisitor;
+
+impl<'de> Visitor<'de> for TagVisitor {
+    type Value = Tag;
+
+    fn expecting(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        formatter.write_str("an NBT tag")
+    }
+
+    fn visit_bool<E: de::Error>(self, v: bool) -> Result<Tag, E> {
+        Ok(Tag::from(v))
+    }
+
+    fn visit_i8<E: de::Error>(self, v: i8) -> Result<Tag, E> {
+        Ok(Tag::Byte(v))
+    }
+
+    fn visit_i16<E: de::Error>(self, v: i16) -> Result<Tag, E> {
+        Ok(Tag::Short(v))
+    }
+
+    fn visit_i32<E: de::Error>(self, v: i32) -> Result<Tag, E> {
+        Ok(Tag::Int(v))
+    }
+
+    fn visit_i64<E: de::Error>(self, v: i64) -> Result<Tag, E> {
+        Ok(Tag::Long(v))
+    }
+
+    fn visit_u8<E: de::Error>(self, v: u8) -> Result<Tag, E> {
+        Ok(Tag::Short(v.into()))
+    }
+
+    fn visit_u16<E: de::Error>(self, v: u16) -> Result<Tag, E> {
+        Ok(Tag::Int(v.into()))
+    }
+
+    fn visit_u32<E: de::Error>(self, v: u32) -> Result<Tag, E> {
+        Ok(Tag::Long(v.into()))
+    }
+
+    fn visit_u64<E: de::Error>(self, v: u64) -> Result<Tag, E> {
+        i64::try_from(v)
+            .map(Tag::Long)
+            .map_err(|_| E::custom(format!("{v} is too large for a long")))
+    }
+
+    fn visit_f32<E: de::Error>(self, v: f32) -> Result<Tag, E> {
+        Ok(Tag::Float(v))
+    }
+
+    fn visit_f64<E: de::Error>(self, v: f64) -> Result<Tag, E> {
+        Ok(Tag::Double(v))
+    }
+
+    fn visit_str<E: de::Error>(self, v: &str) -> Result<Tag, E> {
+        Ok(Tag::from(v))
+    }
+
+    fn visit_string<E: de::Error>(self, v: String) -> Result<Tag, E> {
+        Ok(Tag::String(v))
+    }
+
+    fn visit_bytes<E: de::Error>(self, v: &[u8]) -> Result<Tag, E> {
+        Ok(Tag::ByteArray(v.iter().map(|&b| b as i8).collect()))
+    }
+
+    fn visit_unit<E: de::Error>(self) -> Result<Tag, E> {
+        Ok(Tag::Compound(Compound::new()))
+    }
+
+    fn visit_some<D: Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        Tag::deserialize(deserializer)
+    }
+
+    fn visit_newtype_struct<D: Deserializer<'de>>(self, deserializer: D) -> Result<Tag, D::Error> {
+        deserializer.deserialize_any(self)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Tag, A::Error> {
+        let mut tags: Vec<Tag> = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(tag) = seq.next_element::<Tag>()? {
+            if tags.first().is_some_and(|first| first.id() != tag.id()) {
+                return Err(de::Error::custom(NbtError::MixedList));
+            }
+            tags.push(tag);
+        }
+        Ok(Tag::List(tags))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Tag, A::Error> {
+        let mut compound = Compound::new();
+        let Some(key) = map.next_key::<String>()? else {
+            return Ok(Tag::Compound(compound));
+        };
+        match key.as_str() {
+            BYTE_ARRAY_TOKEN => return Ok(Tag::ByteArray(map.next_value()?)),
+            INT_ARRAY_TOKEN => return Ok(Tag::IntArray(map.next_value()?)),
+            LONG_ARRAY_TOKEN => return Ok(Tag::LongArray(map.next_value()?)),
+            _ => {}
+        }
+        compound.insert(key, map.next_value()?);
+        while let Some((key, tag)) = map.next_entry()? {
+            compound.insert(key, tag);
+        }
+        Ok(Tag::Compound(compound))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, NbtError> for Tag {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Tag {
+    type Error = NbtError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::Byte(value) => visitor.visit_i8(value),
+            Tag::Short(value) => visitor.visit_i16(value),
+            Tag::Int(value) => visitor.visit_i32(value),
+            Tag::Long(value) => visitor.visit_i64(value),
+            Tag::Float(value) => visitor.visit_f32(value),
+            Tag::Double(value) => visitor.visit_f64(value),
+            Tag::ByteArray(values) => visitor.visit_seq(SeqDeserializer::new(values.into_iter())),
+            Tag::String(value) => visitor.visit_string(value),
+            Tag::List(tags) => visitor.visit_seq(SeqDeserializer::new(tags.into_iter())),
+            Tag::Compound(compound) => {
+                visitor.visit_map(MapDeserializer::new(compound.into_iter()))
+            }
+            Tag::IntArray(values) => visitor.visit_seq(SeqDeserializer::new(values.into_iter())),
+            Tag::LongArray(values) => visitor.visit_seq(SeqDeserializer::new(values.into_iter())),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::Byte(value) => visitor.visit_bool(value != 0),
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::ByteArray(values) => {
+                visitor.visit_byte_buf(values.into_iter().map(|b| b as u8).collect())
+            }
+            tag => tag.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        self.deserialize_bytes(visitor)
+    }
+
+    /// The missing fields are None, so any tag present is Some.
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        let token = match (name, &self) {
+            (TAG_TOKEN, Tag::ByteArray(_)) => BYTE_ARRAY_TOKEN,
+            (TAG_TOKEN, Tag::IntArray(_)) => INT_ARRAY_TOKEN,
+            (TAG_TOKEN, Tag::LongArray(_)) => LONG_ARRAY_TOKEN,
+            _ => return visitor.visit_newtype_struct(self),
+        };
+        visitor.visit_map(MapDeserializer::new(std::iter::once((token, self))))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, NbtError> {
+        match self {
+            Tag::String(variant) => visitor.visit_enum(variant.into_deserializer()),
+            Tag::Compound(compound) if compound.len() == 1 => visitor.visit_enum(
+                MapAccessDeserializer::new(MapDeserializer::new(compound.into_iter())),
+            ),
+            tag => Err(NbtError::Custom(format!(
+                "Expected an enum variant, found {tag}"
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, NbtError> {
+        visitor.visit_unit()
+    }
+
+    forward_to_deserialize_any! {
+        i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        seq tuple tuple_struct map struct identifier
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::random_tag;
+    use crate::nbt::{binary, snbt, to_tag};
+    use serde::{Deserialize, Serialize};
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    #[serde(rename_all = "PascalCase")]
+    struct Level {
+        level_name: String,
+        hardcore: bool,
+        spawn_x: i32,
+        time: u32,
+        version: Option<Version>,
+        wandering_trader_id: Option<i32>,
+        game_rules: std::collections::BTreeMap<String, String>,
+        seeds: Tag,
+        difficulty: Difficulty,
+        heights: Vec<f32>,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    struct Version {
+        id: i32,
+        name: String,
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Difficulty {
+        Peaceful,
+        Custom { scale: f64 },
+    }
+
+    fn level() -> Level {
+        Level {
+            level_name: "world".to_string(),
+            hardcore: true,
+            spawn_x: -12,
+            time: 3_000_000_000,
+            version: Some(Version {
+                id: 3955,
+                name: "1.21.1".to_string(),
+            }),
+            wandering_trader_id: None,
+            game_rules: [("keepInventory".to_string(), "true".to_string())].into(),
+            seeds: Tag::LongArray(vec![1, -2]),
+            difficulty: Difficulty::Custom { scale: 1.5 },
+            heights: vec![64.0, 65.5],
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let tag = to_tag(&level()).unwrap();
+        let compound = tag.as_compound().unwrap();
+        assert_eq!(compound["Hardcore"], Tag::Byte(1));
+        assert_eq!(compound["Time"], Tag::Long(3_000_000_000));
+        assert_eq!(compound["Seeds"], Tag::LongArray(vec![1, -2]));
+        assert!(!compound.contains_key("WanderingTraderId"));
+        assert_eq!(
+            compound["Difficulty"],
+            Tag::Compound(Compound::from([(
+                "Custom".to_string(),
+                Tag::Compound(Compound::from([("scale".to_string(), Tag::Double(1.5))]))
+            )]))
+        );
+
+        assert_eq!(from_tag::<Level>(tag.clone()).unwrap(), level());
+
+        // Through the binary and the text formats.
+        let data = binary::write_network(&tag).unwrap();
+        let (decoded, _) = binary::read_network(&data).unwrap();
+        assert_eq!(from_tag::<Level>(decoded.unwrap()).unwrap(), level());
+        let decoded = snbt::parse(&tag.to_string()).unwrap();
+        assert_eq!(from_tag::<Level>(decoded).unwrap(), level());
+
+        assert_eq!(
+            to_tag(&Difficulty::Peaceful).unwrap(),
+            Tag::from("Peaceful")
+        );
+        assert_eq!(
+            from_tag::<Difficulty>(Tag::from("Peaceful")).unwrap(),
+            Difficulty::Peaceful
+        );
+    }
+
+    #[test]
+    fn test_serde_errors() {
+        // Too large for the field.
+        assert!(from_tag::<u8>(Tag::Int(300)).is_err());
+        assert!(from_tag::<String>(Tag::Int(1)).is_err());
+        assert!(matches!(
+            to_tag(&vec![Some(1), None]),
+            Err(NbtError::Custom(_))
+        ));
+        assert!(matches!(to_tag(&u64::MAX), Err(NbtError::Custom(_))));
+    }
+
+    #[test]
+    fn test_tag_serde() {
+        #[derive(Serialize, Deserialize, Debug, PartialEq)]
+        struct Chunk {
+            status: String,
+            sections: Tag,
+        }
+
+        let mut rng = rand::thread_rng();
+        for _ in 0..50 {
+            let tag = random_tag(&mut rng, 4);
+            // The arrays stay arrays.
+            assert_eq!(from_tag::<Tag>(to_tag(&tag).unwrap()).unwrap(), tag);
+            let chunk = Chunk {
+                status: "minecraft:full".to_string(),
+                sections: tag,
+            };
+            assert_eq!(from_tag::<Chunk>(to_tag(&chunk).unwrap()).unwrap(), chunk);
+        }
+
+        let json = serde_json::json!({"Heights": [1, 2], "Name": "world"});
+        let tag = serde_json::from_value::<Tag>(json).unwrap();
+        assert_eq!(
+            tag.as_compound().unwrap()["Heights"],
+            Tag::List(vec![Tag::Long(1), Tag::Long(2)])
+        );
+        assert!(serde_json::from_value::<Tag>(serde_json::json!([1, "a"])).is_err());
+    }
+}
@@ -0,0 +1,241 @@
+//! NBT (Named Binary Tag), the format of the world files, the registry data, the item
+//! components and the chat components.
+//! - `binary`: the big-endian binary format, in files (named root, maybe gzipped or zlib
+//!   compressed) and in packets (nameless root, since 1.20.2).
+//! - `snbt`: the text format, like `{name:"Steve",Health:20.0f}`.
+//! - `to_tag` and `from_tag`: conversions between tags and Rust types, with serde.
+//!
+//! See https://wiki.vg/NBT
+
+pub mod binary;
+mod de;
+mod ser;
+pub mod snbt;
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+pub use de::from_tag;
+pub use ser::to_tag;
+
+/// The tags are nested at most this deep, like vanilla. Deeper data is rejected.
+pub const MAX_DEPTH: usize = 512;
+
+/// The tags of a compound, by name.
+pub type Compound = BTreeMap<String, Tag>;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Tag {
+    Byte(i8),
+    Short(i16),
+    Int(i32),
+    Long(i64),
+    Float(f32),
+    Double(f64),
+    ByteArray(Vec<i8>),
+    String(String),
+    /// The elements must all be of the same type.
+    List(Vec<Tag>),
+    Compound(Compound),
+    IntArray(Vec<i32>),
+    LongArray(Vec<i64>),
+}
+
+/// The IDs of the tag types, in the binary format.
+pub mod ids {
+    pub const END: u8 = 0;
+    pub const BYTE: u8 = 1;
+    pub const SHORT: u8 = 2;
+    pub const INT: u8 = 3;
+    pub const LONG: u8 = 4;
+    pub const FLOAT: u8 = 5;
+    pub const DOUBLE: u8 = 6;
+    pub const BYTE_ARRAY: u8 = 7;
+    pub const STRING: u8 = 8;
+    pub const LIST: u8 = 9;
+    pub const COMPOUND: u8 = 10;
+    pub const INT_ARRAY: u8 = 11;
+    pub const LONG_ARRAY: u8 = 12;
+}
+
+impl Tag {
+    /// The ID of the type of the tag, in the binary format.
+    pub fn id(&self) -> u8 {
+        match self {
+            Tag::Byte(_) => ids::BYTE,
+            Tag::Short(_) => ids::SHORT,
+            Tag::Int(_) => ids::INT,
+            Tag::Long(_) => ids::LONG,
+            Tag::Float(_) => ids::FLOAT,
+            Tag::Double(_) => ids::DOUBLE,
+            Tag::ByteArray(_) => ids::BYTE_ARRAY,
+            Tag::String(_) => ids::STRING,
+            Tag::List(_) => ids::LIST,
+            Tag::Compound(_) => ids::COMPOUND,
+            Tag::IntArray(_) => ids::INT_ARRAY,
+            Tag::LongArray(_) => ids::LONG_ARRAY,
+        }
+    }
+
+    pub fn as_compound(&self) -> Option<&Compound> {
+        match self {
+            Tag::Compound(compound) => Some(compound),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Tag::String(value) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Returns the value of a numeric tag, which is how booleans are stored too.
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Tag::Byte(value) => Some(value.into()),
+            Tag::Short(value) => Some(value.into()),
+            Tag::Int(value) => Some(value.into()),
+            Tag::Long(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+impl From<&str> for Tag {
+    fn from(value: &str) -> Self {
+        Tag::String(value.to_string())
+    }
+}
+
+impl From<String> for Tag {
+    fn from(value: String) -> Self {
+        Tag::String(value)
+    }
+}
+
+impl From<bool> for Tag {
+    fn from(value: bool) -> Self {
+        Tag::Byte(value.into())
+    }
+}
+
+impl From<Compound> for Tag {
+    fn from(value: Compound) -> Self {
+        Tag::Compound(value)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum NbtError {
+    #[error("Unexpected end of NBT data")]
+    UnexpectedEnd,
+    #[error("Invalid NBT tag type {0}")]
+    InvalidTagType(u8),
+    #[error("The root of NBT data must be a compound, found tag type {0}")]
+    InvalidRoot(u8),
+    #[error("NBT data nested deeper than {MAX_DEPTH} levels")]
+    TooDeep,
+    #[error("Invalid NBT length {0}")]
+    InvalidLength(i32),
+    #[error("Invalid modified UTF-8 string in NBT data")]
+    InvalidString,
+    #[error("NBT string too long: {0} bytes, at most 65535")]
+    StringTooLong(usize),
+    #[error(
+        "NBT file larger than {} bytes once decompressed",
+        binary::MAX_FILE_SIZE
+    )]
+    FileTooLarge,
+    #[error("NBT list with elements of different types")]
+    MixedList,
+    #[error("Invalid SNBT at position {position}: {message}")]
+    Syntax { position: usize, message: String },
+    #[error("{0}")]
+    Custom(String),
+    #[error("I/O error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl serde::ser::Error for NbtError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        NbtError::Custom(msg.to_string())
+    }
+}
+
+impl serde::de::Error for NbtError {
+    fn custom<T: std::fmt::Display>(msg: T) -> Self {
+        NbtError::Custom(msg.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    /// Generates a random tag, nested at most `depth` levels, to fuzz the formats.
+    pub fn random_tag(rng: &mut impl Rng, depth: usize) -> Tag {
+        let max_id = if depth == 0 {
+            ids::STRING
+        } else {
+            ids::LONG_ARRAY
+        };
+        let id = loop {
+            let id = rng.gen_range(ids::BYTE..=max_id);
+            if depth > 0 || id != ids::BYTE_ARRAY {
+                break id;
+            }
+        };
+        random_tag_of(rng, id, depth)
+    }
+
+    fn random_tag_of(rng: &mut impl Rng, id: u8, depth: usize) -> Tag {
+        let len = rng.gen_range(0..6);
+        match id {
+            ids::BYTE => Tag::Byte(rng.gen()),
+            ids::SHORT => Tag::Short(rng.gen()),
+            ids::INT => Tag::Int(rng.gen()),
+            ids::LONG => Tag::Long(rng.gen()),
+            ids::FLOAT => Tag::Float(rng.gen_range(-1e9..1e9)),
+            ids::DOUBLE => Tag::Double(rng.gen_range(-1e300..1e300)),
+            ids::BYTE_ARRAY => Tag::ByteArray((0..len).map(|_| rng.gen()).collect()),
+            ids::STRING => Tag::String(random_string(rng)),
+            ids::LIST => {
+                let id = random_tag(rng, depth - 1).id();
+                Tag::List(
+                    (0..len)
+                        .map(|_| random_tag_of(rng, id, depth - 1))
+                        .collect(),
+                )
+            }
+            ids::COMPOUND => Tag::Compound(
+                (0..len)
+                    .map(|_| (random_string(rng), random_tag(rng, depth - 1)))
+                    .collect(),
+            ),
+            ids::INT_ARRAY => Tag::IntArray((0..len).map(|_| rng.gen()).collect()),
+            _ => Tag::LongArray((0..len).map(|_| rng.gen()).collect()),
+        }
+    }
+
+    fn random_string(rng: &mut impl Rng) -> String {
+        const CHARS: &[char] = &[
+            'a', 'Z', '0', '_', '-', '.', '+', ' ', '"', '\'', '\\', ':', ',', '{', ']', '\0', 'é',
+            '日', '🍌',
+        ];
+        (0..rng.gen_range(0..8))
+            .map(|_| CHARS[rng.gen_range(0..CHARS.len())])
+            .collect()
+    }
+
+    #[test]
+    fn test_accessors() {
+        assert_eq!(Tag::from(true), Tag::Byte(1));
+        assert_eq!(Tag::Short(-3).as_i64(), Some(-3));
+        assert_eq!(Tag::from("stone").as_str(), Some("stone"));
+        assert!(Tag::Int(0).as_compound().is_none());
+    }
+}
@@ -0,0 +1,556 @@
+//! Converts Rust values to tags with serde.
+//! - Structs and maps are compounds, and their `None` fields are left out.
+//! - Sequences are lists, and the array tags stay arrays.
+//! - Booleans are bytes. The unsigned integers take the next larger type, since NBT has no
+//!   unsigned types: `u8` is a short, `u16` an int, `u32` and `u64` are longs.
+//! - Unit variants are strings, the others are compounds with the variant as the only key.
+
+use serde::ser::{self, Impossible, Serialize};
+
+use super::{Compound, NbtError, Tag};
+
+/// The names of the newtype structs the array tags serialize to, to tell them from the other
+/// sequences.
+pub const BYTE_ARRAY_TOKEN: &str = "$copper::nbt::ByteArray";
+pub const INT_ARRAY_TOKEN: &str = "$copper::nbt::IntArray";
+pub const LONG_ARRAY_TOKEN: &str = "$copper::nbt::LongArray";
+
+/// Converts a value to a tag.
+pub fn to_tag<T: Serialize + ?Sized>(value: &T) -> Result<Tag, NbtError> {
+    value
+        .serialize(Serializer)
+        .and_then(|tag| tag.ok_or_else(|| NbtError::Custom("Cannot convert None to NBT".into())))
+}
+
+/// Tags serialize to themselves, so that typed values can keep raw NBT in their fields.
+impl Serialize for Tag {
+    fn serialize<S: ser::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            Tag::Byte(value) => serializer.serialize_i8(*value),
+            Tag::Short(value) => serializer.serialize_i16(*value),
+            Tag::Int(value) => serializer.serialize_i32(*value),
+            Tag::Long(value) => serializer.serialize_i64(*value),
+            Tag::Float(value) => serializer.serialize_f32(*value),
+            Tag::Double(value) => serializer.serialize_f64(*value),
+            Tag::ByteArray(values) => serializer.serialize_newtype_struct(BYTE_ARRAY_TOKEN, values),
+            Tag::String(value) => serializer.serialize_str(value),
+            Tag::List(tags) => tags.serialize(serializer),
+            Tag::Compound(compound) => compound.serialize(serializer),
+            Tag::IntArray(values) => serializer.serialize_newtype_struct(INT_ARRAY_TOKEN, values),
+            Tag::LongArray(values) => serializer.serialize_newtype_struct(LONG_ARRAY_TOKEN, values),
+        }
+    }
+}
+
+/// Serializes to a tag, or to None for the values left out of compounds.
+struct Serializer;
+
+/// Wraps the tag of an enum variant in a compound with the variant as the only key.
+fn with_variant(name: &str, tag: Tag) -> Option<Tag> {
+    Some(Tag::Compound(Compound::from([(name.to_string(), tag)])))
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeList;
+    type SerializeMap = SerializeCompound;
+    type SerializeStruct = SerializeCompound;
+    type SerializeStructVariant = SerializeCompound;
+
+    fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::from(v)))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Byte(v)))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Short(v)))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Int(v)))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Long(v)))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Short(v.into())))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Int(v.into())))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Long(v.into())))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
+        let v = i64::try_from(v)
+            .map_err(|_| NbtError::Custom(format!("{v} is too large for a long")))?;
+        Ok(Some(Tag::Long(v)))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Float(v)))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Double(v)))
+    }
+
+    fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::String(v.to_string())))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::from(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::ByteArray(v.iter().map(|&b| b as i8).collect())))
+    }
+
+    fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+        Ok(Some(Tag::Compound(Compound::new())))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Self::Ok, Self::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        let tag = value.serialize(self)?;
+        let Some(Tag::List(tags)) = tag else {
+            return Ok(tag);
+        };
+        let numbers = tags.iter().filter_map(Tag::as_i64);
+        Ok(Some(match name {
+            BYTE_ARRAY_TOKEN => Tag::ByteArray(numbers.map(|n| n as i8).collect()),
+            INT_ARRAY_TOKEN => Tag::IntArray(numbers.map(|n| n as i32).collect()),
+            LONG_ARRAY_TOKEN => Tag::LongArray(numbers.collect()),
+            _ => Tag::List(tags),
+        }))
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Self::Ok, Self::Error> {
+        Ok(with_variant(variant, to_tag(value)?))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+        Ok(SerializeList {
+            variant: None,
+            tags: Vec::with_capacity(len.unwrap_or_default()),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+        Ok(SerializeList {
+            variant: Some(variant),
+            tags: Vec::with_capacity(len),
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        Ok(SerializeCompound::default())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, Self::Error> {
+        Ok(SerializeCompound::default())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Self::Error> {
+        Ok(SerializeCompound {
+            variant: Some(variant),
+            ..Default::default()
+        })
+    }
+}
+
+struct SerializeList {
+    /// The variant of a tuple variant.
+    variant: Option<&'static str>,
+    tags: Vec<Tag>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.tags.push(to_tag(value)?);
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, NbtError> {
+        if self
+            .tags
+            .windows(2)
+            .any(|pair| pair[0].id() != pair[1].id())
+        {
+            return Err(NbtError::MixedList);
+        }
+        let tag = Tag::List(self.tags);
+        Ok(match self.variant {
+            Some(name) => with_variant(name, tag),
+            None => Some(tag),
+        })
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeList {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+#[derive(Default)]
+struct SerializeCompound {
+    /// The variant of a struct variant.
+    variant: Option<&'static str>,
+    compound: Compound,
+    /// The key of the entry being serialized, for maps.
+    key: Option<String>,
+}
+
+impl SerializeCompound {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), NbtError> {
+        if let Some(tag) = value.serialize(Serializer)? {
+            self.compound.insert(key, tag);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<Option<Tag>, NbtError> {
+        let tag = Tag::Compound(self.compound);
+        Ok(match self.variant {
+            Some(name) => with_variant(name, tag),
+            None => Some(tag),
+        })
+    }
+}
+
+impl ser::SerializeMap for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), NbtError> {
+        self.key = Some(key.serialize(KeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), NbtError> {
+        let key = self.key.take().expect("serialize_key is called first");
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStruct for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), NbtError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeCompound {
+    type Ok = Option<Tag>;
+    type Error = NbtError;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), NbtError> {
+        self.insert(key.to_string(), value)
+    }
+
+    fn end(self) -> Result<Self::Ok, Self::Error> {
+        self.finish()
+    }
+}
+
+/// Serializes the keys of maps, which must be strings, or numbers written as strings.
+struct KeySerializer;
+
+fn key_error() -> NbtError {
+    NbtError::Custom("The keys of a compound must be strings".into())
+}
+
+impl ser::Serializer for KeySerializer {
+    type Ok = String;
+    type Error = NbtError;
+    type SerializeSeq = Impossible<String, NbtError>;
+    type SerializeTuple = Impossible<String, NbtError>;
+    type SerializeTupleStruct = Impossible<String, NbtError>;
+    type SerializeTupleVariant = Impossible<String, NbtError>;
+    type SerializeMap = Impossible<String, NbtError>;
+    type SerializeStruct = Impossible<String, NbtError>;
+    type SerializeStructVariant = Impossible<String, NbtError>;
+
+    fn serialize_bool(self, _v: bool) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, NbtError> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_none(self) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, NbtError> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, NbtError> {
+        Ok(variant.to_string())
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, NbtError> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, NbtError> {
+        Err(key_error())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, NbtError> {
+        Err(key_error())
+    }
+}
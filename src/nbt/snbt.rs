@@ -0,0 +1,487 @@
+//! SNBT, the text format of NBT used by commands and data packs, like
+//! `{name:"Steve",Health:20.0f,Pos:[0.5d,64.0d,0.5d],Heights:[L;1L,2L]}`.
+//! The suffixes give the types of numbers: `b`yte, `s`hort, `L`ong, `f`loat and `d`ouble, the
+//! numbers without one are ints, or doubles with a decimal point. `true` and `false` are bytes.
+
+use std::fmt::{self, Display, Write};
+use std::str::FromStr;
+
+use super::{Compound, NbtError, Tag, MAX_DEPTH};
+
+/// Parses SNBT text into a tag.
+pub fn parse(text: &str) -> Result<Tag, NbtError> {
+    let mut parser = Parser {
+        text,
+        position: 0,
+        depth: 0,
+    };
+    let tag = parser.value()?;
+    parser.skip_whitespace();
+    if parser.position < text.len() {
+        return Err(parser.error("Unexpected trailing data"));
+    }
+    Ok(tag)
+}
+
+/// Prints a tag as SNBT, compactly, like vanilla.
+impl Display for Tag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Tag::Byte(value) => write!(f, "{value}b"),
+            Tag::Short(value) => write!(f, "{value}s"),
+            Tag::Int(value) => write!(f, "{value}"),
+            Tag::Long(value) => write!(f, "{value}L"),
+            // Debug is the shortest text that parses back to the same number.
+            Tag::Float(value) => write!(f, "{value:?}f"),
+            Tag::Double(value) => write!(f, "{value:?}d"),
+            Tag::ByteArray(values) => write_array(f, "B", values, "B"),
+            Tag::String(value) => write_quoted(f, value),
+            Tag::List(tags) => write_array(f, "", tags, ""),
+            Tag::Compound(compound) => {
+                f.write_char('{')?;
+                for (i, (name, tag)) in compound.iter().enumerate() {
+                    if i > 0 {
+                        f.write_char(',')?;
+                    }
+                    if !name.is_empty() && name.chars().all(is_unquoted_char) {
+                        f.write_str(name)?;
+                    } else {
+                        write_quoted(f, name)?;
+                    }
+                    write!(f, ":{tag}")?;
+                }
+                f.write_char('}')
+            }
+            Tag::IntArray(values) => write_array(f, "I", values, ""),
+            Tag::LongArray(values) => write_array(f, "L", values, "L"),
+        }
+    }
+}
+
+impl FromStr for Tag {
+    type Err = NbtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse(s)
+    }
+}
+
+/// Writes a list, or an array when `prefix` is the type of its elements.
+fn write_array<T: Display>(
+    f: &mut fmt::Formatter<'_>,
+    prefix: &str,
+    values: &[T],
+    suffix: &str,
+) -> fmt::Result {
+    f.write_char('[')?;
+    if !prefix.is_empty() {
+        write!(f, "{prefix};")?;
+    }
+    for (i, value) in values.iter().enumerate() {
+        if i > 0 {
+            f.write_char(',')?;
+        }
+        write!(f, "{value}{suffix}")?;
+    }
+    f.write_char(']')
+}
+
+/// Quotes a string with double quotes, or single quotes if that avoids escaping.
+fn write_quoted(f: &mut fmt::Formatter<'_>, value: &str) -> fmt::Result {
+    let quote = if value.contains('"') && !value.contains('\'') {
+        '\''
+    } else {
+        '"'
+    };
+    f.write_char(quote)?;
+    for c in value.chars() {
+        if c == quote || c == '\\' {
+            f.write_char('\\')?;
+        }
+        f.write_char(c)?;
+    }
+    f.write_char(quote)
+}
+
+fn is_unquoted_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.' | '+')
+}
+
+struct Parser<'a> {
+    text: &'a str,
+    /// In bytes.
+    position: usize,
+    /// How many lists and compounds the parser is in.
+    depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, message: &str) -> NbtError {
+        NbtError::Syntax {
+            position: self.position,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek().filter(|c| c.is_whitespace()) {
+            self.position += c.len_utf8();
+        }
+    }
+
+    /// Skips the whitespace, then expects `expected`.
+    fn expect(&mut self, expected: char) -> Result<(), NbtError> {
+        self.skip_whitespace();
+        if self.peek() != Some(expected) {
+            return Err(self.error(&format!("Expected '{expected}'")));
+        }
+        self.position += 1;
+        Ok(())
+    }
+
+    /// Skips the whitespace, then skips `c` if it is next.
+    fn accept(&mut self, c: char) -> bool {
+        self.skip_whitespace();
+        let accepted = self.peek() == Some(c);
+        if accepted {
+            self.position += 1;
+        }
+        accepted
+    }
+
+    // The recursive functions are kept small, since the stack may only have room for a few
+    // hundred bytes per level in debug builds.
+    fn value(&mut self) -> Result<Tag, NbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.compound(),
+            Some('[') => self.list_or_array(),
+            _ => self.primitive(),
+        }
+    }
+
+    /// Parses a string or a number.
+    fn primitive(&mut self) -> Result<Tag, NbtError> {
+        match self.peek() {
+            Some('"' | '\'') => Ok(Tag::String(self.quoted()?)),
+            Some(_) => Ok(typed_token(self.unquoted()?)),
+            None => Err(self.error("Expected a value")),
+        }
+    }
+
+    /// Enters a list or a compound.
+    fn enter(&mut self) -> Result<(), NbtError> {
+        if self.depth >= MAX_DEPTH {
+            return Err(NbtError::TooDeep);
+        }
+        self.depth += 1;
+        Ok(())
+    }
+
+    fn unquoted(&mut self) -> Result<&'a str, NbtError> {
+        let start = self.position;
+        while self.peek().is_some_and(is_unquoted_char) {
+            self.position += 1;
+        }
+        if start == self.position {
+            return Err(self.error("Expected a value"));
+        }
+        Ok(&self.text[start..self.position])
+    }
+
+    fn quoted(&mut self) -> Result<String, NbtError> {
+        let quote = self.peek().unwrap();
+        self.position += 1;
+        let mut result = String::new();
+        loop {
+            let Some(c) = self.peek() else {
+                return Err(self.error("Unclosed quoted string"));
+            };
+            self.position += c.len_utf8();
+            match c {
+                '\\' => match self.peek() {
+                    Some(escaped @ ('\\' | '"' | '\'')) => {
+                        self.position += 1;
+                        result.push(escaped);
+                    }
+                    _ => return Err(self.error("Invalid escape sequence")),
+                },
+                c if c == quote => return Ok(result),
+                c => result.push(c),
+            }
+        }
+    }
+
+    fn key(&mut self) -> Result<String, NbtError> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('"' | '\'') => self.quoted(),
+            _ => Ok(self.unquoted()?.to_string()),
+        }
+    }
+
+    fn compound(&mut self) -> Result<Tag, NbtError> {
+        self.enter()?;
+        self.expect('{')?;
+        let mut compound = Compound::new();
+        if !self.accept('}') {
+            loop {
+                let key = self.key()?;
+                self.expect(':')?;
+                let value = self.value()?;
+                compound.insert(key, value);
+                if !self.accept(',') {
+                    self.expect('}')?;
+                    break;
+                }
+            }
+        }
+        self.depth -= 1;
+        Ok(Tag::Compound(compound))
+    }
+
+    fn list_or_array(&mut self) -> Result<Tag, NbtError> {
+        self.enter()?;
+        self.expect('[')?;
+        let rest = &self.text.as_bytes()[self.position..];
+        let tag = if rest.len() >= 2 && rest[1] == b';' && !matches!(rest[0], b'"' | b'\'') {
+            self.array()?
+        } else {
+            self.list()?
+        };
+        self.depth -= 1;
+        Ok(tag)
+    }
+
+    /// Parses an array after its opening bracket.
+    fn array(&mut self) -> Result<Tag, NbtError> {
+        let kind = self.text.as_bytes()[self.position];
+        self.position += 2;
+        let elements = self.elements()?;
+        let start = self.position;
+        let invalid = |tag: &Tag| NbtError::Syntax {
+            position: start,
+            message: format!("Invalid element {tag} in an array"),
+        };
+        match kind {
+            b'B' => elements
+                .into_iter()
+                .map(|tag| match tag {
+                    Tag::Byte(value) => Ok(value),
+                    tag => Err(invalid(&tag)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Tag::ByteArray),
+            b'I' => elements
+                .into_iter()
+                .map(|tag| match tag {
+                    Tag::Int(value) => Ok(value),
+                    tag => Err(invalid(&tag)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Tag::IntArray),
+            b'L' => elements
+                .into_iter()
+                .map(|tag| match tag {
+                    Tag::Long(value) => Ok(value),
+                    tag => Err(invalid(&tag)),
+                })
+                .collect::<Result<_, _>>()
+                .map(Tag::LongArray),
+            _ => Err(NbtError::Syntax {
+                position: start,
+                message: format!("Invalid array type '{}'", kind as char),
+            }),
+        }
+    }
+
+    /// Parses a list after its opening bracket.
+    fn list(&mut self) -> Result<Tag, NbtError> {
+        let start = self.position;
+        let elements = self.elements()?;
+        if elements.windows(2).any(|pair| pair[0].id() != pair[1].id()) {
+            return Err(NbtError::Syntax {
+                position: start,
+                message: "Elements of different types in a list".to_string(),
+            });
+        }
+        Ok(Tag::List(elements))
+    }
+
+    /// Parses the elements of a list or an array, until the closing bracket.
+    fn elements(&mut self) -> Result<Vec<Tag>, NbtError> {
+        let mut elements = Vec::new();
+        if self.accept(']') {
+            return Ok(elements);
+        }
+        loop {
+            elements.push(self.value()?);
+            if !self.accept(',') {
+                self.expect(']')?;
+                return Ok(elements);
+            }
+        }
+    }
+}
+
+/// Types an unquoted token: a number, a boolean or a string.
+fn typed_token(token: &str) -> Tag {
+    let lower = token.to_ascii_lowercase();
+    if lower == "true" || lower == "false" {
+        return Tag::from(lower == "true");
+    }
+
+    let (body, suffix) = match lower.char_indices().last() {
+        Some((i, c @ ('b' | 's' | 'l' | 'f' | 'd'))) => (&token[..i], Some(c)),
+        _ => (token, None),
+    };
+    let number = match suffix {
+        Some('b') if is_integer(body) => body.parse().ok().map(Tag::Byte),
+        Some('s') if is_integer(body) => body.parse().ok().map(Tag::Short),
+        Some('l') if is_integer(body) => body.parse().ok().map(Tag::Long),
+        Some('f') if is_decimal(body) => body.parse().ok().map(Tag::Float),
+        Some('d') if is_decimal(body) => body.parse().ok().map(Tag::Double),
+        None if is_integer(body) => body.parse().ok().map(Tag::Int),
+        None if is_decimal(body) && body.contains('.') => body.parse().ok().map(Tag::Double),
+        _ => return Tag::from(token),
+    };
+    // Like vanilla, the numbers out of range are strings.
+    number.unwrap_or_else(|| Tag::from(token))
+}
+
+/// Matches `[-+]?(0|[1-9][0-9]*)`.
+fn is_integer(text: &str) -> bool {
+    let digits = text.strip_prefix(['-', '+']).unwrap_or(text);
+    digits == "0"
+        || (!digits.starts_with('0')
+            && !digits.is_empty()
+            && digits.bytes().all(|b| b.is_ascii_digit()))
+}
+
+/// Matches `[-+]?([0-9]+\.?|[0-9]*\.[0-9]+)(e[-+]?[0-9]+)?`, case-insensitively.
+fn is_decimal(text: &str) -> bool {
+    let text = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let (mantissa, exponent) = match text.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => (mantissa, Some(exponent)),
+        None => (text, None),
+    };
+    let digits = |s: &str| s.bytes().all(|b| b.is_ascii_digit());
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let mantissa_valid =
+        digits(integer) && digits(fraction) && (!integer.is_empty() || !fraction.is_empty());
+    let exponent_valid = exponent.is_none_or(|exponent| {
+        let exponent = exponent.strip_prefix(['-', '+']).unwrap_or(exponent);
+        !exponent.is_empty() && digits(exponent)
+    });
+    mantissa_valid && exponent_valid
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::tests::random_tag;
+
+    #[test]
+    fn test_parse() {
+        let tag = parse(
+            r#" { name : "Steve", 'quoted key':'say "hi"', Health:20.0f, Count:1b, Air:300s,
+                Seed:-42L, Pos:[0.5d, 64.0, 1e3d], Inventory:[{id:stone}], flag:true,
+                Bytes:[B;1b,-2B], Ints:[I;], Longs:[L;7l], big:2147483648 } "#,
+        )
+        .unwrap();
+        let compound = tag.as_compound().unwrap();
+        assert_eq!(compound["name"], Tag::from("Steve"));
+        assert_eq!(compound["quoted key"], Tag::from("say \"hi\""));
+        assert_eq!(compound["Health"], Tag::Float(20.0));
+        assert_eq!(compound["Count"], Tag::Byte(1));
+        assert_eq!(compound["Air"], Tag::Short(300));
+        assert_eq!(compound["Seed"], Tag::Long(-42));
+        assert_eq!(
+            compound["Pos"],
+            Tag::List(vec![
+                Tag::Double(0.5),
+                Tag::Double(64.0),
+                Tag::Double(1000.0)
+            ])
+        );
+        assert_eq!(
+            compound["Inventory"],
+            Tag::List(vec![Tag::Compound(Compound::from([(
+                "id".to_string(),
+                Tag::from("stone")
+            )]))])
+        );
+        assert_eq!(compound["flag"], Tag::Byte(1));
+        assert_eq!(compound["Bytes"], Tag::ByteArray(vec![1, -2]));
+        assert_eq!(compound["Ints"], Tag::IntArray(vec![]));
+        assert_eq!(compound["Longs"], Tag::LongArray(vec![7]));
+        // Out of the range of an int.
+        assert_eq!(compound["big"], Tag::from("2147483648"));
+    }
+
+    #[test]
+    fn test_invalid() {
+        for text in [
+            "",
+            "{",
+            "{a:1,}",
+            "{a 1}",
+            "[1,2b]",
+            "[B;1,2]",
+            "[X;1]",
+            "\"unclosed",
+            "'bad \\n escape'",
+            "{a:1} trailing",
+            "[1,,2]",
+        ] {
+            assert!(
+                matches!(parse(text), Err(NbtError::Syntax { .. })),
+                "{text:?} should not parse"
+            );
+        }
+        assert!(matches!(
+            parse(&"[".repeat(MAX_DEPTH + 1)),
+            Err(NbtError::TooDeep)
+        ));
+        assert!(parse(&format!(
+            "{}{}",
+            "[".repeat(MAX_DEPTH),
+            "]".repeat(MAX_DEPTH)
+        ))
+        .is_ok());
+    }
+
+    #[test]
+    fn test_print() {
+        let tag = Tag::Compound(Compound::from([
+            ("a".to_string(), Tag::Byte(1)),
+            ("b c".to_string(), Tag::from("it's")),
+            ("d".to_string(), Tag::from("say \"hi\"")),
+            (
+                "e".to_string(),
+                Tag::List(vec![Tag::Float(0.5), Tag::Float(1e-7)]),
+            ),
+            ("f".to_string(), Tag::ByteArray(vec![1, 2])),
+            ("g".to_string(), Tag::LongArray(vec![3])),
+            ("h".to_string(), Tag::IntArray(vec![])),
+        ]));
+        assert_eq!(
+            tag.to_string(),
+            r#"{a:1b,"b c":"it's",d:'say "hi"',e:[0.5f,1e-7f],f:[B;1B,2B],g:[L;3L],h:[I;]}"#
+        );
+    }
+
+    #[test]
+    fn test_random_round_trips() {
+        let mut rng = rand::thread_rng();
+        for _ in 0..500 {
+            let tag = random_tag(&mut rng, 4);
+            let text = tag.to_string();
+            assert_eq!(parse(&text).unwrap(), tag, "{text}");
+        }
+    }
+}
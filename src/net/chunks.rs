@@ -0,0 +1,329 @@
+//! The chunks each player sees. As the player moves, the chunks within the view distance are
+//! loaded, those within the simulation distance ticked, and those left behind unloaded. The
+//! loaded chunks are sent nearest first, in batches as big as the client says it can take,
+//! like vanilla's chunk sender.
+//!
+//! See https://wiki.vg/Protocol#Chunk_Batch_Received
+
+use std::collections::HashSet;
+
+use crate::world::chunk::Chunk;
+
+/// The view distance is clamped to this range, like vanilla.
+const MIN_VIEW_DISTANCE: u8 = 2;
+const MAX_VIEW_DISTANCE: u8 = 32;
+
+/// The rate, in chunks per tick, that the client reports is clamped to this range.
+const MIN_CHUNKS_PER_TICK: f32 = 0.01;
+const MAX_CHUNKS_PER_TICK: f32 = 64.0;
+
+/// The rate until the client reports one.
+const START_CHUNKS_PER_TICK: f32 = 9.0;
+
+/// How many batches may be sent without the client acknowledging them, once it acknowledged
+/// the first one.
+const MAX_UNACKNOWLEDGED_BATCHES: u32 = 10;
+
+/// The coordinates of a chunk.
+pub type ChunkPos = (i32, i32);
+
+/// Whether a chunk is within `distance` of the center, in a circle that includes the chunks
+/// touching it.
+fn in_range(center: ChunkPos, chunk: ChunkPos, distance: i32) -> bool {
+    let offset = |a: i32, b: i32| (a as i64 - b as i64).abs().saturating_sub(1).max(0);
+    let (dx, dz) = (offset(chunk.0, center.0), offset(chunk.1, center.1));
+    dx * dx + dz * dz < (distance as i64) * (distance as i64)
+}
+
+/// The chunks within `distance` of the center.
+fn chunks_in_range(center: ChunkPos, distance: i32) -> HashSet<ChunkPos> {
+    let mut chunks = HashSet::new();
+    for x in center.0 - distance - 1..=center.0 + distance + 1 {
+        for z in center.1 - distance - 1..=center.1 + distance + 1 {
+            if in_range(center, (x, z), distance) {
+                chunks.insert((x, z));
+            }
+        }
+    }
+    chunks
+}
+
+fn distance_squared(center: ChunkPos, chunk: ChunkPos) -> i64 {
+    let (dx, dz) = (
+        chunk.0 as i64 - center.0 as i64,
+        chunk.1 as i64 - center.1 as i64,
+    );
+    dx * dx + dz * dz
+}
+
+/// What changed for a player after they moved.
+#[derive(Debug, Default, PartialEq)]
+pub struct TrackerUpdate {
+    /// The new center chunk, if it changed.
+    pub center: Option<ChunkPos>,
+    /// The chunks to load, nearest first.
+    pub watch: Vec<ChunkPos>,
+    /// The chunks the player no longer sees.
+    pub unwatch: Vec<ChunkPos>,
+    /// The chunks the player no longer sees and that were sent, for the client to unload them.
+    pub forget: Vec<ChunkPos>,
+    pub simulate: Vec<ChunkPos>,
+    pub stop_simulating: Vec<ChunkPos>,
+}
+
+/// Paces the batches of chunks after the rate the client reports, like vanilla.
+#[derive(Debug)]
+struct BatchPacing {
+    chunks_per_tick: f32,
+    /// How many chunks may be sent now.
+    quota: f32,
+    unacknowledged: u32,
+    max_unacknowledged: u32,
+}
+
+impl Default for BatchPacing {
+    fn default() -> Self {
+        BatchPacing {
+            chunks_per_tick: START_CHUNKS_PER_TICK,
+            quota: 0.0,
+            unacknowledged: 0,
+            // Until the client reports its rate.
+            max_unacknowledged: 1,
+        }
+    }
+}
+
+impl BatchPacing {
+    /// Called every tick: returns how many of the `pending` chunks to send in a batch, if any.
+    fn next(&mut self, pending: usize) -> usize {
+        if self.unacknowledged >= self.max_unacknowledged {
+            return 0;
+        }
+        self.quota = (self.quota + self.chunks_per_tick).min(self.chunks_per_tick.max(1.0));
+        if self.quota < 1.0 || pending == 0 {
+            return 0;
+        }
+        let count = pending.min(self.quota as usize);
+        self.quota -= count as f32;
+        self.unacknowledged += 1;
+        count
+    }
+
+    /// The client received a batch, and can now take `chunks_per_tick`.
+    fn acknowledged(&mut self, chunks_per_tick: f32) {
+        self.unacknowledged = self.unacknowledged.saturating_sub(1);
+        self.chunks_per_tick = match chunks_per_tick.is_nan() {
+            true => MIN_CHUNKS_PER_TICK,
+            false => chunks_per_tick.clamp(MIN_CHUNKS_PER_TICK, MAX_CHUNKS_PER_TICK),
+        };
+        if self.unacknowledged == 0 {
+            self.quota = 1.0;
+        }
+        self.max_unacknowledged = MAX_UNACKNOWLEDGED_BATCHES;
+    }
+}
+
+/// The chunks of one player: those they see, whether they were sent, and those they simulate.
+pub struct ChunkTracker {
+    view_distance: i32,
+    simulation_distance: i32,
+    /// The dimension and the center chunk, None until the first move.
+    position: Option<(&'static str, ChunkPos)>,
+    /// The chunks the player sees, loading, loaded or sent.
+    watched: HashSet<ChunkPos>,
+    simulated: HashSet<ChunkPos>,
+    /// The loaded chunks waiting to be sent.
+    ready: Vec<Chunk>,
+    sent: HashSet<ChunkPos>,
+    batches: BatchPacing,
+}
+
+impl ChunkTracker {
+    /// The simulation distance can't be greater than the view distance.
+    pub fn new(view_distance: u8, simulation_distance: u8) -> Self {
+        let view_distance = view_distance.clamp(MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE);
+        ChunkTracker {
+            view_distance: view_distance as i32,
+            simulation_distance: simulation_distance.min(view_distance) as i32,
+            position: None,
+            watched: HashSet::new(),
+            simulated: HashSet::new(),
+            ready: Vec::new(),
+            sent: HashSet::new(),
+            batches: BatchPacing::default(),
+        }
+    }
+
+    /// The dimension of the tracked chunks, None until the first move.
+    pub fn dimension(&self) -> Option<&'static str> {
+        self.position.map(|(dimension, _)| dimension)
+    }
+
+    /// Centers the tracked chunks on the player's chunk. Going to another dimension needs a
+    /// `clear` first, the chunks of the previous one are not unwatched here.
+    pub fn move_to(&mut self, dimension: &'static str, center: ChunkPos) -> TrackerUpdate {
+        if self.position == Some((dimension, center)) {
+            return TrackerUpdate::default();
+        }
+        self.position = Some((dimension, center));
+
+        let watched = chunks_in_range(center, self.view_distance);
+        let simulated = chunks_in_range(center, self.simulation_distance);
+        let mut update = TrackerUpdate {
+            center: Some(center),
+            watch: watched.difference(&self.watched).copied().collect(),
+            unwatch: self.watched.difference(&watched).copied().collect(),
+            simulate: simulated.difference(&self.simulated).copied().collect(),
+            stop_simulating: self.simulated.difference(&simulated).copied().collect(),
+            ..Default::default()
+        };
+        update
+            .watch
+            .sort_by_key(|chunk| distance_squared(center, *chunk));
+        update.forget = update
+            .unwatch
+            .iter()
+            .filter(|chunk| self.sent.remove(chunk))
+            .copied()
+            .collect();
+        self.ready
+            .retain(|chunk| watched.contains(&(chunk.x, chunk.z)));
+        self.watched = watched;
+        self.simulated = simulated;
+        update
+    }
+
+    /// Stops tracking every chunk, when the player leaves the dimension.
+    pub fn clear(&mut self) -> TrackerUpdate {
+        self.position = None;
+        self.ready.clear();
+        TrackerUpdate {
+            center: None,
+            watch: Vec::new(),
+            unwatch: self.watched.drain().collect(),
+            forget: self.sent.drain().collect(),
+            simulate: Vec::new(),
+            stop_simulating: self.simulated.drain().collect(),
+        }
+    }
+
+    /// Queues a loaded chunk to be sent, unless the player no longer sees it.
+    pub fn loaded(&mut self, dimension: &'static str, chunk: Chunk) {
+        let pos = (chunk.x, chunk.z);
+        let queued = self.ready.iter().any(|ready| (ready.x, ready.z) == pos);
+        if self.dimension() == Some(dimension)
+            && self.watched.contains(&pos)
+            && !self.sent.contains(&pos)
+            && !queued
+        {
+            self.ready.push(chunk);
+        }
+    }
+
+    /// Called every tick: the chunks to send now in a batch, nearest first.
+    pub fn next_batch(&mut self) -> Vec<Chunk> {
+        let count = self.batches.next(self.ready.len());
+        if count == 0 {
+            return Vec::new();
+        }
+        let center = self.position.map_or((0, 0), |(_, center)| center);
+        self.ready
+            .sort_by_key(|chunk| distance_squared(center, (chunk.x, chunk.z)));
+        let batch: Vec<Chunk> = self.ready.drain(..count).collect();
+        self.sent
+            .extend(batch.iter().map(|chunk| (chunk.x, chunk.z)));
+        batch
+    }
+
+    /// The client received a batch, and can now take `chunks_per_tick`.
+    pub fn acknowledged(&mut self, chunks_per_tick: f32) {
+        self.batches.acknowledged(chunks_per_tick);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::ChunkHeight;
+
+    const OVERWORLD: &str = "minecraft:overworld";
+
+    fn chunk((x, z): ChunkPos) -> Chunk {
+        Chunk::new(x, z, ChunkHeight::OVERWORLD, 0)
+    }
+
+    #[test]
+    fn test_tracker() {
+        let mut tracker = ChunkTracker::new(4, 2);
+        let update = tracker.move_to(OVERWORLD, (0, 0));
+        assert_eq!(update.center, Some((0, 0)));
+        assert_eq!(update.watch[0], (0, 0));
+        assert!(update.watch.contains(&(4, 0)));
+        assert!(update.watch.contains(&(3, -3)));
+        assert!(!update.watch.contains(&(5, 0)));
+        assert!(!update.watch.contains(&(4, 4)));
+        assert!(update
+            .simulate
+            .iter()
+            .all(|chunk| update.watch.contains(chunk)));
+        assert!(update.simulate.len() < update.watch.len());
+        assert!(update.unwatch.is_empty());
+        assert_eq!(tracker.move_to(OVERWORLD, (0, 0)), TrackerUpdate::default());
+
+        // Sent nearest first, once loaded.
+        for pos in update.watch.iter().rev() {
+            tracker.loaded(OVERWORLD, chunk(*pos));
+        }
+        tracker.loaded(OVERWORLD, chunk((40, 40)));
+        tracker.loaded("minecraft:the_end", chunk((1, 1)));
+        let batch = tracker.next_batch();
+        assert_eq!(batch.len(), 9);
+        assert_eq!((batch[0].x, batch[0].z), (0, 0));
+        assert!(batch
+            .iter()
+            .all(|chunk| chunk.x.abs() <= 1 && chunk.z.abs() <= 1));
+        // The first batch is not acknowledged yet.
+        assert!(tracker.next_batch().is_empty());
+
+        // Moving away: the new chunks are loaded, and the sent ones that are too far unloaded.
+        let update = tracker.move_to(OVERWORLD, (3, 0));
+        assert!(update.watch.iter().all(|chunk| chunk.0 > 0));
+        assert!(update.unwatch.contains(&(-4, 0)));
+        assert!(update.stop_simulating.contains(&(-2, 0)));
+        assert!(update.forget.is_empty());
+        let update = tracker.move_to(OVERWORLD, (10, 0));
+        assert_eq!(update.forget.len(), 9);
+        tracker.loaded(OVERWORLD, chunk((9, 0)));
+        tracker.acknowledged(20.0);
+        let batch = tracker.next_batch();
+        assert_eq!(batch.len(), 1);
+        assert_eq!((batch[0].x, batch[0].z), (9, 0));
+
+        let update = tracker.clear();
+        assert_eq!(tracker.dimension(), None);
+        assert!(update.unwatch.contains(&(10, 0)));
+        assert_eq!(update.forget, [(9, 0)]);
+        assert!(tracker.next_batch().is_empty());
+    }
+
+    #[test]
+    fn test_batches() {
+        let mut batches = BatchPacing::default();
+        assert_eq!(batches.next(20), 9);
+        assert_eq!(batches.next(11), 0);
+
+        batches.acknowledged(2.0);
+        assert_eq!(batches.next(11), 2);
+        assert_eq!(batches.next(9), 2);
+        assert_eq!(batches.next(0), 0);
+        batches.acknowledged(100.0);
+        assert_eq!(batches.chunks_per_tick, MAX_CHUNKS_PER_TICK);
+
+        // A client that can't take more gets a chunk now and then.
+        batches.acknowledged(f32::NAN);
+        batches.acknowledged(f32::NAN);
+        assert_eq!(batches.next(5), 1);
+        assert_eq!((0..90).map(|_| batches.next(5)).sum::<usize>(), 0);
+        assert_eq!((0..60).map(|_| batches.next(5)).sum::<usize>(), 1);
+    }
+}
@@ -0,0 +1,1152 @@
+//! A client connection: it splits the received bytes into packets and handles them according to
+//! the state of the connection. In the Play state, it also streams the chunks around the player.
+
+use std::borrow::Cow;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{debug, info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::MissedTickBehavior;
+
+use super::chunks::{ChunkTracker, TrackerUpdate};
+use super::online::ONLINE_PLAYERS;
+use super::{login, registries};
+use crate::commands::graph::CommandGraph;
+use crate::commands::source::{CommandSource, PlayerOutput};
+use crate::commands::{self, COMMANDS};
+use crate::config::Settings;
+use crate::consts::{disconnect_reasons, minecraft};
+use crate::metrics::METRICS;
+use crate::nbt::{self, Tag};
+use crate::packet::data_types::{double, float, position, string, uuid, varint};
+use crate::packet::{self, ids, Packet};
+use crate::player;
+use crate::tick::{self, TICK_DURATION};
+use crate::user_lists::ops::OPERATORS;
+use crate::user_lists::usercache::USERCACHE;
+use crate::world::chunk::{Chunk, VanillaRegistry};
+use crate::world::dimension::{DimensionType, Location};
+use crate::world::level::LevelData;
+use crate::world::LEVEL;
+use crate::{logging, world};
+
+/// Global buffer size when reading from the socket (in bytes).
+const BUFFER_SIZE: usize = 1024;
+
+/// How often the players in the Play state are sent a Keep Alive. They are disconnected if they
+/// have not answered the previous one by then.
+const KEEP_ALIVE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// State of each connection. (e.g.: handshake, play, ...)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionState {
+    Handshake,
+    Status,
+    Login,
+    Configuration,
+    Play,
+}
+
+impl ConnectionState {
+    pub const ALL: [Self; 5] = [
+        Self::Handshake,
+        Self::Status,
+        Self::Login,
+        Self::Configuration,
+        Self::Play,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Handshake => "handshake",
+            Self::Status => "status",
+            Self::Login => "login",
+            Self::Configuration => "configuration",
+            Self::Play => "play",
+        }
+    }
+}
+
+/// The player behind a connection, once they have logged in.
+struct Profile {
+    uuid: String,
+    name: String,
+    /// Identifies this connection among the ones of the player, see `OnlinePlayers::remove`.
+    session: u64,
+}
+
+/// Object representing a TCP connection.
+pub struct Connection {
+    socket: TcpStream,
+    addr: SocketAddr,
+    /// The settings of the server, read when it started listening.
+    settings: Arc<Settings>,
+    state: ConnectionState,
+    /// Received bytes that do not make a whole packet yet.
+    buffer: Vec<u8>,
+    profile: Option<Profile>,
+    /// Receives the reason when the player has to be kicked.
+    kick_receiver: Option<UnboundedReceiver<String>>,
+    /// Receives the system messages to show to the player.
+    message_receiver: Option<UnboundedReceiver<String>>,
+    /// Receives the locations the player is teleported to.
+    teleport_receiver: Option<UnboundedReceiver<Location>>,
+    /// The dimension the client is in, once the player has joined.
+    dimension: Option<&'static str>,
+    /// The chunks the player sees, once they are in the Play state.
+    chunks: Option<ChunkTracker>,
+    /// The chunks loaded for the player, with the ID of their dimension.
+    chunk_sender: UnboundedSender<(&'static str, Chunk)>,
+    chunk_receiver: UnboundedReceiver<(&'static str, Chunk)>,
+    /// Set once Finish Configuration is sent, so the client can only acknowledge it afterwards.
+    configured: bool,
+    /// The ID of the last Synchronize Player Position.
+    teleport_id: i32,
+    /// Set until the client confirms the last Synchronize Player Position, its moves are ignored
+    /// meanwhile.
+    awaiting_teleport: bool,
+    /// The ID of the Keep Alive the client has not answered yet.
+    keep_alive: Option<i64>,
+    /// Set when the connection has to be closed after the current packet.
+    closed: bool,
+}
+
+impl Connection {
+    pub fn new(socket: TcpStream, addr: SocketAddr, settings: Arc<Settings>) -> Self {
+        METRICS.connection_opened();
+        let (chunk_sender, chunk_receiver) = mpsc::unbounded_channel();
+        Self {
+            socket,
+            addr,
+            settings,
+            state: ConnectionState::Handshake,
+            buffer: Vec::new(),
+            profile: None,
+            kick_receiver: None,
+            message_receiver: None,
+            teleport_receiver: None,
+            dimension: None,
+            chunks: None,
+            chunk_sender,
+            chunk_receiver,
+            configured: false,
+            teleport_id: 0,
+            awaiting_teleport: false,
+            keep_alive: None,
+            closed: false,
+        }
+    }
+
+    /// Handles the connection until it is closed.
+    pub async fn handle(mut self) -> Result<(), Box<dyn std::error::Error>> {
+        debug!(addr:% = self.addr; "New connection: {}", logging::ip(self.addr));
+        let result = self.handle_inner().await;
+
+        // The client is gone, only the world forgets the chunks, saving them in the background.
+        if let Some(tracker) = &mut self.chunks {
+            if let Some(dimension) = tracker.dimension() {
+                let update = tracker.clear();
+                tokio::spawn(async move { release_chunks(dimension, &update).await });
+            }
+        }
+        if let Some(profile) = &self.profile {
+            ONLINE_PLAYERS.remove(&profile.uuid, profile.session);
+            info!(uuid = profile.uuid.as_str(); "{} left the game", profile.name);
+        }
+        METRICS.connection_closed(self.state);
+        debug!(addr:% = self.addr; "Connection closed: {}", logging::ip(self.addr));
+
+        result
+    }
+
+    async fn handle_inner(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut buf = [0; BUFFER_SIZE];
+        let mut ticks = tokio::time::interval(TICK_DURATION);
+        ticks.set_missed_tick_behavior(MissedTickBehavior::Skip);
+        let start = tokio::time::Instant::now() + KEEP_ALIVE_INTERVAL;
+        let mut keep_alives = tokio::time::interval_at(start, KEEP_ALIVE_INTERVAL);
+
+        while !self.closed {
+            tokio::select! {
+                n = self.socket.read(&mut buf) => {
+                    let n = n?;
+                    if n == 0 {
+                        return Ok(());
+                    }
+                    self.buffer.extend_from_slice(&buf[..n]);
+
+                    while let Some(length) = packet::frame_length(&self.buffer)? {
+                        let frame: Vec<u8> = self.buffer.drain(..length).collect();
+                        self.handle_packet(&frame).await?;
+                        if self.closed {
+                            break;
+                        }
+                    }
+                }
+                Some(reason) = recv(&mut self.kick_receiver) => {
+                    self.disconnect(&reason).await?;
+                }
+                Some(message) = recv(&mut self.message_receiver) => {
+                    self.send_system_message(&message).await?;
+                }
+                Some(location) = recv(&mut self.teleport_receiver) => {
+                    self.teleport(&location).await?;
+                }
+                Some((dimension, chunk)) = self.chunk_receiver.recv() => {
+                    if let Some(tracker) = &mut self.chunks {
+                        tracker.loaded(dimension, chunk);
+                    }
+                }
+                _ = ticks.tick(), if self.chunks.is_some() => {
+                    self.tick_chunks().await?;
+                }
+                _ = keep_alives.tick(), if self.state == ConnectionState::Play => {
+                    self.keep_alive().await?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles one whole packet.
+    async fn handle_packet(&mut self, frame: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let packet = Packet::new(frame)?;
+        let payload = packet.get_payload();
+        METRICS.packet_received(self.state, packet.get_id().get_value(), frame.len());
+
+        match (self.state, packet.get_id().get_value()) {
+            (ConnectionState::Handshake, ids::handshake::serverbound::HANDSHAKE) => {
+                self.handle_handshake(payload).await?
+            }
+            (ConnectionState::Login, ids::login::serverbound::LOGIN_START) => {
+                self.handle_login_start(payload).await?
+            }
+            (ConnectionState::Login, ids::login::serverbound::LOGIN_ACKNOWLEDGED) => {
+                self.set_state(ConnectionState::Configuration);
+                self.send(
+                    ids::configuration::clientbound::KNOWN_PACKS,
+                    &registries::known_packs(),
+                )
+                .await?;
+            }
+            (ConnectionState::Configuration, ids::configuration::serverbound::KNOWN_PACKS) => {
+                self.handle_known_packs(payload).await?
+            }
+            (
+                ConnectionState::Configuration,
+                ids::configuration::serverbound::ACKNOWLEDGE_FINISH_CONFIGURATION,
+            ) if self.configured => {
+                self.set_state(ConnectionState::Play);
+                self.join().await?;
+            }
+            (
+                ConnectionState::Play,
+                ids::play::serverbound::CHAT_COMMAND | ids::play::serverbound::SIGNED_CHAT_COMMAND,
+            ) => self.handle_chat_command(payload).await?,
+            (ConnectionState::Play, ids::play::serverbound::CONFIRM_TELEPORTATION) => {
+                self.handle_confirm_teleportation(payload)?
+            }
+            (ConnectionState::Play, ids::play::serverbound::KEEP_ALIVE) => {
+                self.handle_keep_alive(payload).await?
+            }
+            (ConnectionState::Play, ids::play::serverbound::COMMAND_SUGGESTIONS_REQUEST) => {
+                self.handle_command_suggestions(payload).await?
+            }
+            (ConnectionState::Play, ids::play::serverbound::CHUNK_BATCH_RECEIVED) => {
+                self.handle_chunk_batch_received(payload)?
+            }
+            (
+                ConnectionState::Play,
+                ids::play::serverbound::SET_PLAYER_POSITION
+                | ids::play::serverbound::SET_PLAYER_POSITION_AND_ROTATION,
+            ) => self.handle_player_position(payload)?,
+            (state, id) => debug!(
+                addr:% = self.addr, packet_id = id;
+                "Unhandled packet {id:#04X} in state {state:?} from {}",
+                logging::ip(self.addr)
+            ),
+        }
+
+        Ok(())
+    }
+
+    fn set_state(&mut self, state: ConnectionState) {
+        METRICS.connection_changed_state(self.state, state);
+        self.state = state;
+    }
+
+    /// Handshake: Protocol Version (VarInt), Server Address (String), Server Port (Unsigned
+    /// Short), Next State (VarInt)
+    async fn handle_handshake(&mut self, payload: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        let (protocol_version, mut offset) = varint::read(payload)?;
+        let (address, length) = string::read(&payload[offset..])?;
+        offset += length + 2;
+        let (next_state, _) = varint::read(payload.get(offset..).unwrap_or_default())?;
+
+        debug!(
+            addr:% = self.addr;
+            "Handshake from {} (protocol {protocol_version}, address {address})",
+            logging::ip(self.addr)
+        );
+
+        match next_state {
+            1 => self.set_state(ConnectionState::Status),
+            // 3 is a login coming from a transfer.
+            2 | login::TRANSFER_INTENT => self.set_state(ConnectionState::Login),
+            _ => self.closed = true,
+        }
+
+        if self.state == ConnectionState::Login {
+            let checked = login::check_handshake(
+                protocol_version,
+                next_state,
+                self.settings.accepts_transfers,
+            )
+            .and_then(|()| login::check_ip(self.addr.ip()));
+            if let Err(reason) = checked {
+                info!(addr:% = self.addr; "Disconnecting {}: {reason}", logging::ip(self.addr));
+                return self.disconnect(&reason).await;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Login Start: Name (String), Player UUID (UUID)
+    async fn handle_login_start(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (name, offset) = string::read(payload)?;
+        let (raw_uuid, _) = uuid::read(&payload[offset..])?;
+        let player_uuid = uuid::to_string(raw_uuid);
+        if self.profile.is_some() {
+            return Ok(());
+        }
+        if !player::is_valid_name(&name) {
+            return self.disconnect(disconnect_reasons::INVALID_NAME).await;
+        }
+
+        if let Err(reason) = login::check_login(&player_uuid, &name) {
+            info!(
+                addr:% = self.addr, uuid = player_uuid.as_str();
+                "Disconnecting {name} ({}): {reason}",
+                logging::ip(self.addr)
+            );
+            return self.disconnect(&reason).await;
+        }
+
+        // Login Success: UUID, Username, Number Of Properties, Strict Error Handling
+        let mut response = uuid::write(raw_uuid);
+        response.extend(string::write(&name));
+        response.extend(varint::write(0));
+        response.push(1);
+        self.send(ids::login::clientbound::LOGIN_SUCCESS, &response)
+            .await?;
+
+        info!(
+            addr:% = self.addr, uuid = player_uuid.as_str();
+            "{name}[/{}] logged in",
+            logging::ip(self.addr)
+        );
+        if let Err(e) = USERCACHE.update(&player_uuid, &name) {
+            warn!("Failed to save the user cache: {e}");
+        }
+        let receivers = ONLINE_PLAYERS.add(&player_uuid, &name, self.addr);
+        self.kick_receiver = Some(receivers.kicks);
+        self.message_receiver = Some(receivers.messages);
+        self.teleport_receiver = Some(receivers.teleports);
+        self.profile = Some(Profile {
+            uuid: player_uuid,
+            name,
+            session: receivers.session,
+        });
+
+        Ok(())
+    }
+
+    /// Serverbound Known Packs: the data packs the client has, among the ones it was sent.
+    /// The registries are then sent, with the names of their vanilla entries only, so the client
+    /// must have the vanilla data pack of the server's version. Finish Configuration has no
+    /// fields.
+    async fn handle_known_packs(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if !registries::has_core_pack(payload)? {
+            let reason = format!(
+                "{} {}",
+                disconnect_reasons::MISSING_CORE_PACK,
+                minecraft::VERSION
+            );
+            return self.disconnect(&reason).await;
+        }
+        for payload in registries::registry_data()? {
+            self.send(ids::configuration::clientbound::REGISTRY_DATA, &payload)
+                .await?;
+        }
+        self.send(ids::configuration::clientbound::FINISH_CONFIGURATION, &[])
+            .await?;
+        self.configured = true;
+        Ok(())
+    }
+
+    /// Spawns the player in the world, once the configuration is finished: the world and the
+    /// commands are sent, then where the player is. The chunks around them follow, on the next
+    /// ticks.
+    async fn join(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(player) = self
+            .profile
+            .as_ref()
+            .and_then(|profile| ONLINE_PLAYERS.get(&profile.uuid))
+        else {
+            return Ok(());
+        };
+        let location = &player.location;
+
+        // Login (play): Entity ID (Int), Is Hardcore (Boolean), Dimension Names (Prefixed Array
+        // of Identifier), Max Players, View Distance, Simulation Distance (VarInt), Reduced
+        // Debug Info, Enable Respawn Screen, Do Limited Crafting (Boolean), the spawn info,
+        // Enforces Secure Chat (Boolean)
+        // Change Difficulty: Difficulty (Unsigned Byte), Difficulty Locked (Boolean)
+        // Set Default Spawn Position: Location (Position), Angle (Float)
+        let (login, difficulty, spawn) = {
+            let level = LEVEL.lock().unwrap();
+            let level = match level.as_ref() {
+                Some(level) => Cow::Borrowed(level),
+                None => Cow::Owned(LevelData::new(&self.settings)),
+            };
+            let mut login = player.entity_id.to_be_bytes().to_vec();
+            login.push(level.hardcore as u8);
+            login.extend(varint::write(minecraft::DIMENSIONS.len() as i32));
+            for dimension in minecraft::DIMENSIONS {
+                login.extend(string::write(dimension));
+            }
+            login.extend(varint::write(self.settings.max_players as i32));
+            login.extend(varint::write(self.settings.view_distance.into()));
+            login.extend(varint::write(self.settings.simulation_distance.into()));
+            login.push(level.game_rule_bool("reducedDebugInfo", false) as u8);
+            login.push(!level.game_rule_bool("doImmediateRespawn", false) as u8);
+            login.push(level.game_rule_bool("doLimitedCrafting", false) as u8);
+            login.extend(spawn_info(location.dimension, &level)?);
+            login.push(0);
+
+            let difficulty = vec![level.difficulty().id() as u8, level.difficulty_locked as u8];
+
+            let mut spawn = position::write(level.spawn_x, level.spawn_y, level.spawn_z);
+            spawn.extend(float::write(level.spawn_angle));
+            (login, difficulty, spawn)
+        };
+
+        self.send(ids::play::clientbound::LOGIN, &login).await?;
+        self.send(ids::play::clientbound::CHANGE_DIFFICULTY, &difficulty)
+            .await?;
+        self.send_commands().await?;
+        self.send(ids::play::clientbound::SET_DEFAULT_SPAWN_POSITION, &spawn)
+            .await?;
+
+        self.synchronize_position(location).await?;
+        self.start_waiting_for_chunks().await?;
+
+        self.dimension = Some(location.dimension);
+        self.chunks = Some(ChunkTracker::new(
+            self.settings.view_distance,
+            self.settings.simulation_distance,
+        ));
+        Ok(())
+    }
+
+    /// Moves the client to where the player was teleported, respawning it first in the new
+    /// dimension if it changed, where it then waits for the chunks.
+    /// Respawn: the spawn info, Data Kept (Byte), here the attributes and the metadata
+    async fn teleport(&mut self, location: &Location) -> Result<(), Box<dyn std::error::Error>> {
+        // The player is spawned where they are when they join.
+        let Some(dimension) = self.dimension else {
+            return Ok(());
+        };
+        let respawned = dimension != location.dimension;
+        if respawned {
+            let mut respawn = {
+                let level = LEVEL.lock().unwrap();
+                let level = match level.as_ref() {
+                    Some(level) => Cow::Borrowed(level),
+                    None => Cow::Owned(LevelData::new(&self.settings)),
+                };
+                spawn_info(location.dimension, &level)?
+            };
+            respawn.push(0x03);
+            self.send(ids::play::clientbound::RESPAWN, &respawn).await?;
+            self.dimension = Some(location.dimension);
+        }
+        self.synchronize_position(location).await?;
+        if respawned {
+            self.start_waiting_for_chunks().await?;
+        }
+        Ok(())
+    }
+
+    /// Tells the client that the chunks around the player are coming, so that it leaves the
+    /// loading screen once they are there.
+    /// Game Event: Event (Unsigned Byte), here 13 "start waiting for level chunks", Value (Float)
+    async fn start_waiting_for_chunks(&mut self) -> Result<(), std::io::Error> {
+        let mut payload = vec![13];
+        payload.extend(float::write(0.0));
+        self.send(ids::play::clientbound::GAME_EVENT, &payload)
+            .await
+    }
+
+    /// Sends the position of the player, whose moves are then ignored until the client confirms
+    /// it.
+    /// Synchronize Player Position: X, Y, Z (Double), Yaw, Pitch (Float), Flags (Byte), all
+    /// absolute, Teleport ID (VarInt)
+    async fn synchronize_position(&mut self, location: &Location) -> Result<(), std::io::Error> {
+        self.teleport_id += 1;
+        let mut payload = double::write(location.x);
+        payload.extend(double::write(location.y));
+        payload.extend(double::write(location.z));
+        payload.extend(float::write(0.0));
+        payload.extend(float::write(0.0));
+        payload.push(0);
+        payload.extend(varint::write(self.teleport_id));
+        self.send(
+            ids::play::clientbound::SYNCHRONIZE_PLAYER_POSITION,
+            &payload,
+        )
+        .await?;
+        self.awaiting_teleport = true;
+        Ok(())
+    }
+
+    /// Confirm Teleportation: Teleport ID (VarInt), that of a Synchronize Player Position. Only
+    /// the last one lets the player move again.
+    fn handle_confirm_teleportation(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (id, _) = varint::read(payload)?;
+        if id == self.teleport_id {
+            self.awaiting_teleport = false;
+        }
+        Ok(())
+    }
+
+    /// Sends a Keep Alive: Keep Alive ID (Long), which the client sends back. The player is
+    /// disconnected if they did not answer the previous one.
+    async fn keep_alive(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        if self.keep_alive.is_some() {
+            return self.disconnect(disconnect_reasons::TIMED_OUT).await;
+        }
+        let id = chrono::Utc::now().timestamp_millis();
+        self.keep_alive = Some(id);
+        self.send(ids::play::clientbound::KEEP_ALIVE, &id.to_be_bytes())
+            .await?;
+        Ok(())
+    }
+
+    /// Keep Alive: Keep Alive ID (Long), that of the last Keep Alive the client was sent.
+    async fn handle_keep_alive(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let id = payload
+            .get(..8)
+            .and_then(|bytes| bytes.try_into().ok())
+            .map(i64::from_be_bytes);
+        match id.is_some() && id == self.keep_alive {
+            true => {
+                self.keep_alive = None;
+                Ok(())
+            }
+            false => self.disconnect(disconnect_reasons::TIMED_OUT).await,
+        }
+    }
+
+    /// Returns the permission level of the player, 0 until they have logged in.
+    fn permission_level(&self) -> u8 {
+        self.profile
+            .as_ref()
+            .map_or(0, |profile| OPERATORS.level_of(&profile.uuid))
+    }
+
+    /// Declare Commands: the commands the player may use, for the client to highlight and
+    /// complete them.
+    async fn send_commands(&mut self) -> Result<(), std::io::Error> {
+        let graph = CommandGraph::build(&COMMANDS, self.permission_level());
+        self.send(ids::play::clientbound::COMMANDS, &graph.encode())
+            .await
+    }
+
+    /// Chat Command: Command (String), without the leading slash.
+    /// Signed Chat Command: the same, followed by the signatures of its arguments, which are not
+    /// checked since secure chat is not enforced.
+    /// The command runs on the main loop, its feedback and errors come back as system messages.
+    async fn handle_chat_command(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (input, _) = string::read(payload)?;
+        let Some(profile) = &self.profile else {
+            return Ok(());
+        };
+        info!(
+            uuid = profile.uuid.as_str();
+            "{} issued server command: /{input}",
+            profile.name
+        );
+
+        let output = PlayerOutput {
+            uuid: profile.uuid.clone(),
+        };
+        let source = CommandSource::player(
+            &profile.uuid,
+            &profile.name,
+            self.permission_level(),
+            output,
+        );
+        commands::submit(tick::tasks(), &input, source).await;
+
+        Ok(())
+    }
+
+    /// Command Suggestions Request: Transaction Id (VarInt), Text (String), the text starting
+    /// with a slash.
+    /// Command Suggestions Response: Transaction Id (VarInt), Start (VarInt), Length (VarInt),
+    /// Count (VarInt), then for each match: Match (String), Has Tooltip (Boolean)
+    async fn handle_command_suggestions(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (transaction_id, offset) = varint::read(payload)?;
+        let (text, _) = string::read(&payload[offset..])?;
+        let suggestions = COMMANDS.suggest(&text, self.permission_level());
+
+        // The client counts in UTF-16 code units, like Java strings.
+        let start = text[..suggestions.start].encode_utf16().count();
+        let length = text[suggestions.start..].encode_utf16().count();
+
+        let mut response = varint::write(transaction_id);
+        response.extend(varint::write(start as i32));
+        response.extend(varint::write(length as i32));
+        response.extend(varint::write(suggestions.matches.len() as i32));
+        for suggestion in &suggestions.matches {
+            response.extend(string::write(suggestion));
+            response.push(0);
+        }
+        self.send(
+            ids::play::clientbound::COMMAND_SUGGESTIONS_RESPONSE,
+            &response,
+        )
+        .await?;
+
+        Ok(())
+    }
+
+    /// Set Player Position: X (Double), Feet Y (Double), Z (Double), On Ground (Boolean)
+    /// Set Player Position and Rotation: the same with Yaw (Float) and Pitch (Float) before On
+    /// Ground.
+    /// The position is ignored until the client confirms the last teleport, as it may be from
+    /// before it.
+    fn handle_player_position(&mut self, payload: &[u8]) -> Result<(), Box<dyn std::error::Error>> {
+        if self.awaiting_teleport {
+            return Ok(());
+        }
+        let (x, offset) = double::read(payload)?;
+        let (y, length) = double::read(&payload[offset..])?;
+        let (z, _) = double::read(&payload[offset + length..])?;
+        if let Some(profile) = &self.profile {
+            ONLINE_PLAYERS.set_position(&profile.uuid, x, y, z);
+        }
+        Ok(())
+    }
+
+    /// Chunk Batch Received: Chunks Per Tick (Float), how many chunks the client can take.
+    fn handle_chunk_batch_received(
+        &mut self,
+        payload: &[u8],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        let (chunks_per_tick, _) = float::read(payload)?;
+        if let Some(tracker) = &mut self.chunks {
+            tracker.acknowledged(chunks_per_tick);
+        }
+        Ok(())
+    }
+
+    /// Follows the player with the chunks they see, then sends a batch of the loaded ones if the
+    /// client can take it.
+    /// Chunk Batch Start has no fields, Chunk Batch Finished: Batch Size (VarInt)
+    async fn tick_chunks(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let Some(player) = self
+            .profile
+            .as_ref()
+            .and_then(|profile| ONLINE_PLAYERS.get(&profile.uuid))
+        else {
+            return Ok(());
+        };
+        let Some(tracker) = &mut self.chunks else {
+            return Ok(());
+        };
+        let location = player.location;
+        let previous = tracker
+            .dimension()
+            .filter(|dimension| *dimension != location.dimension);
+        let left = previous.map(|dimension| (dimension, tracker.clear()));
+        let update = tracker.move_to(location.dimension, location.chunk());
+        let batch = tracker.next_batch();
+
+        if let Some((dimension, update)) = left {
+            self.update_chunks(dimension, update).await?;
+        }
+        self.update_chunks(location.dimension, update).await?;
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.send(ids::play::clientbound::CHUNK_BATCH_START, &[])
+            .await?;
+        for chunk in &batch {
+            self.send(
+                ids::play::clientbound::CHUNK_DATA_AND_UPDATE_LIGHT,
+                &chunk.to_packet(&VanillaRegistry)?,
+            )
+            .await?;
+        }
+        self.send(
+            ids::play::clientbound::CHUNK_BATCH_FINISHED,
+            &varint::write(batch.len() as i32),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Applies the changes of the chunks the player sees to the dimension, loading the new ones
+    /// in the background, then tells the client.
+    /// Set Center Chunk: Chunk X (VarInt), Chunk Z (VarInt)
+    /// Unload Chunk: Chunk Z (Int), Chunk X (Int)
+    async fn update_chunks(
+        &mut self,
+        id: &'static str,
+        update: TrackerUpdate,
+    ) -> Result<(), std::io::Error> {
+        release_chunks(id, &update).await;
+        if let Some(dimension) = world::dimension(id) {
+            for &(x, z) in &update.watch {
+                dimension.watch(x, z);
+                let sender = self.chunk_sender.clone();
+                tokio::spawn(async move {
+                    match dimension.watched_chunk(x, z).await {
+                        Ok(Some(chunk)) => {
+                            let _ = sender.send((id, chunk));
+                        }
+                        Ok(None) => {}
+                        Err(e) => warn!("Failed to load the chunk {x}, {z} of {id}: {e}"),
+                    }
+                });
+            }
+            for &(x, z) in &update.simulate {
+                dimension.simulate(x, z, true);
+            }
+        }
+
+        if let Some((x, z)) = update.center {
+            let mut payload = varint::write(x);
+            payload.extend(varint::write(z));
+            self.send(ids::play::clientbound::SET_CENTER_CHUNK, &payload)
+                .await?;
+        }
+        for (x, z) in update.forget {
+            let mut payload = z.to_be_bytes().to_vec();
+            payload.extend(x.to_be_bytes());
+            self.send(ids::play::clientbound::UNLOAD_CHUNK, &payload)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// System Chat Message: Content (Text Component), Overlay (Boolean)
+    async fn send_system_message(&mut self, message: &str) -> Result<(), std::io::Error> {
+        let mut payload = text_component(message)?;
+        payload.push(0);
+        self.send(ids::play::clientbound::SYSTEM_CHAT_MESSAGE, &payload)
+            .await
+    }
+
+    /// Sends a packet to the client.
+    async fn send(&mut self, id: i32, payload: &[u8]) -> Result<(), std::io::Error> {
+        let frame = packet::encode(id, payload);
+        METRICS.packet_sent(self.state, id, frame.len());
+        self.socket.write_all(&frame).await
+    }
+
+    /// Sends the Disconnect packet of the current state, then closes the connection.
+    async fn disconnect(&mut self, reason: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match self.state {
+            ConnectionState::Login => {
+                let reason = serde_json::json!({ "text": reason }).to_string();
+                self.send(ids::login::clientbound::DISCONNECT, &string::write(&reason))
+                    .await?;
+            }
+            ConnectionState::Configuration => {
+                self.send(
+                    ids::configuration::clientbound::DISCONNECT,
+                    &text_component(reason)?,
+                )
+                .await?;
+            }
+            ConnectionState::Play => {
+                self.send(ids::play::clientbound::DISCONNECT, &text_component(reason)?)
+                    .await?;
+            }
+            ConnectionState::Handshake | ConnectionState::Status => {}
+        }
+
+        self.closed = true;
+        Ok(())
+    }
+}
+
+/// Removes the player from the chunks they no longer see or simulate, which are unloaded when
+/// no one else does.
+async fn release_chunks(id: &str, update: &TrackerUpdate) {
+    let Some(dimension) = world::dimension(id) else {
+        return;
+    };
+    for &(x, z) in &update.stop_simulating {
+        dimension.simulate(x, z, false);
+    }
+    for &(x, z) in &update.unwatch {
+        if let Err(e) = dimension.unwatch(x, z).await {
+            warn!("Failed to save the chunk {x}, {z} of {id}: {e}");
+        }
+    }
+}
+
+/// The part of Login (play) and Respawn about the dimension the player spawns in:
+/// Dimension Type (VarInt), Dimension Name (Identifier), Hashed Seed (Long), Game Mode (Unsigned
+/// Byte), Previous Game Mode (Byte), Is Debug, Is Flat, Has Death Location (Boolean), Portal
+/// Cooldown (VarInt)
+fn spawn_info(dimension: &str, level: &LevelData) -> Result<Vec<u8>, &'static str> {
+    let kind = DimensionType::get(dimension).ok_or("Unknown dimension type")?;
+    let dimension_type = world::dimension::DIMENSION_TYPES
+        .iter()
+        .position(|other| other.id == kind.id)
+        .ok_or("Unknown dimension type")?;
+
+    let mut payload = varint::write(dimension_type as i32);
+    payload.extend(string::write(dimension));
+    payload.extend(level.world_gen_settings.hashed_seed().to_be_bytes());
+    payload.push(level.gamemode().id() as u8);
+    payload.push(-1i8 as u8);
+    payload.push(0);
+    payload.push(level.world_gen_settings.is_flat(dimension) as u8);
+    payload.push(0);
+    payload.extend(varint::write(0));
+    Ok(payload)
+}
+
+/// Waits for a message from the rest of the server, forever if the connection has no player
+/// yet.
+async fn recv<T>(receiver: &mut Option<UnboundedReceiver<T>>) -> Option<T> {
+    match receiver {
+        Some(receiver) => receiver.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Encodes a plain text component as network NBT: a nameless root String tag.
+fn text_component(text: &str) -> Result<Vec<u8>, std::io::Error> {
+    nbt::binary::write_network(&Tag::from(text))
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::consts::file_content;
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    /// The client side of a connection.
+    struct Client {
+        socket: TcpStream,
+        buffer: Vec<u8>,
+    }
+
+    impl Client {
+        async fn send(&mut self, id: i32, payload: &[u8]) {
+            self.socket
+                .write_all(&packet::encode(id, payload))
+                .await
+                .unwrap();
+        }
+
+        /// Returns the ID and the payload of the next packet.
+        async fn receive(&mut self) -> (i32, Vec<u8>) {
+            loop {
+                if let Some(length) = packet::frame_length(&self.buffer).unwrap() {
+                    let frame: Vec<u8> = self.buffer.drain(..length).collect();
+                    let packet = Packet::new(&frame).unwrap();
+                    return (packet.get_id().get_value(), packet.get_payload().to_vec());
+                }
+                let mut buf = [0; BUFFER_SIZE];
+                let n = self.socket.read(&mut buf).await.unwrap();
+                assert_ne!(n, 0, "The server closed the connection");
+                self.buffer.extend_from_slice(&buf[..n]);
+            }
+        }
+
+        /// Skips the packets until one with the given ID, and returns its payload.
+        async fn expect(&mut self, id: i32) -> Vec<u8> {
+            loop {
+                let (received, payload) = self.receive().await;
+                if received == id {
+                    return payload;
+                }
+            }
+        }
+    }
+
+    /// Accepts one connection with the default settings. The returned task ends once the
+    /// connection is closed and the player has left.
+    async fn server() -> (Client, JoinHandle<()>) {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("server.properties");
+        std::fs::write(&path, file_content::server_properties()).unwrap();
+        let settings = Arc::new(Settings::from_file(&path));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let task = tokio::spawn(async move {
+            let (socket, addr) = listener.accept().await.unwrap();
+            let _ = Connection::new(socket, addr, settings).handle().await;
+        });
+        let client = Client {
+            socket: TcpStream::connect(addr).await.unwrap(),
+            buffer: Vec::new(),
+        };
+        (client, task)
+    }
+
+    /// A UUID of its own for each player of the tests, which share the online players.
+    fn test_uuid(name: &str) -> u128 {
+        name.bytes().fold(0, |uuid, byte| uuid << 8 | byte as u128)
+    }
+
+    async fn join(client: &mut Client, name: &str) {
+        // Handshake: Protocol Version, Server Address, Server Port, Next State
+        let mut handshake = varint::write(minecraft::PROTOCOL_VERSION as i32);
+        handshake.extend(string::write("localhost"));
+        handshake.extend(25565u16.to_be_bytes());
+        handshake.extend(varint::write(2));
+        client
+            .send(ids::handshake::serverbound::HANDSHAKE, &handshake)
+            .await;
+
+        let mut login_start = string::write(name);
+        login_start.extend(uuid::write(test_uuid(name)));
+        client
+            .send(ids::login::serverbound::LOGIN_START, &login_start)
+            .await;
+        let payload = client.expect(ids::login::clientbound::LOGIN_SUCCESS).await;
+        let (raw_uuid, length) = uuid::read(&payload).unwrap();
+        assert_eq!(raw_uuid, test_uuid(name));
+        assert_eq!(string::read(&payload[length..]).unwrap().0, name);
+        client
+            .send(ids::login::serverbound::LOGIN_ACKNOWLEDGED, &[])
+            .await;
+    }
+
+    #[tokio::test]
+    async fn test_join() {
+        let (mut client, task) = server().await;
+        join(&mut client, "Joiner").await;
+
+        // The client has the same vanilla data pack.
+        let packs = client
+            .expect(ids::configuration::clientbound::KNOWN_PACKS)
+            .await;
+        client
+            .send(ids::configuration::serverbound::KNOWN_PACKS, &packs)
+            .await;
+        let mut registries = Vec::new();
+        loop {
+            match client.receive().await {
+                (ids::configuration::clientbound::REGISTRY_DATA, payload) => {
+                    registries.push(string::read(&payload).unwrap().0)
+                }
+                (ids::configuration::clientbound::FINISH_CONFIGURATION, _) => break,
+                (id, _) => panic!("Unexpected packet {id:#04X} in the configuration"),
+            }
+        }
+        assert_eq!(registries.len(), 11);
+        assert_eq!(registries[0], "minecraft:dimension_type");
+        client
+            .send(
+                ids::configuration::serverbound::ACKNOWLEDGE_FINISH_CONFIGURATION,
+                &[],
+            )
+            .await;
+
+        let payload = client.expect(ids::play::clientbound::LOGIN).await;
+        let (count, length) = varint::read(&payload[5..]).unwrap();
+        assert_eq!(count, 3);
+        let (dimension, _) = string::read(&payload[5 + length..]).unwrap();
+        assert_eq!(dimension, "minecraft:overworld");
+
+        // The world is not loaded, the player is at the default spawn.
+        let payload = client
+            .expect(ids::play::clientbound::SYNCHRONIZE_PLAYER_POSITION)
+            .await;
+        assert_eq!(double::read(&payload).unwrap().0, 0.5);
+        assert_eq!(double::read(&payload[8..]).unwrap().0, 64.0);
+        assert_eq!(double::read(&payload[16..]).unwrap().0, 0.5);
+        let payload = client.expect(ids::play::clientbound::GAME_EVENT).await;
+        assert_eq!(payload, [13, 0, 0, 0, 0]);
+
+        // Signed Chat Command: Command, Timestamp, Salt, no Argument Signatures, Message Count
+        // and Acknowledged.
+        let mut command = string::write("unknown");
+        command.extend(0i64.to_be_bytes());
+        command.extend(0i64.to_be_bytes());
+        command.extend(varint::write(0));
+        command.extend(varint::write(0));
+        command.extend([0; 3]);
+        client
+            .send(ids::play::serverbound::SIGNED_CHAT_COMMAND, &command)
+            .await;
+        let payload = client
+            .expect(ids::play::clientbound::SYSTEM_CHAT_MESSAGE)
+            .await;
+        let (message, _) = nbt::binary::read_network(&payload).unwrap();
+        assert_eq!(message, Some(Tag::from("Unknown command: unknown")));
+
+        drop(client);
+        task.await.unwrap();
+        assert!(ONLINE_PLAYERS.get_by_name("Joiner").is_none());
+    }
+
+    #[tokio::test]
+    async fn test_teleport() {
+        let (mut client, task) = server().await;
+        join(&mut client, "Traveler").await;
+        let packs = client
+            .expect(ids::configuration::clientbound::KNOWN_PACKS)
+            .await;
+        client
+            .send(ids::configuration::serverbound::KNOWN_PACKS, &packs)
+            .await;
+        client
+            .expect(ids::configuration::clientbound::FINISH_CONFIGURATION)
+            .await;
+        client
+            .send(
+                ids::configuration::serverbound::ACKNOWLEDGE_FINISH_CONFIGURATION,
+                &[],
+            )
+            .await;
+        let uuid = uuid::to_string(test_uuid("Traveler"));
+
+        // Set Player Position: X, Feet Y, Z, On Ground. An unknown command is answered once the
+        // packets before it are handled.
+        let mut position = double::write(8.5);
+        position.extend(double::write(70.0));
+        position.extend(double::write(-3.5));
+        position.push(1);
+        async fn handled(client: &mut Client) {
+            client
+                .send(
+                    ids::play::serverbound::CHAT_COMMAND,
+                    &string::write("unknown"),
+                )
+                .await;
+            client
+                .expect(ids::play::clientbound::SYSTEM_CHAT_MESSAGE)
+                .await;
+        }
+
+        // The client moves before confirming where it spawned.
+        let payload = client
+            .expect(ids::play::clientbound::SYNCHRONIZE_PLAYER_POSITION)
+            .await;
+        let (teleport_id, _) = varint::read(&payload[33..]).unwrap();
+        client
+            .send(ids::play::serverbound::SET_PLAYER_POSITION, &position)
+            .await;
+        handled(&mut client).await;
+        assert_eq!(ONLINE_PLAYERS.get(&uuid).unwrap().location, world::spawn());
+
+        client
+            .send(
+                ids::play::serverbound::CONFIRM_TELEPORTATION,
+                &varint::write(teleport_id),
+            )
+            .await;
+        client
+            .send(ids::play::serverbound::SET_PLAYER_POSITION, &position)
+            .await;
+        handled(&mut client).await;
+        let location = ONLINE_PLAYERS.get(&uuid).unwrap().location;
+        assert_eq!((location.x, location.y, location.z), (8.5, 70.0, -3.5));
+
+        // Another dimension respawns the client there.
+        let nether = Location {
+            dimension: "minecraft:the_nether",
+            x: 1.5,
+            y: 40.0,
+            z: 2.5,
+        };
+        assert!(ONLINE_PLAYERS.teleport(&uuid, nether.clone()));
+        let payload = client.expect(ids::play::clientbound::RESPAWN).await;
+        let (_, length) = varint::read(&payload).unwrap();
+        assert_eq!(
+            string::read(&payload[length..]).unwrap().0,
+            "minecraft:the_nether"
+        );
+        let payload = client
+            .expect(ids::play::clientbound::SYNCHRONIZE_PLAYER_POSITION)
+            .await;
+        assert_eq!(double::read(&payload).unwrap().0, 1.5);
+        assert_eq!(varint::read(&payload[33..]).unwrap().0, teleport_id + 1);
+        let payload = client.expect(ids::play::clientbound::GAME_EVENT).await;
+        assert_eq!(payload[0], 13);
+        client
+            .send(ids::play::serverbound::SET_PLAYER_POSITION, &position)
+            .await;
+        handled(&mut client).await;
+        assert_eq!(ONLINE_PLAYERS.get(&uuid).unwrap().location, nether);
+
+        drop(client);
+        task.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_missing_core_pack() {
+        let (mut client, task) = server().await;
+        join(&mut client, "Modded").await;
+
+        client
+            .expect(ids::configuration::clientbound::KNOWN_PACKS)
+            .await;
+        client
+            .send(
+                ids::configuration::serverbound::KNOWN_PACKS,
+                &varint::write(0),
+            )
+            .await;
+        let payload = client
+            .expect(ids::configuration::clientbound::DISCONNECT)
+            .await;
+        let (reason, _) = nbt::binary::read_network(&payload).unwrap();
+        assert_eq!(
+            reason,
+            Some(Tag::from(
+                "Missing the vanilla data pack of Minecraft 1.21.1"
+            ))
+        );
+        task.await.unwrap();
+    }
+}
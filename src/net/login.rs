@@ -0,0 +1,76 @@
+//! Checks done when a player tries to join the server.
+
+use std::net::IpAddr;
+
+use crate::consts::{disconnect_reasons, minecraft};
+use crate::user_lists::bans::{BANNED_IPS, BANNED_PLAYERS};
+use crate::user_lists::whitelist;
+
+/// The Next State of the handshake of a login after a transfer.
+pub const TRANSFER_INTENT: i32 = 3;
+
+/// Checks whether a client may log in, right after the handshake. `accepts_transfers` tells
+/// whether the players may come from another server, with the Transfer packet.
+/// Returns the reason shown to the client if they may not.
+pub fn check_handshake(
+    protocol_version: i32,
+    intent: i32,
+    accepts_transfers: bool,
+) -> Result<(), String> {
+    if protocol_version != minecraft::PROTOCOL_VERSION as i32 {
+        return Err(format!(
+            "{} {}",
+            disconnect_reasons::INCOMPATIBLE,
+            minecraft::VERSION
+        ));
+    }
+    if intent == TRANSFER_INTENT && !accepts_transfers {
+        return Err(disconnect_reasons::TRANSFERS_DISABLED.to_string());
+    }
+    Ok(())
+}
+
+/// Checks whether a client may log in from `ip`, right after the handshake.
+/// Returns the reason shown to the client if they may not.
+pub fn check_ip(ip: IpAddr) -> Result<(), String> {
+    match BANNED_IPS.get(ip) {
+        Some(ban) => Err(ban.info.message(disconnect_reasons::IP_BANNED)),
+        None => Ok(()),
+    }
+}
+
+/// Checks whether a player may join the server.
+/// Returns the reason shown to the player if they may not.
+pub fn check_login(uuid: &str, _name: &str) -> Result<(), String> {
+    if let Some(ban) = BANNED_PLAYERS.get(uuid) {
+        return Err(ban.info.message(disconnect_reasons::BANNED));
+    }
+
+    if !whitelist::is_allowed(uuid) {
+        return Err(disconnect_reasons::NOT_WHITELISTED.to_string());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_handshake() {
+        let protocol = minecraft::PROTOCOL_VERSION as i32;
+
+        assert!(check_handshake(protocol, 2, false).is_ok());
+        assert_eq!(
+            check_handshake(protocol - 1, 2, false),
+            Err("Incompatible client! Please use 1.21.1".to_string())
+        );
+        assert!(check_handshake(protocol + 1, 2, false).is_err());
+        assert_eq!(
+            check_handshake(protocol, TRANSFER_INTENT, false),
+            Err(disconnect_reasons::TRANSFERS_DISABLED.to_string())
+        );
+        assert!(check_handshake(protocol, TRANSFER_INTENT, true).is_ok());
+    }
+}
@@ -0,0 +1,247 @@
+//! This module keeps track of the players currently connected to the server.
+
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::RwLock;
+
+use once_cell::sync::Lazy;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::consts::disconnect_reasons;
+use crate::world;
+use crate::world::dimension::Location;
+
+/// Every player that finished logging in, until they disconnect.
+pub static ONLINE_PLAYERS: Lazy<OnlinePlayers> = Lazy::new(OnlinePlayers::default);
+
+/// A connected player.
+#[derive(Clone, Debug)]
+pub struct OnlinePlayer {
+    pub uuid: String,
+    pub name: String,
+    pub addr: SocketAddr,
+    pub entity_id: i32,
+    /// Where the player is, at the world spawn when they join.
+    pub location: Location,
+    /// Tells apart the connections of a player who logged in again.
+    session: u64,
+    /// Used to ask the player's connection to disconnect them, with a reason.
+    kick_sender: UnboundedSender<String>,
+    /// Used to ask the player's connection to show them a system message.
+    message_sender: UnboundedSender<String>,
+    /// Used to ask the player's connection to move them to a location.
+    teleport_sender: UnboundedSender<Location>,
+}
+
+/// What the connection of a player listens on.
+pub struct PlayerReceivers {
+    /// Identifies the connection when it unregisters the player.
+    pub session: u64,
+    /// The reason when the player has to be kicked.
+    pub kicks: UnboundedReceiver<String>,
+    /// The system messages to show to the player.
+    pub messages: UnboundedReceiver<String>,
+    /// The locations the player is teleported to.
+    pub teleports: UnboundedReceiver<Location>,
+}
+
+/// The players connected to the server, by UUID.
+#[derive(Default)]
+pub struct OnlinePlayers {
+    players: RwLock<HashMap<String, OnlinePlayer>>,
+    sessions: AtomicU64,
+}
+
+impl OnlinePlayers {
+    /// Registers a player. The connection must listen on the returned receivers to be told when
+    /// the player has to be kicked, shown a message or teleported.
+    /// If the player was already online, their previous connection is kicked.
+    pub fn add(&self, uuid: &str, name: &str, addr: SocketAddr) -> PlayerReceivers {
+        let (kick_sender, kicks) = mpsc::unbounded_channel();
+        let (message_sender, messages) = mpsc::unbounded_channel();
+        let (teleport_sender, teleports) = mpsc::unbounded_channel();
+        let session = self.sessions.fetch_add(1, Ordering::Relaxed);
+        let player = OnlinePlayer {
+            uuid: uuid.to_string(),
+            name: name.to_string(),
+            addr,
+            entity_id: world::next_entity_id(),
+            location: world::spawn(),
+            session,
+            kick_sender,
+            message_sender,
+            teleport_sender,
+        };
+
+        let previous = self
+            .players
+            .write()
+            .unwrap()
+            .insert(uuid.to_string(), player);
+        if let Some(previous) = previous {
+            let _ = previous
+                .kick_sender
+                .send(disconnect_reasons::DUPLICATE_LOGIN.to_string());
+        }
+        PlayerReceivers {
+            session,
+            kicks,
+            messages,
+            teleports,
+        }
+    }
+
+    /// Unregisters a player, once the connection of `session` is closed. Nothing happens if
+    /// the player has logged in again since.
+    pub fn remove(&self, uuid: &str, session: u64) {
+        let mut players = self.players.write().unwrap();
+        if players
+            .get(uuid)
+            .is_some_and(|player| player.session == session)
+        {
+            players.remove(uuid);
+        }
+    }
+
+    /// Returns a copy of every online player.
+    pub fn list(&self) -> Vec<OnlinePlayer> {
+        self.players.read().unwrap().values().cloned().collect()
+    }
+
+    /// Returns an online player given their UUID.
+    pub fn get(&self, uuid: &str) -> Option<OnlinePlayer> {
+        self.players.read().unwrap().get(uuid).cloned()
+    }
+
+    /// Returns an online player given their name, ignoring case.
+    pub fn get_by_name(&self, name: &str) -> Option<OnlinePlayer> {
+        let players = self.players.read().unwrap();
+        players
+            .values()
+            .find(|p| p.name.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Returns the number of online players.
+    pub fn count(&self) -> usize {
+        self.players.read().unwrap().len()
+    }
+
+    /// Moves a player, and asks their connection to move them on the client too.
+    /// Returns `false` if the player is not online.
+    pub fn teleport(&self, uuid: &str, location: Location) -> bool {
+        match self.players.write().unwrap().get_mut(uuid) {
+            Some(player) => {
+                player.location = location.clone();
+                let _ = player.teleport_sender.send(location);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves a player within their dimension. Returns `false` if the player is not online.
+    pub fn set_position(&self, uuid: &str, x: f64, y: f64, z: f64) -> bool {
+        match self.players.write().unwrap().get_mut(uuid) {
+            Some(player) => {
+                (player.location.x, player.location.y, player.location.z) = (x, y, z);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Asks the connection of a player to disconnect them with `reason`.
+    /// Returns `false` if the player is not online.
+    pub fn kick(&self, uuid: &str, reason: &str) -> bool {
+        let players = self.players.read().unwrap();
+        match players.get(uuid) {
+            Some(player) => player.kick_sender.send(reason.to_string()).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Asks the connection of a player to show them a system message.
+    /// Returns `false` if the player is not online.
+    pub fn send_message(&self, uuid: &str, message: &str) -> bool {
+        let players = self.players.read().unwrap();
+        match players.get(uuid) {
+            Some(player) => player.message_sender.send(message.to_string()).is_ok(),
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kick() {
+        let players = OnlinePlayers::default();
+        let addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let mut receivers = players.add("uuid", "Notch", addr);
+
+        assert_eq!(players.count(), 1);
+        assert_eq!(players.get_by_name("notch").unwrap().uuid, "uuid");
+
+        assert!(players.kick("uuid", "Bye"));
+        assert_eq!(receivers.kicks.try_recv().unwrap(), "Bye");
+        assert!(!players.kick("unknown", "Bye"));
+
+        assert!(players.send_message("uuid", "Hello"));
+        assert_eq!(receivers.messages.try_recv().unwrap(), "Hello");
+
+        players.remove("uuid", receivers.session);
+        assert_eq!(players.count(), 0);
+    }
+
+    #[test]
+    fn test_duplicate_login() {
+        let players = OnlinePlayers::default();
+        let addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let mut first = players.add("uuid", "Notch", addr);
+        let mut second = players.add("uuid", "Notch", addr);
+
+        assert_eq!(
+            first.kicks.try_recv().unwrap(),
+            disconnect_reasons::DUPLICATE_LOGIN
+        );
+        assert!(second.kicks.try_recv().is_err());
+
+        // The first connection closing does not unregister the second one.
+        players.remove("uuid", first.session);
+        assert_eq!(players.count(), 1);
+        assert!(players.send_message("uuid", "Hello"));
+        assert_eq!(second.messages.try_recv().unwrap(), "Hello");
+        players.remove("uuid", second.session);
+        assert_eq!(players.count(), 0);
+    }
+
+    #[test]
+    fn test_teleport() {
+        let players = OnlinePlayers::default();
+        let addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let mut receivers = players.add("uuid", "Notch", addr);
+        assert_eq!(players.get("uuid").unwrap().location, world::spawn());
+
+        let location = Location {
+            dimension: "minecraft:the_end",
+            x: 100.5,
+            y: 50.0,
+            z: 0.5,
+        };
+        assert!(players.teleport("uuid", location.clone()));
+        assert_eq!(players.get("uuid").unwrap().location, location);
+        assert_eq!(receivers.teleports.try_recv().unwrap(), location);
+        assert!(players.set_position("uuid", -3.0, 70.5, 8.25));
+        let moved = players.get("uuid").unwrap().location;
+        assert_eq!(moved.dimension, "minecraft:the_end");
+        assert_eq!((moved.x, moved.y, moved.z), (-3.0, 70.5, 8.25));
+        // The connection moved the player itself.
+        assert!(receivers.teleports.try_recv().is_err());
+        assert!(!players.teleport("unknown", location));
+        assert!(players.get("unknown").is_none());
+    }
+}
@@ -0,0 +1,391 @@
+//! The registries sent to the client during the configuration, like the biomes and the
+//! dimension types. The client has the vanilla entries of its version in its "core" data pack,
+//! so only their names are sent, except for the dimension types, whose heights the chunks
+//! depend on.
+
+use crate::consts::minecraft;
+use crate::nbt::{self, NbtError, Tag};
+use crate::packet::data_types::{string, varint, CodecError};
+use crate::world::biome::BIOMES;
+use crate::world::dimension::DIMENSION_TYPES;
+
+/// The data pack of the vanilla entries: Namespace, ID and Version.
+pub const CORE_PACK: (&str, &str, &str) = ("minecraft", "core", minecraft::VERSION);
+
+const CHAT_TYPES: &[&str] = &[
+    "chat",
+    "emote_command",
+    "msg_command_incoming",
+    "msg_command_outgoing",
+    "say_command",
+    "team_msg_command_incoming",
+    "team_msg_command_outgoing",
+];
+const TRIM_PATTERNS: &[&str] = &[
+    "bolt",
+    "coast",
+    "dune",
+    "eye",
+    "flow",
+    "host",
+    "raiser",
+    "rib",
+    "sentry",
+    "shaper",
+    "silence",
+    "snout",
+    "spire",
+    "tide",
+    "vex",
+    "ward",
+    "wayfinder",
+    "wild",
+];
+const TRIM_MATERIALS: &[&str] = &[
+    "amethyst",
+    "copper",
+    "diamond",
+    "emerald",
+    "gold",
+    "iron",
+    "lapis",
+    "netherite",
+    "quartz",
+    "redstone",
+];
+const WOLF_VARIANTS: &[&str] = &[
+    "ashen", "black", "chestnut", "pale", "rusty", "snowy", "spotted", "striped", "woods",
+];
+const PAINTING_VARIANTS: &[&str] = &[
+    "alban",
+    "aztec",
+    "aztec2",
+    "backyard",
+    "baroque",
+    "bomb",
+    "bouquet",
+    "burning_skull",
+    "bust",
+    "cavebird",
+    "changing",
+    "cotan",
+    "courbet",
+    "creebet",
+    "donkey_kong",
+    "earth",
+    "endboss",
+    "fern",
+    "fighters",
+    "finding",
+    "fire",
+    "graham",
+    "humble",
+    "kebab",
+    "lowmist",
+    "match",
+    "meditative",
+    "orb",
+    "owlemons",
+    "passage",
+    "pigscene",
+    "plant",
+    "pointer",
+    "pond",
+    "pool",
+    "prairie_ride",
+    "sea",
+    "skeleton",
+    "skull_and_roses",
+    "stage",
+    "sunflowers",
+    "sunset",
+    "tides",
+    "unpacked",
+    "void",
+    "wanderer",
+    "wasteland",
+    "water",
+    "wind",
+    "wither",
+];
+const DAMAGE_TYPES: &[&str] = &[
+    "arrow",
+    "bad_respawn_point",
+    "cactus",
+    "cramming",
+    "dragon_breath",
+    "drown",
+    "dry_out",
+    "explosion",
+    "fall",
+    "falling_anvil",
+    "falling_block",
+    "falling_stalactite",
+    "fireball",
+    "fireworks",
+    "fly_into_wall",
+    "freeze",
+    "generic",
+    "generic_kill",
+    "hot_floor",
+    "in_fire",
+    "in_wall",
+    "indirect_magic",
+    "lava",
+    "lightning_bolt",
+    "mace_smash",
+    "magic",
+    "mob_attack",
+    "mob_attack_no_aggro",
+    "mob_projectile",
+    "on_fire",
+    "out_of_world",
+    "outside_border",
+    "player_attack",
+    "player_explosion",
+    "sonic_boom",
+    "spit",
+    "stalagmite",
+    "starve",
+    "sting",
+    "sweet_berry_bush",
+    "thorns",
+    "thrown",
+    "trident",
+    "unattributed_fireball",
+    "wind_charge",
+    "wither",
+    "wither_skull",
+];
+const BANNER_PATTERNS: &[&str] = &[
+    "base",
+    "border",
+    "bricks",
+    "circle",
+    "creeper",
+    "cross",
+    "curly_border",
+    "diagonal_left",
+    "diagonal_right",
+    "diagonal_up_left",
+    "diagonal_up_right",
+    "flow",
+    "flower",
+    "globe",
+    "gradient",
+    "gradient_up",
+    "guster",
+    "half_horizontal",
+    "half_horizontal_bottom",
+    "half_vertical",
+    "half_vertical_right",
+    "mojang",
+    "piglin",
+    "rhombus",
+    "skull",
+    "small_stripes",
+    "square_bottom_left",
+    "square_bottom_right",
+    "square_top_left",
+    "square_top_right",
+    "straight_cross",
+    "stripe_bottom",
+    "stripe_center",
+    "stripe_downleft",
+    "stripe_downright",
+    "stripe_left",
+    "stripe_middle",
+    "stripe_right",
+    "stripe_top",
+    "triangle_bottom",
+    "triangle_top",
+    "triangles_bottom",
+    "triangles_top",
+];
+const ENCHANTMENTS: &[&str] = &[
+    "aqua_affinity",
+    "bane_of_arthropods",
+    "binding_curse",
+    "blast_protection",
+    "breach",
+    "channeling",
+    "density",
+    "depth_strider",
+    "efficiency",
+    "feather_falling",
+    "fire_aspect",
+    "fire_protection",
+    "flame",
+    "fortune",
+    "frost_walker",
+    "impaling",
+    "infinity",
+    "knockback",
+    "looting",
+    "loyalty",
+    "luck_of_the_sea",
+    "lure",
+    "mending",
+    "multishot",
+    "piercing",
+    "power",
+    "projectile_protection",
+    "protection",
+    "punch",
+    "quick_charge",
+    "respiration",
+    "riptide",
+    "sharpness",
+    "silk_touch",
+    "smite",
+    "soul_speed",
+    "sweeping_edge",
+    "swift_sneak",
+    "thorns",
+    "unbreaking",
+    "vanishing_curse",
+    "wind_burst",
+];
+const JUKEBOX_SONGS: &[&str] = &[
+    "11",
+    "13",
+    "5",
+    "blocks",
+    "cat",
+    "chirp",
+    "creator",
+    "creator_music_box",
+    "far",
+    "mall",
+    "mellohi",
+    "otherside",
+    "pigstep",
+    "precipice",
+    "relic",
+    "stal",
+    "strad",
+    "wait",
+    "ward",
+];
+
+/// The registries whose entries are only sent by name, without the namespace, apart from the
+/// biomes and the dimension types.
+const NAMED_REGISTRIES: [(&str, &[&str]); 9] = [
+    ("minecraft:chat_type", CHAT_TYPES),
+    ("minecraft:trim_pattern", TRIM_PATTERNS),
+    ("minecraft:trim_material", TRIM_MATERIALS),
+    ("minecraft:wolf_variant", WOLF_VARIANTS),
+    ("minecraft:painting_variant", PAINTING_VARIANTS),
+    ("minecraft:damage_type", DAMAGE_TYPES),
+    ("minecraft:banner_pattern", BANNER_PATTERNS),
+    ("minecraft:enchantment", ENCHANTMENTS),
+    ("minecraft:jukebox_song", JUKEBOX_SONGS),
+];
+
+/// Clientbound Known Packs: Known Pack Count (VarInt), then for each pack Namespace (String),
+/// ID (String) and Version (String).
+pub fn known_packs() -> Vec<u8> {
+    let (namespace, id, version) = CORE_PACK;
+    let mut payload = varint::write(1);
+    for field in [namespace, id, version] {
+        payload.extend(string::write(field));
+    }
+    payload
+}
+
+/// Reads the Serverbound Known Packs, with the same fields, and returns whether the client has
+/// the core pack of the server's version.
+pub fn has_core_pack(payload: &[u8]) -> Result<bool, CodecError> {
+    let (count, mut offset) = varint::read(payload)?;
+    let mut found = false;
+    for _ in 0..count {
+        let mut fields = Vec::with_capacity(3);
+        for _ in 0..3 {
+            let (field, length) = string::read(&payload[offset..])?;
+            offset += length;
+            fields.push(field);
+        }
+        let (namespace, id, version) = CORE_PACK;
+        found |= fields == [namespace, id, version];
+    }
+    Ok(found)
+}
+
+/// The payloads of the Registry Data packets, one per registry: Registry ID (Identifier),
+/// Entry Count (VarInt), then for each entry Entry ID (Identifier), Has Data (Boolean) and
+/// Data (Optional NBT).
+/// The entries of a registry are given IDs in that order, which is why the dimension types are
+/// in the order of `DIMENSION_TYPES`, and the biomes in the order of `BIOMES`.
+pub fn registry_data() -> Result<Vec<Vec<u8>>, NbtError> {
+    let mut payloads = Vec::with_capacity(NAMED_REGISTRIES.len() + 2);
+
+    let mut payload = string::write("minecraft:dimension_type");
+    payload.extend(varint::write(DIMENSION_TYPES.len() as i32));
+    for kind in DIMENSION_TYPES {
+        payload.extend(string::write(kind.id));
+        payload.push(1);
+        payload.extend(nbt::binary::write_network(&Tag::Compound(kind.to_nbt()))?);
+    }
+    payloads.push(payload);
+
+    let mut payload = string::write("minecraft:worldgen/biome");
+    payload.extend(varint::write(BIOMES.len() as i32));
+    for biome in BIOMES {
+        payload.extend(string::write(biome));
+        payload.push(0);
+    }
+    payloads.push(payload);
+
+    for (registry, entries) in NAMED_REGISTRIES {
+        let mut payload = string::write(registry);
+        payload.extend(varint::write(entries.len() as i32));
+        for entry in entries {
+            payload.extend(string::write(&format!("minecraft:{entry}")));
+            payload.push(0);
+        }
+        payloads.push(payload);
+    }
+
+    Ok(payloads)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_packs() {
+        assert!(has_core_pack(&known_packs()).unwrap());
+        assert!(!has_core_pack(&varint::write(0)).unwrap());
+
+        let mut older = varint::write(1);
+        for field in ["minecraft", "core", "1.21"] {
+            older.extend(string::write(field));
+        }
+        assert!(!has_core_pack(&older).unwrap());
+        assert!(has_core_pack(&older[..5]).is_err());
+    }
+
+    #[test]
+    fn test_registry_data() {
+        let payloads = registry_data().unwrap();
+        let registries: Vec<String> = payloads
+            .iter()
+            .map(|payload| string::read(payload).unwrap().0)
+            .collect();
+        assert_eq!(registries.len(), 11);
+        assert!(registries.contains(&"minecraft:damage_type".to_string()));
+
+        // The dimension types are sent with their data, in order.
+        let payload = &payloads[0];
+        let (_, mut offset) = string::read(payload).unwrap();
+        let (count, length) = varint::read(&payload[offset..]).unwrap();
+        offset += length;
+        assert_eq!(count, 3);
+        let (id, length) = string::read(&payload[offset..]).unwrap();
+        assert_eq!(id, "minecraft:overworld");
+        assert_eq!(payload[offset + length], 1);
+        let (data, _) = nbt::binary::read_network(&payload[offset + length + 1..]).unwrap();
+        assert_eq!(data, Some(Tag::Compound(DIMENSION_TYPES[0].to_nbt())));
+    }
+}
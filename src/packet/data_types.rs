@@ -130,12 +130,134 @@ pub mod varlong {
     }
 }
 
+/// Strings are prefixed by their length in bytes as a VarInt and encoded in UTF-8.
+pub mod string {
+    use super::{varint, CodecError};
+
+    /// Tries to read a String **beginning from the first byte of the data**.
+    /// Returns the String and the number of bytes read (length prefix included).
+    pub fn read(data: &[u8]) -> Result<(String, usize), CodecError> {
+        let (length, length_size) = varint::read(data)?;
+        let length: usize = length.try_into().map_err(|_| CodecError::NegativeLength)?;
+
+        let bytes = data
+            .get(length_size..length_size + length)
+            .ok_or(CodecError::UnexpectedEnd)?;
+        let value = std::str::from_utf8(bytes).map_err(|_| CodecError::DecodeStringInvalidUtf8)?;
+
+        Ok((value.to_string(), length_size + length))
+    }
+
+    /// This function encodes a &str to a Vec<u8>, prefixed by its length.
+    pub fn write(value: &str) -> Vec<u8> {
+        let mut result = varint::write(value.len() as i32);
+        result.extend_from_slice(value.as_bytes());
+        result
+    }
+}
+
+/// UUIDs are sent as an unsigned 128-bit integer, most significant bits first.
+pub mod uuid {
+    use super::CodecError;
+
+    /// Tries to read a UUID **beginning from the first byte of the data**.
+    pub fn read(data: &[u8]) -> Result<(u128, usize), CodecError> {
+        let bytes: [u8; 16] = data
+            .get(..16)
+            .ok_or(CodecError::UnexpectedEnd)?
+            .try_into()
+            .map_err(|_| CodecError::UnexpectedEnd)?;
+        Ok((u128::from_be_bytes(bytes), 16))
+    }
+
+    /// This function encodes a UUID to a Vec<u8> of 16 elements.
+    pub fn write(value: u128) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+
+    /// Returns the hyphenated representation of a UUID, like in the server's JSON files.
+    pub fn to_string(value: u128) -> String {
+        let hex = format!("{value:032x}");
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+
+    /// Parses a UUID, with or without hyphens.
+    pub fn from_str(value: &str) -> Option<u128> {
+        let hex: String = value.chars().filter(|c| *c != '-').collect();
+        if hex.len() != 32 {
+            return None;
+        }
+        u128::from_str_radix(&hex, 16).ok()
+    }
+}
+
+/// Doubles are sent as IEEE 754 double-precision floats, big-endian.
+pub mod double {
+    use super::CodecError;
+
+    /// Tries to read a Double **beginning from the first byte of the data**.
+    pub fn read(data: &[u8]) -> Result<(f64, usize), CodecError> {
+        let bytes: [u8; 8] = data
+            .get(..8)
+            .ok_or(CodecError::UnexpectedEnd)?
+            .try_into()
+            .map_err(|_| CodecError::UnexpectedEnd)?;
+        Ok((f64::from_be_bytes(bytes), 8))
+    }
+
+    pub fn write(value: f64) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+}
+
+/// Floats are sent as IEEE 754 single-precision floats, big-endian.
+pub mod float {
+    use super::CodecError;
+
+    /// Tries to read a Float **beginning from the first byte of the data**.
+    pub fn read(data: &[u8]) -> Result<(f32, usize), CodecError> {
+        let bytes: [u8; 4] = data
+            .get(..4)
+            .ok_or(CodecError::UnexpectedEnd)?
+            .try_into()
+            .map_err(|_| CodecError::UnexpectedEnd)?;
+        Ok((f32::from_be_bytes(bytes), 4))
+    }
+
+    pub fn write(value: f32) -> Vec<u8> {
+        value.to_be_bytes().to_vec()
+    }
+}
+
+/// Block positions are packed in a Long: X in the 26 most significant bits, then Z in 26 bits
+/// and Y in the 12 least significant bits.
+pub mod position {
+    pub fn write(x: i32, y: i32, z: i32) -> Vec<u8> {
+        let packed =
+            ((x as i64 & 0x3FF_FFFF) << 38) | ((z as i64 & 0x3FF_FFFF) << 12) | (y as i64 & 0xFFF);
+        packed.to_be_bytes().to_vec()
+    }
+}
+
 #[derive(Error, Debug)]
 pub enum CodecError {
     #[error("VarInt decoding error: value too long (max 5 bytes)")]
     DecodeVarIntTooLong,
     #[error("VarLong decoding error: value too long (max 10 bytes)")]
     DecodeVarLongTooLong,
+    #[error("String decoding error: invalid UTF-8")]
+    DecodeStringInvalidUtf8,
+    #[error("Decoding error: negative length")]
+    NegativeLength,
+    #[error("Decoding error: unexpected end of data")]
+    UnexpectedEnd,
 }
 
 /// Tests mostly written by AI, and not human-checked.
@@ -352,4 +474,67 @@ mod tests {
             Err(CodecError::DecodeVarLongTooLong)
         ));
     }
+
+    #[test]
+    fn test_string_roundtrip() {
+        for value in ["", "localhost", "Notch", "こんにちは世界"] {
+            let encoded = string::write(value);
+            let (decoded, length) = string::read(&encoded).unwrap();
+            assert_eq!(decoded, value);
+            assert_eq!(length, encoded.len());
+        }
+
+        // The length says 5 bytes but only 2 follow.
+        assert!(matches!(
+            string::read(&[5, b'a', b'b']),
+            Err(CodecError::UnexpectedEnd)
+        ));
+        assert!(matches!(
+            string::read(&[2, 0xC3, 0x28]),
+            Err(CodecError::DecodeStringInvalidUtf8)
+        ));
+    }
+
+    #[test]
+    fn test_uuid() {
+        let value = 0x069a79f444e94726a5befca90e38aaf5;
+        let encoded = uuid::write(value);
+        assert_eq!(encoded.len(), 16);
+        assert_eq!(uuid::read(&encoded).unwrap(), (value, 16));
+        assert_eq!(
+            uuid::to_string(value),
+            "069a79f4-44e9-4726-a5be-fca90e38aaf5"
+        );
+        assert_eq!(
+            uuid::from_str("069a79f4-44e9-4726-a5be-fca90e38aaf5"),
+            Some(value)
+        );
+        assert_eq!(
+            uuid::from_str("069a79f444e94726a5befca90e38aaf5"),
+            Some(value)
+        );
+        assert_eq!(uuid::from_str("not a uuid"), None);
+        assert!(uuid::read(&[0; 15]).is_err());
+    }
+
+    #[test]
+    fn test_floats() {
+        let encoded = [0xC0, 0x5E, 0xDD, 0x2F, 0x1A, 0x9F, 0xBE, 0x77, 0xFF];
+        assert_eq!(double::read(&encoded).unwrap(), (-123.456, 8));
+        assert_eq!(float::read(&[0x41, 0x10, 0x00, 0x00]).unwrap(), (9.0, 4));
+        assert!(double::read(&encoded[..7]).is_err());
+        assert!(float::read(&[0x41]).is_err());
+        assert_eq!(double::write(-123.456), encoded[..8]);
+        assert_eq!(float::write(9.0), [0x41, 0x10, 0x00, 0x00]);
+    }
+
+    #[test]
+    fn test_position() {
+        // The example of wiki.vg.
+        assert_eq!(
+            position::write(18357644, 831, -20882616),
+            0x4607632C15B4833Fu64.to_be_bytes()
+        );
+        assert_eq!(position::write(-1, -1, -1), [0xFF; 8]);
+    }
 }
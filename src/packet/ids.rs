@@ -0,0 +1,66 @@
+//! Packet IDs for protocol version 767 (1.21.1), sorted by connection state and direction.
+//! Taken from https://wiki.vg/Protocol
+
+pub mod handshake {
+    pub mod serverbound {
+        pub const HANDSHAKE: i32 = 0x00;
+    }
+}
+
+pub mod login {
+    pub mod serverbound {
+        pub const LOGIN_START: i32 = 0x00;
+        pub const LOGIN_ACKNOWLEDGED: i32 = 0x03;
+    }
+
+    pub mod clientbound {
+        pub const DISCONNECT: i32 = 0x00;
+        pub const LOGIN_SUCCESS: i32 = 0x02;
+    }
+}
+
+pub mod configuration {
+    pub mod serverbound {
+        pub const ACKNOWLEDGE_FINISH_CONFIGURATION: i32 = 0x03;
+        pub const KNOWN_PACKS: i32 = 0x07;
+    }
+
+    pub mod clientbound {
+        pub const DISCONNECT: i32 = 0x02;
+        pub const FINISH_CONFIGURATION: i32 = 0x03;
+        pub const REGISTRY_DATA: i32 = 0x07;
+        pub const KNOWN_PACKS: i32 = 0x0E;
+    }
+}
+
+pub mod play {
+    pub mod serverbound {
+        pub const CONFIRM_TELEPORTATION: i32 = 0x00;
+        pub const CHAT_COMMAND: i32 = 0x04;
+        pub const SIGNED_CHAT_COMMAND: i32 = 0x05;
+        pub const CHUNK_BATCH_RECEIVED: i32 = 0x08;
+        pub const COMMAND_SUGGESTIONS_REQUEST: i32 = 0x0B;
+        pub const KEEP_ALIVE: i32 = 0x18;
+        pub const SET_PLAYER_POSITION: i32 = 0x1A;
+        pub const SET_PLAYER_POSITION_AND_ROTATION: i32 = 0x1B;
+    }
+
+    pub mod clientbound {
+        pub const CHANGE_DIFFICULTY: i32 = 0x0B;
+        pub const CHUNK_BATCH_FINISHED: i32 = 0x0C;
+        pub const CHUNK_BATCH_START: i32 = 0x0D;
+        pub const COMMAND_SUGGESTIONS_RESPONSE: i32 = 0x10;
+        pub const COMMANDS: i32 = 0x11;
+        pub const DISCONNECT: i32 = 0x1D;
+        pub const UNLOAD_CHUNK: i32 = 0x21;
+        pub const GAME_EVENT: i32 = 0x22;
+        pub const KEEP_ALIVE: i32 = 0x26;
+        pub const CHUNK_DATA_AND_UPDATE_LIGHT: i32 = 0x27;
+        pub const LOGIN: i32 = 0x2B;
+        pub const SYNCHRONIZE_PLAYER_POSITION: i32 = 0x40;
+        pub const RESPAWN: i32 = 0x47;
+        pub const SET_CENTER_CHUNK: i32 = 0x54;
+        pub const SET_DEFAULT_SPAWN_POSITION: i32 = 0x56;
+        pub const SYSTEM_CHAT_MESSAGE: i32 = 0x6C;
+    }
+}
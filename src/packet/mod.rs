@@ -2,6 +2,7 @@
 //! standardized way.
 
 pub mod data_types;
+pub mod ids;
 pub mod utils;
 
 use core::fmt;
@@ -10,6 +11,9 @@ use data_types::varint;
 use log::warn;
 use thiserror::Error;
 
+/// The maximum length of a packet, Packet ID and data included. (2^21 - 1 bytes)
+pub const MAX_PACKET_LENGTH: usize = 2097151;
+
 // It is true that I could lazily evaluate the length, and Id for more performance but I chose to do it eagerly.
 
 /// An abstraction for a Minecraft packet.
@@ -137,6 +141,41 @@ impl<'a> fmt::Display for Packet<'a> {
     }
 }
 
+/// Encodes a packet ready to be sent over the network:
+/// Length (VarInt), Packet ID (VarInt) and the payload.
+pub fn encode(id: i32, payload: &[u8]) -> Vec<u8> {
+    let id = varint::write(id);
+    let mut packet = varint::write((id.len() + payload.len()) as i32);
+    packet.extend(id);
+    packet.extend_from_slice(payload);
+    packet
+}
+
+/// Returns the number of bytes of the first packet in `data` (Length VarInt included), or
+/// `None` if `data` does not contain a whole packet yet.
+pub fn frame_length(data: &[u8]) -> Result<Option<usize>, PacketError> {
+    if data.is_empty() {
+        return Ok(None);
+    }
+
+    let (length, length_size) = varint::read(data).map_err(|_| PacketError::LengthDecodingError)?;
+
+    // The VarInt itself is not complete.
+    if length_size == data.len() && data[length_size - 1] & 0x80 != 0 {
+        return Ok(None);
+    }
+
+    let length: usize = length
+        .try_into()
+        .map_err(|_| PacketError::LengthDecodingError)?;
+    if length > MAX_PACKET_LENGTH {
+        return Err(PacketError::TooLong(length));
+    }
+
+    let total = length_size + length;
+    Ok((data.len() >= total).then_some(total))
+}
+
 pub enum PacketType {
     Todo,
 }
@@ -226,6 +265,8 @@ pub enum PacketError {
     IdDecodingError,
     #[error("Failed to decode the packet length")]
     LengthDecodingError,
+    #[error("Packet too long ({0} bytes)")]
+    TooLong(usize),
 }
 
 // TODO: I wonder if having "invalid" value, like a too short/long Length should propagate an error
@@ -335,4 +376,32 @@ mod tests {
         assert_eq!(packet.get_full_packet(), init_data);
         assert_eq!(packet.len(), init_data.len());
     }
+
+    #[test]
+    fn test_encode() {
+        let encoded = encode(0x02, &[1, 2, 3]);
+        assert_eq!(encoded, &[4, 2, 1, 2, 3]);
+
+        let packet = Packet::new(&encoded).expect("Failed to create packet");
+        assert_eq!(packet.get_id().get_value(), 0x02);
+        assert_eq!(packet.get_payload(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_frame_length() {
+        assert_eq!(frame_length(&[]).unwrap(), None);
+        assert_eq!(frame_length(&[4, 2, 1]).unwrap(), None);
+        assert_eq!(frame_length(&[4, 2, 1, 2, 3]).unwrap(), Some(5));
+        // Two packets in a row, only the first one is counted.
+        assert_eq!(frame_length(&[1, 0, 1, 0]).unwrap(), Some(2));
+
+        // Length VarInt cut in half.
+        assert_eq!(frame_length(&[0x80]).unwrap(), None);
+
+        let too_long = varint::write(MAX_PACKET_LENGTH as i32 + 1);
+        assert!(matches!(
+            frame_length(&too_long),
+            Err(PacketError::TooLong(_))
+        ));
+    }
 }
@@ -0,0 +1,191 @@
+//! This module is about the players' profiles, and how to find them given a name.
+
+use std::future::Future;
+
+use log::warn;
+use serde::Deserialize;
+use thiserror::Error;
+
+use crate::user_lists::format_uuid;
+use crate::user_lists::usercache::{UserCache, USERCACHE};
+
+/// The Mojang API endpoint returning the UUID of a player given their name.
+const PROFILE_API_URL: &str = "https://api.mojang.com/users/profiles/minecraft/";
+
+/// The identity of a player.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Profile {
+    /// The hyphenated UUID, like in the server's JSON files.
+    pub uuid: String,
+    /// The name with its real case, which may differ from what was typed.
+    pub name: String,
+}
+
+/// A service finding the profile of a player given their name.
+pub trait ProfileService {
+    /// Returns `Ok(None)` when no player has this name.
+    fn lookup(
+        &self,
+        name: &str,
+    ) -> impl Future<Output = Result<Option<Profile>, PlayerError>> + Send;
+}
+
+/// Finds profiles using the Mojang API.
+pub struct MojangProfileService;
+
+/// The response of the Mojang API.
+#[derive(Deserialize)]
+struct MojangProfile {
+    id: String,
+    name: String,
+}
+
+impl ProfileService for MojangProfileService {
+    async fn lookup(&self, name: &str) -> Result<Option<Profile>, PlayerError> {
+        let response = reqwest::get(format!("{PROFILE_API_URL}{name}"))
+            .await
+            .map_err(|e| PlayerError::Service(e.to_string()))?;
+
+        match response.status() {
+            reqwest::StatusCode::NO_CONTENT | reqwest::StatusCode::NOT_FOUND => return Ok(None),
+            status if !status.is_success() => {
+                return Err(PlayerError::Service(format!("HTTP status {status}")))
+            }
+            _ => {}
+        }
+
+        let profile: MojangProfile = response
+            .json()
+            .await
+            .map_err(|e| PlayerError::Service(e.to_string()))?;
+
+        Ok(Some(Profile {
+            uuid: format_uuid(&profile.id),
+            name: profile.name,
+        }))
+    }
+}
+
+/// Returns whether `name` could be a Minecraft player name.
+pub fn is_valid_name(name: &str) -> bool {
+    (1..=16).contains(&name.len()) && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Finds the profile of a player, first in `cache` and then using `service`.
+/// Profiles found by `service` are added to `cache`.
+pub async fn resolve_profile<S: ProfileService>(
+    name: &str,
+    cache: &UserCache,
+    service: &S,
+) -> Result<Profile, PlayerError> {
+    if !is_valid_name(name) {
+        return Err(PlayerError::NotFound(name.to_string()));
+    }
+
+    if let Some(entry) = cache.get_by_name(name) {
+        return Ok(Profile {
+            uuid: entry.uuid,
+            name: entry.name,
+        });
+    }
+
+    let profile = service
+        .lookup(name)
+        .await?
+        .ok_or_else(|| PlayerError::NotFound(name.to_string()))?;
+
+    if let Err(e) = cache.update(&profile.uuid, &profile.name) {
+        warn!("Failed to save the user cache: {e}");
+    }
+
+    Ok(profile)
+}
+
+/// Finds the profile of a player given their name, using the server's user cache when possible.
+pub async fn get_profile(name: &str) -> Result<Profile, PlayerError> {
+    resolve_profile(name, &USERCACHE, &MojangProfileService).await
+}
+
+#[derive(Error, Debug)]
+pub enum PlayerError {
+    #[error("That player does not exist: {0}")]
+    NotFound(String),
+    #[error("Failed to query the profile service: {0}")]
+    Service(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+
+    /// A stand-in for the Mojang API, which counts how many times it was asked.
+    #[derive(Default)]
+    struct LocalProfileService {
+        profiles: HashMap<String, Profile>,
+        lookups: AtomicUsize,
+    }
+
+    impl LocalProfileService {
+        fn with(mut self, uuid: &str, name: &str) -> Self {
+            let profile = Profile {
+                uuid: uuid.to_string(),
+                name: name.to_string(),
+            };
+            self.profiles.insert(name.to_lowercase(), profile);
+            self
+        }
+    }
+
+    impl ProfileService for LocalProfileService {
+        async fn lookup(&self, name: &str) -> Result<Option<Profile>, PlayerError> {
+            self.lookups.fetch_add(1, Ordering::SeqCst);
+            Ok(self.profiles.get(&name.to_lowercase()).cloned())
+        }
+    }
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    #[tokio::test]
+    async fn test_cache_is_used_before_service() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let cache = UserCache::load(&dir.path().join("usercache.json"), 10)?;
+        let service = LocalProfileService::default().with(NOTCH, "Notch");
+
+        let profile = resolve_profile("notch", &cache, &service).await?;
+        assert_eq!(profile.uuid, NOTCH);
+        assert_eq!(profile.name, "Notch");
+        assert_eq!(service.lookups.load(Ordering::SeqCst), 1);
+
+        // Resolved offline this time.
+        let profile = resolve_profile("NOTCH", &cache, &service).await?;
+        assert_eq!(profile.uuid, NOTCH);
+        assert_eq!(service.lookups.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unknown_and_invalid_names() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = TempDir::new()?;
+        let cache = UserCache::load(&dir.path().join("usercache.json"), 10)?;
+        let service = LocalProfileService::default();
+
+        assert!(matches!(
+            resolve_profile("jeb_", &cache, &service).await,
+            Err(PlayerError::NotFound(_))
+        ));
+        assert_eq!(cache.len(), 0);
+
+        // Never sent to the service.
+        assert!(resolve_profile("../../evil", &cache, &service)
+            .await
+            .is_err());
+        assert!(resolve_profile("", &cache, &service).await.is_err());
+        assert_eq!(service.lookups.load(Ordering::SeqCst), 1);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,326 @@
+//! The query server, which answers the GameSpy4 UDP protocol used by server lists and tools to
+//! get the details of the server, including the names of the online players.
+//! See https://wiki.vg/Query
+
+use std::collections::HashMap;
+use std::io;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use tokio::net::UdpSocket;
+
+use crate::consts::minecraft::VERSION;
+use crate::logging;
+use crate::net::online::ONLINE_PLAYERS;
+
+/// Starts every request.
+const MAGIC: [u8; 2] = [0xFE, 0xFD];
+const HANDSHAKE: u8 = 9;
+const STAT: u8 = 0;
+
+/// How long a challenge token can be used after the handshake which created it.
+const CHALLENGE_LIFETIME: Duration = Duration::from_secs(30);
+
+/// Written before the key/value section of a full stat, for historical reasons.
+const KEY_VALUES_PADDING: [u8; 11] = *b"splitnum\0\x80\0";
+/// Written before the player list of a full stat.
+const PLAYERS_PADDING: [u8; 10] = *b"\x01player_\0\0";
+
+/// The game type and id, which are the same for every Minecraft server.
+const GAME_TYPE: &str = "SMP";
+const GAME_ID: &str = "MINECRAFT";
+
+/// What the server tells about itself.
+#[derive(Clone, Debug)]
+pub struct QueryInfo {
+    pub motd: String,
+    /// The name of the world.
+    pub map: String,
+    pub max_players: u32,
+    pub host_ip: String,
+    pub host_port: u16,
+    /// Leaves the player list empty.
+    pub hide_online_players: bool,
+}
+
+/// The challenge tokens given by the handshakes, by client address.
+#[derive(Default)]
+struct Challenges {
+    tokens: HashMap<SocketAddr, (i32, Instant)>,
+}
+
+impl Challenges {
+    /// Creates a new token for `addr`, replacing the previous one.
+    fn create(&mut self, addr: SocketAddr, now: Instant) -> i32 {
+        self.tokens
+            .retain(|_, (_, created)| now.duration_since(*created) < CHALLENGE_LIFETIME);
+
+        let token = rand::random::<i32>() & 0x7FFF_FFFF;
+        self.tokens.insert(addr, (token, now));
+        token
+    }
+
+    /// Whether `token` was given to `addr` less than 30 seconds ago.
+    fn is_valid(&self, addr: SocketAddr, token: i32, now: Instant) -> bool {
+        self.tokens.get(&addr).is_some_and(|&(expected, created)| {
+            expected == token && now.duration_since(created) < CHALLENGE_LIFETIME
+        })
+    }
+}
+
+pub struct QueryServer {
+    socket: UdpSocket,
+    info: QueryInfo,
+    challenges: Challenges,
+}
+
+impl QueryServer {
+    pub async fn bind(addr: SocketAddr, info: QueryInfo) -> io::Result<Self> {
+        Ok(Self {
+            socket: UdpSocket::bind(addr).await?,
+            info,
+            challenges: Challenges::default(),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Answers the requests until the server stops.
+    pub async fn run(mut self) {
+        let mut buf = [0; 1460];
+
+        loop {
+            let (length, addr) = match self.socket.recv_from(&mut buf).await {
+                Ok(received) => received,
+                Err(e) => {
+                    warn!("Failed to receive a query: {e}");
+                    continue;
+                }
+            };
+
+            let mut players: Vec<String> =
+                ONLINE_PLAYERS.list().into_iter().map(|p| p.name).collect();
+            players.sort_unstable_by_key(|name| name.to_lowercase());
+
+            let Some(response) = self.respond(&buf[..length], addr, &players, Instant::now())
+            else {
+                debug!(addr:% = addr; "Ignored an invalid query from {}", logging::ip(addr));
+                continue;
+            };
+            if let Err(e) = self.socket.send_to(&response, addr).await {
+                warn!(addr:% = addr; "Failed to answer the query from {}: {e}", logging::ip(addr));
+            }
+        }
+    }
+
+    /// Returns the response to a request, or `None` if it is invalid or its challenge token
+    /// has expired.
+    fn respond(
+        &mut self,
+        request: &[u8],
+        addr: SocketAddr,
+        players: &[String],
+        now: Instant,
+    ) -> Option<Vec<u8>> {
+        if request.len() < 7 || request[..2] != MAGIC {
+            return None;
+        }
+        let kind = request[2];
+        let session_id = &request[3..7];
+
+        let mut response = vec![kind];
+        response.extend_from_slice(session_id);
+
+        match (kind, request.len()) {
+            // Handshake: the token, as a null-terminated decimal string.
+            (HANDSHAKE, 7) => {
+                let token = self.challenges.create(addr, now);
+                write_string(&mut response, &token.to_string());
+            }
+            // Basic stat: Challenge Token (i32). Full stat: the same, with 4 padding bytes.
+            (STAT, 11 | 15) => {
+                let token = i32::from_be_bytes(request[7..11].try_into().unwrap());
+                if !self.challenges.is_valid(addr, token, now) {
+                    return None;
+                }
+                if request.len() == 11 {
+                    self.write_basic_stat(&mut response, players.len());
+                } else {
+                    self.write_full_stat(&mut response, players);
+                }
+            }
+            _ => return None,
+        }
+
+        Some(response)
+    }
+
+    /// MOTD, Game Type, Map, Player Count and Max Players as strings, Host Port (u16,
+    /// little-endian), Host IP
+    fn write_basic_stat(&self, response: &mut Vec<u8>, player_count: usize) {
+        write_string(response, &self.info.motd);
+        write_string(response, GAME_TYPE);
+        write_string(response, &self.info.map);
+        write_string(response, &player_count.to_string());
+        write_string(response, &self.info.max_players.to_string());
+        response.extend_from_slice(&self.info.host_port.to_le_bytes());
+        write_string(response, &self.info.host_ip);
+    }
+
+    /// The key/value pairs ended by an empty key, then the player names ended by an empty name.
+    fn write_full_stat(&self, response: &mut Vec<u8>, players: &[String]) {
+        response.extend_from_slice(&KEY_VALUES_PADDING);
+        let pairs = [
+            ("hostname", self.info.motd.clone()),
+            ("gametype", GAME_TYPE.to_string()),
+            ("game_id", GAME_ID.to_string()),
+            ("version", VERSION.to_string()),
+            ("plugins", String::new()),
+            ("map", self.info.map.clone()),
+            ("numplayers", players.len().to_string()),
+            ("maxplayers", self.info.max_players.to_string()),
+            ("hostport", self.info.host_port.to_string()),
+            ("hostip", self.info.host_ip.clone()),
+        ];
+        for (key, value) in pairs {
+            write_string(response, key);
+            write_string(response, &value);
+        }
+        response.push(0);
+
+        response.extend_from_slice(&PLAYERS_PADDING);
+        if !self.info.hide_online_players {
+            for player in players {
+                write_string(response, player);
+            }
+        }
+        response.push(0);
+    }
+}
+
+/// Writes a null-terminated string.
+fn write_string(buffer: &mut Vec<u8>, value: &str) {
+    buffer.extend_from_slice(value.as_bytes());
+    buffer.push(0);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn info() -> QueryInfo {
+        QueryInfo {
+            motd: "A Minecraft Server".to_string(),
+            map: "world".to_string(),
+            max_players: 20,
+            host_ip: "127.0.0.1".to_string(),
+            host_port: 25565,
+            hide_online_players: false,
+        }
+    }
+
+    /// A request with the session id 1.
+    fn request(kind: u8, payload: &[u8]) -> Vec<u8> {
+        let mut request = vec![0xFE, 0xFD, kind, 0, 0, 0, 1];
+        request.extend_from_slice(payload);
+        request
+    }
+
+    /// Sends a request and returns the response.
+    async fn exchange(client: &UdpSocket, request: &[u8]) -> Vec<u8> {
+        client.send(request).await.unwrap();
+        let mut buf = [0; 1460];
+        let length = client.recv(&mut buf).await.unwrap();
+        buf[..length].to_vec()
+    }
+
+    /// Does the handshake, and returns the challenge token.
+    async fn handshake(client: &UdpSocket) -> i32 {
+        let response = exchange(client, &request(HANDSHAKE, &[])).await;
+        assert_eq!(response[..5], [HANDSHAKE, 0, 0, 0, 1]);
+        assert_eq!(response.last(), Some(&0));
+        std::str::from_utf8(&response[5..response.len() - 1])
+            .unwrap()
+            .parse()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_udp() {
+        let server = QueryServer::bind("127.0.0.1:0".parse().unwrap(), info())
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+
+        let client = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        client.connect(addr).await.unwrap();
+        let token = handshake(&client).await.to_be_bytes();
+
+        // The players depend on the other tests, see `test_players_and_tokens` for them.
+        let contains = |response: &[u8], part: &[u8]| {
+            response.windows(part.len()).any(|window| window == part)
+        };
+        let response = exchange(&client, &request(STAT, &token)).await;
+        assert!(response.starts_with(b"\0\0\0\0\x01A Minecraft Server\0SMP\0world\0"));
+        assert!(response.ends_with(b"\x0020\0\xDD\x63127.0.0.1\0"));
+
+        let mut full_request = token.to_vec();
+        full_request.extend([0; 4]);
+        let mut expected = vec![STAT, 0, 0, 0, 1];
+        expected.extend(b"splitnum\0\x80\0");
+        expected.extend(b"hostname\0A Minecraft Server\0gametype\0SMP\0game_id\0MINECRAFT\0");
+        expected.extend(b"version\x001.21.1\0plugins\0\0map\0world\0numplayers\0");
+        let response = exchange(&client, &request(STAT, &full_request)).await;
+        assert!(response.starts_with(&expected));
+        assert!(contains(
+            &response,
+            b"\0maxplayers\x0020\0hostport\x0025565\0hostip\x00127.0.0.1\0\0\x01player_\0\0"
+        ));
+        assert!(response.ends_with(b"\0\0"));
+    }
+
+    #[tokio::test]
+    async fn test_players_and_tokens() {
+        let mut server = QueryServer::bind("127.0.0.1:0".parse().unwrap(), info())
+            .await
+            .unwrap();
+        let addr: SocketAddr = "127.0.0.1:50000".parse().unwrap();
+        let players = ["jeb_".to_string(), "Notch".to_string()];
+        let now = Instant::now();
+
+        // No response without a valid token.
+        assert_eq!(
+            server.respond(&request(STAT, &[0; 4]), addr, &players, now),
+            None
+        );
+        let handshake = server
+            .respond(&request(HANDSHAKE, &[]), addr, &players, now)
+            .unwrap();
+        let token: i32 = std::str::from_utf8(&handshake[5..handshake.len() - 1])
+            .unwrap()
+            .parse()
+            .unwrap();
+        let mut full_request = request(STAT, &token.to_be_bytes());
+        full_request.extend([0; 4]);
+
+        let response = server.respond(&full_request, addr, &players, now).unwrap();
+        let count = b"numplayers\x002\0";
+        assert!(response.windows(count.len()).any(|w| w == count));
+        assert!(response.ends_with(b"\x01player_\0\0jeb_\0Notch\0\0"));
+
+        server.info.hide_online_players = true;
+        let response = server.respond(&full_request, addr, &players, now).unwrap();
+        assert!(response.ends_with(b"hostip\x00127.0.0.1\0\0\x01player_\0\0\0"));
+
+        // The token is only valid for 30 seconds, and for its client.
+        let other: SocketAddr = "127.0.0.1:50001".parse().unwrap();
+        assert_eq!(server.respond(&full_request, other, &players, now), None);
+        let later = now + CHALLENGE_LIFETIME;
+        assert_eq!(server.respond(&full_request, addr, &players, later), None);
+    }
+}
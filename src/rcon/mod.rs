@@ -0,0 +1,391 @@
+//! The RCON server, which lets remote tools execute commands once they have logged in with
+//! 'rcon.password'. The commands are executed on the main loop, like the console's.
+
+pub mod packet;
+
+use std::collections::HashMap;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use log::{debug, warn};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::commands;
+use crate::commands::source::{CapturedOutput, CommandOutput, CommandSource, MAX_PERMISSION_LEVEL};
+use crate::logging;
+use crate::net::online::ONLINE_PLAYERS;
+use crate::tick::TaskQueue;
+use crate::user_lists::ops::OPERATORS;
+use packet::{
+    RconPacket, AUTH_FAILURE_ID, SERVERDATA_AUTH, SERVERDATA_AUTH_RESPONSE, SERVERDATA_EXECCOMMAND,
+    SERVERDATA_RESPONSE_VALUE,
+};
+
+/// The name of the RCON clients when they execute commands.
+pub const RCON_NAME: &str = "Rcon";
+
+/// The largest body of a response packet, longer outputs are split into several packets.
+const MAX_RESPONSE_BODY: usize = 4096;
+
+/// The failed logins allowed from an IP address during `FAILED_LOGINS_WINDOW`, the next ones
+/// are refused without checking the password.
+const MAX_FAILED_LOGINS: u32 = 5;
+const FAILED_LOGINS_WINDOW: Duration = Duration::from_secs(60);
+
+pub struct RconServer {
+    listener: TcpListener,
+    context: Arc<RconContext>,
+}
+
+/// What the clients share.
+struct RconContext {
+    password: String,
+    broadcast_to_ops: bool,
+    /// Where the commands are queued, the main loop's outside of the tests.
+    tasks: &'static TaskQueue,
+    failed_logins: FailedLogins,
+}
+
+/// The failed logins of each IP address, counted from the first one of their window.
+#[derive(Default)]
+struct FailedLogins(Mutex<HashMap<IpAddr, (Instant, u32)>>);
+
+impl FailedLogins {
+    /// Returns whether `ip` may still try to log in.
+    fn allows(&self, ip: IpAddr) -> bool {
+        let mut failures = self.0.lock().unwrap();
+        failures.retain(|_, (start, _)| start.elapsed() < FAILED_LOGINS_WINDOW);
+        failures
+            .get(&ip)
+            .is_none_or(|(_, count)| *count < MAX_FAILED_LOGINS)
+    }
+
+    fn add(&self, ip: IpAddr) {
+        let mut failures = self.0.lock().unwrap();
+        failures.entry(ip).or_insert((Instant::now(), 0)).1 += 1;
+    }
+}
+
+/// Compares the passwords in a time that doesn't depend on what they have in common, so that
+/// the password can't be guessed from how long the logins take.
+fn passwords_match(given: &str, password: &str) -> bool {
+    let given = Sha256::digest(given);
+    let password = Sha256::digest(password);
+    given
+        .iter()
+        .zip(password.iter())
+        .fold(0, |difference, (a, b)| difference | (a ^ b))
+        == 0
+}
+
+impl RconServer {
+    /// Binds the RCON server to `addr`. It is refused without a password, since anybody could
+    /// then execute commands.
+    pub async fn bind(
+        addr: SocketAddr,
+        password: &str,
+        broadcast_to_ops: bool,
+        tasks: &'static TaskQueue,
+    ) -> Result<Self, RconError> {
+        if password.is_empty() {
+            return Err(RconError::EmptyPassword);
+        }
+
+        Ok(Self {
+            listener: TcpListener::bind(addr).await?,
+            context: Arc::new(RconContext {
+                password: password.to_string(),
+                broadcast_to_ops,
+                tasks,
+                failed_logins: FailedLogins::default(),
+            }),
+        })
+    }
+
+    #[cfg(test)]
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// Accepts the clients until the server stops.
+    pub async fn run(self) {
+        loop {
+            let (socket, addr) = match self.listener.accept().await {
+                Ok(client) => client,
+                Err(e) => {
+                    warn!("Failed to accept an RCON client: {e}");
+                    continue;
+                }
+            };
+
+            let context = self.context.clone();
+            tokio::spawn(async move {
+                debug!(addr:% = addr; "RCON client {} connected", logging::ip(addr));
+                if let Err(e) = handle_client(socket, addr, &context).await {
+                    warn!(addr:% = addr; "Error handling RCON client {}: {e}", logging::ip(addr));
+                }
+                debug!(addr:% = addr; "RCON client {} disconnected", logging::ip(addr));
+            });
+        }
+    }
+}
+
+/// Answers the requests of a client until it disconnects.
+async fn handle_client(
+    mut socket: TcpStream,
+    addr: SocketAddr,
+    context: &RconContext,
+) -> Result<(), RconError> {
+    let mut authenticated = false;
+
+    while let Some(request) = RconPacket::read(&mut socket).await? {
+        let responses = match request.kind {
+            SERVERDATA_AUTH => {
+                let ip = addr.ip();
+                authenticated = if context.failed_logins.allows(ip) {
+                    passwords_match(&request.body, &context.password)
+                } else {
+                    warn!(
+                        addr:% = addr;
+                        "Refused the RCON login of {}, after too many failed ones",
+                        logging::ip(addr)
+                    );
+                    false
+                };
+                if !authenticated {
+                    context.failed_logins.add(ip);
+                }
+                let id = if authenticated {
+                    request.id
+                } else {
+                    AUTH_FAILURE_ID
+                };
+                vec![RconPacket::new(id, SERVERDATA_AUTH_RESPONSE, "")]
+            }
+            SERVERDATA_EXECCOMMAND if authenticated => {
+                let output = execute(context, &request.body).await;
+                split_body(&output)
+                    .into_iter()
+                    .map(|body| RconPacket::new(request.id, SERVERDATA_RESPONSE_VALUE, body))
+                    .collect()
+            }
+            SERVERDATA_EXECCOMMAND => {
+                vec![RconPacket::new(
+                    AUTH_FAILURE_ID,
+                    SERVERDATA_AUTH_RESPONSE,
+                    "",
+                )]
+            }
+            kind => vec![RconPacket::new(
+                request.id,
+                SERVERDATA_RESPONSE_VALUE,
+                format!("Unknown request {kind:x}"),
+            )],
+        };
+
+        for response in responses {
+            socket.write_all(&response.encode()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Executes a command on the main loop, and returns its output.
+async fn execute(context: &RconContext, input: &str) -> String {
+    let output = RconOutput {
+        captured: CapturedOutput::default(),
+        broadcast_to_ops: context.broadcast_to_ops,
+    };
+    let captured = output.captured.clone();
+    let source = CommandSource::new(RCON_NAME, MAX_PERMISSION_LEVEL, output);
+
+    let input = input.strip_prefix('/').unwrap_or(input);
+    debug!("{RCON_NAME} issued server command: /{input}");
+    // The sender is only dropped without sending if the server stops first.
+    let _ = commands::submit(context.tasks, input, source).await.await;
+
+    captured.lines().join("\n")
+}
+
+/// Splits the output of a command into the bodies of the response packets, on UTF-8 character
+/// boundaries. There is always at least one packet, even for an empty output.
+fn split_body(output: &str) -> Vec<&str> {
+    let mut bodies = Vec::new();
+    let mut rest = output;
+
+    loop {
+        let mut end = rest.len().min(MAX_RESPONSE_BODY);
+        while !rest.is_char_boundary(end) {
+            end -= 1;
+        }
+        let (body, remaining) = rest.split_at(end);
+        bodies.push(body);
+        rest = remaining;
+        if rest.is_empty() {
+            return bodies;
+        }
+    }
+}
+
+/// Captures the feedback for the client, and shows it to the online operators if
+/// 'broadcast-rcon-to-ops' is set.
+struct RconOutput {
+    captured: CapturedOutput,
+    broadcast_to_ops: bool,
+}
+
+impl CommandOutput for RconOutput {
+    fn send(&self, message: &str) {
+        self.captured.send(message);
+
+        if self.broadcast_to_ops {
+            let message = format!("[{RCON_NAME}: {message}]");
+            for player in ONLINE_PLAYERS.list() {
+                if OPERATORS.level_of(&player.uuid) > 0 {
+                    ONLINE_PLAYERS.send_message(&player.uuid, &message);
+                }
+            }
+        }
+    }
+
+    /// Errors are only for the client.
+    fn send_error(&self, message: &str) {
+        self.captured.send(message);
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RconError {
+    #[error("No rcon password set in server.properties, rcon disabled!")]
+    EmptyPassword,
+    #[error("Invalid RCON packet length: {0}")]
+    InvalidLength(i32),
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tick;
+
+    /// A minimal RCON client.
+    struct Client(TcpStream);
+
+    impl Client {
+        async fn request(&mut self, id: i32, kind: i32, body: &str) -> RconPacket {
+            self.send(id, kind, body).await;
+            RconPacket::read(&mut self.0).await.unwrap().unwrap()
+        }
+
+        async fn send(&mut self, id: i32, kind: i32, body: &str) {
+            let packet = RconPacket::new(id, kind, body);
+            self.0.write_all(&packet.encode()).await.unwrap();
+        }
+
+        /// Executes a command, and reassembles its output from the response packets: an unknown
+        /// request is sent right after it, which ends the output once answered.
+        async fn execute(&mut self, id: i32, command: &str) -> String {
+            self.send(id, SERVERDATA_EXECCOMMAND, command).await;
+            self.send(id + 1, 200, "").await;
+
+            let mut output = String::new();
+            loop {
+                let response = RconPacket::read(&mut self.0).await.unwrap().unwrap();
+                if response.id == id + 1 {
+                    return output;
+                }
+                assert_eq!(response.id, id);
+                output += &response.body;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_password() {
+        let addr: SocketAddr = "127.0.0.1:0".parse().unwrap();
+        assert!(matches!(
+            RconServer::bind(addr, "", false, tick::tasks()).await,
+            Err(RconError::EmptyPassword)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_client() {
+        let tasks = Box::leak(Box::default());
+        tokio::spawn(tick::run_tasks(tasks));
+        let server = RconServer::bind("127.0.0.1:0".parse().unwrap(), "secret", false, tasks)
+            .await
+            .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut client = Client(TcpStream::connect(addr).await.unwrap());
+
+        // Commands need a login.
+        let response = client.request(1, SERVERDATA_EXECCOMMAND, "help").await;
+        assert_eq!(response.id, AUTH_FAILURE_ID);
+        let response = client.request(2, SERVERDATA_AUTH, "wrong").await;
+        assert_eq!(response.id, AUTH_FAILURE_ID);
+
+        let response = client.request(3, SERVERDATA_AUTH, "secret").await;
+        assert_eq!(response, RconPacket::new(3, SERVERDATA_AUTH_RESPONSE, ""));
+
+        let output = client.execute(4, "/help whitelist").await;
+        assert!(output.contains("/whitelist add <targets>"));
+        let output = client.execute(6, "unknown").await;
+        assert_eq!(output, "Unknown command: unknown");
+
+        let response = client.request(8, 200, "").await;
+        assert_eq!(response.body, "Unknown request c8");
+    }
+
+    #[tokio::test]
+    async fn test_failed_logins() {
+        let server = RconServer::bind(
+            "127.0.0.1:0".parse().unwrap(),
+            "secret",
+            false,
+            tick::tasks(),
+        )
+        .await
+        .unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(server.run());
+        let mut client = Client(TcpStream::connect(addr).await.unwrap());
+
+        for id in 0..MAX_FAILED_LOGINS as i32 {
+            let response = client.request(id, SERVERDATA_AUTH, "wrong").await;
+            assert_eq!(response.id, AUTH_FAILURE_ID);
+        }
+        // Even the right password is refused now, from any connection.
+        let mut client = Client(TcpStream::connect(addr).await.unwrap());
+        let response = client.request(10, SERVERDATA_AUTH, "secret").await;
+        assert_eq!(response.id, AUTH_FAILURE_ID);
+    }
+
+    #[test]
+    fn test_passwords_match() {
+        assert!(passwords_match("secret", "secret"));
+        assert!(!passwords_match("secreT", "secret"));
+        assert!(!passwords_match("secret ", "secret"));
+        assert!(!passwords_match("", "secret"));
+    }
+
+    #[test]
+    fn test_split_body() {
+        assert_eq!(split_body(""), [""]);
+        assert_eq!(split_body("list"), ["list"]);
+
+        let output = "é".repeat(MAX_RESPONSE_BODY);
+        let bodies = split_body(&output);
+        assert_eq!(bodies.len(), 2);
+        assert_eq!(bodies[0].len(), MAX_RESPONSE_BODY);
+        assert_eq!(bodies.concat(), output);
+    }
+}
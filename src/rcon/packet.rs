@@ -0,0 +1,103 @@
+//! The packets of the Source RCON protocol: Length (i32), Request Id (i32), Type (i32), then a
+//! null-terminated Body and an empty null-terminated string. Integers are little-endian.
+//! See https://developer.valvesoftware.com/wiki/Source_RCON_Protocol
+
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+use super::RconError;
+
+/// Sent by the clients to log in, the body being the password.
+pub const SERVERDATA_AUTH: i32 = 3;
+/// Sent by the clients to execute the command in the body.
+pub const SERVERDATA_EXECCOMMAND: i32 = 2;
+/// Sent by the server to tell whether a login succeeded.
+pub const SERVERDATA_AUTH_RESPONSE: i32 = 2;
+/// Sent by the server with the output of a command.
+pub const SERVERDATA_RESPONSE_VALUE: i32 = 0;
+
+/// The Request Id of the answer to a failed login.
+pub const AUTH_FAILURE_ID: i32 = -1;
+
+/// The smallest length of a packet: the two integers and the two null bytes.
+const MIN_LENGTH: i32 = 10;
+/// The largest length of a packet sent by the clients.
+const MAX_LENGTH: i32 = 4096;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RconPacket {
+    pub id: i32,
+    pub kind: i32,
+    pub body: String,
+}
+
+impl RconPacket {
+    pub fn new(id: i32, kind: i32, body: impl Into<String>) -> Self {
+        Self {
+            id,
+            kind,
+            body: body.into(),
+        }
+    }
+
+    /// Reads the next packet, or `None` if the stream ended before it.
+    pub async fn read(reader: &mut (impl AsyncRead + Unpin)) -> Result<Option<Self>, RconError> {
+        let mut length = [0; 4];
+        match reader.read_exact(&mut length).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+        let length = i32::from_le_bytes(length);
+        if !(MIN_LENGTH..=MAX_LENGTH).contains(&length) {
+            return Err(RconError::InvalidLength(length));
+        }
+
+        let mut data = vec![0; length as usize];
+        reader.read_exact(&mut data).await?;
+
+        let id = i32::from_le_bytes([data[0], data[1], data[2], data[3]]);
+        let kind = i32::from_le_bytes([data[4], data[5], data[6], data[7]]);
+        let body = &data[8..];
+        let end = body.iter().position(|&b| b == 0).unwrap_or(body.len());
+
+        Ok(Some(Self::new(
+            id,
+            kind,
+            String::from_utf8_lossy(&body[..end]),
+        )))
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        let length = 4 + 4 + self.body.len() + 2;
+        let mut result = Vec::with_capacity(4 + length);
+        result.extend_from_slice(&(length as i32).to_le_bytes());
+        result.extend_from_slice(&self.id.to_le_bytes());
+        result.extend_from_slice(&self.kind.to_le_bytes());
+        result.extend_from_slice(self.body.as_bytes());
+        result.extend_from_slice(&[0, 0]);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_encode_and_read() {
+        let packet = RconPacket::new(7, SERVERDATA_EXECCOMMAND, "list");
+        let bytes = packet.encode();
+        assert_eq!(&bytes[..4], &14i32.to_le_bytes());
+        assert_eq!(&bytes[12..], b"list\0\0");
+
+        let mut reader = bytes.as_slice();
+        assert_eq!(RconPacket::read(&mut reader).await.unwrap(), Some(packet));
+        assert_eq!(RconPacket::read(&mut reader).await.unwrap(), None);
+
+        let mut too_short = &[4, 0, 0, 0, 1, 0, 0, 0][..];
+        assert!(matches!(
+            RconPacket::read(&mut too_short).await,
+            Err(RconError::InvalidLength(4))
+        ));
+    }
+}
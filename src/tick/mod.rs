@@ -0,0 +1,184 @@
+//! The main loop of the server. It ticks 20 times per second on the main thread, and runs the
+//! tasks queued by the other threads, like commands, so that the server's state has one writer.
+
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use log::warn;
+use once_cell::sync::Lazy;
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use tokio::time::Instant;
+
+use crate::metrics::METRICS;
+
+/// The number of ticks per second, when the server keeps up.
+pub const TICKS_PER_SECOND: u32 = 20;
+
+/// The time between the start of two ticks.
+pub const TICK_DURATION: Duration = Duration::from_millis(1000 / TICKS_PER_SECOND as u64);
+
+/// How late the loop may fall before skipping the missed ticks, like vanilla.
+const MAX_LATENESS: Duration = Duration::from_secs(2);
+
+/// The number of tick durations kept, like vanilla.
+pub const TICK_TIMES_SAMPLES: usize = 100;
+
+/// Some work to do on the main loop.
+pub type Task = Box<dyn FnOnce() + Send>;
+
+/// The tasks waiting for the next tick.
+static TASKS: Lazy<TaskQueue> = Lazy::new(TaskQueue::new);
+
+/// Set to end the main loop after the current tick.
+static STOPPING: AtomicBool = AtomicBool::new(false);
+
+/// The number of ticks done since the server started.
+static TICK_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// The durations of the last ticks in nanoseconds, the tick `n` at `n % TICK_TIMES_SAMPLES`.
+static TICK_TIMES: [AtomicU64; TICK_TIMES_SAMPLES] =
+    [const { AtomicU64::new(0) }; TICK_TIMES_SAMPLES];
+
+/// Tasks run in the order they were queued.
+pub struct TaskQueue {
+    sender: UnboundedSender<Task>,
+    receiver: Mutex<UnboundedReceiver<Task>>,
+}
+
+impl TaskQueue {
+    pub fn new() -> Self {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        Self {
+            sender,
+            receiver: Mutex::new(receiver),
+        }
+    }
+
+    /// Queues a task.
+    pub fn push(&self, task: impl FnOnce() + Send + 'static) {
+        // The receiver lives as long as the queue.
+        let _ = self.sender.send(Box::new(task));
+    }
+
+    /// Runs every queued task, returning how many ran.
+    pub fn run_pending(&self) -> usize {
+        let mut receiver = self.receiver.lock().unwrap();
+        let mut count = 0;
+        while let Ok(task) = receiver.try_recv() {
+            task();
+            count += 1;
+        }
+        count
+    }
+}
+
+impl Default for TaskQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Returns the tasks to run on the main loop, at the start of the next tick.
+pub fn tasks() -> &'static TaskQueue {
+    &TASKS
+}
+
+/// Runs the tasks of `queue` every tick, without ticking the world, for the tests of what
+/// queues tasks.
+#[cfg(test)]
+pub async fn run_tasks(queue: &'static TaskQueue) {
+    loop {
+        queue.run_pending();
+        tokio::time::sleep(TICK_DURATION).await;
+    }
+}
+
+/// Asks the main loop to end after the current tick.
+pub fn request_stop() {
+    STOPPING.store(true, Ordering::SeqCst);
+}
+
+/// Runs the main loop until a stop is requested.
+pub async fn run() {
+    let mut next_tick = Instant::now();
+    // The start of the ticks of the last second, to compute the TPS.
+    let mut recent_ticks = VecDeque::with_capacity(TICKS_PER_SECOND as usize + 1);
+
+    while !STOPPING.load(Ordering::SeqCst) {
+        let start = Instant::now();
+        tick();
+        record_tick_time(start.elapsed());
+
+        recent_ticks.push_back(start);
+        if recent_ticks.len() > TICKS_PER_SECOND as usize {
+            let oldest = recent_ticks.pop_front().unwrap();
+            let elapsed = start.duration_since(oldest).as_secs_f64();
+            METRICS.set_tps((TICKS_PER_SECOND as f64 / elapsed).min(TICKS_PER_SECOND as f64));
+        }
+
+        next_tick += TICK_DURATION;
+        let now = Instant::now();
+        if now > next_tick + MAX_LATENESS {
+            let behind = now - next_tick;
+            warn!(
+                "Can't keep up! Is the server overloaded? Running {}ms or {} ticks behind",
+                behind.as_millis(),
+                behind.as_millis() / TICK_DURATION.as_millis()
+            );
+            next_tick = now;
+        }
+        tokio::time::sleep_until(next_tick).await;
+    }
+}
+
+fn record_tick_time(duration: Duration) {
+    METRICS.tick_finished(duration);
+    let count = TICK_COUNT.fetch_add(1, Ordering::Relaxed);
+    TICK_TIMES[count as usize % TICK_TIMES_SAMPLES]
+        .store(duration.as_nanos() as u64, Ordering::Relaxed);
+}
+
+/// Returns the durations of the last ticks in nanoseconds, in no particular order. The slots of
+/// the ticks not done yet are zeros, like vanilla.
+pub fn tick_times() -> Vec<u64> {
+    TICK_TIMES
+        .iter()
+        .map(|time| time.load(Ordering::Relaxed))
+        .collect()
+}
+
+/// Returns the average duration of the last ticks, in milliseconds.
+pub fn average_tick_time() -> f64 {
+    let total: u64 = tick_times().iter().sum();
+    total as f64 / TICK_TIMES_SAMPLES as f64 / 1_000_000.0
+}
+
+/// One tick of the server.
+fn tick() {
+    TASKS.run_pending();
+    crate::world::tick();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_tasks_run_in_order() {
+        let queue = TaskQueue::new();
+        let done = Arc::new(Mutex::new(Vec::new()));
+
+        for i in 0..3 {
+            let done = done.clone();
+            queue.push(move || done.lock().unwrap().push(i));
+        }
+        assert!(done.lock().unwrap().is_empty());
+
+        assert_eq!(queue.run_pending(), 3);
+        assert_eq!(*done.lock().unwrap(), [0, 1, 2]);
+        assert_eq!(queue.run_pending(), 0);
+    }
+}
@@ -0,0 +1,551 @@
+//! The player and IP ban lists, backed by the 'banned-players.json' and 'banned-ips.json' files.
+
+use std::fmt;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Mutex;
+
+use chrono::{DateTime, FixedOffset, Local};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{
+    format_uuid, load_or_recover, read_json_list, write_json_list, UserListError, DATE_FORMAT,
+};
+use crate::consts;
+
+/// The banned players, loaded from 'banned-players.json' the first time it is used.
+pub static BANNED_PLAYERS: Lazy<BanList<PlayerBan>> = Lazy::new(|| {
+    let path = Path::new(consts::filepaths::BANNED_PLAYERS);
+    load_or_recover(path, "player ban list", BanList::load, BanList::empty)
+});
+
+/// The banned IPs, loaded from 'banned-ips.json' the first time it is used.
+pub static BANNED_IPS: Lazy<BanList<IpBan>> = Lazy::new(|| {
+    let path = Path::new(consts::filepaths::BANNED_IP);
+    load_or_recover(path, "IP ban list", BanList::load, BanList::empty)
+});
+
+/// The default ban reason, when none is given.
+pub const DEFAULT_REASON: &str = "Banned by an operator.";
+
+/// The fields shared by both kinds of bans.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct BanInfo {
+    #[serde(with = "super::date_format")]
+    pub created: DateTime<FixedOffset>,
+    pub source: String,
+    /// `None` is a permanent ban, written "forever" in the files.
+    #[serde(with = "expiry_format")]
+    pub expires: Option<DateTime<FixedOffset>>,
+    pub reason: String,
+}
+
+impl BanInfo {
+    /// Creates a ban made now by `source`.
+    pub fn new(source: &str, reason: Option<&str>, expires: Option<DateTime<FixedOffset>>) -> Self {
+        Self {
+            created: Local::now().fixed_offset(),
+            source: source.to_string(),
+            expires,
+            reason: reason.unwrap_or(DEFAULT_REASON).to_string(),
+        }
+    }
+
+    /// Returns whether a temporary ban is over.
+    pub fn is_expired(&self) -> bool {
+        self.expires.is_some_and(|expires| expires <= Local::now())
+    }
+
+    /// Returns the message shown to a client which is refused because of this ban.
+    /// `header` is the first line, like "You are banned from this server.".
+    pub fn message(&self, header: &str) -> String {
+        let mut message = format!("{header}\nReason: {}", self.reason);
+        if let Some(expires) = self.expires {
+            message += &format!(
+                "\nYour ban will be removed on {}",
+                expires.format(DATE_FORMAT)
+            );
+        }
+        message
+    }
+}
+
+/// One entry of 'banned-players.json'.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PlayerBan {
+    pub uuid: String,
+    pub name: String,
+    #[serde(flatten)]
+    pub info: BanInfo,
+}
+
+/// One entry of 'banned-ips.json'. The IP may also be a CIDR range, like "10.0.0.0/8".
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct IpBan {
+    #[serde(with = "ip_range_format")]
+    pub ip: IpRange,
+    #[serde(flatten)]
+    pub info: BanInfo,
+}
+
+/// What both kinds of bans have in common, so that they can share the same list.
+pub trait BanEntry: Clone + Serialize + for<'de> Deserialize<'de> {
+    /// The value that makes the ban unique in its list.
+    fn key(&self) -> String;
+    fn info(&self) -> &BanInfo;
+    /// Makes the entry's key comparable, e.g. the UUID in its hyphenated form.
+    fn normalize(&mut self) {}
+}
+
+impl BanEntry for PlayerBan {
+    fn key(&self) -> String {
+        self.uuid.clone()
+    }
+
+    fn info(&self) -> &BanInfo {
+        &self.info
+    }
+
+    fn normalize(&mut self) {
+        self.uuid = format_uuid(&self.uuid);
+    }
+}
+
+impl BanEntry for IpBan {
+    fn key(&self) -> String {
+        self.ip.to_string()
+    }
+
+    fn info(&self) -> &BanInfo {
+        &self.info
+    }
+}
+
+/// An in-memory ban list, written back to its file by `save`.
+/// Expired bans are ignored, and left out of the file when it is saved.
+pub struct BanList<T: BanEntry> {
+    path: PathBuf,
+    entries: Mutex<Vec<T>>,
+    /// Held while saving, so that an older save never overwrites a newer one.
+    saving: Mutex<()>,
+}
+
+impl<T: BanEntry> BanList<T> {
+    /// Creates an empty list that will be saved to `path`.
+    pub fn empty(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(Vec::new()),
+            saving: Mutex::new(()),
+        }
+    }
+
+    /// Loads the list from `path`.
+    pub fn load(path: &Path) -> Result<Self, UserListError> {
+        let mut entries: Vec<T> = Vec::new();
+
+        for mut entry in read_json_list::<T>(path)? {
+            entry.normalize();
+            let key = entry.key();
+            match entries.iter_mut().find(|e| e.key() == key) {
+                Some(existing) => *existing = entry,
+                None => entries.push(entry),
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            saving: Mutex::new(()),
+        })
+    }
+
+    /// Adds a ban, replacing the previous ban with the same key.
+    pub fn add(&self, entry: T) {
+        let mut entry = entry;
+        entry.normalize();
+        let key = entry.key();
+
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter_mut().find(|e| e.key() == key) {
+            Some(existing) => *existing = entry,
+            None => entries.push(entry),
+        }
+    }
+
+    /// Removes a ban given its key. Returns `false` if there was no such ban.
+    pub fn remove(&self, key: &str) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        let len_before = entries.len();
+        entries.retain(|e| e.key() != key);
+        entries.len() != len_before
+    }
+
+    /// Returns the first ban matching `predicate` which is still in effect.
+    pub fn find(&self, predicate: impl Fn(&T) -> bool) -> Option<T> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|e| !e.info().is_expired() && predicate(e))
+            .cloned()
+    }
+
+    /// Returns a copy of the bans still in effect.
+    pub fn entries(&self) -> Vec<T> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .filter(|e| !e.info().is_expired())
+            .cloned()
+            .collect()
+    }
+
+    /// Returns the number of bans, expired ones included.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+
+    /// Writes the bans still in effect to the list's file, with the entries it has when the
+    /// previous save is done. The expired bans are then dropped.
+    pub fn save(&self) -> Result<(), UserListError> {
+        let _saving = self.saving.lock().unwrap();
+        let entries = {
+            let mut entries = self.entries.lock().unwrap();
+            entries.retain(|e| !e.info().is_expired());
+            entries.clone()
+        };
+        write_json_list(&self.path, &entries)
+    }
+}
+
+impl BanList<PlayerBan> {
+    /// Returns the ban of a player given their UUID.
+    pub fn get(&self, uuid: &str) -> Option<PlayerBan> {
+        let uuid = format_uuid(uuid);
+        self.find(|ban| ban.uuid == uuid)
+    }
+
+    /// Returns the ban of a player given their name, ignoring case.
+    pub fn get_by_name(&self, name: &str) -> Option<PlayerBan> {
+        self.find(|ban| ban.name.eq_ignore_ascii_case(name))
+    }
+}
+
+impl BanList<IpBan> {
+    /// Returns the ban covering an IP address, either directly or through a range.
+    pub fn get(&self, ip: IpAddr) -> Option<IpBan> {
+        self.find(|ban| ban.ip.contains(ip))
+    }
+}
+
+/// An IP address, or a range of addresses in CIDR notation.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct IpRange {
+    addr: IpAddr,
+    prefix: u8,
+}
+
+impl IpRange {
+    /// Creates the range of the first `prefix` bits of `addr`. The other bits are cleared,
+    /// so that "10.0.0.1/8" and "10.0.0.0/8" are the same range.
+    fn new(addr: IpAddr, prefix: u8) -> Self {
+        let addr = match addr {
+            IpAddr::V4(addr) => {
+                let mask = u32::MAX.checked_shl(32 - prefix as u32).unwrap_or(0);
+                IpAddr::V4((u32::from(addr) & mask).into())
+            }
+            IpAddr::V6(addr) => {
+                let mask = u128::MAX.checked_shl(128 - prefix as u32).unwrap_or(0);
+                IpAddr::V6((u128::from(addr) & mask).into())
+            }
+        };
+        Self { addr, prefix }
+    }
+
+    /// Returns whether `ip` is part of the range.
+    pub fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        ip.is_ipv4() == self.addr.is_ipv4() && Self::new(ip, self.prefix).addr == self.addr
+    }
+
+    fn max_prefix(addr: IpAddr) -> u8 {
+        match addr {
+            IpAddr::V4(_) => 32,
+            IpAddr::V6(_) => 128,
+        }
+    }
+}
+
+impl From<IpAddr> for IpRange {
+    fn from(addr: IpAddr) -> Self {
+        let addr = addr.to_canonical();
+        Self {
+            addr,
+            prefix: Self::max_prefix(addr),
+        }
+    }
+}
+
+impl FromStr for IpRange {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid IP address or range: {s}");
+
+        let (addr, prefix) = match s.split_once('/') {
+            Some((addr, prefix)) => (addr, Some(prefix)),
+            None => (s, None),
+        };
+        let addr = addr.trim().parse::<IpAddr>().map_err(|_| invalid())?;
+        let max_prefix = Self::max_prefix(addr);
+
+        let prefix = match prefix {
+            Some(prefix) => prefix.trim().parse::<u8>().map_err(|_| invalid())?,
+            None => max_prefix,
+        };
+        if prefix > max_prefix {
+            return Err(invalid());
+        }
+
+        // An IPv4-mapped IPv6 range is the IPv4 range it maps, like the addresses it contains.
+        match addr.to_canonical() {
+            IpAddr::V4(v4) if addr.is_ipv6() && prefix >= 96 => {
+                Ok(Self::new(IpAddr::V4(v4), prefix - 96))
+            }
+            _ => Ok(Self::new(addr, prefix)),
+        }
+    }
+}
+
+impl fmt::Display for IpRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.prefix == Self::max_prefix(self.addr) {
+            write!(f, "{}", self.addr)
+        } else {
+            write!(f, "{}/{}", self.addr, self.prefix)
+        }
+    }
+}
+
+/// Parses a ban duration like "30m", "12h", "7d" or "2w".
+pub fn parse_duration(s: &str) -> Option<chrono::Duration> {
+    let (split, _) = s.char_indices().last()?;
+    let (amount, unit) = s.split_at(split);
+    let amount = amount.parse::<i64>().ok().filter(|a| *a > 0)?;
+
+    match unit {
+        "s" => chrono::Duration::try_seconds(amount),
+        "m" => chrono::Duration::try_minutes(amount),
+        "h" => chrono::Duration::try_hours(amount),
+        "d" => chrono::Duration::try_days(amount),
+        "w" => chrono::Duration::try_weeks(amount),
+        _ => None,
+    }
+}
+
+mod expiry_format {
+    use crate::user_lists::DATE_FORMAT;
+    use chrono::{DateTime, FixedOffset};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    const FOREVER: &str = "forever";
+
+    pub fn serialize<S: Serializer>(
+        date: &Option<DateTime<FixedOffset>>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match date {
+            Some(date) => serializer.serialize_str(&date.format(DATE_FORMAT).to_string()),
+            None => serializer.serialize_str(FOREVER),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<DateTime<FixedOffset>>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        if s == FOREVER {
+            return Ok(None);
+        }
+        DateTime::parse_from_str(&s, DATE_FORMAT)
+            .map(Some)
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+mod ip_range_format {
+    use super::IpRange;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(ip: &IpRange, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&ip.to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<IpRange, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    fn player_ban(expires: Option<DateTime<FixedOffset>>) -> PlayerBan {
+        PlayerBan {
+            uuid: NOTCH.to_string(),
+            name: "Notch".to_string(),
+            info: BanInfo::new("Server", None, expires),
+        }
+    }
+
+    #[test]
+    fn test_vanilla_format() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("banned-players.json");
+        fs::write(
+            &path,
+            r#"[{
+                "uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5",
+                "name": "Notch",
+                "created": "2024-08-31 14:02:51 +0200",
+                "source": "Server",
+                "expires": "forever",
+                "reason": "Griefing"
+            }]"#,
+        )?;
+
+        let bans = BanList::<PlayerBan>::load(&path)?;
+        let ban = bans.get_by_name("notch").unwrap();
+        assert_eq!(ban.info.reason, "Griefing");
+        assert_eq!(ban.info.expires, None);
+        assert_eq!(ban.info.created.to_rfc3339(), "2024-08-31T14:02:51+02:00");
+
+        // Saving must keep the vanilla format.
+        bans.add(ban);
+        bans.save()?;
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?).unwrap();
+        assert_eq!(json[0]["created"], "2024-08-31 14:02:51 +0200");
+        assert_eq!(json[0]["expires"], "forever");
+        assert_eq!(json[0]["uuid"], NOTCH);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_temporary_ban_expires() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("banned-players.json");
+        let bans = BanList::<PlayerBan>::load(&path)?;
+
+        let future = Local::now().fixed_offset() + chrono::Duration::hours(1);
+        bans.add(player_ban(Some(future)));
+        bans.save()?;
+        assert!(bans.get(NOTCH).is_some());
+        assert!(bans
+            .get(NOTCH)
+            .unwrap()
+            .info
+            .message("You are banned from this server.")
+            .contains("Your ban will be removed on"));
+
+        let past = Local::now().fixed_offset() - chrono::Duration::seconds(1);
+        bans.add(player_ban(Some(past)));
+        assert!(bans.get(NOTCH).is_none());
+        assert!(bans.entries().is_empty());
+
+        // Looking at the bans never writes, the lifted ban leaves the file with the next save.
+        assert_eq!(BanList::<PlayerBan>::load(&path)?.len(), 1);
+        bans.save()?;
+        assert_eq!(BanList::<PlayerBan>::load(&path)?.len(), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_ip_ranges() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let bans = BanList::<IpBan>::load(&dir.path().join("banned-ips.json"))?;
+
+        let ban = |ip: &str| IpBan {
+            ip: ip.parse().unwrap(),
+            info: BanInfo::new("Server", Some("Spam"), None),
+        };
+        bans.add(ban("10.0.0.0/8"));
+        bans.add(ban("192.168.1.20"));
+        bans.add(ban("2001:db8::/32"));
+
+        assert!(bans.get("10.20.30.40".parse().unwrap()).is_some());
+        assert!(bans.get("11.0.0.1".parse().unwrap()).is_none());
+        assert!(bans.get("192.168.1.20".parse().unwrap()).is_some());
+        assert!(bans.get("192.168.1.21".parse().unwrap()).is_none());
+        assert!(bans.get("2001:db8:1::1".parse().unwrap()).is_some());
+        // IPv4 clients seen through an IPv6 socket.
+        assert!(bans.get("::ffff:10.1.1.1".parse().unwrap()).is_some());
+
+        assert!(bans.remove("10.0.0.0/8"));
+        assert!(bans.get("10.20.30.40".parse().unwrap()).is_none());
+
+        // A range is stored as its network address, whatever host bits it was written with.
+        bans.add(ban("10.0.0.1/8"));
+        bans.add(ban("10.0.0.0/8"));
+        assert_eq!(bans.entries().len(), 3);
+        assert_eq!(
+            "10.0.0.1/8".parse::<IpRange>().unwrap().to_string(),
+            "10.0.0.0/8"
+        );
+        assert!(bans.remove("10.0.0.0/8"));
+        assert!(bans.get("10.20.30.40".parse().unwrap()).is_none());
+
+        // Written like the IPv4 range it maps.
+        let mapped: IpRange = "::ffff:172.16.0.0/108".parse().unwrap();
+        assert_eq!(mapped, "172.16.0.0/12".parse().unwrap());
+        assert_eq!(mapped.to_string(), "172.16.0.0/12");
+        assert!(mapped.contains("172.20.1.1".parse().unwrap()));
+        assert!(mapped.contains("::ffff:172.20.1.1".parse().unwrap()));
+        assert_eq!(
+            "::ffff:10.0.0.1".parse::<IpRange>().unwrap().to_string(),
+            "10.0.0.1"
+        );
+
+        assert!("10.0.0.0/33".parse::<IpRange>().is_err());
+        assert!("not an ip".parse::<IpRange>().is_err());
+        assert_eq!(
+            "0.0.0.0/0".parse::<IpRange>().unwrap().to_string(),
+            "0.0.0.0/0"
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_unreadable_file_is_not_overwritten() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("banned-players.json");
+        let bans = BanList::<PlayerBan>::load(&path)?;
+        bans.add(player_ban(None));
+        bans.save()?;
+
+        fs::write(&path, "{")?;
+        assert!(bans.remove(NOTCH));
+        assert!(bans.save().is_err());
+        assert_eq!(fs::read_to_string(&path)?, "{");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("30m"), Some(chrono::Duration::minutes(30)));
+        assert_eq!(parse_duration("7d"), Some(chrono::Duration::days(7)));
+        assert_eq!(parse_duration("Griefing"), None);
+        assert_eq!(parse_duration("0h"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+}
@@ -0,0 +1,214 @@
+//! This module manages the JSON "user lists" stored next to the server binary, like 'ops.json'.
+//! Each list is kept in memory, and written back to disk by what changes it: the commands save
+//! them on the I/O thread. A file that can't be read is never overwritten: a malformed one is
+//! moved aside at startup, and the others are left as they are.
+
+pub mod bans;
+pub mod ops;
+pub mod usercache;
+pub mod whitelist;
+
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+use log::{error, warn};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tempfile::NamedTempFile;
+use thiserror::Error;
+
+use crate::config::Settings;
+
+/// The date format of the vanilla JSON files, like "2024-08-31 14:02:51 +0200".
+pub(crate) const DATE_FORMAT: &str = "%Y-%m-%d %H:%M:%S %z";
+
+/// Loads every user list so that problems with the files are reported at startup, and turns the
+/// whitelist on according to `settings`.
+pub fn init(settings: &Settings) {
+    ops::OPERATORS.len();
+    bans::BANNED_PLAYERS.len();
+    bans::BANNED_IPS.len();
+    usercache::USERCACHE.len();
+    whitelist::WHITELIST.set_enabled(settings.white_list);
+    whitelist::WHITELIST.set_enforced(settings.enforce_whitelist);
+}
+
+/// Reads a JSON array of entries from `path`.
+///
+/// A missing or empty file is treated as an empty list, as the server creates these files empty.
+pub(crate) fn read_json_list<T: DeserializeOwned>(path: &Path) -> Result<Vec<T>, UserListError> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    if content.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    serde_json::from_str(&content).map_err(|source| UserListError::Malformed {
+        path: path.to_string_lossy().to_string(),
+        source,
+    })
+}
+
+/// Loads a list with `load`, or returns the `empty` one if the file can't be read. A malformed
+/// file is first moved aside, so that the next change doesn't overwrite what the user wrote.
+pub(crate) fn load_or_recover<L>(
+    path: &Path,
+    what: &str,
+    load: impl FnOnce(&Path) -> Result<L, UserListError>,
+    empty: impl FnOnce(&Path) -> L,
+) -> L {
+    match load(path) {
+        Ok(list) => list,
+        Err(e @ UserListError::Malformed { .. }) => match move_aside(path) {
+            Ok(backup) => {
+                warn!(
+                    "Failed to load the {what}, starting with an empty one: {e}. The file was \
+                     moved to '{}'",
+                    backup.display()
+                );
+                empty(path)
+            }
+            Err(move_error) => {
+                error!(
+                    "Failed to load the {what}: {e}. It could not be moved aside \
+                     ({move_error}), so it won't be saved until it is fixed"
+                );
+                empty(path)
+            }
+        },
+        Err(e) => {
+            error!("Failed to load the {what}: {e}. It won't be saved until it can be read");
+            empty(path)
+        }
+    }
+}
+
+/// Renames a file that can't be read to '<name>.<date>.corrupt', next to it.
+fn move_aside(path: &Path) -> io::Result<PathBuf> {
+    let date = chrono::Local::now().format("%Y-%m-%d_%H.%M.%S");
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(format!(".{date}.corrupt"));
+    let backup = path.with_file_name(name);
+    fs::rename(path, &backup)?;
+    Ok(backup)
+}
+
+/// Writes `entries` as a pretty JSON array to `path`.
+///
+/// The content is first written to a temporary file in the same directory, which then replaces
+/// `path`, so a crash never leaves a half-written list behind. The current file must be a valid
+/// list, it is never replaced otherwise.
+pub(crate) fn write_json_list<T: Serialize + DeserializeOwned>(
+    path: &Path,
+    entries: &[T],
+) -> Result<(), UserListError> {
+    if let Err(e) = read_json_list::<T>(path) {
+        return Err(UserListError::Unreadable {
+            path: path.to_string_lossy().to_string(),
+            source: Box::new(e),
+        });
+    }
+
+    let json =
+        serde_json::to_string_pretty(entries).map_err(|source| UserListError::Malformed {
+            path: path.to_string_lossy().to_string(),
+            source,
+        })?;
+
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+
+    let mut file = NamedTempFile::new_in(dir)?;
+    file.write_all(json.as_bytes())?;
+    file.as_file().sync_all()?;
+    file.persist(path).map_err(|e| e.error)?;
+
+    Ok(())
+}
+
+/// Returns the UUID in its hyphenated lowercase form, the one used by the vanilla JSON files.
+///
+/// The Mojang API returns UUIDs without hyphens, so both forms are accepted. Anything else is
+/// only lowercased.
+pub fn format_uuid(uuid: &str) -> String {
+    let hex: String = uuid
+        .chars()
+        .filter(|c| *c != '-')
+        .collect::<String>()
+        .to_lowercase();
+
+    if hex.len() != 32 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return uuid.to_lowercase();
+    }
+
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// (De)serializes dates with `DATE_FORMAT`.
+pub(crate) mod date_format {
+    use super::DATE_FORMAT;
+    use chrono::{DateTime, FixedOffset};
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        date: &DateTime<FixedOffset>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&date.format(DATE_FORMAT).to_string())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<FixedOffset>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        DateTime::parse_from_str(&s, DATE_FORMAT).map_err(serde::de::Error::custom)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum UserListError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed JSON in '{path}': {source}")]
+    Malformed {
+        path: String,
+        source: serde_json::Error,
+    },
+    #[error("Refusing to overwrite '{path}', which can't be read: {source}")]
+    Unreadable {
+        path: String,
+        source: Box<UserListError>,
+    },
+    #[error("Invalid operator level {0}, it must be between 1 and 4")]
+    InvalidLevel(u8),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_uuid() {
+        let notch = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+        assert_eq!(format_uuid("069A79F444E94726A5BEFCA90E38AAF5"), notch);
+        assert_eq!(format_uuid(notch), notch);
+        assert_eq!(format_uuid("Not-A-UUID"), "not-a-uuid");
+        // 32 bytes, but not 32 hexadecimal digits.
+        let accented = "é".repeat(16);
+        assert_eq!(format_uuid(&accented), accented);
+    }
+}
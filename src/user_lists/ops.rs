@@ -0,0 +1,377 @@
+//! The operator list, backed by the 'ops.json' file.
+
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use log::warn;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{format_uuid, load_or_recover, read_json_list, write_json_list, UserListError};
+use crate::consts;
+
+/// The server's operator list, loaded from 'ops.json' the first time it is used.
+pub static OPERATORS: Lazy<OperatorList> = Lazy::new(|| {
+    let path = Path::new(consts::filepaths::OPERATORS);
+    load_or_recover(
+        path,
+        "operator list",
+        OperatorList::load,
+        OperatorList::empty,
+    )
+});
+
+/// The permission levels an operator may have.
+pub const LEVELS: std::ops::RangeInclusive<u8> = 1..=4;
+
+/// One entry of 'ops.json'.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct OperatorEntry {
+    pub uuid: String,
+    pub name: String,
+    pub level: u8,
+    #[serde(rename = "bypassesPlayerLimit")]
+    pub bypasses_player_limit: bool,
+}
+
+/// What happened when adding an operator.
+#[derive(Debug, PartialEq, Eq)]
+pub enum OpChange {
+    Added,
+    /// The player already was an operator, but with another level or player limit bypass.
+    Updated,
+    /// The player already was an operator with the exact same settings.
+    Unchanged,
+}
+
+/// An in-memory operator list, written back to its file by `save`.
+pub struct OperatorList {
+    path: PathBuf,
+    entries: Mutex<Vec<OperatorEntry>>,
+    /// Held while saving, so that an older save never overwrites a newer one.
+    saving: Mutex<()>,
+}
+
+impl OperatorList {
+    /// Creates an empty list that will be saved to `path`.
+    pub fn empty(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(Vec::new()),
+            saving: Mutex::new(()),
+        }
+    }
+
+    /// Loads the list from `path`. Duplicated UUIDs are merged, keeping the last entry, and the
+    /// entries with an invalid level are left out.
+    pub fn load(path: &Path) -> Result<Self, UserListError> {
+        let mut entries: Vec<OperatorEntry> = Vec::new();
+
+        for mut entry in read_json_list::<OperatorEntry>(path)? {
+            if !LEVELS.contains(&entry.level) {
+                warn!(
+                    "Ignoring the operator {} of '{}': {}",
+                    entry.name,
+                    path.display(),
+                    UserListError::InvalidLevel(entry.level)
+                );
+                continue;
+            }
+            entry.uuid = format_uuid(&entry.uuid);
+            match entries.iter_mut().find(|e| e.uuid == entry.uuid) {
+                Some(existing) => *existing = entry,
+                None => entries.push(entry),
+            }
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(entries),
+            saving: Mutex::new(()),
+        })
+    }
+
+    /// Adds an operator, or updates its level and player limit bypass if it already is one.
+    pub fn add(&self, entry: OperatorEntry) -> Result<OpChange, UserListError> {
+        let mut entry = entry;
+        entry.uuid = format_uuid(&entry.uuid);
+        if !LEVELS.contains(&entry.level) {
+            return Err(UserListError::InvalidLevel(entry.level));
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter_mut().find(|e| e.uuid == entry.uuid) {
+            Some(existing) if *existing == entry => Ok(OpChange::Unchanged),
+            Some(existing) => {
+                *existing = entry;
+                Ok(OpChange::Updated)
+            }
+            None => {
+                entries.push(entry);
+                Ok(OpChange::Added)
+            }
+        }
+    }
+
+    /// Removes an operator. Returns `false` if the player was not an operator.
+    pub fn remove(&self, uuid: &str) -> bool {
+        let uuid = format_uuid(uuid);
+
+        let mut entries = self.entries.lock().unwrap();
+        let len_before = entries.len();
+        entries.retain(|e| e.uuid != uuid);
+        entries.len() != len_before
+    }
+
+    /// Writes the list to its file, with the entries it has when the previous save is done.
+    pub fn save(&self) -> Result<(), UserListError> {
+        let _saving = self.saving.lock().unwrap();
+        let entries = self.entries.lock().unwrap().clone();
+        write_json_list(&self.path, &entries)
+    }
+
+    /// Returns the entry of an operator given its UUID.
+    pub fn get(&self, uuid: &str) -> Option<OperatorEntry> {
+        let uuid = format_uuid(uuid);
+        let entries = self.entries.lock().unwrap();
+        entries.iter().find(|e| e.uuid == uuid).cloned()
+    }
+
+    /// Returns the entry of an operator given its name, ignoring case.
+    pub fn get_by_name(&self, name: &str) -> Option<OperatorEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Returns the permission level of a player, 0 if they are not an operator.
+    pub fn level_of(&self, uuid: &str) -> u8 {
+        self.get(uuid).map_or(0, |e| e.level)
+    }
+
+    /// Returns a copy of all the entries.
+    pub fn entries(&self) -> Vec<OperatorEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Returns the number of operators.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::sync::Arc;
+    use std::thread;
+    use tempfile::TempDir;
+
+    fn entry(uuid: &str, name: &str, level: u8) -> OperatorEntry {
+        OperatorEntry {
+            uuid: uuid.to_string(),
+            name: name.to_string(),
+            level,
+            bypasses_player_limit: false,
+        }
+    }
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+
+    #[test]
+    fn test_add_update_remove() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("ops.json");
+        let ops = OperatorList::load(&path)?;
+
+        assert_eq!(ops.add(entry(NOTCH, "Notch", 4))?, OpChange::Added);
+        assert_eq!(ops.add(entry(NOTCH, "Notch", 4))?, OpChange::Unchanged);
+        assert_eq!(ops.add(entry(NOTCH, "Notch", 2))?, OpChange::Updated);
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops.level_of(NOTCH), 2);
+
+        // The Mojang API form of the UUID must be detected as a duplicate.
+        let hyphenless = NOTCH.replace('-', "");
+        assert_eq!(
+            ops.add(entry(&hyphenless, "Notch", 2))?,
+            OpChange::Unchanged
+        );
+        assert_eq!(ops.get_by_name("notch").unwrap().uuid, NOTCH);
+
+        assert!(ops.remove(NOTCH));
+        assert!(!ops.remove(NOTCH));
+        assert_eq!(ops.level_of(NOTCH), 0);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_persisted_format() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("ops.json");
+        let ops = OperatorList::load(&path)?;
+        ops.add(entry(NOTCH, "Notch", 3))?;
+        ops.save()?;
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([{
+                "uuid": NOTCH,
+                "name": "Notch",
+                "level": 3,
+                "bypassesPlayerLimit": false
+            }])
+        );
+
+        let reloaded = OperatorList::load(&path)?;
+        assert_eq!(reloaded.entries(), ops.entries());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_merges_duplicates() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("ops.json");
+        fs::write(
+            &path,
+            r#"[
+                {"uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "name": "Notch", "level": 4, "bypassesPlayerLimit": true},
+                {"uuid": "069A79F444E94726A5BEFCA90E38AAF5", "name": "Notch", "level": 1, "bypassesPlayerLimit": false}
+            ]"#,
+        )?;
+
+        let ops = OperatorList::load(&path)?;
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops.level_of(NOTCH), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_load_empty_and_malformed_files() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("ops.json");
+
+        // Files created by `fs_manager::create_other_files` are empty.
+        fs::write(&path, "")?;
+        assert_eq!(OperatorList::load(&path)?.len(), 0);
+
+        fs::write(&path, "[{\"uuid\": ")?;
+        assert!(matches!(
+            OperatorList::load(&path),
+            Err(UserListError::Malformed { .. })
+        ));
+
+        // Missing fields are malformed too.
+        fs::write(
+            &path,
+            r#"[{"uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5"}]"#,
+        )?;
+        assert!(matches!(
+            OperatorList::load(&path),
+            Err(UserListError::Malformed { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_levels() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let ops = OperatorList::load(&dir.path().join("ops.json"))?;
+        for level in [0, 5] {
+            assert!(matches!(
+                ops.add(entry(NOTCH, "Notch", level)),
+                Err(UserListError::InvalidLevel(_))
+            ));
+        }
+        assert_eq!(ops.len(), 0);
+
+        // A hand-edited file does not grant them either.
+        let path = dir.path().join("edited.json");
+        fs::write(
+            &path,
+            r#"[
+                {"uuid": "069a79f4-44e9-4726-a5be-fca90e38aaf5", "name": "Notch", "level": 9, "bypassesPlayerLimit": false},
+                {"uuid": "853c80ef-3c37-49fd-aa49-938b674adae6", "name": "jeb_", "level": 0, "bypassesPlayerLimit": false},
+                {"uuid": "61699b2e-d327-4a01-9f1e-0ea8c3f06bc6", "name": "Dinnerbone", "level": 3, "bypassesPlayerLimit": false}
+            ]"#,
+        )?;
+        let ops = OperatorList::load(&path)?;
+        assert_eq!(ops.len(), 1);
+        assert_eq!(ops.level_of(NOTCH), 0);
+        assert_eq!(ops.level_of("61699b2e-d327-4a01-9f1e-0ea8c3f06bc6"), 3);
+        Ok(())
+    }
+
+    #[test]
+    fn test_malformed_file_is_kept() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("ops.json");
+        fs::write(&path, "[{\"uuid\": ")?;
+
+        // Moved aside, so the next change starts from an empty file.
+        let ops = load_or_recover(
+            &path,
+            "operator list",
+            OperatorList::load,
+            OperatorList::empty,
+        );
+        assert_eq!(ops.len(), 0);
+        assert!(!path.exists());
+        let backups: Vec<_> = fs::read_dir(dir.path())?.collect::<Result<_, _>>()?;
+        assert_eq!(backups.len(), 1);
+        let backup = backups[0].path();
+        assert!(backup.to_string_lossy().ends_with(".corrupt"));
+        assert_eq!(fs::read_to_string(&backup)?, "[{\"uuid\": ");
+        ops.add(entry(NOTCH, "Notch", 4))?;
+        ops.save()?;
+        assert_eq!(OperatorList::load(&path)?.len(), 1);
+
+        // A file that can't be read is never overwritten.
+        fs::write(&path, "not json")?;
+        ops.add(entry(NOTCH, "Notch", 2))?;
+        assert!(matches!(ops.save(), Err(UserListError::Unreadable { .. })));
+        assert_eq!(fs::read_to_string(&path)?, "not json");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_concurrent_edits() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("ops.json");
+        let ops = Arc::new(OperatorList::load(&path)?);
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let ops = Arc::clone(&ops);
+                thread::spawn(move || {
+                    for i in 0..16 {
+                        let uuid = format!("00000000-0000-0000-0000-{:06}{:06}", t, i);
+                        ops.add(entry(&uuid, &format!("p{t}_{i}"), 4)).unwrap();
+                        if i % 2 == 0 {
+                            ops.remove(&uuid);
+                        }
+                        ops.save().unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        assert_eq!(ops.len(), 8 * 8);
+        let reloaded = OperatorList::load(&path)?;
+        assert_eq!(reloaded.len(), 8 * 8);
+
+        Ok(())
+    }
+}
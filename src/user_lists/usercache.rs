@@ -0,0 +1,223 @@
+//! The user cache, backed by the 'usercache.json' file. It remembers the UUID of every player
+//! seen recently, so that names can be resolved without asking the Mojang API.
+
+use std::cmp::Reverse;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use chrono::{DateTime, FixedOffset, Local, Months};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::{format_uuid, load_or_recover, read_json_list, write_json_list, UserListError};
+use crate::consts;
+
+/// The server's user cache, loaded from 'usercache.json' the first time it is used.
+pub static USERCACHE: Lazy<UserCache> = Lazy::new(|| {
+    let path = Path::new(consts::filepaths::USERCACHE);
+    load_or_recover(
+        path,
+        "user cache",
+        |path| UserCache::load(path, MAX_ENTRIES),
+        |path| UserCache::empty(path, MAX_ENTRIES),
+    )
+});
+
+/// The number of players remembered, like vanilla.
+const MAX_ENTRIES: usize = 1000;
+
+/// How long an entry stays valid after it was last updated.
+const EXPIRATION: Months = Months::new(1);
+
+/// One entry of 'usercache.json'.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct UserCacheEntry {
+    pub name: String,
+    pub uuid: String,
+    #[serde(rename = "expiresOn", with = "super::date_format")]
+    pub expires_on: DateTime<FixedOffset>,
+}
+
+impl UserCacheEntry {
+    fn is_expired(&self) -> bool {
+        self.expires_on <= Local::now()
+    }
+}
+
+/// An in-memory user cache which is written back to its file after every change.
+/// Entries are kept from the most to the least recently updated.
+pub struct UserCache {
+    path: PathBuf,
+    max_entries: usize,
+    entries: Mutex<Vec<UserCacheEntry>>,
+}
+
+impl UserCache {
+    /// Creates an empty cache, holding up to `max_entries` players, that will be saved to `path`.
+    pub fn empty(path: &Path, max_entries: usize) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            max_entries,
+            entries: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Loads the cache from `path`. Expired entries are dropped.
+    pub fn load(path: &Path, max_entries: usize) -> Result<Self, UserListError> {
+        let mut entries: Vec<UserCacheEntry> = read_json_list(path)?;
+        entries.retain(|e| !e.is_expired());
+        for entry in entries.iter_mut() {
+            entry.uuid = format_uuid(&entry.uuid);
+        }
+
+        // The most recently updated entries expire last.
+        entries.sort_by_key(|e| Reverse(e.expires_on));
+        entries.truncate(max_entries);
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_entries,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Remembers a player, e.g. when they log in. Their previous entry, if any, is replaced.
+    /// Only profiles confirmed by Mojang belong here, never the ones claimed by clients.
+    pub fn update(&self, uuid: &str, name: &str) -> Result<(), UserListError> {
+        let uuid = format_uuid(uuid);
+        let entry = UserCacheEntry {
+            name: name.to_string(),
+            uuid: uuid.clone(),
+            expires_on: Local::now().fixed_offset() + EXPIRATION,
+        };
+
+        let mut entries = self.entries.lock().unwrap();
+        // A name can move to another account, so both are matched.
+        let mut updated: Vec<UserCacheEntry> = entries
+            .iter()
+            .filter(|e| e.uuid != uuid && !e.name.eq_ignore_ascii_case(name))
+            .cloned()
+            .collect();
+        updated.insert(0, entry);
+        updated.truncate(self.max_entries);
+
+        write_json_list(&self.path, &updated)?;
+        *entries = updated;
+        Ok(())
+    }
+
+    /// Returns the entry of a player given their name, ignoring case.
+    pub fn get_by_name(&self, name: &str) -> Option<UserCacheEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name) && !e.is_expired())
+            .cloned()
+    }
+
+    /// Returns the entry of a player given their UUID.
+    pub fn get_by_uuid(&self, uuid: &str) -> Option<UserCacheEntry> {
+        let uuid = format_uuid(uuid);
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|e| e.uuid == uuid && !e.is_expired())
+            .cloned()
+    }
+
+    /// Returns a copy of all the entries, including the expired ones.
+    pub fn entries(&self) -> Vec<UserCacheEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Returns the number of entries.
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+    const JEB: &str = "853c80ef-3c37-49fd-aa49-938b674adae6";
+
+    #[test]
+    fn test_update_and_lookup() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("usercache.json");
+        let cache = UserCache::load(&path, 10)?;
+
+        cache.update(&NOTCH.replace('-', ""), "Notch")?;
+        assert_eq!(cache.get_by_name("notch").unwrap().uuid, NOTCH);
+        assert_eq!(cache.get_by_uuid(NOTCH).unwrap().name, "Notch");
+
+        // Renamed account.
+        cache.update(NOTCH, "NotNotch")?;
+        assert!(cache.get_by_name("Notch").is_none());
+        assert_eq!(cache.get_by_name("NotNotch").unwrap().uuid, NOTCH);
+        assert_eq!(cache.len(), 1);
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?).unwrap();
+        assert_eq!(json[0]["name"], "NotNotch");
+        assert_eq!(json[0]["uuid"], NOTCH);
+        assert!(json[0]["expiresOn"].is_string());
+
+        // A file that can't be read is left alone.
+        fs::write(&path, "[")?;
+        assert!(cache.update(JEB, "jeb_").is_err());
+        assert!(cache.get_by_name("jeb_").is_none());
+        assert_eq!(fs::read_to_string(&path)?, "[");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_max_entries() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("usercache.json");
+        let cache = UserCache::load(&path, 3)?;
+
+        for i in 0..5 {
+            cache.update(
+                &format!("00000000-0000-0000-0000-{i:012}"),
+                &format!("player{i}"),
+            )?;
+        }
+
+        // Only the 3 most recent players are kept.
+        assert_eq!(cache.len(), 3);
+        assert!(cache.get_by_name("player1").is_none());
+        assert!(cache.get_by_name("player4").is_some());
+        let names = |cache: &UserCache| -> Vec<String> {
+            cache.entries().into_iter().map(|e| e.name).collect()
+        };
+        assert_eq!(names(&UserCache::load(&path, 3)?), names(&cache));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_expired_entries() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("usercache.json");
+        fs::write(
+            &path,
+            format!(
+                r#"[
+                    {{"name": "Notch", "uuid": "{NOTCH}", "expiresOn": "2000-01-01 00:00:00 +0000"}},
+                    {{"name": "jeb_", "uuid": "{JEB}", "expiresOn": "2999-01-01 00:00:00 +0000"}}
+                ]"#
+            ),
+        )?;
+
+        let cache = UserCache::load(&path, 10)?;
+        assert!(cache.get_by_name("Notch").is_none());
+        assert_eq!(cache.get_by_name("jeb_").unwrap().uuid, JEB);
+
+        Ok(())
+    }
+}
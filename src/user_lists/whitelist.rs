@@ -0,0 +1,254 @@
+//! The whitelist, backed by the 'whitelist.json' file.
+
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use log::info;
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use super::ops::{OperatorList, OPERATORS};
+use super::{format_uuid, load_or_recover, read_json_list, write_json_list, UserListError};
+use crate::consts;
+use crate::net::online::ONLINE_PLAYERS;
+
+/// The server's whitelist, loaded from 'whitelist.json' the first time it is used.
+/// It is off until `user_lists::init` applies 'server.properties'.
+pub static WHITELIST: Lazy<Whitelist> = Lazy::new(|| {
+    let path = Path::new(consts::filepaths::WHITELIST);
+    load_or_recover(path, "whitelist", Whitelist::load, Whitelist::empty)
+});
+
+/// One entry of 'whitelist.json'.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct WhitelistEntry {
+    pub uuid: String,
+    pub name: String,
+}
+
+/// An in-memory whitelist, written back to its file by `save`.
+pub struct Whitelist {
+    path: PathBuf,
+    entries: Mutex<Vec<WhitelistEntry>>,
+    /// Held while saving, so that an older save never overwrites a newer one.
+    saving: Mutex<()>,
+    /// 'white-list': only the whitelisted players and the operators may join.
+    enabled: AtomicBool,
+    /// 'enforce-whitelist': the players are kicked when they are no longer allowed.
+    enforced: AtomicBool,
+}
+
+impl Whitelist {
+    /// Creates an empty whitelist that will be saved to `path`.
+    pub fn empty(path: &Path) -> Self {
+        Self {
+            path: path.to_path_buf(),
+            entries: Mutex::new(Vec::new()),
+            saving: Mutex::new(()),
+            enabled: AtomicBool::new(false),
+            enforced: AtomicBool::new(false),
+        }
+    }
+
+    /// Loads the whitelist from `path`.
+    pub fn load(path: &Path) -> Result<Self, UserListError> {
+        let whitelist = Self::empty(path);
+        whitelist.reload()?;
+        Ok(whitelist)
+    }
+
+    /// Reads the file again, replacing the entries in memory.
+    /// On error, the entries in memory are left untouched.
+    pub fn reload(&self) -> Result<(), UserListError> {
+        let mut entries: Vec<WhitelistEntry> = Vec::new();
+
+        for mut entry in read_json_list::<WhitelistEntry>(&self.path)? {
+            entry.uuid = format_uuid(&entry.uuid);
+            if !entries.iter().any(|e| e.uuid == entry.uuid) {
+                entries.push(entry);
+            }
+        }
+
+        *self.entries.lock().unwrap() = entries;
+        Ok(())
+    }
+
+    /// Adds a player to the whitelist. Returns `false` if they already were whitelisted.
+    pub fn add(&self, entry: WhitelistEntry) -> bool {
+        let mut entry = entry;
+        entry.uuid = format_uuid(&entry.uuid);
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.iter().any(|e| e.uuid == entry.uuid) {
+            return false;
+        }
+        entries.push(entry);
+        true
+    }
+
+    /// Removes a player from the whitelist. Returns `false` if they were not whitelisted.
+    pub fn remove(&self, uuid: &str) -> bool {
+        let uuid = format_uuid(uuid);
+
+        let mut entries = self.entries.lock().unwrap();
+        let len_before = entries.len();
+        entries.retain(|e| e.uuid != uuid);
+        entries.len() != len_before
+    }
+
+    /// Writes the whitelist to its file, with the entries it has when the previous save is done.
+    pub fn save(&self) -> Result<(), UserListError> {
+        let _saving = self.saving.lock().unwrap();
+        let entries = self.entries.lock().unwrap().clone();
+        write_json_list(&self.path, &entries)
+    }
+
+    /// Returns whether a player is on the whitelist.
+    pub fn contains(&self, uuid: &str) -> bool {
+        let uuid = format_uuid(uuid);
+        self.entries.lock().unwrap().iter().any(|e| e.uuid == uuid)
+    }
+
+    /// Returns the entry of a player given their name, ignoring case.
+    pub fn get_by_name(&self, name: &str) -> Option<WhitelistEntry> {
+        let entries = self.entries.lock().unwrap();
+        entries
+            .iter()
+            .find(|e| e.name.eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
+    /// Returns a copy of all the entries.
+    pub fn entries(&self) -> Vec<WhitelistEntry> {
+        self.entries.lock().unwrap().clone()
+    }
+
+    /// Returns whether the whitelist is turned on.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    /// Turns the whitelist on or off, once 'server.properties' has been changed.
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Returns whether the players who are no longer allowed are kicked.
+    pub fn is_enforced(&self) -> bool {
+        self.enforced.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enforced(&self, enforced: bool) {
+        self.enforced.store(enforced, Ordering::Relaxed);
+    }
+
+    /// Returns whether a player may join when the whitelist is turned on.
+    /// Operators may always join.
+    pub fn allows(&self, uuid: &str, operators: &OperatorList) -> bool {
+        self.contains(uuid) || operators.get(uuid).is_some()
+    }
+}
+
+/// Returns whether a player may join the server, according to the 'white-list' setting.
+pub fn is_allowed(uuid: &str) -> bool {
+    !WHITELIST.is_enabled() || WHITELIST.allows(uuid, &OPERATORS)
+}
+
+/// Kicks the online players who are not whitelisted, if 'white-list' and 'enforce-whitelist'
+/// are both turned on.
+pub fn enforce() {
+    if !(WHITELIST.is_enabled() && WHITELIST.is_enforced()) {
+        return;
+    }
+
+    for player in ONLINE_PLAYERS.list() {
+        if !WHITELIST.allows(&player.uuid, &OPERATORS)
+            && ONLINE_PLAYERS.kick(&player.uuid, consts::disconnect_reasons::NOT_WHITELISTED)
+        {
+            info!("Kicked {} as they are not whitelisted", player.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::user_lists::ops::OperatorEntry;
+    use std::fs;
+    use tempfile::TempDir;
+
+    const NOTCH: &str = "069a79f4-44e9-4726-a5be-fca90e38aaf5";
+    const JEB: &str = "853c80ef-3c37-49fd-aa49-938b674adae6";
+
+    fn entry(uuid: &str, name: &str) -> WhitelistEntry {
+        WhitelistEntry {
+            uuid: uuid.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_add_remove() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("whitelist.json");
+        let whitelist = Whitelist::load(&path)?;
+
+        assert!(whitelist.add(entry(NOTCH, "Notch")));
+        assert!(!whitelist.add(entry(&NOTCH.replace('-', ""), "Notch")));
+        assert!(whitelist.contains(NOTCH));
+        assert_eq!(whitelist.get_by_name("NOTCH").unwrap().uuid, NOTCH);
+
+        assert!(whitelist.remove(NOTCH));
+        assert!(!whitelist.remove(NOTCH));
+        assert!(!whitelist.contains(NOTCH));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_vanilla_format_and_reload() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let path = dir.path().join("whitelist.json");
+        let whitelist = Whitelist::load(&path)?;
+        whitelist.add(entry(NOTCH, "Notch"));
+        whitelist.save()?;
+
+        let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&path)?).unwrap();
+        assert_eq!(json, serde_json::json!([{"uuid": NOTCH, "name": "Notch"}]));
+
+        // Edited by hand while the server is running.
+        fs::write(&path, format!(r#"[{{"uuid": "{JEB}", "name": "jeb_"}}]"#))?;
+        whitelist.reload()?;
+        assert!(!whitelist.contains(NOTCH));
+        assert!(whitelist.contains(JEB));
+
+        // A broken file keeps the previous entries, and is not overwritten.
+        fs::write(&path, "[{")?;
+        assert!(whitelist.reload().is_err());
+        assert!(whitelist.contains(JEB));
+        assert!(whitelist.add(entry(NOTCH, "Notch")));
+        assert!(whitelist.save().is_err());
+        assert_eq!(fs::read_to_string(&path)?, "[{");
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_operators_bypass_whitelist() -> Result<(), UserListError> {
+        let dir = TempDir::new()?;
+        let whitelist = Whitelist::load(&dir.path().join("whitelist.json"))?;
+        let operators = OperatorList::load(&dir.path().join("ops.json"))?;
+
+        assert!(!whitelist.allows(JEB, &operators));
+        operators.add(OperatorEntry {
+            uuid: JEB.to_string(),
+            name: "jeb_".to_string(),
+            level: 4,
+            bypasses_player_limit: false,
+        })?;
+        assert!(whitelist.allows(JEB, &operators));
+
+        Ok(())
+    }
+}
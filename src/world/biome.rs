@@ -0,0 +1,101 @@
+//! The biome registry. The chunks store the biomes by their ID in the `minecraft:worldgen/biome`
+//! registry sent to the client, which has the vanilla biomes in the order of their names.
+
+/// The vanilla biomes, by ID.
+pub const BIOMES: [&str; 64] = [
+    "minecraft:badlands",
+    "minecraft:bamboo_jungle",
+    "minecraft:basalt_deltas",
+    "minecraft:beach",
+    "minecraft:birch_forest",
+    "minecraft:cherry_grove",
+    "minecraft:cold_ocean",
+    "minecraft:crimson_forest",
+    "minecraft:dark_forest",
+    "minecraft:deep_cold_ocean",
+    "minecraft:deep_dark",
+    "minecraft:deep_frozen_ocean",
+    "minecraft:deep_lukewarm_ocean",
+    "minecraft:deep_ocean",
+    "minecraft:desert",
+    "minecraft:dripstone_caves",
+    "minecraft:end_barrens",
+    "minecraft:end_highlands",
+    "minecraft:end_midlands",
+    "minecraft:eroded_badlands",
+    "minecraft:flower_forest",
+    "minecraft:forest",
+    "minecraft:frozen_ocean",
+    "minecraft:frozen_peaks",
+    "minecraft:frozen_river",
+    "minecraft:grove",
+    "minecraft:ice_spikes",
+    "minecraft:jagged_peaks",
+    "minecraft:jungle",
+    "minecraft:lukewarm_ocean",
+    "minecraft:lush_caves",
+    "minecraft:mangrove_swamp",
+    "minecraft:meadow",
+    "minecraft:mushroom_fields",
+    "minecraft:nether_wastes",
+    "minecraft:ocean",
+    "minecraft:old_growth_birch_forest",
+    "minecraft:old_growth_pine_taiga",
+    "minecraft:old_growth_spruce_taiga",
+    "minecraft:plains",
+    "minecraft:river",
+    "minecraft:savanna",
+    "minecraft:savanna_plateau",
+    "minecraft:small_end_islands",
+    "minecraft:snowy_beach",
+    "minecraft:snowy_plains",
+    "minecraft:snowy_slopes",
+    "minecraft:snowy_taiga",
+    "minecraft:soul_sand_valley",
+    "minecraft:sparse_jungle",
+    "minecraft:stony_peaks",
+    "minecraft:stony_shore",
+    "minecraft:sunflower_plains",
+    "minecraft:swamp",
+    "minecraft:taiga",
+    "minecraft:the_end",
+    "minecraft:the_void",
+    "minecraft:warm_ocean",
+    "minecraft:warped_forest",
+    "minecraft:windswept_forest",
+    "minecraft:windswept_gravelly_hills",
+    "minecraft:windswept_hills",
+    "minecraft:windswept_savanna",
+    "minecraft:wooded_badlands",
+];
+
+/// The ID of a biome, with or without the "minecraft" namespace.
+pub fn id(name: &str) -> Option<u32> {
+    let name = match name.contains(':') {
+        true => name.to_string(),
+        false => format!("minecraft:{name}"),
+    };
+    BIOMES
+        .binary_search(&name.as_str())
+        .ok()
+        .map(|id| id as u32)
+}
+
+pub fn name(id: u32) -> Option<&'static str> {
+    BIOMES.get(id as usize).copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ids() {
+        assert!(BIOMES.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(id("minecraft:plains"), Some(39));
+        assert_eq!(id("the_void"), Some(56));
+        assert_eq!(id("minecraft:nowhere"), None);
+        assert_eq!(name(39), Some("minecraft:plains"));
+        assert_eq!(name(64), None);
+    }
+}
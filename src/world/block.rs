@@ -0,0 +1,382 @@
+//! The block registry, generated at build time from the vanilla `blocks.json` data report, with
+//! the global state IDs of the client for `consts::minecraft::PROTOCOL_VERSION`.
+//!
+//! The states of a block have the IDs from its `min_state`, going through the values of its
+//! properties, the last property changing the fastest. The states are written like in the
+//! commands: `minecraft:oak_stairs[facing=north,half=bottom]`, where the properties that are
+//! not given are the ones of the default state.
+
+use std::borrow::Cow;
+
+use thiserror::Error;
+
+use crate::nbt::{Compound, Tag};
+
+include!(concat!(env!("OUT_DIR"), "/blocks.rs"));
+
+#[derive(Error, Debug, PartialEq, Eq)]
+pub enum BlockStateError {
+    #[error("Unknown block '{0}'")]
+    UnknownBlock(String),
+    #[error("Block {block} has no property '{property}'")]
+    UnknownProperty {
+        block: &'static str,
+        property: String,
+    },
+    #[error("Invalid value '{value}' for the property '{property}'")]
+    InvalidValue {
+        property: &'static str,
+        value: String,
+    },
+    #[error("The property '{0}' is given more than once")]
+    DuplicateProperty(String),
+    #[error("Invalid block state '{0}'")]
+    Syntax(String),
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Property {
+    pub name: &'static str,
+    pub values: &'static [&'static str],
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct Block {
+    pub name: &'static str,
+    /// Sorted by name.
+    pub properties: &'static [Property],
+    pub min_state: u32,
+    pub default_state: u32,
+}
+
+impl Block {
+    pub fn state_count(&self) -> u32 {
+        self.properties
+            .iter()
+            .map(|property| property.values.len() as u32)
+            .product()
+    }
+
+    pub fn has_state(&self, state: u32) -> bool {
+        (self.min_state..self.min_state + self.state_count()).contains(&state)
+    }
+
+    /// The values of the properties in a state of the block, in the order of the properties.
+    pub fn values(&self, state: u32) -> Option<Vec<&'static str>> {
+        if !self.has_state(state) {
+            return None;
+        }
+        let mut offset = state - self.min_state;
+        let mut values = vec![""; self.properties.len()];
+        for (value, property) in values.iter_mut().zip(self.properties).rev() {
+            let count = property.values.len() as u32;
+            *value = property.values[(offset % count) as usize];
+            offset /= count;
+        }
+        Some(values)
+    }
+
+    /// The state with the given values, and the ones of the default state for the others.
+    pub fn state_with<'a>(
+        &self,
+        values: impl IntoIterator<Item = (&'a str, &'a str)>,
+    ) -> Result<u32, BlockStateError> {
+        let mut indices = self.value_indices(self.default_state);
+        let mut given = vec![false; self.properties.len()];
+        for (name, value) in values {
+            let index = self
+                .properties
+                .iter()
+                .position(|property| property.name == name)
+                .ok_or_else(|| BlockStateError::UnknownProperty {
+                    block: self.name,
+                    property: name.to_string(),
+                })?;
+            if std::mem::replace(&mut given[index], true) {
+                return Err(BlockStateError::DuplicateProperty(name.to_string()));
+            }
+            let property = &self.properties[index];
+            indices[index] = property
+                .values
+                .iter()
+                .position(|v| *v == value)
+                .ok_or_else(|| BlockStateError::InvalidValue {
+                    property: property.name,
+                    value: value.to_string(),
+                })?;
+        }
+        let offset = self
+            .properties
+            .iter()
+            .zip(indices)
+            .fold(0, |offset, (property, index)| {
+                offset * property.values.len() as u32 + index as u32
+            });
+        Ok(self.min_state + offset)
+    }
+
+    fn value_indices(&self, state: u32) -> Vec<usize> {
+        let values = self.values(state).unwrap_or_default();
+        self.properties
+            .iter()
+            .zip(values)
+            .map(|(property, value)| property.values.iter().position(|v| *v == value).unwrap())
+            .collect()
+    }
+}
+
+/// Adds the "minecraft" namespace to the names without one.
+fn full_name(name: &str) -> Cow<'_, str> {
+    match name.contains(':') {
+        true => Cow::Borrowed(name),
+        false => Cow::Owned(format!("minecraft:{name}")),
+    }
+}
+
+/// The block of a name, with or without the "minecraft" namespace.
+pub fn block(name: &str) -> Option<&'static Block> {
+    let name = full_name(name);
+    BY_NAME
+        .binary_search_by(|index| BLOCKS[*index as usize].name.cmp(&name))
+        .ok()
+        .map(|index| &BLOCKS[BY_NAME[index] as usize])
+}
+
+/// The block of a state ID.
+pub fn block_of(state: u32) -> Option<&'static Block> {
+    let index = BLOCKS
+        .partition_point(|block| block.min_state <= state)
+        .checked_sub(1)?;
+    Some(&BLOCKS[index]).filter(|block| block.has_state(state))
+}
+
+/// Whether a state is one of the air blocks, which are not counted in the chunk sections.
+pub fn is_air(state: u32) -> bool {
+    block_of(state).is_some_and(|block| {
+        matches!(
+            block.name,
+            "minecraft:air" | "minecraft:cave_air" | "minecraft:void_air"
+        )
+    })
+}
+
+/// Reads a state like `minecraft:oak_stairs[facing=north,half=bottom]`.
+pub fn parse_state(state: &str) -> Result<u32, BlockStateError> {
+    let syntax = || BlockStateError::Syntax(state.to_string());
+    let (name, properties) = match state.split_once('[') {
+        Some((name, rest)) => (name, Some(rest.strip_suffix(']').ok_or_else(syntax)?)),
+        None => (state, None),
+    };
+    let block =
+        block(name.trim()).ok_or_else(|| BlockStateError::UnknownBlock(name.to_string()))?;
+    let properties = properties
+        .filter(|properties| !properties.trim().is_empty())
+        .map(|properties| {
+            properties
+                .split(',')
+                .map(|property| {
+                    let (name, value) = property.split_once('=').ok_or_else(syntax)?;
+                    Ok((name.trim(), value.trim()))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .transpose()?
+        .unwrap_or_default();
+    block.state_with(properties)
+}
+
+/// A state like in the palettes of the world files:
+/// `{Name:"minecraft:oak_stairs",Properties:{facing:"north",...}}`.
+pub fn state_to_nbt(state: u32) -> Option<Compound> {
+    let block = block_of(state)?;
+    let mut nbt = Compound::from([("Name".to_string(), Tag::from(block.name))]);
+    if !block.properties.is_empty() {
+        let properties = block
+            .properties
+            .iter()
+            .zip(block.values(state)?)
+            .map(|(property, value)| (property.name.to_string(), Tag::from(value)))
+            .collect();
+        nbt.insert("Properties".to_string(), Tag::Compound(properties));
+    }
+    Some(nbt)
+}
+
+/// Reads a state of the world files, None if the block, a property or a value is unknown.
+pub fn state_from_nbt(nbt: &Compound) -> Option<u32> {
+    let block = block(nbt.get("Name")?.as_str()?)?;
+    let values = match nbt.get("Properties") {
+        Some(properties) => properties
+            .as_compound()?
+            .iter()
+            .map(|(name, value)| Some((name.as_str(), value.as_str()?)))
+            .collect::<Option<Vec<_>>>()?,
+        None => Vec::new(),
+    };
+    block.state_with(values).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::snbt;
+    use crate::world::chunk::palette::{ceil_log2, Strategy};
+
+    /// Writes a state like `minecraft:oak_stairs[facing=north,half=bottom,...]`, with all the
+    /// properties.
+    fn format_state(state: u32) -> Option<String> {
+        let block = block_of(state)?;
+        if block.properties.is_empty() {
+            return Some(block.name.to_string());
+        }
+        let properties: Vec<_> = block
+            .properties
+            .iter()
+            .zip(block.values(state)?)
+            .map(|(property, value)| format!("{}={value}", property.name))
+            .collect();
+        Some(format!("{}[{}]", block.name, properties.join(",")))
+    }
+
+    fn state_count() -> u32 {
+        BLOCKS.iter().map(Block::state_count).sum()
+    }
+
+    #[test]
+    fn test_lookups() {
+        assert_eq!(parse_state("minecraft:air"), Ok(0));
+        assert_eq!(parse_state("stone"), Ok(1));
+        assert_eq!(parse_state("minecraft:grass_block"), Ok(9));
+        assert_eq!(parse_state("minecraft:grass_block[snowy=true]"), Ok(8));
+        assert_eq!(parse_state("minecraft:grass_block[ snowy = false ]"), Ok(9));
+        assert_eq!(parse_state("minecraft:grass_block[]"), Ok(9));
+        assert_eq!(
+            parse_state(
+                "minecraft:mangrove_propagule[waterlogged=false,age=2,hanging=true,stage=1]"
+            ),
+            Ok(39 + 2 * 8 + 2 + 1)
+        );
+        assert_eq!(parse_state("minecraft:water[level=15]"), Ok(95));
+
+        assert_eq!(format_state(0).as_deref(), Some("minecraft:air"));
+        assert_eq!(
+            format_state(44).as_deref(),
+            Some("minecraft:mangrove_propagule[age=0,hanging=false,stage=0,waterlogged=false]")
+        );
+        assert_eq!(
+            block("minecraft:mangrove_propagule").unwrap().default_state,
+            44
+        );
+        assert_eq!(block_of(state_count()), None);
+
+        assert!(is_air(0));
+        assert!(!is_air(1));
+        assert_eq!(
+            state_to_nbt(8).unwrap(),
+            Compound::from([
+                ("Name".to_string(), Tag::from("minecraft:grass_block")),
+                (
+                    "Properties".to_string(),
+                    Tag::Compound(Compound::from([("snowy".to_string(), Tag::from("true"))]))
+                ),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_every_state() {
+        let mut states = 0;
+        for (index, block) in BLOCKS.iter().enumerate() {
+            assert_eq!(block.min_state, states);
+            assert!(block.has_state(block.default_state));
+            assert_eq!(super::block(block.name), Some(&BLOCKS[index]));
+            states += block.state_count();
+        }
+        assert_eq!(states, state_count());
+
+        for state in 0..states {
+            let text = format_state(state).unwrap();
+            assert_eq!(parse_state(&text), Ok(state), "{text}");
+            assert_eq!(state_from_nbt(&state_to_nbt(state).unwrap()), Some(state));
+        }
+    }
+
+    /// The states of the full report, which the client numbers the same way.
+    #[test]
+    #[ignore = "needs the full 1.21.1 reports in fixtures/reports"]
+    fn test_vanilla_states() {
+        // The client sends the states of the direct palettes with 15 bits.
+        assert_eq!(
+            ceil_log2(state_count() as usize),
+            Strategy::BLOCKS.direct_bits
+        );
+        assert!(BLOCKS.len() > 1000);
+
+        // facing, half, shape and waterlogged, with straight and false by default.
+        let stairs = block("oak_stairs").unwrap();
+        assert_eq!(stairs.state_count(), 4 * 2 * 5 * 2);
+        let state = parse_state("minecraft:oak_stairs[facing=north,half=bottom]").unwrap();
+        assert_eq!(state, stairs.default_state);
+        assert_eq!(state, stairs.min_state + 2 * 5 + 1);
+        assert_eq!(
+            format_state(state).as_deref(),
+            Some("minecraft:oak_stairs[facing=north,half=bottom,shape=straight,waterlogged=false]")
+        );
+        assert_eq!(
+            Tag::Compound(state_to_nbt(state).unwrap()),
+            snbt::parse(
+                r#"{Name:"minecraft:oak_stairs",Properties:{facing:"north",half:"bottom",
+                    shape:"straight",waterlogged:"false"}}"#
+            )
+            .unwrap()
+        );
+        let east = parse_state("oak_stairs[half=top,facing=east,waterlogged=true]").unwrap();
+        assert_eq!(east, stairs.min_state + 3 * 20);
+        assert_eq!(block_of(east), Some(stairs));
+
+        for name in [
+            "minecraft:netherrack",
+            "minecraft:end_stone",
+            "minecraft:soul_sand",
+        ] {
+            assert!(block(name).is_some(), "{name}");
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            parse_state("minecraft:nothing"),
+            Err(BlockStateError::UnknownBlock(
+                "minecraft:nothing".to_string()
+            ))
+        );
+        assert_eq!(
+            parse_state("stone[snowy=true]"),
+            Err(BlockStateError::UnknownProperty {
+                block: "minecraft:stone",
+                property: "snowy".to_string()
+            })
+        );
+        assert_eq!(
+            parse_state("grass_block[snowy=maybe]"),
+            Err(BlockStateError::InvalidValue {
+                property: "snowy",
+                value: "maybe".to_string()
+            })
+        );
+        assert_eq!(
+            parse_state("grass_block[snowy=true,snowy=false]"),
+            Err(BlockStateError::DuplicateProperty("snowy".to_string()))
+        );
+        for invalid in ["grass_block[snowy=true", "grass_block[snowy]"] {
+            assert!(matches!(
+                parse_state(invalid),
+                Err(BlockStateError::Syntax(_))
+            ));
+        }
+
+        let unknown = Compound::from([("Name".to_string(), Tag::from("minecraft:nothing"))]);
+        assert_eq!(state_from_nbt(&unknown), None);
+    }
+}
@@ -0,0 +1,390 @@
+//! The chunks of the region files. The sections are in the 'sections' list, each with its Y,
+//! its 'block_states' and its 'biomes' as a palette of names and packed indices into it, and
+//! its 'BlockLight' and 'SkyLight'. The light goes one section beyond the world on each side.
+//!
+//! See https://minecraft.wiki/w/Chunk_format
+
+use log::warn;
+
+use crate::consts::minecraft::DATA_VERSION;
+use crate::nbt::{Compound, Tag};
+
+use super::heightmap::Heightmaps;
+use super::palette::{self, PalettedContainer, Strategy};
+use super::{
+    Chunk, ChunkError, ChunkHeight, ChunkRegistry, Section, UnknownStates, AIR, LIGHT_SIZE,
+};
+
+/// The entries of the chunks that are modeled, the others are kept as they are.
+const KNOWN_ENTRIES: [&str; 9] = [
+    "DataVersion",
+    "xPos",
+    "zPos",
+    "yPos",
+    "Status",
+    "InhabitedTime",
+    "LastUpdate",
+    "sections",
+    "Heightmaps",
+];
+
+fn get<'a>(nbt: &'a Compound, key: &'static str) -> Result<&'a Tag, ChunkError> {
+    nbt.get(key).ok_or(ChunkError::Missing(key))
+}
+
+fn int(nbt: &Compound, key: &'static str) -> Result<i64, ChunkError> {
+    get(nbt, key)?.as_i64().ok_or(ChunkError::Missing(key))
+}
+
+/// The palette and the data of a container.
+fn palette_and_data(nbt: &Compound) -> Result<(&[Tag], Option<Vec<u64>>), ChunkError> {
+    let Some(Tag::List(palette)) = nbt.get("palette") else {
+        return Err(ChunkError::Missing("palette"));
+    };
+    let data = match nbt.get("data") {
+        Some(Tag::LongArray(data)) => Some(data.iter().map(|long| *long as u64).collect()),
+        _ => None,
+    };
+    Ok((palette, data))
+}
+
+/// The block states of a section, where the states unknown to the registry are air, and the
+/// unknown states.
+fn block_states(
+    palette: &[Tag],
+    data: Option<Vec<u64>>,
+    registry: &impl ChunkRegistry,
+) -> Result<(PalettedContainer, UnknownStates), ChunkError> {
+    let mut unknown = UnknownStates::default();
+    let mut ids = Vec::with_capacity(palette.len());
+    // The index in the unknown states of each palette entry, if it is unknown.
+    let mut unknown_entries = Vec::with_capacity(palette.len());
+    for state in palette {
+        match state
+            .as_compound()
+            .and_then(|state| registry.state_id(state))
+        {
+            Some(id) => {
+                ids.push(id);
+                unknown_entries.push(None);
+            }
+            None => {
+                ids.push(AIR);
+                unknown_entries.push(Some(unknown.states.len()));
+                unknown.states.push(state.clone());
+            }
+        }
+    }
+    if !unknown.states.is_empty() {
+        let entries = (0..palette.len() as u32).collect();
+        let entries =
+            PalettedContainer::from_palette_and_data(Strategy::BLOCKS, entries, data.clone())?;
+        for (block, entry) in entries.iter().enumerate() {
+            if let Some(state) = unknown_entries[entry as usize] {
+                unknown.blocks.insert(block, state);
+            }
+        }
+    }
+    let block_states = PalettedContainer::from_palette_and_data(Strategy::BLOCKS, ids, data)?;
+    Ok((block_states, unknown))
+}
+
+fn container_nbt<T>((palette, data): (Vec<T>, Option<Vec<u64>>), name: impl Fn(T) -> Tag) -> Tag {
+    let mut nbt = Compound::from([(
+        "palette".to_string(),
+        Tag::List(palette.into_iter().map(name).collect()),
+    )]);
+    if let Some(data) = data {
+        let data = data.into_iter().map(|long| long as i64).collect();
+        nbt.insert("data".to_string(), Tag::LongArray(data));
+    }
+    Tag::Compound(nbt)
+}
+
+fn light(nbt: &Compound, key: &str) -> Result<Option<Vec<u8>>, ChunkError> {
+    match nbt.get(key) {
+        Some(Tag::ByteArray(light)) if light.len() == LIGHT_SIZE => {
+            Ok(Some(light.iter().map(|b| *b as u8).collect()))
+        }
+        Some(Tag::ByteArray(light)) => Err(ChunkError::InvalidLight(light.len())),
+        _ => Ok(None),
+    }
+}
+
+impl Chunk {
+    /// Reads a chunk of a region file, in a dimension of the given height. The unknown block
+    /// states are air, and `to_nbt` writes them back, while the unknown biomes are replaced by
+    /// the default one.
+    pub fn from_nbt(
+        mut nbt: Compound,
+        height: ChunkHeight,
+        registry: &impl ChunkRegistry,
+    ) -> Result<Self, ChunkError> {
+        let default_biome = registry.default_biome();
+        let mut chunk = Chunk::new(
+            int(&nbt, "xPos")? as i32,
+            int(&nbt, "zPos")? as i32,
+            height,
+            default_biome,
+        );
+        if let Some(Tag::String(status)) = nbt.get("Status") {
+            chunk.status = status.clone();
+        }
+        chunk.inhabited_time = nbt.get("InhabitedTime").and_then(Tag::as_i64).unwrap_or(0);
+        chunk.last_update = nbt.get("LastUpdate").and_then(Tag::as_i64).unwrap_or(0);
+
+        let Tag::List(sections) = get(&nbt, "sections")? else {
+            return Err(ChunkError::Missing("sections"));
+        };
+        for section in sections {
+            let Some(section) = section.as_compound() else {
+                continue;
+            };
+            // From -1 for the light below the world.
+            let index = int(section, "Y")? - height.min_section() as i64;
+            let Ok(light_index) = usize::try_from(index + 1) else {
+                continue;
+            };
+            if light_index < height.sections() + 2 {
+                chunk.sky_light[light_index] = light(section, "SkyLight")?;
+                chunk.block_light[light_index] = light(section, "BlockLight")?;
+            }
+            if index < 0 || index as usize >= height.sections() {
+                continue;
+            }
+
+            let (block_states, unknown_states) = match section.get("block_states") {
+                Some(Tag::Compound(nbt)) => {
+                    let (palette, data) = palette_and_data(nbt)?;
+                    block_states(palette, data, registry)?
+                }
+                _ => (
+                    PalettedContainer::new(Strategy::BLOCKS, AIR),
+                    UnknownStates::default(),
+                ),
+            };
+            for state in &unknown_states.states {
+                warn!(
+                    "Unknown block state {state} in chunk [{}, {}], kept as it is",
+                    chunk.x, chunk.z
+                );
+            }
+            let biomes = match section.get("biomes") {
+                Some(Tag::Compound(nbt)) => {
+                    let (palette, data) = palette_and_data(nbt)?;
+                    let biome = |biome: &Tag| {
+                        let id = biome.as_str().and_then(|name| registry.biome_id(name));
+                        id.unwrap_or_else(|| {
+                            warn!("Unknown biome {biome} in chunk [{}, {}]", chunk.x, chunk.z);
+                            default_biome
+                        })
+                    };
+                    let biomes = palette.iter().map(biome).collect();
+                    PalettedContainer::from_palette_and_data(Strategy::BIOMES, biomes, data)?
+                }
+                _ => PalettedContainer::new(Strategy::BIOMES, default_biome),
+            };
+            chunk.sections[index as usize] = Section {
+                block_states,
+                biomes,
+                unknown_states,
+            };
+        }
+
+        if let Some(Tag::Compound(heightmaps)) = nbt.get("Heightmaps") {
+            chunk.heightmaps = Heightmaps::from_nbt(heightmaps, height.height)?;
+        }
+        for key in KNOWN_ENTRIES {
+            nbt.remove(key);
+        }
+        chunk.other = nbt;
+        Ok(chunk)
+    }
+
+    /// The chunk as in the region files, with the unknown states that were read. The state IDs
+    /// unknown to the registry are written as air.
+    pub fn to_nbt(&self, registry: &impl ChunkRegistry) -> Compound {
+        let mut sections = Vec::with_capacity(self.sky_light.len());
+        for light_index in 0..self.sky_light.len() {
+            let y = self.height.min_section() + light_index as i32 - 1;
+            let mut section = Compound::from([("Y".to_string(), Tag::Byte(y as i8))]);
+            if let Some(blocks) = light_index
+                .checked_sub(1)
+                .and_then(|index| self.sections.get(index))
+            {
+                // The unknown states are the Err entries.
+                let unknown = &blocks.unknown_states;
+                let entries = blocks.block_states.iter().enumerate().map(|(block, id)| {
+                    unknown
+                        .blocks
+                        .get(&block)
+                        .map_or(Ok(id), |state| Err(*state))
+                });
+                let state = |entry: Result<u32, usize>| match entry {
+                    Ok(id) => {
+                        let state = registry.state(id);
+                        Tag::Compound(
+                            state.unwrap_or_else(|| registry.state(AIR).unwrap_or_default()),
+                        )
+                    }
+                    Err(state) => unknown.states[state].clone(),
+                };
+                let block_states = palette::palette_and_data(Strategy::BLOCKS, entries);
+                section.insert(
+                    "block_states".to_string(),
+                    container_nbt(block_states, state),
+                );
+                let biome = |id| Tag::from(registry.biome_name(id).unwrap_or("minecraft:plains"));
+                let biomes = blocks.biomes.to_palette_and_data();
+                section.insert("biomes".to_string(), container_nbt(biomes, biome));
+            }
+            for (key, light) in [
+                ("SkyLight", &self.sky_light[light_index]),
+                ("BlockLight", &self.block_light[light_index]),
+            ] {
+                if let Some(light) = light {
+                    let light = light.iter().map(|b| *b as i8).collect();
+                    section.insert(key.to_string(), Tag::ByteArray(light));
+                }
+            }
+            // The light sections are only written if they have light.
+            if section.len() > 1 {
+                sections.push(Tag::Compound(section));
+            }
+        }
+
+        let mut nbt = self.other.clone();
+        nbt.extend([
+            ("DataVersion".to_string(), Tag::Int(DATA_VERSION)),
+            ("xPos".to_string(), Tag::Int(self.x)),
+            ("zPos".to_string(), Tag::Int(self.z)),
+            ("yPos".to_string(), Tag::Int(self.height.min_section())),
+            ("Status".to_string(), Tag::from(self.status.as_str())),
+            ("InhabitedTime".to_string(), Tag::Long(self.inhabited_time)),
+            ("LastUpdate".to_string(), Tag::Long(self.last_update)),
+            ("sections".to_string(), Tag::List(sections)),
+            (
+                "Heightmaps".to_string(),
+                Tag::Compound(self.heightmaps.to_nbt(self.height.height)),
+            ),
+        ]);
+        nbt
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::snbt;
+    use crate::world::chunk::tests::TestRegistry;
+    use crate::world::region::RegionFile;
+    use std::path::Path;
+
+    #[test]
+    fn test_round_trip() {
+        let registry = TestRegistry::new();
+        let mut chunk = Chunk::new(-7, 12, ChunkHeight::OVERWORLD, 0);
+        for i in 0..2000 {
+            chunk.set_block(i % 16, i % 300 - 48, i / 16 % 16, (i % 40) as u32);
+        }
+        chunk.set_block(1, -64, 0, 1);
+        for i in 0..64 {
+            chunk.set_biome(i * 4 % 16, i * 4 - 64, i % 16, (i % 5) as u32);
+        }
+        chunk.update_heightmaps(&registry);
+        chunk.sky_light[0] = Some(vec![0xFF; LIGHT_SIZE]);
+        chunk.block_light[5] = Some(vec![0x12; LIGHT_SIZE]);
+        chunk.status = "minecraft:full".to_string();
+        chunk.other.insert("isLightOn".to_string(), Tag::Byte(1));
+
+        let nbt = chunk.to_nbt(&registry);
+        assert_eq!(nbt["yPos"], Tag::Int(-4));
+        let sections = match &nbt["sections"] {
+            Tag::List(sections) => sections,
+            tag => panic!("Not a list: {tag}"),
+        };
+        // The light below the world, then the 24 sections.
+        assert_eq!(sections.len(), 25);
+        assert_eq!(sections[0].as_compound().unwrap()["Y"], Tag::Byte(-5));
+        let first = sections[1].as_compound().unwrap();
+        let palette = &first["block_states"].as_compound().unwrap()["palette"];
+        assert_eq!(
+            palette,
+            &snbt::parse(r#"[{Name:"minecraft:air"},{Name:"minecraft:stone"}]"#).unwrap()
+        );
+
+        let read = Chunk::from_nbt(nbt, ChunkHeight::OVERWORLD, &registry).unwrap();
+        assert_eq!(read, chunk);
+    }
+
+    #[test]
+    fn test_unknown_names() {
+        let mut nbt = snbt::parse(
+            r#"{xPos:1,zPos:2,Status:"minecraft:full",sections:[{Y:0b,
+                block_states:{palette:[{Name:"minecraft:stone"},{Name:"minecraft:unknown"}]},
+                biomes:{palette:["minecraft:nowhere"]}}]}"#,
+        )
+        .unwrap();
+        // With 4 bits, the entry 1 is the second palette entry, and the others the first.
+        let mut data = vec![0; 256];
+        data[0] = 1 << 4;
+        let Tag::Compound(nbt) = &mut nbt else {
+            unreachable!()
+        };
+        let Tag::List(sections) = nbt.get_mut("sections").unwrap() else {
+            unreachable!()
+        };
+        let Tag::Compound(section) = &mut sections[0] else {
+            unreachable!()
+        };
+        let Tag::Compound(block_states) = section.get_mut("block_states").unwrap() else {
+            unreachable!()
+        };
+        block_states.insert("data".to_string(), Tag::LongArray(data));
+        let original = Tag::Compound(block_states.clone());
+
+        let registry = TestRegistry::new();
+        let mut chunk = Chunk::from_nbt(nbt.clone(), ChunkHeight::NETHER, &registry).unwrap();
+        assert_eq!(chunk.block(0, 0, 0), 1);
+        assert_eq!(chunk.block(1, 0, 0), AIR);
+        assert_eq!(chunk.block(2, 0, 0), 1);
+        assert_eq!(chunk.biome(0, 0, 0), Some(0));
+        // The sections that are missing are empty.
+        assert_eq!(chunk.block(0, 16, 0), AIR);
+
+        // The unknown state is written back, until its block is set.
+        let written = |chunk: &Chunk| {
+            let Tag::List(sections) = &chunk.to_nbt(&registry)["sections"] else {
+                unreachable!()
+            };
+            sections[0].as_compound().unwrap()["block_states"].clone()
+        };
+        assert_eq!(written(&chunk), original);
+        chunk.set_block(1, 0, 0, 1);
+        assert_eq!(
+            written(&chunk),
+            snbt::parse(r#"{palette:[{Name:"minecraft:stone"}]}"#).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_region_fixture() {
+        let registry = TestRegistry::new();
+        let dir = tempfile::TempDir::new().unwrap();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/region");
+        for name in ["r.0.0.mca", "c.4.0.mcc"] {
+            std::fs::copy(fixtures.join(name), dir.path().join(name)).unwrap();
+        }
+        let mut region = RegionFile::open(&dir.path().join("r.0.0.mca")).unwrap();
+        let nbt = region.read_chunk(4, 0).unwrap().unwrap();
+        let chunk = Chunk::from_nbt(nbt, ChunkHeight::OVERWORLD, &registry).unwrap();
+        assert_eq!((chunk.x, chunk.z), (4, 0));
+        assert_eq!(chunk.status, "minecraft:full");
+        assert_eq!(chunk.block(0, 0, 0), AIR);
+        // The other entries are kept.
+        assert_eq!(
+            chunk.to_nbt(&registry)["Padding"],
+            Tag::from("x".repeat(100))
+        );
+    }
+}
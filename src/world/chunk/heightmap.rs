@@ -0,0 +1,89 @@
+//! The heightmaps of a chunk: for each column, one above the highest block of a kind, counted
+//! from the bottom of the world, or 0 if the column has none. The client needs the
+//! 'MOTION_BLOCKING' one for the rain and 'WORLD_SURFACE' one for the sky light.
+
+use crate::nbt::{Compound, Tag};
+
+use super::palette::{ceil_log2, BitStorage};
+use super::ChunkError;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Heightmap {
+    heights: Vec<u16>,
+}
+
+impl Default for Heightmap {
+    fn default() -> Self {
+        Heightmap {
+            heights: vec![0; 256],
+        }
+    }
+}
+
+impl Heightmap {
+    /// The height of a column, with `x` and `z` inside the chunk.
+    #[cfg(test)]
+    pub fn get(&self, x: usize, z: usize) -> u16 {
+        self.heights[z * 16 + x]
+    }
+
+    pub fn set(&mut self, x: usize, z: usize, height: u16) {
+        self.heights[z * 16 + x] = height;
+    }
+
+    /// Packs the heights with the bits needed for a world of `height` blocks.
+    pub fn to_longs(&self, height: u32) -> Vec<i64> {
+        let mut storage = BitStorage::new(ceil_log2(height as usize + 1), 256);
+        for (index, height) in self.heights.iter().enumerate() {
+            storage.set(index, *height as u32);
+        }
+        storage.data().iter().map(|long| *long as i64).collect()
+    }
+
+    pub fn from_longs(data: &[i64], height: u32) -> Result<Self, ChunkError> {
+        let data = data.iter().map(|long| *long as u64).collect();
+        let storage = BitStorage::from_data(ceil_log2(height as usize + 1), 256, data)?;
+        Ok(Heightmap {
+            heights: storage.iter().map(|height| height as u16).collect(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Heightmaps {
+    /// The highest blocks that block motion or hold a fluid.
+    pub motion_blocking: Heightmap,
+    /// The highest blocks that are not air.
+    pub world_surface: Heightmap,
+}
+
+impl Heightmaps {
+    const MOTION_BLOCKING: &'static str = "MOTION_BLOCKING";
+    const WORLD_SURFACE: &'static str = "WORLD_SURFACE";
+
+    /// The heightmaps as in the world files and the chunk packets.
+    pub fn to_nbt(&self, height: u32) -> Compound {
+        Compound::from([
+            (
+                Self::MOTION_BLOCKING.to_string(),
+                Tag::LongArray(self.motion_blocking.to_longs(height)),
+            ),
+            (
+                Self::WORLD_SURFACE.to_string(),
+                Tag::LongArray(self.world_surface.to_longs(height)),
+            ),
+        ])
+    }
+
+    /// Reads the heightmaps, which are 0 when missing.
+    pub fn from_nbt(nbt: &Compound, height: u32) -> Result<Self, ChunkError> {
+        let read = |name: &str| match nbt.get(name) {
+            Some(Tag::LongArray(data)) => Heightmap::from_longs(data, height),
+            _ => Ok(Heightmap::default()),
+        };
+        Ok(Heightmaps {
+            motion_blocking: read(Self::MOTION_BLOCKING)?,
+            world_surface: read(Self::WORLD_SURFACE)?,
+        })
+    }
+}
@@ -0,0 +1,389 @@
+//! The chunks: columns of 16×16 blocks over the height of the world, made of sections of
+//! 16×16×16 blocks. The sections store their block states and their biomes, one per 4×4×4
+//! blocks, in paletted containers of global IDs.
+//! - `anvil`: the chunks of the region files, where the palettes hold names.
+//! - `network`: the Chunk Data and Update Light packet.
+
+mod anvil;
+pub mod heightmap;
+mod network;
+pub mod palette;
+#[cfg(test)]
+mod reader;
+
+use std::collections::BTreeMap;
+
+use thiserror::Error;
+
+use crate::nbt::{Compound, NbtError, Tag};
+use crate::packet::data_types::CodecError;
+use crate::world::{biome, block};
+use heightmap::Heightmaps;
+use palette::{PalettedContainer, Strategy};
+
+/// The state ID of air, and the value of the blocks of new sections.
+pub const AIR: u32 = 0;
+
+/// The light of a section, 4 bits per block.
+pub const LIGHT_SIZE: usize = 2048;
+
+#[derive(Error, Debug)]
+pub enum ChunkError {
+    #[error("Invalid chunk NBT: {0}")]
+    Nbt(#[from] NbtError),
+    #[error("Invalid chunk packet: {0}")]
+    Codec(#[from] CodecError),
+    #[error("Missing '{0}' in the chunk")]
+    Missing(&'static str),
+    #[error("Empty palette")]
+    EmptyPalette,
+    #[error("Palette index larger than the palette")]
+    InvalidPaletteIndex,
+    #[error("Invalid number of longs {0} in a paletted container")]
+    InvalidDataLength(usize),
+    #[error("Invalid light array of {0} bytes")]
+    InvalidLight(usize),
+}
+
+/// The names of the block states and the biomes, in the world files, for their IDs.
+pub trait ChunkRegistry {
+    /// The ID of a block state, given like in the palettes of the world files:
+    /// `{Name:"minecraft:oak_stairs",Properties:{facing:"north",...}}`.
+    fn state_id(&self, state: &Compound) -> Option<u32>;
+
+    /// The block state of an ID, like in the palettes of the world files.
+    fn state(&self, id: u32) -> Option<Compound>;
+
+    fn biome_id(&self, name: &str) -> Option<u32>;
+
+    fn biome_name(&self, id: u32) -> Option<&str>;
+
+    fn is_air(&self, state: u32) -> bool;
+
+    /// Whether a block state stops the entities and the rain, for the heightmaps.
+    fn blocks_motion(&self, state: u32) -> bool {
+        !self.is_air(state)
+    }
+
+    /// The biome of the sections missing from the world files.
+    fn default_biome(&self) -> u32 {
+        self.biome_id("minecraft:plains").unwrap_or(0)
+    }
+}
+
+/// The vanilla blocks and biomes, see `world::block` and `world::biome`.
+pub struct VanillaRegistry;
+
+impl ChunkRegistry for VanillaRegistry {
+    fn state_id(&self, state: &Compound) -> Option<u32> {
+        block::state_from_nbt(state)
+    }
+
+    fn state(&self, id: u32) -> Option<Compound> {
+        block::state_to_nbt(id)
+    }
+
+    fn biome_id(&self, name: &str) -> Option<u32> {
+        biome::id(name)
+    }
+
+    fn biome_name(&self, id: u32) -> Option<&str> {
+        biome::name(id)
+    }
+
+    fn is_air(&self, state: u32) -> bool {
+        block::is_air(state)
+    }
+}
+
+/// The vertical extent of the chunks of a dimension.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChunkHeight {
+    /// The lowest block, a multiple of 16.
+    pub min_y: i32,
+    /// The number of blocks, a multiple of 16.
+    pub height: u32,
+}
+
+impl ChunkHeight {
+    pub const OVERWORLD: ChunkHeight = ChunkHeight {
+        min_y: -64,
+        height: 384,
+    };
+    pub const NETHER: ChunkHeight = ChunkHeight {
+        min_y: 0,
+        height: 256,
+    };
+    pub const END: ChunkHeight = ChunkHeight {
+        min_y: 0,
+        height: 256,
+    };
+
+    pub fn sections(&self) -> usize {
+        self.height as usize / 16
+    }
+
+    /// The Y of the lowest section, in sections.
+    pub fn min_section(&self) -> i32 {
+        self.min_y >> 4
+    }
+
+    /// The index of the section of a block, if the block is inside the world.
+    pub fn section_index(&self, y: i32) -> Option<usize> {
+        let index = usize::try_from((y >> 4) - self.min_section()).ok()?;
+        (index < self.sections()).then_some(index)
+    }
+}
+
+/// The block states of a world file that the registry does not know. The blocks are air in the
+/// section, and the states are written back where the blocks were not set since.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct UnknownStates {
+    /// The entries of the palettes of the world files.
+    states: Vec<Tag>,
+    /// The index in `states` of each unknown block, by the index of the block.
+    blocks: BTreeMap<usize, usize>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Section {
+    /// 16×16×16 state IDs, by Y, then Z, then X.
+    pub block_states: PalettedContainer,
+    /// 4×4×4 biome IDs, by Y, then Z, then X.
+    pub biomes: PalettedContainer,
+    unknown_states: UnknownStates,
+}
+
+impl Section {
+    pub fn new(block_state: u32, biome: u32) -> Self {
+        Section {
+            block_states: PalettedContainer::new(Strategy::BLOCKS, block_state),
+            biomes: PalettedContainer::new(Strategy::BIOMES, biome),
+            unknown_states: UnknownStates::default(),
+        }
+    }
+
+    /// The number of blocks that are not air.
+    pub fn block_count(&self, registry: &impl ChunkRegistry) -> u16 {
+        match self.block_states.palette() {
+            palette::Palette::Single(state) if registry.is_air(*state) => 0,
+            palette::Palette::Single(_) => 4096,
+            _ => self
+                .block_states
+                .iter()
+                .filter(|state| !registry.is_air(*state))
+                .count() as u16,
+        }
+    }
+}
+
+fn block_index(x: i32, y: i32, z: i32) -> usize {
+    (((y & 15) << 8) | ((z & 15) << 4) | (x & 15)) as usize
+}
+
+fn biome_index(x: i32, y: i32, z: i32) -> usize {
+    ((((y >> 2) & 3) << 4) | (((z >> 2) & 3) << 2) | ((x >> 2) & 3)) as usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Chunk {
+    pub x: i32,
+    pub z: i32,
+    height: ChunkHeight,
+    sections: Vec<Section>,
+    pub heightmaps: Heightmaps,
+    /// The light of the sections, with one more section below and one above, if it is known.
+    pub sky_light: Vec<Option<Vec<u8>>>,
+    pub block_light: Vec<Option<Vec<u8>>>,
+    /// How far the generation went, like "minecraft:full".
+    pub status: String,
+    /// The number of ticks players spent in the chunk.
+    pub inhabited_time: i64,
+    /// The game time of the last save.
+    pub last_update: i64,
+    /// The entries of the world file that are not modeled, kept as they are.
+    other: Compound,
+}
+
+impl Chunk {
+    /// A chunk of air in one biome, which has to be generated.
+    pub fn new(x: i32, z: i32, height: ChunkHeight, biome: u32) -> Self {
+        Chunk {
+            x,
+            z,
+            height,
+            sections: vec![Section::new(AIR, biome); height.sections()],
+            heightmaps: Heightmaps::default(),
+            sky_light: vec![None; height.sections() + 2],
+            block_light: vec![None; height.sections() + 2],
+            status: "minecraft:empty".to_string(),
+            inhabited_time: 0,
+            last_update: 0,
+            other: Compound::new(),
+        }
+    }
+
+    pub fn height(&self) -> ChunkHeight {
+        self.height
+    }
+
+    /// The sections, from the bottom.
+    #[cfg(test)]
+    pub fn sections(&self) -> &[Section] {
+        &self.sections
+    }
+
+    /// The state of a block, air outside the world. Only the low 4 bits of `x` and `z` are used,
+    /// so that they can be in the chunk or in the world.
+    pub fn block(&self, x: i32, y: i32, z: i32) -> u32 {
+        match self.height.section_index(y) {
+            Some(index) => self.sections[index].block_states.get(block_index(x, y, z)),
+            None => AIR,
+        }
+    }
+
+    /// Sets the state of a block, and returns the previous one, or None outside the world.
+    /// The heightmaps are not updated.
+    pub fn set_block(&mut self, x: i32, y: i32, z: i32, state: u32) -> Option<u32> {
+        let section = &mut self.sections[self.height.section_index(y)?];
+        let index = block_index(x, y, z);
+        section.unknown_states.blocks.remove(&index);
+        Some(section.block_states.set(index, state))
+    }
+
+    /// The biome at a block, stored for each 4×4×4 blocks.
+    #[cfg(test)]
+    pub fn biome(&self, x: i32, y: i32, z: i32) -> Option<u32> {
+        let index = self.height.section_index(y)?;
+        Some(self.sections[index].biomes.get(biome_index(x, y, z)))
+    }
+
+    pub fn set_biome(&mut self, x: i32, y: i32, z: i32, biome: u32) -> Option<u32> {
+        let index = self.height.section_index(y)?;
+        Some(self.sections[index].biomes.set(biome_index(x, y, z), biome))
+    }
+
+    /// Computes the heightmaps from the blocks.
+    pub fn update_heightmaps(&mut self, registry: &impl ChunkRegistry) {
+        let top = self.height.min_y + self.height.height as i32 - 1;
+        for x in 0..16 {
+            for z in 0..16 {
+                let mut surface = None;
+                let mut motion_blocking = None;
+                for y in (self.height.min_y..=top).rev() {
+                    let index = self.height.section_index(y).unwrap();
+                    if matches!(
+                        self.sections[index].block_states.palette(),
+                        palette::Palette::Single(state) if registry.is_air(*state)
+                    ) {
+                        continue;
+                    }
+                    let state = self.block(x, y, z);
+                    if surface.is_none() && !registry.is_air(state) {
+                        surface = Some(y);
+                    }
+                    if registry.blocks_motion(state) {
+                        motion_blocking = Some(y);
+                        break;
+                    }
+                }
+                let height = |y: Option<i32>| y.map_or(0, |y| (y - self.height.min_y + 1) as u16);
+                self.heightmaps
+                    .world_surface
+                    .set(x as usize, z as usize, height(surface));
+                self.heightmaps.motion_blocking.set(
+                    x as usize,
+                    z as usize,
+                    height(motion_blocking),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// States: air, then "minecraft:stone", then "minecraft:test" with a 'value' property.
+    /// The biomes: "minecraft:plains", "minecraft:biome_1", ...
+    pub struct TestRegistry {
+        biomes: Vec<String>,
+    }
+
+    impl TestRegistry {
+        pub fn new() -> Self {
+            let mut biomes = vec!["minecraft:plains".to_string()];
+            biomes.extend((1..100).map(|i| format!("minecraft:biome_{i}")));
+            TestRegistry { biomes }
+        }
+    }
+
+    fn name(name: &str) -> Compound {
+        Compound::from([("Name".to_string(), Tag::from(name))])
+    }
+
+    impl ChunkRegistry for TestRegistry {
+        fn state_id(&self, state: &Compound) -> Option<u32> {
+            match state.get("Name")?.as_str()? {
+                "minecraft:air" => Some(0),
+                "minecraft:stone" => Some(1),
+                "minecraft:test" => {
+                    let properties = state.get("Properties")?.as_compound()?;
+                    properties.get("value")?.as_str()?.parse().ok()
+                }
+                _ => None,
+            }
+        }
+
+        fn state(&self, id: u32) -> Option<Compound> {
+            Some(match id {
+                0 => name("minecraft:air"),
+                1 => name("minecraft:stone"),
+                id => {
+                    let mut state = name("minecraft:test");
+                    let properties = Compound::from([("value".to_string(), id.to_string().into())]);
+                    state.insert("Properties".to_string(), Tag::Compound(properties));
+                    state
+                }
+            })
+        }
+
+        fn biome_id(&self, name: &str) -> Option<u32> {
+            self.biomes.iter().position(|b| b == name).map(|i| i as u32)
+        }
+
+        fn biome_name(&self, id: u32) -> Option<&str> {
+            self.biomes.get(id as usize).map(String::as_str)
+        }
+
+        fn is_air(&self, state: u32) -> bool {
+            state == AIR
+        }
+    }
+
+    #[test]
+    fn test_blocks_and_heightmaps() {
+        let registry = TestRegistry::new();
+        let mut chunk = Chunk::new(3, -2, ChunkHeight::OVERWORLD, 0);
+        assert_eq!(chunk.sections().len(), 24);
+        assert_eq!(chunk.set_block(5, -64, 7, 1), Some(AIR));
+        assert_eq!(chunk.set_block(5, 100, 7, 2), Some(AIR));
+        assert_eq!(chunk.set_block(0, 320, 0, 1), None);
+        assert_eq!(chunk.block(5, 100, 7), 2);
+        // World coordinates.
+        assert_eq!(chunk.block(16 * 3 + 5, 100, -32 + 7), 2);
+        assert_eq!(chunk.block(0, -65, 0), AIR);
+
+        assert_eq!(chunk.set_biome(15, 319, 15, 4), Some(0));
+        assert_eq!(chunk.biome(12, 316, 12), Some(4));
+        assert_eq!(chunk.biome(11, 316, 12), Some(0));
+
+        chunk.update_heightmaps(&registry);
+        assert_eq!(chunk.heightmaps.world_surface.get(5, 7), 165);
+        assert_eq!(chunk.heightmaps.motion_blocking.get(5, 7), 165);
+        assert_eq!(chunk.heightmaps.world_surface.get(0, 0), 0);
+        assert_eq!(chunk.sections()[0].block_count(&registry), 1);
+        assert_eq!(chunk.sections()[1].block_count(&registry), 0);
+    }
+}
@@ -0,0 +1,216 @@
+//! The Chunk Data and Update Light packet, which sends a chunk and its light to the client.
+//!
+//! Chunk X (Int), Chunk Z (Int), Heightmaps (NBT), Size (VarInt), Data (Byte Array: for each
+//! section, Block Count (Short), Block States and Biomes (Paletted Containers)), Number of
+//! Block Entities (VarInt), Block Entities, then the light: Sky Light Mask, Block Light Mask,
+//! Empty Sky Light Mask, Empty Block Light Mask (BitSets), Sky Light Array Count (VarInt) and
+//! Sky Light Arrays (VarInt length, 2048 bytes each), Block Light Array Count and Arrays.
+//!
+//! The bit N of the light masks is the section N from one below the world. The sections with
+//! only 0 light are in the empty masks without an array, and those of unknown light in none.
+//!
+//! See https://wiki.vg/Protocol#Chunk_Data_and_Update_Light
+
+use crate::nbt::{binary, Tag};
+use crate::packet::data_types::varint;
+
+use super::{Chunk, ChunkError, ChunkRegistry};
+
+fn write_bit_set(out: &mut Vec<u8>, bits: &[bool]) {
+    let mut longs = vec![0u64; bits.len().div_ceil(64)];
+    for (index, _) in bits.iter().enumerate().filter(|(_, bit)| **bit) {
+        longs[index / 64] |= 1 << (index % 64);
+    }
+    // The trailing empty longs are left out.
+    while longs.last() == Some(&0) {
+        longs.pop();
+    }
+    out.extend(varint::write(longs.len() as i32));
+    for long in longs {
+        out.extend(long.to_be_bytes());
+    }
+}
+
+/// Writes the masks and the arrays of the light of one kind.
+fn light_masks(light: &[Option<Vec<u8>>]) -> (Vec<bool>, Vec<bool>, Vec<&[u8]>) {
+    let mut mask = vec![false; light.len()];
+    let mut empty_mask = vec![false; light.len()];
+    let mut arrays = Vec::new();
+    for (index, light) in light.iter().enumerate() {
+        match light {
+            Some(light) if light.iter().all(|b| *b == 0) => empty_mask[index] = true,
+            Some(light) => {
+                mask[index] = true;
+                arrays.push(&light[..]);
+            }
+            None => {}
+        }
+    }
+    (mask, empty_mask, arrays)
+}
+
+impl Chunk {
+    /// The payload of the Chunk Data and Update Light packet, without block entities.
+    pub fn to_packet(&self, registry: &impl ChunkRegistry) -> Result<Vec<u8>, ChunkError> {
+        let mut out = Vec::new();
+        out.extend(self.x.to_be_bytes());
+        out.extend(self.z.to_be_bytes());
+        let heightmaps = Tag::Compound(self.heightmaps.to_nbt(self.height.height));
+        out.extend(binary::write_network(&heightmaps)?);
+
+        let mut data = Vec::new();
+        for section in &self.sections {
+            data.extend((section.block_count(registry) as i16).to_be_bytes());
+            section.block_states.write(&mut data);
+            section.biomes.write(&mut data);
+        }
+        out.extend(varint::write(data.len() as i32));
+        out.extend(data);
+        // No block entities.
+        out.extend(varint::write(0));
+
+        let (sky_mask, empty_sky_mask, sky_arrays) = light_masks(&self.sky_light);
+        let (block_mask, empty_block_mask, block_arrays) = light_masks(&self.block_light);
+        for mask in [sky_mask, block_mask, empty_sky_mask, empty_block_mask] {
+            write_bit_set(&mut out, &mask);
+        }
+        for arrays in [sky_arrays, block_arrays] {
+            out.extend(varint::write(arrays.len() as i32));
+            for array in arrays {
+                out.extend(varint::write(array.len() as i32));
+                out.extend(array);
+            }
+        }
+        Ok(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::heightmap::Heightmaps;
+    use crate::world::chunk::palette::{PalettedContainer, Strategy};
+    use crate::world::chunk::reader::Reader;
+    use crate::world::chunk::tests::TestRegistry;
+    use crate::world::chunk::{ChunkHeight, AIR, LIGHT_SIZE};
+
+    fn read_bit_set(reader: &mut Reader, len: usize) -> Result<Vec<bool>, ChunkError> {
+        let count = reader.length()?;
+        let mut longs = Vec::with_capacity(count);
+        for _ in 0..count {
+            longs.push(reader.i64()? as u64);
+        }
+        Ok((0..len)
+            .map(|index| {
+                longs
+                    .get(index / 64)
+                    .is_some_and(|long| long >> (index % 64) & 1 == 1)
+            })
+            .collect())
+    }
+
+    fn read_light(
+        reader: &mut Reader,
+        mask: &[bool],
+        empty_mask: &[bool],
+    ) -> Result<Vec<Option<Vec<u8>>>, ChunkError> {
+        let count = reader.length()?;
+        if count != mask.iter().filter(|bit| **bit).count() {
+            return Err(ChunkError::InvalidLight(count));
+        }
+        let mut light = vec![None; mask.len()];
+        for (index, entry) in light.iter_mut().enumerate() {
+            if mask[index] {
+                let len = reader.length()?;
+                if len != LIGHT_SIZE {
+                    return Err(ChunkError::InvalidLight(len));
+                }
+                *entry = Some(reader.bytes(len)?.to_vec());
+            } else if empty_mask[index] {
+                *entry = Some(vec![0; LIGHT_SIZE]);
+            }
+        }
+        Ok(light)
+    }
+
+    /// Reads the payload of a Chunk Data and Update Light packet, for a dimension of the given
+    /// height. The block entities are skipped.
+    fn from_packet(data: &[u8], height: ChunkHeight) -> Result<Chunk, ChunkError> {
+        let mut reader = Reader::new(data);
+        let x = reader.i32()?;
+        let z = reader.i32()?;
+        let mut chunk = Chunk::new(x, z, height, 0);
+
+        let (heightmaps, len) = binary::read_network(reader.rest())?;
+        reader.skip(len)?;
+        if let Some(Tag::Compound(heightmaps)) = heightmaps {
+            chunk.heightmaps = Heightmaps::from_nbt(&heightmaps, height.height)?;
+        }
+
+        let len = reader.length()?;
+        let mut sections = Reader::new(reader.bytes(len)?);
+        for section in &mut chunk.sections {
+            // The block count.
+            sections.i16()?;
+            section.block_states = PalettedContainer::read(Strategy::BLOCKS, &mut sections)?;
+            section.biomes = PalettedContainer::read(Strategy::BIOMES, &mut sections)?;
+        }
+
+        for _ in 0..reader.length()? {
+            // Packed XZ (Byte), Y (Short), Type (VarInt), Data (NBT).
+            reader.skip(3)?;
+            reader.varint()?;
+            let (_, len) = binary::read_network(reader.rest())?;
+            reader.skip(len)?;
+        }
+
+        let sections = height.sections() + 2;
+        let sky_mask = read_bit_set(&mut reader, sections)?;
+        let block_mask = read_bit_set(&mut reader, sections)?;
+        let empty_sky_mask = read_bit_set(&mut reader, sections)?;
+        let empty_block_mask = read_bit_set(&mut reader, sections)?;
+        chunk.sky_light = read_light(&mut reader, &sky_mask, &empty_sky_mask)?;
+        chunk.block_light = read_light(&mut reader, &block_mask, &empty_block_mask)?;
+        Ok(chunk)
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let registry = TestRegistry::new();
+        let mut chunk = Chunk::new(100, -3, ChunkHeight::OVERWORLD, 2);
+        for i in 0..5000 {
+            chunk.set_block(i % 16, (i * 7) % 384 - 64, i / 16 % 16, (i % 300) as u32);
+        }
+        chunk.set_biome(0, 0, 0, 70);
+        chunk.update_heightmaps(&registry);
+        chunk.sky_light[25] = Some(vec![0xF0; LIGHT_SIZE]);
+        chunk.sky_light[24] = Some(vec![0; LIGHT_SIZE]);
+        chunk.block_light[3] = Some((0..LIGHT_SIZE).map(|i| i as u8).collect());
+        // Not sent.
+        chunk.status = "minecraft:full".to_string();
+
+        let packet = chunk.to_packet(&registry).unwrap();
+        assert_eq!(&packet[..8], &[0, 0, 0, 100, 0xFF, 0xFF, 0xFF, 0xFD]);
+        let mut read = from_packet(&packet, ChunkHeight::OVERWORLD).unwrap();
+        read.status = chunk.status.clone();
+        assert_eq!(read, chunk);
+
+        // Truncated.
+        assert!(from_packet(&packet[..packet.len() - 1], ChunkHeight::OVERWORLD).is_err());
+    }
+
+    #[test]
+    fn test_empty_chunk() {
+        let registry = TestRegistry::new();
+        let chunk = Chunk::new(0, 0, ChunkHeight::NETHER, 0);
+        let packet = chunk.to_packet(&registry).unwrap();
+
+        let mut reader = Reader::new(&packet[8..]);
+        let (_, len) = binary::read_network(reader.rest()).unwrap();
+        reader.skip(len).unwrap();
+        // 16 sections of a block count, then a single air block and a single biome.
+        assert_eq!(reader.length().unwrap(), 16 * 8);
+        assert_eq!(reader.bytes(8).unwrap(), [0, 0, 0, AIR as u8, 0, 0, 0, 0]);
+        assert_eq!(from_packet(&packet, ChunkHeight::NETHER).unwrap(), chunk);
+    }
+}
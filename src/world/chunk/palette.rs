@@ -0,0 +1,464 @@
+//! Paletted containers, which store the block states of a section or its biomes.
+//! - Single: every entry has the same value, and nothing else is stored.
+//! - Indirect: the entries are indices into a palette of the values, packed with few bits each.
+//!   The bits grow as the palette fills up.
+//! - Direct: once the palette would need too many bits, the entries are the global IDs.
+//!
+//! The entries are packed in longs, from the lowest bits, and never span two longs.
+
+use crate::packet::data_types::varint;
+use crate::world::biome;
+
+#[cfg(test)]
+use super::reader::Reader;
+use super::ChunkError;
+
+/// How a kind of container grows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Strategy {
+    /// The number of entries, 16×16×16 blocks or 4×4×4 biomes.
+    pub entries: usize,
+    /// The bits of the smallest indirect palette.
+    pub min_bits: u8,
+    /// The bits of the largest indirect palette, above which the IDs are stored directly.
+    pub max_bits: u8,
+    /// The bits of the global IDs.
+    pub direct_bits: u8,
+}
+
+impl Strategy {
+    pub const BLOCKS: Strategy = Strategy {
+        entries: 4096,
+        min_bits: 4,
+        max_bits: 8,
+        direct_bits: 15,
+    };
+    pub const BIOMES: Strategy = Strategy {
+        entries: 64,
+        min_bits: 1,
+        max_bits: 3,
+        direct_bits: ceil_log2(biome::BIOMES.len()),
+    };
+
+    /// The bits of an indirect palette of `len` values, at least the minimum.
+    fn bits_for(&self, len: usize) -> u8 {
+        ceil_log2(len).max(self.min_bits)
+    }
+}
+
+/// The bits needed to tell `len` values apart.
+pub const fn ceil_log2(len: usize) -> u8 {
+    match len {
+        0 | 1 => 0,
+        len => (usize::BITS - (len - 1).leading_zeros()) as u8,
+    }
+}
+
+/// Entries of a fixed number of bits packed in longs.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BitStorage {
+    bits: u8,
+    len: usize,
+    data: Vec<u64>,
+}
+
+impl BitStorage {
+    /// Storage for `len` entries, all 0.
+    pub fn new(bits: u8, len: usize) -> Self {
+        BitStorage {
+            bits,
+            len,
+            data: vec![0; Self::longs(bits, len)],
+        }
+    }
+
+    /// Storage for `len` entries from packed longs, which must be of the right number.
+    pub fn from_data(bits: u8, len: usize, data: Vec<u64>) -> Result<Self, ChunkError> {
+        if data.len() != Self::longs(bits, len) {
+            return Err(ChunkError::InvalidDataLength(data.len()));
+        }
+        Ok(BitStorage { bits, len, data })
+    }
+
+    fn longs(bits: u8, len: usize) -> usize {
+        match bits {
+            0 => 0,
+            bits => len.div_ceil(64 / bits as usize),
+        }
+    }
+
+    pub fn data(&self) -> &[u64] {
+        &self.data
+    }
+
+    /// The long and the shift of an entry.
+    fn position(&self, index: usize) -> (usize, u32) {
+        let per_long = 64 / self.bits as usize;
+        let shift = (index % per_long) * self.bits as usize;
+        (index / per_long, shift as u32)
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        assert!(index < self.len, "Entry {index} out of {}", self.len);
+        if self.bits == 0 {
+            return 0;
+        }
+        let (long, shift) = self.position(index);
+        let mask = (1u64 << self.bits) - 1;
+        ((self.data[long] >> shift) & mask) as u32
+    }
+
+    pub fn set(&mut self, index: usize, value: u32) {
+        assert!(index < self.len, "Entry {index} out of {}", self.len);
+        if self.bits == 0 {
+            return;
+        }
+        let (long, shift) = self.position(index);
+        let mask = (1u64 << self.bits) - 1;
+        self.data[long] = (self.data[long] & !(mask << shift)) | ((value as u64 & mask) << shift);
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.len).map(|index| self.get(index))
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Palette {
+    Single(u32),
+    Indirect(Vec<u32>),
+    Direct,
+}
+
+#[derive(Clone, Debug)]
+pub struct PalettedContainer {
+    strategy: Strategy,
+    palette: Palette,
+    storage: BitStorage,
+}
+
+/// The containers are equal if their entries are, whatever their palettes.
+impl PartialEq for PalettedContainer {
+    fn eq(&self, other: &Self) -> bool {
+        self.strategy == other.strategy && self.iter().eq(other.iter())
+    }
+}
+
+impl Eq for PalettedContainer {}
+
+impl PalettedContainer {
+    /// A container with every entry set to `value`.
+    pub fn new(strategy: Strategy, value: u32) -> Self {
+        PalettedContainer {
+            strategy,
+            palette: Palette::Single(value),
+            storage: BitStorage::new(0, strategy.entries),
+        }
+    }
+
+    pub fn palette(&self) -> &Palette {
+        &self.palette
+    }
+
+    /// The bits of each entry, 0 for a single value.
+    #[cfg(test)]
+    pub fn bits(&self) -> u8 {
+        self.storage.bits
+    }
+
+    pub fn get(&self, index: usize) -> u32 {
+        let entry = self.storage.get(index);
+        match &self.palette {
+            Palette::Single(value) => *value,
+            Palette::Indirect(values) => values[entry as usize],
+            Palette::Direct => entry,
+        }
+    }
+
+    /// Sets an entry and returns its previous value. The palette grows if the value is new.
+    pub fn set(&mut self, index: usize, value: u32) -> u32 {
+        let previous = self.get(index);
+        if previous == value {
+            return previous;
+        }
+        let entry = match &self.palette {
+            Palette::Single(single) => {
+                let values = vec![*single, value];
+                self.resize(Palette::Indirect(values), self.strategy.min_bits);
+                1
+            }
+            Palette::Indirect(values) => match values.iter().position(|v| *v == value) {
+                Some(entry) => entry as u32,
+                None => self.add(value),
+            },
+            Palette::Direct => value,
+        };
+        self.storage.set(index, entry);
+        previous
+    }
+
+    /// Adds a value to an indirect palette, and returns its entry.
+    fn add(&mut self, value: u32) -> u32 {
+        if let Palette::Indirect(values) = &mut self.palette {
+            if values.len() < 1 << self.storage.bits {
+                values.push(value);
+                return values.len() as u32 - 1;
+            }
+        }
+
+        // The palette is full.
+        let bits = self.storage.bits + 1;
+        if bits > self.strategy.max_bits {
+            self.resize(Palette::Direct, self.strategy.direct_bits);
+            return value;
+        }
+        let Palette::Indirect(values) = &self.palette else {
+            unreachable!("Only indirect palettes grow");
+        };
+        let mut values = values.clone();
+        values.push(value);
+        let entry = values.len() as u32 - 1;
+        self.resize(Palette::Indirect(values), bits);
+        entry
+    }
+
+    /// Repacks the entries with another palette. The values must all be in the new palette.
+    fn resize(&mut self, palette: Palette, bits: u8) {
+        let mut storage = BitStorage::new(bits, self.strategy.entries);
+        for index in 0..self.strategy.entries {
+            let value = self.get(index);
+            let entry = match &palette {
+                Palette::Single(_) => 0,
+                Palette::Indirect(values) => {
+                    values.iter().position(|v| *v == value).unwrap() as u32
+                }
+                Palette::Direct => value,
+            };
+            storage.set(index, entry);
+        }
+        self.palette = palette;
+        self.storage = storage;
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = u32> + '_ {
+        (0..self.strategy.entries).map(|index| self.get(index))
+    }
+
+    /// The values in use and the entries as indices into them, see `palette_and_data`.
+    pub fn to_palette_and_data(&self) -> (Vec<u32>, Option<Vec<u64>>) {
+        palette_and_data(self.strategy, self.iter())
+    }
+
+    /// The reverse of `to_palette_and_data`. Missing data means that every entry is the first
+    /// value.
+    pub fn from_palette_and_data(
+        strategy: Strategy,
+        values: Vec<u32>,
+        data: Option<Vec<u64>>,
+    ) -> Result<Self, ChunkError> {
+        let Some(&first) = values.first() else {
+            return Err(ChunkError::EmptyPalette);
+        };
+        let data = match data {
+            Some(data) if values.len() > 1 => data,
+            _ => return Ok(Self::new(strategy, first)),
+        };
+        let storage =
+            BitStorage::from_data(strategy.bits_for(values.len()), strategy.entries, data)?;
+        if storage.iter().any(|entry| entry as usize >= values.len()) {
+            return Err(ChunkError::InvalidPaletteIndex);
+        }
+
+        let bits = storage.bits;
+        let mut container = PalettedContainer {
+            strategy,
+            palette: Palette::Indirect(values),
+            storage,
+        };
+        if bits > strategy.max_bits {
+            container.resize(Palette::Direct, strategy.direct_bits);
+        }
+        Ok(container)
+    }
+
+    /// Writes the container like in the chunk packets: the bits, the palette and the longs.
+    pub fn write(&self, out: &mut Vec<u8>) {
+        out.push(self.storage.bits);
+        match &self.palette {
+            Palette::Single(value) => out.extend(varint::write(*value as i32)),
+            Palette::Indirect(values) => {
+                out.extend(varint::write(values.len() as i32));
+                for value in values {
+                    out.extend(varint::write(*value as i32));
+                }
+            }
+            Palette::Direct => {}
+        }
+        out.extend(varint::write(self.storage.data.len() as i32));
+        for long in &self.storage.data {
+            out.extend(long.to_be_bytes());
+        }
+    }
+
+    /// Reads a container written by `write`.
+    #[cfg(test)]
+    pub(super) fn read(strategy: Strategy, reader: &mut Reader) -> Result<Self, ChunkError> {
+        let bits = reader.u8()?;
+        let palette = match bits {
+            0 => Palette::Single(reader.varint()? as u32),
+            bits if bits <= strategy.max_bits => {
+                let len = reader.length()?;
+                let mut values = Vec::with_capacity(len.min(1 << strategy.max_bits));
+                for _ in 0..len {
+                    values.push(reader.varint()? as u32);
+                }
+                Palette::Indirect(values)
+            }
+            _ => Palette::Direct,
+        };
+        let bits = match palette {
+            Palette::Single(_) => 0,
+            Palette::Indirect(_) => bits.max(strategy.min_bits),
+            Palette::Direct => strategy.direct_bits,
+        };
+
+        let len = reader.length()?;
+        let mut data = Vec::with_capacity(len.min(strategy.entries));
+        for _ in 0..len {
+            data.push(reader.i64()? as u64);
+        }
+        let storage = BitStorage::from_data(bits, strategy.entries, data)?;
+        if let Palette::Indirect(values) = &palette {
+            if storage.iter().any(|entry| entry as usize >= values.len()) {
+                return Err(ChunkError::InvalidPaletteIndex);
+            }
+        }
+        Ok(PalettedContainer {
+            strategy,
+            palette,
+            storage,
+        })
+    }
+}
+
+/// The distinct entries, in the order they come, and the entries as indices into them, packed
+/// with the fewest bits. The entries are None for a single value.
+/// This is how the world files store the containers.
+pub fn palette_and_data<T: PartialEq>(
+    strategy: Strategy,
+    entries: impl Iterator<Item = T>,
+) -> (Vec<T>, Option<Vec<u64>>) {
+    let mut values = Vec::new();
+    let mut indices = Vec::with_capacity(strategy.entries);
+    for value in entries {
+        let index = match values.iter().position(|v| *v == value) {
+            Some(index) => index,
+            None => {
+                values.push(value);
+                values.len() - 1
+            }
+        };
+        indices.push(index as u32);
+    }
+    if values.len() == 1 {
+        return (values, None);
+    }
+    let mut storage = BitStorage::new(strategy.bits_for(values.len()), indices.len());
+    for (index, entry) in indices.into_iter().enumerate() {
+        storage.set(index, entry);
+    }
+    (values, Some(storage.data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_bit_storage() {
+        // 5 bits: 12 entries per long, the last 4 bits unused.
+        let mut storage = BitStorage::new(5, 100);
+        assert_eq!(storage.data().len(), 9);
+        for index in 0..100 {
+            storage.set(index, (index * 7 % 32) as u32);
+        }
+        for index in 0..100 {
+            assert_eq!(storage.get(index), (index * 7 % 32) as u32);
+        }
+        assert_eq!(storage.data()[0] >> 60, 0);
+        assert!(BitStorage::from_data(5, 100, vec![0; 8]).is_err());
+    }
+
+    #[test]
+    fn test_palette_resize() {
+        let mut container = PalettedContainer::new(Strategy::BLOCKS, 0);
+        assert_eq!(container.bits(), 0);
+
+        // 16 values fit in 4 bits, then one more bit each time the palette doubles.
+        let mut expected = vec![0; 4096];
+        for value in 1..=300u32 {
+            let index = (value as usize * 13) % 4096;
+            container.set(index, value);
+            expected[index] = value;
+            let bits = match value + 1 {
+                len if len <= 16 => 4,
+                len if len <= 256 => ceil_log2(len as usize),
+                _ => Strategy::BLOCKS.direct_bits,
+            };
+            assert_eq!(container.bits(), bits, "after {value}");
+        }
+        assert_eq!(container.palette(), &Palette::Direct);
+        assert!(container.iter().eq(expected.iter().copied()));
+
+        // Setting the same value keeps the palette.
+        assert_eq!(container.set(13, 1), 1);
+        assert_eq!(container.palette(), &Palette::Direct);
+    }
+
+    #[test]
+    fn test_biome_resize() {
+        let mut container = PalettedContainer::new(Strategy::BIOMES, 3);
+        for index in 0..64 {
+            container.set(index, index as u32 % 9);
+        }
+        assert_eq!(container.palette(), &Palette::Direct);
+        assert_eq!(container.bits(), Strategy::BIOMES.direct_bits);
+        assert_eq!(container.get(17), 8);
+        // The 64 vanilla biomes, like the client.
+        assert_eq!(Strategy::BIOMES.direct_bits, 6);
+    }
+
+    #[test]
+    fn test_codecs() {
+        let mut rng = rand::thread_rng();
+        for distinct in [1, 2, 17, 200, 1000] {
+            let mut container = PalettedContainer::new(Strategy::BLOCKS, 0);
+            for index in 0..4096 {
+                if rng.gen_bool(0.5) {
+                    container.set(index, rng.gen_range(0..distinct));
+                }
+            }
+
+            let mut packet = Vec::new();
+            container.write(&mut packet);
+            let mut reader = Reader::new(&packet);
+            let read = PalettedContainer::read(Strategy::BLOCKS, &mut reader).unwrap();
+            assert!(read.iter().eq(container.iter()));
+            assert_eq!(reader.remaining(), 0);
+
+            let (values, data) = container.to_palette_and_data();
+            let read = PalettedContainer::from_palette_and_data(Strategy::BLOCKS, values, data);
+            assert!(read.unwrap().iter().eq(container.iter()));
+        }
+
+        assert!(matches!(
+            PalettedContainer::from_palette_and_data(
+                Strategy::BIOMES,
+                vec![1, 2, 3],
+                Some(vec![3, 0])
+            ),
+            Err(ChunkError::InvalidPaletteIndex)
+        ));
+    }
+}
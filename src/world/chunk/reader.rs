@@ -0,0 +1,75 @@
+//! Reads the fields of the chunk packets, checking that the data is long enough.
+
+use crate::packet::data_types::{varint, CodecError};
+
+use super::ChunkError;
+
+pub struct Reader<'a> {
+    data: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Reader { data, position: 0 }
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.data.len() - self.position
+    }
+
+    pub fn rest(&self) -> &'a [u8] {
+        &self.data[self.position..]
+    }
+
+    pub fn skip(&mut self, len: usize) -> Result<(), ChunkError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    pub fn bytes(&mut self, len: usize) -> Result<&'a [u8], ChunkError> {
+        let bytes = self
+            .data
+            .get(self.position..self.position + len)
+            .ok_or(CodecError::UnexpectedEnd)?;
+        self.position += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], ChunkError> {
+        Ok(self.bytes(N)?.try_into().unwrap())
+    }
+
+    pub fn u8(&mut self) -> Result<u8, ChunkError> {
+        Ok(self.array::<1>()?[0])
+    }
+
+    pub fn i16(&mut self) -> Result<i16, ChunkError> {
+        Ok(i16::from_be_bytes(self.array()?))
+    }
+
+    pub fn i32(&mut self) -> Result<i32, ChunkError> {
+        Ok(i32::from_be_bytes(self.array()?))
+    }
+
+    pub fn i64(&mut self) -> Result<i64, ChunkError> {
+        Ok(i64::from_be_bytes(self.array()?))
+    }
+
+    pub fn varint(&mut self) -> Result<i32, ChunkError> {
+        if self.remaining() == 0 {
+            return Err(CodecError::UnexpectedEnd.into());
+        }
+        let (value, len) = varint::read(self.rest())?;
+        self.position += len;
+        Ok(value)
+    }
+
+    /// A length prefix, which must not be negative nor longer than the rest of the data.
+    pub fn length(&mut self) -> Result<usize, ChunkError> {
+        let len = usize::try_from(self.varint()?).map_err(|_| CodecError::NegativeLength)?;
+        if len > self.remaining() {
+            return Err(CodecError::UnexpectedEnd.into());
+        }
+        Ok(len)
+    }
+}
@@ -0,0 +1,608 @@
+//! The dimensions of the world. Their types are the vanilla entries of the
+//! `minecraft:dimension_type` registry, which the client is sent, and each loaded dimension has
+//! its generator and its region files, in the folder of the dimension. The chunks that players
+//! see stay loaded until no one sees them, and those they simulate are ticked.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use thiserror::Error;
+
+use super::chunk::{Chunk, ChunkError, ChunkHeight, VanillaRegistry};
+use super::generator::ChunkGenerator;
+use super::region::{RegionError, RegionStorage};
+use crate::metrics::METRICS;
+use crate::nbt::{Compound, Tag};
+
+#[derive(Error, Debug)]
+pub enum DimensionError {
+    #[error(transparent)]
+    Region(#[from] RegionError),
+    #[error(transparent)]
+    Chunk(#[from] ChunkError),
+    #[error("The generation of the chunk was stopped")]
+    Generation,
+    #[error(transparent)]
+    Task(#[from] tokio::task::JoinError),
+}
+
+/// The properties of the dimensions of a type.
+#[derive(Debug, PartialEq)]
+pub struct DimensionType {
+    pub id: &'static str,
+    pub height: ChunkHeight,
+    /// The height in which the portals and the chorus fruits may take the players.
+    pub logical_height: u32,
+    pub has_skylight: bool,
+    pub has_ceiling: bool,
+    /// Water evaporates, and lava flows faster.
+    pub ultrawarm: bool,
+    /// The compasses and the clocks work, and the players may sleep.
+    pub natural: bool,
+    /// How the coordinates are scaled when going to another dimension.
+    pub coordinate_scale: f64,
+    pub bed_works: bool,
+    pub respawn_anchor_works: bool,
+    pub piglin_safe: bool,
+    pub has_raids: bool,
+    pub ambient_light: f32,
+    /// The time of the day that never changes, if any.
+    pub fixed_time: Option<i64>,
+    /// The light levels from which the monsters may spawn, picked uniformly.
+    pub monster_spawn_light_level: (i32, i32),
+    pub monster_spawn_block_light_limit: i32,
+    /// The tag of the blocks on which the fire burns forever.
+    pub infiniburn: &'static str,
+    /// How the client renders the sky and the fog.
+    pub effects: &'static str,
+}
+
+pub static OVERWORLD: DimensionType = DimensionType {
+    id: "minecraft:overworld",
+    height: ChunkHeight::OVERWORLD,
+    logical_height: 384,
+    has_skylight: true,
+    has_ceiling: false,
+    ultrawarm: false,
+    natural: true,
+    coordinate_scale: 1.0,
+    bed_works: true,
+    respawn_anchor_works: false,
+    piglin_safe: false,
+    has_raids: true,
+    ambient_light: 0.0,
+    fixed_time: None,
+    monster_spawn_light_level: (0, 7),
+    monster_spawn_block_light_limit: 0,
+    infiniburn: "#minecraft:infiniburn_overworld",
+    effects: "minecraft:overworld",
+};
+
+pub static THE_NETHER: DimensionType = DimensionType {
+    id: "minecraft:the_nether",
+    height: ChunkHeight::NETHER,
+    logical_height: 128,
+    has_skylight: false,
+    has_ceiling: true,
+    ultrawarm: true,
+    natural: false,
+    coordinate_scale: 8.0,
+    bed_works: false,
+    respawn_anchor_works: true,
+    piglin_safe: true,
+    has_raids: false,
+    ambient_light: 0.1,
+    fixed_time: Some(18000),
+    monster_spawn_light_level: (7, 7),
+    monster_spawn_block_light_limit: 15,
+    infiniburn: "#minecraft:infiniburn_nether",
+    effects: "minecraft:the_nether",
+};
+
+pub static THE_END: DimensionType = DimensionType {
+    id: "minecraft:the_end",
+    height: ChunkHeight::END,
+    logical_height: 256,
+    has_skylight: false,
+    has_ceiling: false,
+    ultrawarm: false,
+    natural: false,
+    coordinate_scale: 1.0,
+    bed_works: false,
+    respawn_anchor_works: false,
+    piglin_safe: false,
+    has_raids: true,
+    ambient_light: 0.0,
+    fixed_time: Some(6000),
+    monster_spawn_light_level: (0, 7),
+    monster_spawn_block_light_limit: 0,
+    infiniburn: "#minecraft:infiniburn_end",
+    effects: "minecraft:the_end",
+};
+
+/// The vanilla dimension types, in the order of `consts::minecraft::DIMENSIONS`.
+pub static DIMENSION_TYPES: [&DimensionType; 3] = [&OVERWORLD, &THE_NETHER, &THE_END];
+
+impl DimensionType {
+    /// The type of a vanilla dimension, whose namespace may be omitted.
+    pub fn get(id: &str) -> Option<&'static DimensionType> {
+        let id = id.strip_prefix("minecraft:").unwrap_or(id);
+        DIMENSION_TYPES
+            .iter()
+            .find(|kind| kind.id.strip_prefix("minecraft:") == Some(id))
+            .copied()
+    }
+
+    /// The entry of the registry, as the client reads it.
+    pub fn to_nbt(&self) -> Compound {
+        let (min, max) = self.monster_spawn_light_level;
+        let light_level = match min == max {
+            true => Tag::Int(min),
+            false => Tag::Compound(Compound::from([
+                ("type".to_string(), Tag::from("minecraft:uniform")),
+                ("min_inclusive".to_string(), Tag::Int(min)),
+                ("max_inclusive".to_string(), Tag::Int(max)),
+            ])),
+        };
+        let mut nbt = Compound::from([
+            ("min_y".to_string(), Tag::Int(self.height.min_y)),
+            ("height".to_string(), Tag::Int(self.height.height as i32)),
+            (
+                "logical_height".to_string(),
+                Tag::Int(self.logical_height as i32),
+            ),
+            ("has_skylight".to_string(), Tag::from(self.has_skylight)),
+            ("has_ceiling".to_string(), Tag::from(self.has_ceiling)),
+            ("ultrawarm".to_string(), Tag::from(self.ultrawarm)),
+            ("natural".to_string(), Tag::from(self.natural)),
+            (
+                "coordinate_scale".to_string(),
+                Tag::Double(self.coordinate_scale),
+            ),
+            ("bed_works".to_string(), Tag::from(self.bed_works)),
+            (
+                "respawn_anchor_works".to_string(),
+                Tag::from(self.respawn_anchor_works),
+            ),
+            ("piglin_safe".to_string(), Tag::from(self.piglin_safe)),
+            ("has_raids".to_string(), Tag::from(self.has_raids)),
+            ("ambient_light".to_string(), Tag::Float(self.ambient_light)),
+            ("monster_spawn_light_level".to_string(), light_level),
+            (
+                "monster_spawn_block_light_limit".to_string(),
+                Tag::Int(self.monster_spawn_block_light_limit),
+            ),
+            ("infiniburn".to_string(), Tag::from(self.infiniburn)),
+            ("effects".to_string(), Tag::from(self.effects)),
+        ]);
+        if let Some(time) = self.fixed_time {
+            nbt.insert("fixed_time".to_string(), Tag::Long(time));
+        }
+        nbt
+    }
+}
+
+/// Where an entity is: its dimension, and its position in it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub dimension: &'static str,
+    pub x: f64,
+    pub y: f64,
+    pub z: f64,
+}
+
+impl Location {
+    /// The chunk of the location.
+    pub fn chunk(&self) -> (i32, i32) {
+        ((self.x.floor() as i32) >> 4, (self.z.floor() as i32) >> 4)
+    }
+}
+
+/// A chunk watched by some players.
+#[derive(Default)]
+struct WatchedChunk {
+    /// None until the chunk is loaded.
+    chunk: Option<Chunk>,
+    /// Whether the chunk changed since it was loaded or saved.
+    dirty: bool,
+    /// Held while the chunk is loaded, so that its other viewers wait for it instead of loading
+    /// it again.
+    loading: Arc<tokio::sync::Mutex<()>>,
+    /// The players who see the chunk.
+    viewers: u32,
+    /// The players close enough for the chunk to be simulated.
+    simulators: u32,
+}
+
+/// A loaded dimension: its chunks are read from its region files, or generated and saved there
+/// the first time they are needed.
+pub struct Dimension {
+    pub kind: &'static DimensionType,
+    /// None if the generator of 'level.dat' is not supported.
+    generator: Option<Arc<dyn ChunkGenerator>>,
+    /// Shared with the blocking threads that read and write the region files.
+    regions: Arc<Mutex<RegionStorage>>,
+    watched: Mutex<HashMap<(i32, i32), WatchedChunk>>,
+}
+
+impl Dimension {
+    pub fn new(
+        kind: &'static DimensionType,
+        generator: Option<Arc<dyn ChunkGenerator>>,
+        regions: RegionStorage,
+    ) -> Self {
+        Dimension {
+            kind,
+            generator,
+            regions: Arc::new(Mutex::new(regions)),
+            watched: Mutex::default(),
+        }
+    }
+
+    pub fn id(&self) -> &'static str {
+        self.kind.id
+    }
+
+    /// Reads a chunk from the region files, None if it was never saved. The files are read on a
+    /// blocking thread, off the runtime.
+    pub async fn load_chunk(&self, x: i32, z: i32) -> Result<Option<Chunk>, DimensionError> {
+        let regions = Arc::clone(&self.regions);
+        let height = self.kind.height;
+        tokio::task::spawn_blocking(move || {
+            let nbt = regions.lock().unwrap().read_chunk(x, z)?;
+            let chunk = nbt.map(|nbt| Chunk::from_nbt(nbt, height, &VanillaRegistry));
+            Ok(chunk.transpose()?)
+        })
+        .await?
+    }
+
+    /// Writes a chunk to the region files, on a blocking thread.
+    pub async fn save_chunk(&self, chunk: Chunk) -> Result<(), DimensionError> {
+        let regions = Arc::clone(&self.regions);
+        tokio::task::spawn_blocking(move || write_chunk(&regions, &chunk)).await?
+    }
+
+    /// Returns a chunk, from the region files if it was saved. Otherwise it is generated and
+    /// saved, or None if the dimension's generator is not supported.
+    pub async fn chunk(&self, x: i32, z: i32) -> Result<Option<Chunk>, DimensionError> {
+        if let Some(chunk) = self.load_chunk(x, z).await? {
+            return Ok(Some(chunk));
+        }
+        let Some(generator) = &self.generator else {
+            return Ok(None);
+        };
+        let chunk = super::WORKERS
+            .generate(generator, x, z)
+            .await
+            .map_err(|_| DimensionError::Generation)?;
+        self.save_chunk(chunk.clone()).await?;
+        Ok(Some(chunk))
+    }
+
+    /// Adds a viewer to a chunk, which stays loaded until it has none.
+    pub fn watch(&self, x: i32, z: i32) {
+        let mut watched = self.watched.lock().unwrap();
+        watched.entry((x, z)).or_default().viewers += 1;
+    }
+
+    /// Removes a viewer from a chunk. The chunk is unloaded when it has none, and saved first if
+    /// it changed. It stays loaded while it is saved, so that a new viewer does not read it from
+    /// the region files before.
+    pub async fn unwatch(&self, x: i32, z: i32) -> Result<(), DimensionError> {
+        let changed = {
+            let mut watched = self.watched.lock().unwrap();
+            let Some(entry) = watched.get_mut(&(x, z)) else {
+                return Ok(());
+            };
+            entry.viewers = entry.viewers.saturating_sub(1);
+            if entry.viewers > 0 {
+                return Ok(());
+            }
+            match entry.dirty {
+                true => {
+                    entry.dirty = false;
+                    entry.chunk.clone()
+                }
+                false => None,
+            }
+        };
+        let saved = match changed {
+            Some(chunk) => self.save_chunk(chunk).await,
+            None => Ok(()),
+        };
+
+        let mut watched = self.watched.lock().unwrap();
+        if watched.get(&(x, z)).is_some_and(|entry| entry.viewers == 0) {
+            let entry = watched.remove(&(x, z));
+            if entry.is_some_and(|entry| entry.chunk.is_some()) {
+                METRICS.add_loaded_chunks(-1);
+            }
+        }
+        saved
+    }
+
+    /// Returns a watched chunk, loading it the first time. Its viewers that ask for it while it
+    /// is loaded wait for it. The chunks that are not watched are returned without being kept.
+    pub async fn watched_chunk(&self, x: i32, z: i32) -> Result<Option<Chunk>, DimensionError> {
+        let loading = match self.watched_entry(x, z) {
+            Some((Some(chunk), _)) => return Ok(Some(chunk)),
+            Some((None, loading)) => loading,
+            None => return self.chunk(x, z).await,
+        };
+        let _loading = loading.lock().await;
+        // Another viewer may have loaded it meanwhile.
+        if let Some((Some(chunk), _)) = self.watched_entry(x, z) {
+            return Ok(Some(chunk));
+        }
+        let Some(chunk) = self.chunk(x, z).await? else {
+            return Ok(None);
+        };
+        let mut watched = self.watched.lock().unwrap();
+        match watched.get_mut(&(x, z)) {
+            Some(entry) => {
+                if entry.chunk.is_none() {
+                    METRICS.add_loaded_chunks(1);
+                }
+                Ok(Some(entry.chunk.get_or_insert(chunk).clone()))
+            }
+            None => Ok(Some(chunk)),
+        }
+    }
+
+    /// The chunk of a watched entry if it is loaded, with the lock held while it is loaded.
+    fn watched_entry(
+        &self,
+        x: i32,
+        z: i32,
+    ) -> Option<(Option<Chunk>, Arc<tokio::sync::Mutex<()>>)> {
+        let watched = self.watched.lock().unwrap();
+        let entry = watched.get(&(x, z))?;
+        Some((entry.chunk.clone(), Arc::clone(&entry.loading)))
+    }
+
+    /// Adds or removes a simulator of a watched chunk.
+    pub fn simulate(&self, x: i32, z: i32, simulated: bool) {
+        if let Some(entry) = self.watched.lock().unwrap().get_mut(&(x, z)) {
+            entry.simulators = match simulated {
+                true => entry.simulators + 1,
+                false => entry.simulators.saturating_sub(1),
+            };
+        }
+    }
+
+    /// The number of chunks watched by players, loaded or not.
+    #[cfg(test)]
+    pub fn watched_chunks(&self) -> usize {
+        self.watched.lock().unwrap().len()
+    }
+
+    /// Saves the loaded chunks that changed, which stay loaded. They are copied first, so that
+    /// the dimension keeps ticking while they are written.
+    pub fn save_watched(&self) -> Result<(), DimensionError> {
+        let chunks: Vec<Chunk> = {
+            let mut watched = self.watched.lock().unwrap();
+            watched
+                .values_mut()
+                .filter(|entry| entry.dirty)
+                .filter_map(|entry| {
+                    entry.dirty = false;
+                    entry.chunk.clone()
+                })
+                .collect()
+        };
+        for (index, chunk) in chunks.iter().enumerate() {
+            if let Err(error) = write_chunk(&self.regions, chunk) {
+                // The chunks that were not saved are saved the next time.
+                let mut watched = self.watched.lock().unwrap();
+                for chunk in &chunks[index..] {
+                    if let Some(entry) = watched.get_mut(&(chunk.x, chunk.z)) {
+                        entry.dirty = true;
+                    }
+                }
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    /// Advances the simulated chunks by one tick.
+    pub fn tick(&self) {
+        let mut watched = self.watched.lock().unwrap();
+        for entry in watched.values_mut().filter(|entry| entry.simulators > 0) {
+            if let Some(chunk) = &mut entry.chunk {
+                chunk.inhabited_time += 1;
+                entry.dirty = true;
+            }
+        }
+    }
+}
+
+/// Writes a chunk to the region files, blocking until it is written.
+fn write_chunk(regions: &Mutex<RegionStorage>, chunk: &Chunk) -> Result<(), DimensionError> {
+    let nbt = chunk.to_nbt(&VanillaRegistry);
+    Ok(regions
+        .lock()
+        .unwrap()
+        .write_chunk(chunk.x, chunk.z, &nbt)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempfile::TempDir;
+
+    use super::*;
+    use crate::world::generator::flat::{FlatGenerator, FlatSettings};
+    use crate::world::region::Compression;
+
+    #[test]
+    fn test_types() {
+        assert_eq!(DimensionType::get("the_nether"), Some(&THE_NETHER));
+        assert_eq!(DimensionType::get("minecraft:the_end"), Some(&THE_END));
+        assert_eq!(DimensionType::get("minecraft:the_moon"), None);
+        for (kind, id) in DIMENSION_TYPES
+            .iter()
+            .zip(crate::consts::minecraft::DIMENSIONS)
+        {
+            assert_eq!(kind.id, id);
+        }
+
+        let nether = THE_NETHER.to_nbt();
+        assert_eq!(nether["fixed_time"], Tag::Long(18000));
+        assert_eq!(nether["monster_spawn_light_level"], Tag::Int(7));
+        assert_eq!(nether["logical_height"], Tag::Int(128));
+        let overworld = OVERWORLD.to_nbt();
+        assert_eq!(overworld["min_y"], Tag::Int(-64));
+        assert!(!overworld.contains_key("fixed_time"));
+        assert!(matches!(
+            overworld["monster_spawn_light_level"],
+            Tag::Compound(_)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_chunks() {
+        let dir = TempDir::new().unwrap();
+        let generator = FlatGenerator::new(&FlatSettings::default(), THE_END.height);
+        let dimension = Dimension::new(
+            &THE_END,
+            Some(Arc::new(generator)),
+            RegionStorage::new(dir.path(), Compression::Zlib),
+        );
+        assert!(dimension.load_chunk(3, -40).await.unwrap().is_none());
+        let chunk = dimension.chunk(3, -40).await.unwrap().unwrap();
+        assert_eq!((chunk.x, chunk.z), (3, -40));
+        assert!(dir.path().join("r.0.-2.mca").exists());
+
+        // Read again from the region file.
+        let dimension = Dimension::new(
+            &THE_END,
+            None,
+            RegionStorage::new(dir.path(), Compression::Zlib),
+        );
+        let loaded = dimension.chunk(3, -40).await.unwrap().unwrap();
+        assert_eq!(loaded.sections(), chunk.sections());
+        assert_eq!(loaded.heightmaps, chunk.heightmaps);
+        assert!(dimension.chunk(4, -40).await.unwrap().is_none());
+
+        let location = Location {
+            dimension: THE_END.id,
+            x: -0.5,
+            y: 70.0,
+            z: 33.0,
+        };
+        assert_eq!(location.chunk(), (-1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_watch() {
+        let dir = TempDir::new().unwrap();
+        let generator = FlatGenerator::new(&FlatSettings::default(), OVERWORLD.height);
+        let dimension = Dimension::new(
+            &OVERWORLD,
+            Some(Arc::new(generator)),
+            RegionStorage::new(dir.path(), Compression::Zlib),
+        );
+        dimension.watch(1, 2);
+        dimension.watch(1, 2);
+        dimension.watch(5, 5);
+        let chunk = dimension.watched_chunk(1, 2).await.unwrap().unwrap();
+        assert_eq!((chunk.x, chunk.z), (1, 2));
+        assert_eq!(dimension.watched_chunks(), 2);
+
+        // Only the loaded and simulated chunks are ticked.
+        dimension.simulate(1, 2, true);
+        dimension.simulate(5, 5, true);
+        dimension.simulate(9, 9, true);
+        for _ in 0..3 {
+            dimension.tick();
+        }
+        dimension.simulate(1, 2, false);
+        dimension.tick();
+        let chunk = dimension.watched_chunk(1, 2).await.unwrap().unwrap();
+        assert_eq!(chunk.inhabited_time, 3);
+        assert_eq!(dimension.watched_chunks(), 2);
+
+        // Saved when the last viewer leaves.
+        dimension.unwatch(1, 2).await.unwrap();
+        let saved = dimension.load_chunk(1, 2).await.unwrap().unwrap();
+        assert_eq!(saved.inhabited_time, 0);
+        dimension.unwatch(1, 2).await.unwrap();
+        let saved = dimension.load_chunk(1, 2).await.unwrap().unwrap();
+        assert_eq!(saved.inhabited_time, 3);
+        dimension.unwatch(5, 5).await.unwrap();
+        dimension.unwatch(5, 5).await.unwrap();
+        assert_eq!(dimension.watched_chunks(), 0);
+    }
+
+    /// A flat generator which counts the chunks it generates.
+    struct CountingGenerator {
+        flat: FlatGenerator,
+        generated: std::sync::atomic::AtomicUsize,
+    }
+
+    impl ChunkGenerator for CountingGenerator {
+        fn generate(&self, x: i32, z: i32) -> Chunk {
+            self.generated
+                .fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+            self.flat.generate(x, z)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_load_once() {
+        let dir = TempDir::new().unwrap();
+        let generator = Arc::new(CountingGenerator {
+            flat: FlatGenerator::new(&FlatSettings::default(), OVERWORLD.height),
+            generated: Default::default(),
+        });
+        let dimension = Dimension::new(
+            &OVERWORLD,
+            Some(Arc::clone(&generator) as Arc<dyn ChunkGenerator>),
+            RegionStorage::new(dir.path(), Compression::Zlib),
+        );
+
+        // Two viewers ask for the same new chunk at once, it is generated for the first only.
+        dimension.watch(-6, 4);
+        dimension.watch(-6, 4);
+        let (first, second) = tokio::join!(
+            dimension.watched_chunk(-6, 4),
+            dimension.watched_chunk(-6, 4)
+        );
+        assert_eq!(first.unwrap(), second.unwrap());
+        let generated = generator
+            .generated
+            .load(std::sync::atomic::Ordering::Relaxed);
+        assert_eq!(generated, 1);
+    }
+
+    #[tokio::test]
+    async fn test_save_changed() {
+        let dir = TempDir::new().unwrap();
+        let generator = FlatGenerator::new(&FlatSettings::default(), OVERWORLD.height);
+        let dimension = Dimension::new(
+            &OVERWORLD,
+            Some(Arc::new(generator)),
+            RegionStorage::new(dir.path(), Compression::Zlib),
+        );
+        dimension.watch(0, 0);
+        let mut chunk = dimension.watched_chunk(0, 0).await.unwrap().unwrap();
+
+        // The chunk did not change since it was generated and saved, so the region file keeps
+        // what is written behind its back.
+        chunk.inhabited_time = 7;
+        dimension.save_chunk(chunk.clone()).await.unwrap();
+        dimension.save_watched().unwrap();
+        let saved = dimension.load_chunk(0, 0).await.unwrap().unwrap();
+        assert_eq!(saved.inhabited_time, 7);
+
+        dimension.simulate(0, 0, true);
+        dimension.tick();
+        dimension.save_watched().unwrap();
+        let saved = dimension.load_chunk(0, 0).await.unwrap().unwrap();
+        assert_eq!(saved.inhabited_time, 1);
+        dimension.save_chunk(chunk).await.unwrap();
+        dimension.unwatch(0, 0).await.unwrap();
+        let saved = dimension.load_chunk(0, 0).await.unwrap().unwrap();
+        assert_eq!(saved.inhabited_time, 7);
+    }
+}
@@ -0,0 +1,275 @@
+//! The superflat generator: every chunk is the same stack of layers of blocks from the bottom of
+//! the world, in one biome. The settings are the `settings` of the `minecraft:flat` generator in
+//! 'level.dat', and the JSON of 'generator-settings' in the same format:
+//! `{"layers":[{"block":"minecraft:bedrock","height":1},...],"biome":"minecraft:plains"}`.
+//!
+//! The features, the lakes and the structures are not generated, their settings are only kept
+//! in the world.
+
+use crate::nbt::{Compound, Tag};
+use crate::world::biome;
+use crate::world::block::{self, Block, BlockStateError};
+use crate::world::chunk::{Chunk, ChunkHeight, VanillaRegistry};
+
+use super::{light_chunk, ChunkGenerator, GeneratorError};
+
+/// The most blocks of a layer, the height of the highest worlds.
+const MAX_LAYER_HEIGHT: i64 = 4064;
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Layer {
+    pub block: &'static Block,
+    pub height: u32,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct FlatSettings {
+    /// From the bottom of the world.
+    pub layers: Vec<Layer>,
+    pub biome: u32,
+    pub features: bool,
+    pub lakes: bool,
+    /// The structures that can generate, a list or a tag of structure sets. All of them if it
+    /// is missing.
+    pub structure_overrides: Option<Tag>,
+}
+
+impl Default for FlatSettings {
+    /// The "Classic Flat" preset: bedrock, two layers of dirt and grass, in plains.
+    fn default() -> Self {
+        let layer = |name, height| Layer {
+            block: block::block(name).expect("The default layers are vanilla blocks"),
+            height,
+        };
+        FlatSettings {
+            layers: vec![
+                layer("minecraft:bedrock", 1),
+                layer("minecraft:dirt", 2),
+                layer("minecraft:grass_block", 1),
+            ],
+            biome: biome::id("minecraft:plains").unwrap(),
+            features: false,
+            lakes: false,
+            structure_overrides: Some(Tag::List(vec![
+                Tag::from("minecraft:strongholds"),
+                Tag::from("minecraft:villages"),
+            ])),
+        }
+    }
+}
+
+impl FlatSettings {
+    pub fn from_nbt(settings: &Compound) -> Result<Self, GeneratorError> {
+        let invalid = |message: &str| GeneratorError::Invalid(message.to_string());
+        let Some(Tag::List(layers)) = settings.get("layers") else {
+            return Err(invalid("missing 'layers'"));
+        };
+        let layers = layers
+            .iter()
+            .map(|layer| {
+                let layer = layer
+                    .as_compound()
+                    .ok_or_else(|| invalid("invalid layer"))?;
+                let name = layer
+                    .get("block")
+                    .and_then(Tag::as_str)
+                    .ok_or_else(|| invalid("missing layer 'block'"))?;
+                let height = layer
+                    .get("height")
+                    .and_then(Tag::as_i64)
+                    .filter(|height| (0..=MAX_LAYER_HEIGHT).contains(height))
+                    .ok_or_else(|| invalid("invalid layer 'height'"))?;
+                Ok(Layer {
+                    block: block::block(name)
+                        .ok_or_else(|| BlockStateError::UnknownBlock(name.to_string()))?,
+                    height: height as u32,
+                })
+            })
+            .collect::<Result<_, GeneratorError>>()?;
+
+        let biome = match settings.get("biome") {
+            Some(name) => {
+                let name = name.as_str().ok_or_else(|| invalid("invalid 'biome'"))?;
+                biome::id(name).ok_or_else(|| GeneratorError::UnknownBiome(name.to_string()))?
+            }
+            None => biome::id("minecraft:plains").unwrap(),
+        };
+        let flag = |name| {
+            settings
+                .get(name)
+                .and_then(Tag::as_i64)
+                .is_some_and(|value| value != 0)
+        };
+        Ok(FlatSettings {
+            layers,
+            biome,
+            features: flag("features"),
+            lakes: flag("lakes"),
+            structure_overrides: settings.get("structure_overrides").cloned(),
+        })
+    }
+
+    /// Reads the JSON of 'generator-settings'.
+    pub fn from_json(json: &str) -> Result<Self, GeneratorError> {
+        let settings: Tag =
+            serde_json::from_str(json).map_err(|e| GeneratorError::Invalid(e.to_string()))?;
+        match settings {
+            Tag::Compound(settings) => Self::from_nbt(&settings),
+            _ => Err(GeneratorError::Invalid("not an object".to_string())),
+        }
+    }
+
+    pub fn to_nbt(&self) -> Compound {
+        let layers = self
+            .layers
+            .iter()
+            .map(|layer| {
+                Tag::Compound(Compound::from([
+                    ("block".to_string(), Tag::from(layer.block.name)),
+                    ("height".to_string(), Tag::Int(layer.height as i32)),
+                ]))
+            })
+            .collect();
+        let mut nbt = Compound::from([
+            ("layers".to_string(), Tag::List(layers)),
+            (
+                "biome".to_string(),
+                Tag::from(biome::name(self.biome).unwrap_or("minecraft:plains")),
+            ),
+            ("features".to_string(), Tag::from(self.features)),
+            ("lakes".to_string(), Tag::from(self.lakes)),
+        ]);
+        if let Some(structure_overrides) = &self.structure_overrides {
+            nbt.insert(
+                "structure_overrides".to_string(),
+                structure_overrides.clone(),
+            );
+        }
+        nbt
+    }
+}
+
+/// Generates the same chunk everywhere, which is made once.
+pub struct FlatGenerator {
+    chunk: Chunk,
+}
+
+impl FlatGenerator {
+    pub fn new(settings: &FlatSettings, height: ChunkHeight) -> Self {
+        let mut chunk = Chunk::new(0, 0, height, settings.biome);
+        // The layers above the top of the world are left out.
+        let mut blocks = settings.layers.iter().flat_map(|layer| {
+            std::iter::repeat_n(layer.block.default_state, layer.height as usize)
+        });
+        for y in height.min_y..height.min_y + height.height as i32 {
+            let Some(state) = blocks.next() else {
+                break;
+            };
+            for x in 0..16 {
+                for z in 0..16 {
+                    chunk.set_block(x, y, z, state);
+                }
+            }
+        }
+        chunk.update_heightmaps(&VanillaRegistry);
+
+        light_chunk(&mut chunk, true);
+        chunk.status = "minecraft:full".to_string();
+        FlatGenerator { chunk }
+    }
+}
+
+impl ChunkGenerator for FlatGenerator {
+    fn generate(&self, x: i32, z: i32) -> Chunk {
+        let mut chunk = self.chunk.clone();
+        chunk.x = x;
+        chunk.z = z;
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::snbt;
+    use crate::world::chunk::AIR;
+
+    #[test]
+    fn test_default_preset() {
+        let generator = FlatGenerator::new(&FlatSettings::default(), ChunkHeight::OVERWORLD);
+        let chunk = generator.generate(5, -9);
+        assert_eq!((chunk.x, chunk.z), (5, -9));
+        let state = |name| block::parse_state(name).unwrap();
+        assert_eq!(chunk.block(0, -64, 0), state("minecraft:bedrock"));
+        assert_eq!(chunk.block(7, -63, 3), state("minecraft:dirt"));
+        assert_eq!(chunk.block(15, -62, 15), state("minecraft:dirt"));
+        assert_eq!(chunk.block(2, -61, 9), state("minecraft:grass_block"));
+        assert_eq!(chunk.block(2, -60, 9), AIR);
+        assert_eq!(chunk.biome(0, 100, 0), biome::id("plains"));
+        assert_eq!(chunk.heightmaps.motion_blocking.get(4, 4), 4);
+
+        // The sky light, from the section below the world.
+        let sky_light = |y: i32| {
+            let section = chunk.sky_light[((y + 64) / 16 + 1) as usize]
+                .as_ref()
+                .unwrap();
+            section[((y & 15) as usize) << 7]
+        };
+        assert_eq!(sky_light(-61), 0);
+        assert_eq!(sky_light(-60), 0xFF);
+        assert_eq!(sky_light(300), 0xFF);
+
+        // The chunks are the same everywhere, and on every run.
+        let other = FlatGenerator::new(&FlatSettings::default(), ChunkHeight::OVERWORLD);
+        let mut other = other.generate(100, 100);
+        (other.x, other.z) = (5, -9);
+        assert_eq!(other, chunk);
+    }
+
+    #[test]
+    fn test_settings() {
+        let settings = FlatSettings::from_json(
+            r#"{"layers":[{"block":"minecraft:bedrock","height":1},{"block":"stone","height":400}],
+                "biome":"minecraft:desert","features":true}"#,
+        )
+        .unwrap();
+        assert_eq!(settings.layers[1].block.name, "minecraft:stone");
+        assert_eq!(settings.biome, biome::id("desert").unwrap());
+        assert!(settings.features && !settings.lakes);
+        assert_eq!(settings.structure_overrides, None);
+        assert_eq!(
+            FlatSettings::from_nbt(&settings.to_nbt()).unwrap(),
+            settings
+        );
+        let defaults = FlatSettings::default();
+        assert_eq!(
+            FlatSettings::from_nbt(&defaults.to_nbt()).unwrap(),
+            defaults
+        );
+
+        // The layers stop at the top of the world.
+        let chunk = FlatGenerator::new(&settings, ChunkHeight::NETHER).generate(0, 0);
+        assert_eq!(chunk.block(0, 255, 0), block::parse_state("stone").unwrap());
+        assert_eq!(chunk.heightmaps.world_surface.get(0, 0), 256);
+
+        for invalid in [
+            "{}",
+            "[]",
+            r#"{"layers":[{"block":"minecraft:nothing","height":1}]}"#,
+            r#"{"layers":[{"block":"minecraft:stone","height":-1}]}"#,
+            r#"{"layers":[],"biome":"minecraft:nowhere"}"#,
+            "not json",
+        ] {
+            assert!(FlatSettings::from_json(invalid).is_err(), "{invalid}");
+        }
+
+        let nbt = snbt::parse(r#"{layers:[],biome:"minecraft:the_void",structure_overrides:[]}"#);
+        let Tag::Compound(nbt) = nbt.unwrap() else {
+            unreachable!()
+        };
+        let settings = FlatSettings::from_nbt(&nbt).unwrap();
+        assert_eq!(settings.structure_overrides, Some(Tag::List(Vec::new())));
+        let chunk = FlatGenerator::new(&settings, ChunkHeight::END).generate(0, 0);
+        assert_eq!(chunk.block(0, 0, 0), AIR);
+    }
+}
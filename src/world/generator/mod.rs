@@ -0,0 +1,101 @@
+//! The world generators, which make the chunks that are not in the region files yet. They are
+//! deterministic: the same settings give the same chunks, on every run.
+//! - `flat`: the superflat worlds, made of layers of blocks.
+//! - `noise`: the default worlds, shaped by noises from the world seed.
+//!
+//! The chunks are generated on the threads of a `pool::WorkerPool`, out of the tokio runtime.
+
+pub mod flat;
+pub mod noise;
+pub mod pool;
+
+use thiserror::Error;
+
+use crate::nbt::Compound;
+use crate::world::block::{self, BlockStateError};
+use crate::world::chunk::{Chunk, ChunkHeight, LIGHT_SIZE};
+
+#[derive(Error, Debug)]
+pub enum GeneratorError {
+    #[error("Unsupported generator '{0}'")]
+    Unsupported(String),
+    #[error("Invalid generator settings: {0}")]
+    Invalid(String),
+    #[error(transparent)]
+    Block(#[from] BlockStateError),
+    #[error("Unknown biome '{0}'")]
+    UnknownBiome(String),
+}
+
+pub trait ChunkGenerator: Send + Sync {
+    /// Generates the chunk at the given chunk coordinates.
+    fn generate(&self, x: i32, z: i32) -> Chunk;
+}
+
+/// The generator of a dimension, from its `generator` entry in 'level.dat' and the world seed.
+pub fn from_nbt(
+    generator: &Compound,
+    height: ChunkHeight,
+    seed: i64,
+) -> Result<Box<dyn ChunkGenerator>, GeneratorError> {
+    let kind = generator
+        .get("type")
+        .and_then(|kind| kind.as_str())
+        .ok_or_else(|| GeneratorError::Invalid("missing 'type'".to_string()))?;
+    match kind {
+        "minecraft:flat" => {
+            let settings = generator
+                .get("settings")
+                .and_then(|settings| settings.as_compound())
+                .ok_or_else(|| GeneratorError::Invalid("missing 'settings'".to_string()))?;
+            let settings = flat::FlatSettings::from_nbt(settings)?;
+            Ok(Box::new(flat::FlatGenerator::new(&settings, height)))
+        }
+        "minecraft:noise" => noise::from_nbt(generator, height, seed),
+        kind => Err(GeneratorError::Unsupported(kind.to_string())),
+    }
+}
+
+/// How much a block dims the sky light that goes through it.
+fn opacity(state: u32) -> u8 {
+    if block::is_air(state) {
+        return 0;
+    }
+    match block::block_of(state).map(|block| block.name) {
+        Some("minecraft:water") => 1,
+        Some(name) if name.ends_with("_sapling") || name.ends_with("_propagule") => 0,
+        _ => 15,
+    }
+}
+
+/// Lights a generated chunk: the sky light comes down each column, through the air and the
+/// plants, and fades in the water. The dimensions without a sky have no sky light. There is no
+/// block light.
+pub fn light_chunk(chunk: &mut Chunk, has_skylight: bool) {
+    chunk.block_light.fill(Some(vec![0; LIGHT_SIZE]));
+    if !has_skylight {
+        chunk.sky_light.fill(None);
+        return;
+    }
+    let height = chunk.height();
+    let below = height.min_y - 16;
+    let mut sky = vec![vec![0u8; LIGHT_SIZE]; chunk.sky_light.len()];
+    // The section above the world is fully lit.
+    if let Some(above) = sky.last_mut() {
+        above.fill(0xFF);
+    }
+    for x in 0..16 {
+        for z in 0..16 {
+            let mut light = 15u8;
+            for y in (height.min_y..height.min_y + height.height as i32).rev() {
+                light = light.saturating_sub(opacity(chunk.block(x, y, z)));
+                if light == 0 {
+                    break;
+                }
+                let index = ((y & 15) * 256 + z * 16 + x) as usize;
+                sky[((y - below) / 16) as usize][index / 2] |= light << (index % 2 * 4);
+            }
+        }
+    }
+    chunk.sky_light = sky.into_iter().map(Some).collect();
+}
@@ -0,0 +1,335 @@
+//! The biome source of the overworld: the climate at a position, from five noises, and the
+//! biome of a climate, after vanilla's tables of the overworld biomes.
+//!
+//! - Temperature and humidity pick the biome in a table, from frozen to hot and from dry to wet.
+//! - Continentalness is the distance from the oceans inland.
+//! - Erosion is low in the mountains and high in the flat lands and the swamps.
+//! - Weirdness picks the variants of the biomes, and makes the peaks and the valleys.
+
+use super::perlin::NormalNoise;
+
+/// The upper bounds of the temperatures and the humidities of the rows and columns of the
+/// tables, the last ones go up to the highest values.
+const TEMPERATURES: [f64; 4] = [-0.45, -0.15, 0.2, 0.55];
+const HUMIDITIES: [f64; 4] = [-0.35, -0.1, 0.1, 0.3];
+/// The upper bounds of the erosion levels, from 0 to 6.
+const EROSIONS: [f64; 6] = [-0.78, -0.375, -0.2225, 0.05, 0.45, 0.55];
+
+const OCEANS: [[&str; 5]; 2] = [
+    [
+        "deep_frozen_ocean",
+        "deep_cold_ocean",
+        "deep_ocean",
+        "deep_lukewarm_ocean",
+        "warm_ocean",
+    ],
+    [
+        "frozen_ocean",
+        "cold_ocean",
+        "ocean",
+        "lukewarm_ocean",
+        "warm_ocean",
+    ],
+];
+
+const MIDDLE: [[&str; 5]; 5] = [
+    [
+        "snowy_plains",
+        "snowy_plains",
+        "snowy_plains",
+        "snowy_taiga",
+        "taiga",
+    ],
+    [
+        "plains",
+        "plains",
+        "forest",
+        "taiga",
+        "old_growth_spruce_taiga",
+    ],
+    [
+        "flower_forest",
+        "plains",
+        "forest",
+        "birch_forest",
+        "dark_forest",
+    ],
+    ["savanna", "savanna", "forest", "jungle", "jungle"],
+    ["desert", "desert", "desert", "desert", "desert"],
+];
+
+/// The variants of the middle biomes, with a positive weirdness.
+const MIDDLE_VARIANTS: [[Option<&str>; 5]; 5] = [
+    [Some("ice_spikes"), None, Some("snowy_taiga"), None, None],
+    [None, None, None, None, Some("old_growth_pine_taiga")],
+    [
+        Some("sunflower_plains"),
+        None,
+        None,
+        Some("old_growth_birch_forest"),
+        None,
+    ],
+    [
+        None,
+        None,
+        Some("plains"),
+        Some("sparse_jungle"),
+        Some("bamboo_jungle"),
+    ],
+    [None, None, None, None, None],
+];
+
+const PLATEAU: [[&str; 5]; 5] = [
+    [
+        "snowy_plains",
+        "snowy_plains",
+        "snowy_plains",
+        "snowy_taiga",
+        "snowy_taiga",
+    ],
+    [
+        "meadow",
+        "meadow",
+        "forest",
+        "taiga",
+        "old_growth_spruce_taiga",
+    ],
+    ["meadow", "meadow", "meadow", "meadow", "dark_forest"],
+    [
+        "savanna_plateau",
+        "savanna_plateau",
+        "forest",
+        "forest",
+        "jungle",
+    ],
+    [
+        "badlands",
+        "badlands",
+        "badlands",
+        "wooded_badlands",
+        "wooded_badlands",
+    ],
+];
+
+/// The climate noises of the world.
+pub struct ClimateSampler {
+    temperature: NormalNoise,
+    vegetation: NormalNoise,
+    continentalness: NormalNoise,
+    erosion: NormalNoise,
+    ridges: NormalNoise,
+    shift: NormalNoise,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Climate {
+    pub temperature: f64,
+    pub humidity: f64,
+    pub continentalness: f64,
+    pub erosion: f64,
+    pub weirdness: f64,
+}
+
+fn level(value: f64, bounds: &[f64]) -> usize {
+    bounds.iter().take_while(|bound| value >= **bound).count()
+}
+
+impl Climate {
+    /// High on the ridges, low in the valleys, from the weirdness.
+    pub fn peaks_and_valleys(&self) -> f64 {
+        -((self.weirdness.abs() - 2.0 / 3.0).abs() - 1.0 / 3.0) * 3.0
+    }
+
+    fn erosion_level(&self) -> usize {
+        level(self.erosion, &EROSIONS)
+    }
+
+    /// The biome of the surface, without the namespace.
+    pub fn surface_biome(&self) -> &'static str {
+        let temperature = level(self.temperature, &TEMPERATURES);
+        let humidity = level(self.humidity, &HUMIDITIES);
+        let erosion = self.erosion_level();
+        let pv = self.peaks_and_valleys();
+        let c = self.continentalness;
+
+        let middle = || match self.weirdness > 0.0 {
+            true => MIDDLE_VARIANTS[temperature][humidity].unwrap_or(MIDDLE[temperature][humidity]),
+            false => MIDDLE[temperature][humidity],
+        };
+        let badlands = || match humidity {
+            0 | 1 => "badlands",
+            2 => "eroded_badlands",
+            _ => "wooded_badlands",
+        };
+
+        if c < -1.05 {
+            return "mushroom_fields";
+        }
+        if c < -0.455 {
+            return OCEANS[0][temperature];
+        }
+        if c < -0.19 {
+            return OCEANS[1][temperature];
+        }
+        let coast = c < -0.11;
+        let inland = c >= 0.03;
+        let far_inland = c >= 0.3;
+
+        // The rivers, in the valleys.
+        if pv < -0.85 && (!far_inland || erosion >= 2) {
+            return match (erosion, temperature) {
+                (6, 1 | 2) => "swamp",
+                (6, 3 | 4) => "mangrove_swamp",
+                (_, 0) => "frozen_river",
+                _ => "river",
+            };
+        }
+        if coast {
+            return match erosion {
+                0..=2 => "stony_shore",
+                _ if temperature == 0 => "snowy_beach",
+                _ if temperature == 4 => "desert",
+                _ => "beach",
+            };
+        }
+        if erosion == 6 {
+            return match temperature {
+                1 | 2 => "swamp",
+                3 | 4 => "mangrove_swamp",
+                _ => middle(),
+            };
+        }
+        if inland && erosion <= 1 && pv > 0.7 {
+            return match temperature {
+                0..=2 if self.weirdness < 0.0 => "jagged_peaks",
+                0..=2 => "frozen_peaks",
+                3 => "stony_peaks",
+                _ => badlands(),
+            };
+        }
+        if inland && erosion <= 1 && pv > 0.2 {
+            return match temperature {
+                0..=2 if humidity <= 1 => "snowy_slopes",
+                0..=2 => "grove",
+                _ => PLATEAU[temperature][humidity],
+            };
+        }
+        if far_inland && erosion <= 3 && pv > -0.2 {
+            return PLATEAU[temperature][humidity];
+        }
+        if erosion == 5 && pv > 0.2 {
+            return match temperature {
+                0 | 1 => "windswept_gravelly_hills",
+                2 => "windswept_forest",
+                _ => "windswept_savanna",
+            };
+        }
+        if temperature == 4 && far_inland {
+            return badlands();
+        }
+        middle()
+    }
+
+    /// The biome of the caves, which are `depth` blocks below the surface, or None for the
+    /// biome of the surface.
+    pub fn cave_biome(&self, depth: i32, y: i32) -> Option<&'static str> {
+        if depth < 16 {
+            return None;
+        }
+        if self.erosion_level() <= 1 && y < 0 && depth > 48 {
+            Some("deep_dark")
+        } else if self.continentalness > 0.8 {
+            Some("dripstone_caves")
+        } else if self.humidity > 0.7 {
+            Some("lush_caves")
+        } else {
+            None
+        }
+    }
+}
+
+impl ClimateSampler {
+    /// The noises of the biomes, 4 times larger with `large_biomes`.
+    pub fn new(seed: i64, large_biomes: bool) -> Self {
+        let larger = if large_biomes { 2 } else { 0 };
+        ClimateSampler {
+            temperature: NormalNoise::named(
+                seed,
+                "temperature",
+                -10 - larger,
+                &[1.5, 0.0, 1.0, 0.0, 0.0, 0.0],
+            ),
+            vegetation: NormalNoise::named(
+                seed,
+                "vegetation",
+                -8 - larger,
+                &[1.0, 1.0, 0.0, 0.0, 0.0, 0.0],
+            ),
+            continentalness: NormalNoise::named(
+                seed,
+                "continentalness",
+                -9 - larger,
+                &[1.0, 1.0, 2.0, 2.0, 2.0, 1.0, 1.0, 1.0, 1.0],
+            ),
+            erosion: NormalNoise::named(seed, "erosion", -9 - larger, &[1.0, 1.0, 0.0, 1.0, 1.0]),
+            ridges: NormalNoise::named(seed, "ridge", -7, &[1.0, 2.0, 1.0, 0.0, 0.0, 0.0]),
+            shift: NormalNoise::named(seed, "offset", -3, &[1.0, 1.0, 1.0, 0.0]),
+        }
+    }
+
+    /// The climate of the column of a block. The noises are sampled at a quarter of the block
+    /// coordinates, which are shifted a bit so that the borders of the biomes are not straight.
+    pub fn sample(&self, x: i32, z: i32) -> Climate {
+        let (block_x, block_z) = (x as f64, z as f64);
+        let x = (block_x + self.shift.sample(block_x, 0.0, block_z) * 4.0) * 0.25;
+        let z = (block_z + self.shift.sample(block_z, block_x, 0.0) * 4.0) * 0.25;
+        Climate {
+            temperature: self.temperature.sample(x, 0.0, z),
+            humidity: self.vegetation.sample(x, 0.0, z),
+            continentalness: self.continentalness.sample(x, 0.0, z),
+            erosion: self.erosion.sample(x, 0.0, z),
+            weirdness: self.ridges.sample(x, 0.0, z),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::biome;
+
+    fn climate(continentalness: f64, erosion: f64, weirdness: f64) -> Climate {
+        Climate {
+            temperature: 0.0,
+            humidity: 0.0,
+            continentalness,
+            erosion,
+            weirdness,
+        }
+    }
+
+    #[test]
+    fn test_biomes() {
+        assert_eq!(climate(-1.1, 0.0, 0.0).surface_biome(), "mushroom_fields");
+        assert_eq!(climate(-0.6, 0.0, 0.0).surface_biome(), "deep_ocean");
+        assert_eq!(climate(-0.3, 0.0, 0.0).surface_biome(), "ocean");
+        assert_eq!(climate(-0.15, 0.2, 0.3).surface_biome(), "beach");
+        assert_eq!(climate(0.1, 0.2, 0.0).surface_biome(), "river");
+        assert_eq!(climate(0.1, 0.2, 0.3).surface_biome(), "forest");
+        assert_eq!(climate(0.5, -0.9, 0.6).surface_biome(), "frozen_peaks");
+        assert_eq!(climate(0.5, -0.9, -0.6).surface_biome(), "jagged_peaks");
+        assert_eq!(climate(0.1, 0.6, 0.3).surface_biome(), "swamp");
+
+        // Every biome of the tables is a vanilla one.
+        let sampler = ClimateSampler::new(3, false);
+        for i in -50..50 {
+            let climate = sampler.sample(i * 1500, i * 700);
+            assert!(biome::id(climate.surface_biome()).is_some());
+            if let Some(cave) = climate.cave_biome(100, -30) {
+                assert!(biome::id(cave).is_some());
+            }
+        }
+        let all = OCEANS.iter().chain(&MIDDLE).chain(&PLATEAU).flatten();
+        assert!(all.into_iter().all(|name| biome::id(name).is_some()));
+    }
+}
@@ -0,0 +1,151 @@
+//! The End: the main island around the origin, a ring of void, then the outer islands from 1024
+//! blocks away, after vanilla's end islands density. The islands are made of end stone, and
+//! the biomes of the outer ones depend on how high they are.
+
+use crate::world::biome;
+use crate::world::chunk::{Chunk, ChunkHeight, VanillaRegistry};
+use crate::world::generator::{light_chunk, ChunkGenerator};
+
+use super::perlin::NormalNoise;
+use super::random;
+use super::CellDensity;
+
+/// The height around which the islands are.
+const ISLAND_Y: f64 = 56.0;
+
+/// The outer islands are farther than this from the origin, in chunks, squared.
+const VOID_RADIUS: i64 = 64 * 64;
+
+/// How many chunks in 10000 have an outer island.
+const ISLAND_RARITY: f64 = 150.0;
+
+pub struct EndGenerator {
+    seed: i64,
+    height: ChunkHeight,
+    end_stone: u32,
+    terrain: NormalNoise,
+}
+
+impl EndGenerator {
+    pub fn new(seed: i64, height: ChunkHeight, end_stone: u32) -> Self {
+        EndGenerator {
+            seed,
+            height,
+            end_stone,
+            terrain: NormalNoise::named(seed, "end_terrain", -5, &[1.0, 1.0, 1.0]),
+        }
+    }
+
+    /// Whether an outer island is centered in a chunk.
+    fn has_island(&self, x: i64, z: i64) -> bool {
+        x * x + z * z > VOID_RADIUS
+            && random::at(self.seed, x as i32, 0, z as i32) < ISLAND_RARITY / 10000.0
+    }
+
+    /// How high the islands are in a column, from -100 to 80, given in cells of 8 blocks. The
+    /// main island is the highest, and there is land where it is above 8.
+    fn island_height(&self, x: i64, z: i64) -> f64 {
+        let height = |x: i64, z: i64, factor: i64| {
+            (100.0 - ((x * x + z * z) as f64).sqrt() * factor as f64).clamp(-100.0, 80.0)
+        };
+        let (chunk_x, chunk_z) = (x.div_euclid(2), z.div_euclid(2));
+        let (rest_x, rest_z) = (x.rem_euclid(2), z.rem_euclid(2));
+        let mut max = height(x, z, 8);
+        for dx in -12..=12 {
+            for dz in -12..=12 {
+                let (island_x, island_z) = (chunk_x + dx, chunk_z + dz);
+                if !self.has_island(island_x, island_z) {
+                    continue;
+                }
+                // The smaller islands fall faster from their center.
+                let factor = (island_x.abs() * 3439 + island_z.abs() * 147) % 13 + 9;
+                max = max.max(height(rest_x - dx * 2, rest_z - dz * 2, factor));
+            }
+        }
+        max
+    }
+
+    /// The biome of a chunk: the main island, then the outer islands after their height.
+    fn biome(&self, x: i32, z: i32) -> &'static str {
+        let (x, z) = (x as i64, z as i64);
+        if x * x + z * z <= VOID_RADIUS {
+            return "the_end";
+        }
+        match self.island_height(x * 2 + 1, z * 2 + 1) {
+            height if height > 40.0 => "end_highlands",
+            height if height >= 0.0 => "end_midlands",
+            height if height >= -20.0 => "small_end_islands",
+            _ => "end_barrens",
+        }
+    }
+}
+
+impl ChunkGenerator for EndGenerator {
+    fn generate(&self, x: i32, z: i32) -> Chunk {
+        let name = self.biome(x, z);
+        let id = biome::id(name).expect("The End biomes are vanilla biomes");
+        let mut chunk = Chunk::new(x, z, self.height, id);
+
+        let density = CellDensity::new(x, z, self.height, |x, z| {
+            let island = self.island_height((x as i64).div_euclid(8), (z as i64).div_euclid(8));
+            // Thin on the edges, and deeper below than high above.
+            let thickness = (island - 8.0) / 2.0;
+            let (top, bottom) = (ISLAND_Y + thickness * 0.35, ISLAND_Y - thickness);
+            let roughness = (thickness / 8.0).clamp(0.0, 1.0) * 0.25;
+            move |y| (top - y).min(y - bottom) / 8.0 + self.terrain.sample(x, y, z) * roughness
+        });
+        for y in self.height.min_y..self.height.min_y + self.height.height as i32 {
+            for lz in 0..16 {
+                for lx in 0..16 {
+                    if density.get(lx, y, lz) > 0.0 {
+                        chunk.set_block(lx, y, lz, self.end_stone);
+                    }
+                }
+            }
+        }
+
+        chunk.update_heightmaps(&VanillaRegistry);
+        light_chunk(&mut chunk, false);
+        chunk.status = "minecraft:full".to_string();
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block;
+    use crate::world::chunk::AIR;
+
+    #[test]
+    fn test_end() {
+        // The excerpt of the block report has no end stone, stone stands in for it.
+        let stone = block::block("stone").unwrap().default_state;
+        let generator = EndGenerator::new(11, ChunkHeight::END, stone);
+
+        // The main island.
+        let chunk = generator.generate(0, 0);
+        assert_eq!(chunk, generator.generate(0, 0));
+        assert!(chunk.sky_light.iter().all(Option::is_none));
+        let top = chunk.heightmaps.world_surface.get(0, 0) as i32 - 1;
+        assert!((58..80).contains(&top), "{top}");
+        assert_eq!(chunk.block(0, top, 0), stone);
+        assert_eq!(chunk.block(0, 5, 0), AIR);
+        assert_eq!(chunk.biome(0, 64, 0), biome::id("the_end"));
+
+        // The void around it.
+        let chunk = generator.generate(40, -20);
+        assert!((0..16).all(|x| (0..16).all(|z| chunk.heightmaps.world_surface.get(x, z) == 0)));
+
+        // The outer islands.
+        let islands = (0..40)
+            .map(|i| generator.generate(100 + i % 8, -30 + i / 8))
+            .filter(|chunk| chunk.heightmaps.world_surface.get(8, 8) > 0)
+            .inspect(|chunk| {
+                let name = biome::name(chunk.biome(8, 64, 8).unwrap()).unwrap();
+                assert_ne!(name, "minecraft:the_end");
+            })
+            .count();
+        assert!((1..40).contains(&islands), "{islands}");
+    }
+}
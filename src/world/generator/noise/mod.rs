@@ -0,0 +1,509 @@
+//! The `minecraft:noise` generators of 'level.dat'. The Nether and the End have their own, in
+//! `nether` and `end`. The one of the overworld works like vanilla's, from the world seed:
+//! 1. `climate`: five noises give the climate of each column, which picks its biome.
+//! 2. `terrain`: the climate shapes the terrain, a density that is positive in the ground, and
+//!    the noise caves are carved out of it.
+//! 3. The open spaces below the sea level are filled with water. The caves are aquifers, with
+//!    their own water level, and lava deep down.
+//! 4. `surface`: the top of the ground is replaced by the blocks of its biome, and the bottom of
+//!    the world by bedrock.
+//!
+//! The noises and the splines are simpler than vanilla's, so the terrain only looks like the
+//! one vanilla makes, and it differs for the same seed. It is the same on every run, and for
+//! every order of generation.
+
+mod climate;
+pub mod end;
+pub mod nether;
+mod perlin;
+mod random;
+mod surface;
+mod terrain;
+
+use crate::nbt::{Compound, Tag};
+use crate::world::biome;
+use crate::world::block;
+use crate::world::chunk::{Chunk, ChunkHeight, VanillaRegistry, AIR};
+
+use super::{light_chunk, ChunkGenerator, GeneratorError};
+use climate::{Climate, ClimateSampler};
+use end::EndGenerator;
+use nether::{NetherGenerator, NetherStates};
+use perlin::{lerp, NormalNoise};
+use surface::States;
+use terrain::{Density, TerrainNoises, TerrainShape, LAVA_LEVEL, SEA_LEVEL};
+
+/// The density is computed at the corners of cells of 4×8×4 blocks, and interpolated inside.
+const CELL_WIDTH: i32 = 4;
+const CELL_HEIGHT: i32 = 8;
+/// The corners of the cells of a chunk, on X and Z.
+const CORNERS: usize = 16 / CELL_WIDTH as usize + 1;
+
+/// The generator of a `minecraft:noise` entry, from its `settings`: "minecraft:nether",
+/// "minecraft:end", or the ones of the overworld.
+pub fn from_nbt(
+    generator: &Compound,
+    height: ChunkHeight,
+    seed: i64,
+) -> Result<Box<dyn ChunkGenerator>, GeneratorError> {
+    match generator.get("settings").and_then(Tag::as_str) {
+        Some("minecraft:nether") => Ok(Box::new(NetherGenerator::new(
+            seed,
+            height,
+            NetherStates::vanilla()?,
+        ))),
+        Some("minecraft:end") => Ok(Box::new(EndGenerator::new(
+            seed,
+            height,
+            block::parse_state("minecraft:end_stone")?,
+        ))),
+        _ => Ok(Box::new(NoiseGenerator::from_nbt(generator, height, seed)?)),
+    }
+}
+
+/// A density of the blocks of a chunk, computed at the corners of the cells and interpolated
+/// inside, for the dimensions whose terrain is a single density.
+struct CellDensity {
+    min_y: i32,
+    /// By Y, then Z, then X.
+    values: Vec<f64>,
+}
+
+impl CellDensity {
+    /// `column` gives the density in a column of corners, from its X and Z, for a Y.
+    fn new<F: Fn(f64) -> f64>(
+        chunk_x: i32,
+        chunk_z: i32,
+        height: ChunkHeight,
+        column: impl Fn(f64, f64) -> F,
+    ) -> Self {
+        let cells_y = height.height as i32 / CELL_HEIGHT;
+        let corner_count = cells_y as usize + 1;
+        let mut corners = Vec::with_capacity(CORNERS * CORNERS * corner_count);
+        for index in 0..CORNERS * CORNERS {
+            let x = chunk_x * 16 + (index % CORNERS) as i32 * CELL_WIDTH;
+            let z = chunk_z * 16 + (index / CORNERS) as i32 * CELL_WIDTH;
+            let density = column(x as f64, z as f64);
+            corners.extend((0..=cells_y).map(|k| density((height.min_y + k * CELL_HEIGHT) as f64)));
+        }
+        let corner = |i: i32, j: i32, k: i32| {
+            corners[(j as usize * CORNERS + i as usize) * corner_count + k as usize]
+        };
+
+        let mut values = Vec::with_capacity(256 * height.height as usize);
+        for y in 0..height.height as i32 {
+            let k = y / CELL_HEIGHT;
+            let ty = (y % CELL_HEIGHT) as f64 / CELL_HEIGHT as f64;
+            for z in 0..16 {
+                for x in 0..16 {
+                    let (i, j) = (x / CELL_WIDTH, z / CELL_WIDTH);
+                    let tx = (x % CELL_WIDTH) as f64 / CELL_WIDTH as f64;
+                    let tz = (z % CELL_WIDTH) as f64 / CELL_WIDTH as f64;
+                    let at = |i, j| lerp(ty, corner(i, j, k), corner(i, j, k + 1));
+                    values.push(lerp(
+                        tz,
+                        lerp(tx, at(i, j), at(i + 1, j)),
+                        lerp(tx, at(i, j + 1), at(i + 1, j + 1)),
+                    ));
+                }
+            }
+        }
+        CellDensity {
+            min_y: height.min_y,
+            values,
+        }
+    }
+
+    /// The density of a block of the chunk.
+    fn get(&self, x: i32, y: i32, z: i32) -> f64 {
+        self.values[((y - self.min_y) * 256 + z * 16 + x) as usize]
+    }
+}
+
+/// The `settings` of the generator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TerrainKind {
+    Overworld,
+    /// Biomes 4 times larger.
+    LargeBiomes,
+    /// Lands twice higher.
+    Amplified,
+}
+
+/// The `biome_source` of the generator.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BiomeSource {
+    /// The biomes of the climate.
+    MultiNoise,
+    /// One biome everywhere, by ID.
+    Fixed(u32),
+}
+
+pub struct NoiseGenerator {
+    seed: i64,
+    kind: TerrainKind,
+    biomes: BiomeSource,
+    height: ChunkHeight,
+    climate: ClimateSampler,
+    terrain: TerrainNoises,
+    surface: NormalNoise,
+    states: States,
+}
+
+impl NoiseGenerator {
+    pub fn new(seed: i64, kind: TerrainKind, biomes: BiomeSource, height: ChunkHeight) -> Self {
+        NoiseGenerator {
+            seed,
+            kind,
+            biomes,
+            height,
+            climate: ClimateSampler::new(seed, kind == TerrainKind::LargeBiomes),
+            terrain: TerrainNoises::new(seed),
+            surface: NormalNoise::named(seed, "surface", -6, &[1.0, 1.0, 1.0]),
+            states: States::new(),
+        }
+    }
+
+    /// Reads the generator of 'level.dat':
+    /// `{type:"minecraft:noise",settings:"minecraft:overworld",biome_source:{...}}`.
+    pub fn from_nbt(
+        generator: &Compound,
+        height: ChunkHeight,
+        seed: i64,
+    ) -> Result<Self, GeneratorError> {
+        let kind = match generator.get("settings").and_then(Tag::as_str) {
+            Some("minecraft:overworld") => TerrainKind::Overworld,
+            Some("minecraft:large_biomes") => TerrainKind::LargeBiomes,
+            Some("minecraft:amplified") => TerrainKind::Amplified,
+            Some(settings) => return Err(GeneratorError::Unsupported(settings.to_string())),
+            None => return Err(GeneratorError::Invalid("missing 'settings'".to_string())),
+        };
+        let source = generator
+            .get("biome_source")
+            .and_then(Tag::as_compound)
+            .ok_or_else(|| GeneratorError::Invalid("missing 'biome_source'".to_string()))?;
+        let biomes = match source.get("type").and_then(Tag::as_str) {
+            Some("minecraft:multi_noise") => BiomeSource::MultiNoise,
+            Some("minecraft:fixed") => {
+                let name = source
+                    .get("biome")
+                    .and_then(Tag::as_str)
+                    .unwrap_or_default();
+                let id = biome::id(name)
+                    .ok_or_else(|| GeneratorError::UnknownBiome(name.to_string()))?;
+                BiomeSource::Fixed(id)
+            }
+            kind => {
+                let kind = kind.unwrap_or_default().to_string();
+                return Err(GeneratorError::Unsupported(kind));
+            }
+        };
+        Ok(Self::new(seed, kind, biomes, height))
+    }
+
+    fn biome(&self, climate: &Climate, surface: i32, y: i32) -> u32 {
+        match self.biomes {
+            BiomeSource::Fixed(id) => id,
+            BiomeSource::MultiNoise => {
+                let name = climate
+                    .cave_biome(surface - y, y)
+                    .unwrap_or_else(|| climate.surface_biome());
+                biome::id(name).expect("The climate biomes are vanilla biomes")
+            }
+        }
+    }
+
+    /// Fills the chunk with stone, and the air below the sea level and in the aquifers with
+    /// water or lava.
+    fn fill(&self, chunk: &mut Chunk, shapes: &[TerrainShape]) {
+        let (min_x, min_z) = (chunk.x * 16, chunk.z * 16);
+        let min_y = self.height.min_y;
+        let cells_y = self.height.height as i32 / CELL_HEIGHT;
+        let corner_count = cells_y as usize + 1;
+
+        let mut corners = Vec::with_capacity(CORNERS * CORNERS * corner_count);
+        for (index, shape) in shapes.iter().enumerate() {
+            let x = (min_x + (index % CORNERS) as i32 * CELL_WIDTH) as f64;
+            let z = (min_z + (index / CORNERS) as i32 * CELL_WIDTH) as f64;
+            for k in 0..=cells_y {
+                let y = (min_y + k * CELL_HEIGHT) as f64;
+                corners.push(self.terrain.density(shape, x, y, z));
+            }
+        }
+        let corner = |i: i32, j: i32, k: i32| {
+            corners[(j as usize * CORNERS + i as usize) * corner_count + k as usize]
+        };
+
+        for x in 0..16 {
+            for z in 0..16 {
+                let (i, j) = (x / CELL_WIDTH, z / CELL_WIDTH);
+                let tx = (x % CELL_WIDTH) as f64 / CELL_WIDTH as f64;
+                let tz = (z % CELL_WIDTH) as f64 / CELL_WIDTH as f64;
+                let (world_x, world_z) = ((min_x + x) as f64, (min_z + z) as f64);
+                let fluid_level = self.terrain.fluid_level(world_x, world_z);
+                for y in min_y..min_y + self.height.height as i32 {
+                    let k = (y - min_y) / CELL_HEIGHT;
+                    let ty = ((y - min_y) % CELL_HEIGHT) as f64 / CELL_HEIGHT as f64;
+                    let at = |i, j| Density::lerp(ty, corner(i, j, k), corner(i, j, k + 1));
+                    let density = Density::lerp(
+                        tz,
+                        Density::lerp(tx, at(i, j), at(i + 1, j)),
+                        Density::lerp(tx, at(i, j + 1), at(i + 1, j + 1)),
+                    );
+
+                    let state = if density.blocks > 0.0 {
+                        self.states.stone
+                    } else if density.terrain <= 0.0 {
+                        // Above the ground.
+                        match y <= SEA_LEVEL {
+                            true => self.states.water,
+                            false => continue,
+                        }
+                    } else if y <= LAVA_LEVEL.max(fluid_level) {
+                        // In a cave.
+                        match self.terrain.is_lava(world_x, world_z, y) {
+                            true => self.states.lava,
+                            false => self.states.water,
+                        }
+                    } else {
+                        continue;
+                    };
+                    chunk.set_block(x, y, z, state);
+                }
+            }
+        }
+    }
+
+    /// Replaces the top of the ground with the blocks of the biomes, and the bottom of the
+    /// world with bedrock.
+    fn build_surface(&self, chunk: &mut Chunk, climates: &[Climate]) {
+        let (min_x, min_z) = (chunk.x * 16, chunk.z * 16);
+        let min_y = self.height.min_y;
+        for x in 0..16 {
+            for z in 0..16 {
+                let (world_x, world_z) = (min_x + x, min_z + z);
+                let climate = &climates[(z / 4) as usize * CORNERS + (x / 4) as usize];
+                let biome = match self.biomes {
+                    BiomeSource::Fixed(id) => biome::name(id).unwrap_or_default(),
+                    BiomeSource::MultiNoise => climate.surface_biome(),
+                };
+                let biome = biome.trim_start_matches("minecraft:");
+                let noise = self.surface.sample(world_x as f64, 0.0, world_z as f64);
+                let random = random::at(self.seed, world_x, 0, world_z);
+                let depth = (3.0 + noise * 2.75 + random * 0.25).clamp(1.0, 6.0) as i32;
+
+                let mut underwater = false;
+                for y in (min_y..min_y + self.height.height as i32).rev() {
+                    let state = chunk.block(x, y, z);
+                    if state == AIR {
+                        continue;
+                    }
+                    if state == self.states.water || state == self.states.lava {
+                        underwater = true;
+                        continue;
+                    }
+                    let (top, under) = self.states.surface(biome, underwater, noise);
+                    for d in 0..depth {
+                        if chunk.block(x, y - d, z) != self.states.stone {
+                            break;
+                        }
+                        let state = if d == 0 { top } else { under };
+                        chunk.set_block(x, y - d, z, state);
+                    }
+                    break;
+                }
+
+                for y in min_y..min_y + 5 {
+                    if States::is_bedrock(self.seed, world_x, y, world_z, y - min_y) {
+                        chunk.set_block(x, y, z, self.states.bedrock);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl ChunkGenerator for NoiseGenerator {
+    fn generate(&self, x: i32, z: i32) -> Chunk {
+        let mut chunk = Chunk::new(x, z, self.height, 0);
+        // The corners of the cells, which are also the columns of the biomes.
+        let climates: Vec<_> = (0..CORNERS * CORNERS)
+            .map(|index| {
+                let corner_x = x * 16 + (index % CORNERS) as i32 * CELL_WIDTH;
+                let corner_z = z * 16 + (index / CORNERS) as i32 * CELL_WIDTH;
+                self.climate.sample(corner_x, corner_z)
+            })
+            .collect();
+        let amplified = self.kind == TerrainKind::Amplified;
+        let shapes: Vec<_> = climates
+            .iter()
+            .map(|climate| TerrainShape::new(climate, amplified))
+            .collect();
+
+        for (index, climate) in climates.iter().enumerate() {
+            let (qx, qz) = ((index % CORNERS) as i32, (index / CORNERS) as i32);
+            if qx == 4 || qz == 4 {
+                continue;
+            }
+            let surface = shapes[index].surface() as i32;
+            for y in (self.height.min_y..self.height.min_y + self.height.height as i32).step_by(4) {
+                chunk.set_biome(qx * 4, y, qz * 4, self.biome(climate, surface, y));
+            }
+        }
+
+        self.fill(&mut chunk, &shapes);
+        self.build_surface(&mut chunk, &climates);
+        chunk.update_heightmaps(&VanillaRegistry);
+        light_chunk(&mut chunk, true);
+        chunk.status = "minecraft:full".to_string();
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::hash::Hasher;
+
+    use twox_hash::XxHash64;
+
+    use super::*;
+    use crate::world::block;
+
+    fn hash(chunk: &Chunk) -> u64 {
+        let mut hasher = XxHash64::with_seed(0);
+        hasher.write(&chunk.to_packet(&VanillaRegistry).unwrap());
+        hasher.finish()
+    }
+
+    /// The chunks of fixed seeds, so that a change of the generation is noticed. If the change
+    /// is wanted, the hashes are updated.
+    #[test]
+    fn test_snapshots() {
+        let overworld = NoiseGenerator::new(
+            12345,
+            TerrainKind::Overworld,
+            BiomeSource::MultiNoise,
+            ChunkHeight::OVERWORLD,
+        );
+        let amplified = NoiseGenerator::new(
+            -99,
+            TerrainKind::Amplified,
+            BiomeSource::MultiNoise,
+            ChunkHeight::OVERWORLD,
+        );
+        let snapshots = [
+            (&overworld, 0, 0, 3890051106873320222),
+            (&overworld, -7, 12, 17962997148590983144),
+            (&overworld, 300, -41, 927096141949641369),
+            (&amplified, 3, 3, 2882272597448790596),
+        ];
+        for (generator, x, z, expected) in snapshots {
+            let chunk = generator.generate(x, z);
+            assert_eq!(hash(&chunk), expected, "Chunk {x} {z}");
+        }
+    }
+
+    #[test]
+    fn test_terrain() {
+        let generator = NoiseGenerator::new(
+            42,
+            TerrainKind::Overworld,
+            BiomeSource::MultiNoise,
+            ChunkHeight::OVERWORLD,
+        );
+        let state = |name| block::block(name).unwrap().default_state;
+        let (mut water, mut land, mut biomes) = (0, 0, Vec::new());
+        for i in 0..16 {
+            let (x, z) = (i * 37 - 300, i * -23 + 100);
+            let chunk = generator.generate(x, z);
+            assert_eq!((chunk.x, chunk.z), (x, z));
+            // Generated the same way again, in any order.
+            assert_eq!(chunk, generator.generate(x, z));
+
+            assert_eq!(chunk.block(0, -64, 0), state("minecraft:bedrock"));
+            assert_eq!(chunk.block(0, 319, 0), AIR);
+            let surface = chunk.heightmaps.world_surface.get(8, 8) as i32 - 64 - 1;
+            assert!((-40..300).contains(&surface), "{surface}");
+            match chunk.block(8, surface, 8) == state("minecraft:water") {
+                true => water += 1,
+                false => land += 1,
+            }
+            biomes.push(chunk.biome(8, surface, 8).unwrap());
+        }
+        assert!(land > 0);
+        assert!(water > 0 || biomes.windows(2).any(|pair| pair[0] != pair[1]));
+
+        // The seed changes the terrain.
+        let other = NoiseGenerator::new(
+            43,
+            TerrainKind::Overworld,
+            BiomeSource::MultiNoise,
+            ChunkHeight::OVERWORLD,
+        );
+        assert_ne!(other.generate(0, 0), generator.generate(0, 0));
+    }
+
+    #[test]
+    fn test_settings() {
+        let nbt = |snbt: &str| match crate::nbt::snbt::parse(snbt).unwrap() {
+            Tag::Compound(nbt) => nbt,
+            _ => unreachable!(),
+        };
+        let generator = NoiseGenerator::from_nbt(
+            &nbt(r#"{type:"minecraft:noise",settings:"minecraft:overworld",
+                biome_source:{type:"minecraft:fixed",biome:"minecraft:plains"}}"#),
+            ChunkHeight::OVERWORLD,
+            1,
+        )
+        .unwrap();
+        let plains = biome::id("plains").unwrap();
+        assert_eq!(generator.biomes, BiomeSource::Fixed(plains));
+        let chunk = generator.generate(2, 2);
+        assert!(chunk
+            .sections()
+            .iter()
+            .all(|section| section.biomes.iter().all(|b| b == plains)));
+
+        let generator = NoiseGenerator::from_nbt(
+            &nbt(
+                r#"{type:"minecraft:noise",settings:"minecraft:large_biomes",
+                biome_source:{type:"minecraft:multi_noise",preset:"minecraft:overworld"}}"#,
+            ),
+            ChunkHeight::OVERWORLD,
+            1,
+        )
+        .unwrap();
+        assert_eq!(generator.kind, TerrainKind::LargeBiomes);
+
+        assert!(NoiseGenerator::from_nbt(
+            &nbt(r#"{type:"minecraft:noise",settings:"minecraft:caves",biome_source:{}}"#),
+            ChunkHeight::OVERWORLD,
+            1,
+        )
+        .is_err());
+    }
+
+    /// The generators of the nether and the end, whose blocks are missing from the excerpt of
+    /// the block report.
+    #[test]
+    #[ignore = "needs the full 1.21.1 reports in fixtures/reports"]
+    fn test_vanilla_dimensions() {
+        let settings = |name: &str| {
+            let mut generator = Compound::new();
+            generator.insert("type".to_string(), Tag::from("minecraft:noise"));
+            generator.insert("settings".to_string(), Tag::from(name));
+            generator
+        };
+        let state = |name| block::block(name).unwrap().default_state;
+
+        let nether = from_nbt(&settings("minecraft:nether"), ChunkHeight::NETHER, 3).unwrap();
+        let chunk = nether.generate(0, 0);
+        assert_eq!(chunk.block(8, 0, 8), state("minecraft:bedrock"));
+        assert!((40..100).any(|y| chunk.block(8, y, 8) == state("minecraft:netherrack")));
+
+        let end = from_nbt(&settings("minecraft:end"), ChunkHeight::END, 3).unwrap();
+        let chunk = end.generate(0, 0);
+        let top = chunk.heightmaps.world_surface.get(0, 0) as i32 - 1;
+        assert_eq!(chunk.block(0, top, 0), state("minecraft:end_stone"));
+    }
+}
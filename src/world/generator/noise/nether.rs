@@ -0,0 +1,257 @@
+//! The Nether: a cave between two layers of bedrock, up to the logical height of the dimension,
+//! with a sea of lava at its bottom. Its biomes come from a temperature and a humidity like in
+//! the overworld, but there are only five of them, and each one covers its floors with its own
+//! blocks.
+
+use crate::world::biome;
+use crate::world::block::{self, BlockStateError};
+use crate::world::chunk::{Chunk, ChunkHeight, VanillaRegistry};
+use crate::world::generator::{light_chunk, ChunkGenerator};
+
+use super::perlin::{lerp, NormalNoise};
+use super::surface::States;
+use super::CellDensity;
+
+/// The top of the sea of lava.
+pub const LAVA_LEVEL: i32 = 31;
+
+/// The logical height of the Nether: the terrain is below it, and there is only air above it.
+const CEILING: i32 = 128;
+
+/// The biomes, at their temperature and humidity, with how far they are pushed away from the
+/// others.
+const BIOMES: [(&str, f64, f64, f64); 5] = [
+    ("nether_wastes", 0.0, 0.0, 0.0),
+    ("soul_sand_valley", 0.0, -0.5, 0.0),
+    ("crimson_forest", 0.4, 0.0, 0.0),
+    ("warped_forest", 0.0, 0.5, 0.375),
+    ("basalt_deltas", -0.5, 0.0, 0.175),
+];
+
+/// The block states that the generator places.
+pub struct NetherStates {
+    pub netherrack: u32,
+    pub lava: u32,
+    pub bedrock: u32,
+    pub soul_sand: u32,
+    pub soul_soil: u32,
+    pub gravel: u32,
+    pub crimson_nylium: u32,
+    pub warped_nylium: u32,
+    pub basalt: u32,
+    pub blackstone: u32,
+}
+
+impl NetherStates {
+    /// The vanilla blocks, which must be in the block registry.
+    pub fn vanilla() -> Result<Self, BlockStateError> {
+        Ok(NetherStates {
+            netherrack: block::parse_state("minecraft:netherrack")?,
+            lava: block::parse_state("minecraft:lava")?,
+            bedrock: block::parse_state("minecraft:bedrock")?,
+            soul_sand: block::parse_state("minecraft:soul_sand")?,
+            soul_soil: block::parse_state("minecraft:soul_soil")?,
+            gravel: block::parse_state("minecraft:gravel")?,
+            crimson_nylium: block::parse_state("minecraft:crimson_nylium")?,
+            warped_nylium: block::parse_state("minecraft:warped_nylium")?,
+            basalt: block::parse_state("minecraft:basalt")?,
+            blackstone: block::parse_state("minecraft:blackstone")?,
+        })
+    }
+}
+
+pub struct NetherGenerator {
+    seed: i64,
+    height: ChunkHeight,
+    states: NetherStates,
+    terrain: NormalNoise,
+    temperature: NormalNoise,
+    vegetation: NormalNoise,
+    surface: NormalNoise,
+}
+
+impl NetherGenerator {
+    pub fn new(seed: i64, height: ChunkHeight, states: NetherStates) -> Self {
+        NetherGenerator {
+            seed,
+            height,
+            states,
+            terrain: NormalNoise::named(seed, "nether_terrain", -6, &[1.0, 1.0, 1.0, 1.0]),
+            temperature: NormalNoise::named(seed, "nether_temperature", -8, &[1.0, 1.0]),
+            vegetation: NormalNoise::named(seed, "nether_vegetation", -8, &[1.0, 1.0]),
+            surface: NormalNoise::named(seed, "nether_surface", -4, &[1.0, 1.0, 1.0]),
+        }
+    }
+
+    /// The biome of a column, the closest one to its temperature and humidity.
+    fn biome(&self, x: f64, z: f64) -> &'static str {
+        let temperature = self.temperature.sample(x, 0.0, z);
+        let humidity = self.vegetation.sample(x, 0.0, z);
+        let distance = |(_, t, h, offset): &(&str, f64, f64, f64)| {
+            (temperature - t).powi(2) + (humidity - h).powi(2) + offset.powi(2)
+        };
+        BIOMES
+            .iter()
+            .min_by(|a, b| distance(a).total_cmp(&distance(b)))
+            .map_or("nether_wastes", |(name, ..)| name)
+    }
+
+    /// The density of the terrain: a 3D noise, which becomes solid near the floor and the
+    /// ceiling.
+    fn density(&self, x: f64, y: f64, z: f64) -> f64 {
+        let noise = self.terrain.sample(x, y * 1.5, z) + 0.05;
+        let ceiling = ((y - (CEILING - 24) as f64) / 24.0).clamp(0.0, 1.0);
+        let floor = ((24.0 - y) / 24.0).clamp(0.0, 1.0);
+        lerp(floor, lerp(ceiling, noise, 1.0), 1.5)
+    }
+
+    /// The block that replaces the netherrack `depth` blocks below a floor of a biome, if any.
+    fn floor(&self, biome: &str, depth: i32, y: i32, noise: f64) -> Option<u32> {
+        let states = &self.states;
+        match biome {
+            "crimson_forest" if depth == 0 => Some(states.crimson_nylium),
+            "warped_forest" if depth == 0 => Some(states.warped_nylium),
+            "soul_sand_valley" if depth < 3 => match noise > 0.0 {
+                true => Some(states.soul_sand),
+                false => Some(states.soul_soil),
+            },
+            "basalt_deltas" if depth < 3 => match noise > 0.0 {
+                true => Some(states.basalt),
+                false => Some(states.blackstone),
+            },
+            // The shores of the sea of lava.
+            "nether_wastes" if depth < 3 && (LAVA_LEVEL - 1..=LAVA_LEVEL + 4).contains(&y) => {
+                match noise {
+                    noise if noise > 0.3 => Some(states.gravel),
+                    noise if noise < -0.3 => Some(states.soul_sand),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ChunkGenerator for NetherGenerator {
+    fn generate(&self, x: i32, z: i32) -> Chunk {
+        let mut chunk = Chunk::new(x, z, self.height, 0);
+        let (min_x, min_z) = (x * 16, z * 16);
+        let min_y = self.height.min_y;
+        let top = CEILING.min(min_y + self.height.height as i32);
+
+        // The biomes are the same from the bottom to the top.
+        let mut biomes = [[""; 4]; 4];
+        for (qz, row) in biomes.iter_mut().enumerate() {
+            for (qx, name) in row.iter_mut().enumerate() {
+                *name = self.biome(
+                    (min_x + qx as i32 * 4) as f64,
+                    (min_z + qz as i32 * 4) as f64,
+                );
+                let id = biome::id(name).expect("The Nether biomes are vanilla biomes");
+                for y in (min_y..min_y + self.height.height as i32).step_by(4) {
+                    chunk.set_biome(qx as i32 * 4, y, qz as i32 * 4, id);
+                }
+            }
+        }
+
+        let density = CellDensity::new(x, z, self.height, |x, z| move |y| self.density(x, y, z));
+        let states = &self.states;
+        for lx in 0..16 {
+            for lz in 0..16 {
+                let (world_x, world_z) = (min_x + lx, min_z + lz);
+                let biome = biomes[lz as usize / 4][lx as usize / 4];
+                let noise = self.surface.sample(world_x as f64, 0.0, world_z as f64);
+                // The blocks since the last air or lava above, for the floors. The terrain
+                // below the ceiling is not a floor.
+                let mut depth = 3;
+                for y in (min_y..top).rev() {
+                    let state = if States::is_bedrock(self.seed, world_x, y, world_z, y - min_y)
+                        || States::is_bedrock(self.seed, world_x, y, world_z, top - 1 - y)
+                    {
+                        states.bedrock
+                    } else if density.get(lx, y, lz) > 0.0 {
+                        let state = self.floor(biome, depth, y, noise);
+                        depth += 1;
+                        state.unwrap_or(states.netherrack)
+                    } else {
+                        depth = 0;
+                        match y <= LAVA_LEVEL {
+                            true => states.lava,
+                            false => continue,
+                        }
+                    };
+                    chunk.set_block(lx, y, lz, state);
+                }
+            }
+        }
+
+        chunk.update_heightmaps(&VanillaRegistry);
+        light_chunk(&mut chunk, false);
+        chunk.status = "minecraft:full".to_string();
+        chunk
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::block;
+    use crate::world::chunk::AIR;
+
+    /// The excerpt of the block report has no Nether blocks, so overworld blocks stand in for
+    /// them.
+    fn states() -> NetherStates {
+        let state = |name| block::block(name).unwrap().default_state;
+        NetherStates {
+            netherrack: state("stone"),
+            lava: state("lava"),
+            bedrock: state("bedrock"),
+            soul_sand: state("sand"),
+            soul_soil: state("dirt"),
+            gravel: state("gravel"),
+            crimson_nylium: state("granite"),
+            warped_nylium: state("diorite"),
+            basalt: state("andesite"),
+            blackstone: state("cobblestone"),
+        }
+    }
+
+    #[test]
+    fn test_nether() {
+        let generator = NetherGenerator::new(7, ChunkHeight::NETHER, states());
+        let states = &generator.states;
+        let (stone, lava, bedrock) = (states.netherrack, states.lava, states.bedrock);
+        let (mut solid, mut total, mut lava_blocks) = (0, 0, 0);
+        let mut found = Vec::new();
+        for i in 0..8 {
+            let chunk = generator.generate(i * 5 - 20, i * -3);
+            assert_eq!(chunk, generator.generate(i * 5 - 20, i * -3));
+            assert!(chunk.sky_light.iter().all(Option::is_none));
+
+            assert_eq!(chunk.block(3, 0, 3), bedrock);
+            assert_eq!(chunk.block(3, CEILING - 1, 3), bedrock);
+            assert_eq!(chunk.block(3, CEILING, 3), AIR);
+            assert_eq!(chunk.block(3, 255, 3), AIR);
+            for y in 40..100 {
+                total += 1;
+                solid += u32::from(chunk.block(8, y, 8) == stone);
+            }
+            lava_blocks += (5..=LAVA_LEVEL)
+                .filter(|y| chunk.block(8, *y, 8) == lava)
+                .count();
+            assert!(((LAVA_LEVEL + 1)..CEILING).all(|y| chunk.block(8, y, 8) != lava));
+
+            let id = chunk.biome(8, 64, 8).unwrap();
+            assert_eq!(chunk.biome(8, 200, 8), Some(id));
+            found.push(biome::name(id).unwrap());
+        }
+        // Caves, and a sea of lava in some of them.
+        assert!(
+            (total / 5..total * 4 / 5).contains(&solid),
+            "{solid} of {total}"
+        );
+        assert!(lava_blocks > 0);
+        let nether = BIOMES.map(|(name, ..)| format!("minecraft:{name}"));
+        assert!(found.iter().all(|name| nether.contains(&name.to_string())));
+    }
+}
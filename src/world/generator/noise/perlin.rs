@@ -0,0 +1,243 @@
+//! Perlin noise like vanilla's: `ImprovedNoise` for one octave, `PerlinNoise` for the sum of
+//! octaves of halving amplitudes, and `NormalNoise`, two of them added together so that the
+//! values are spread evenly around 0, mostly from -1 to 1.
+
+use super::random::Xoroshiro;
+
+/// The gradients of the corners of the cells.
+const GRADIENTS: [[f64; 3]; 16] = [
+    [1.0, 1.0, 0.0],
+    [-1.0, 1.0, 0.0],
+    [1.0, -1.0, 0.0],
+    [-1.0, -1.0, 0.0],
+    [1.0, 0.0, 1.0],
+    [-1.0, 0.0, 1.0],
+    [1.0, 0.0, -1.0],
+    [-1.0, 0.0, -1.0],
+    [0.0, 1.0, 1.0],
+    [0.0, -1.0, 1.0],
+    [0.0, 1.0, -1.0],
+    [0.0, -1.0, -1.0],
+    [1.0, 1.0, 0.0],
+    [0.0, -1.0, 1.0],
+    [-1.0, 1.0, 0.0],
+    [0.0, -1.0, -1.0],
+];
+
+pub fn lerp(t: f64, a: f64, b: f64) -> f64 {
+    a + t * (b - a)
+}
+
+fn smoothstep(t: f64) -> f64 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+/// Keeps the coordinates small, since the noise loses precision far from 0.
+fn wrap(value: f64) -> f64 {
+    const ROUND: f64 = 33_554_432.0;
+    value - (value / ROUND + 0.5).floor() * ROUND
+}
+
+pub struct ImprovedNoise {
+    permutation: [u8; 256],
+    offset: [f64; 3],
+}
+
+impl ImprovedNoise {
+    pub fn new(random: &mut Xoroshiro) -> Self {
+        let offset = [
+            random.next_double() * 256.0,
+            random.next_double() * 256.0,
+            random.next_double() * 256.0,
+        ];
+        let mut permutation = [0; 256];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = i as u8;
+        }
+        for i in 0..256 {
+            let j = i + random.next_int(256 - i as u32) as usize;
+            permutation.swap(i, j);
+        }
+        ImprovedNoise {
+            permutation,
+            offset,
+        }
+    }
+
+    fn hash(&self, i: i32) -> usize {
+        self.permutation[(i & 0xFF) as usize] as usize
+    }
+
+    fn gradient(&self, hash: usize, x: f64, y: f64, z: f64) -> f64 {
+        let [gx, gy, gz] = GRADIENTS[hash & 15];
+        gx * x + gy * y + gz * z
+    }
+
+    /// The noise at a position, from about -1 to 1.
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let (x, y, z) = (x + self.offset[0], y + self.offset[1], z + self.offset[2]);
+        let (fx, fy, fz) = (x.floor(), y.floor(), z.floor());
+        let (xi, yi, zi) = (fx as i32, fy as i32, fz as i32);
+        let (dx, dy, dz) = (x - fx, y - fy, z - fz);
+
+        let a = self.hash(xi);
+        let b = self.hash(xi + 1);
+        let aa = self.hash(a as i32 + yi);
+        let ab = self.hash(a as i32 + yi + 1);
+        let ba = self.hash(b as i32 + yi);
+        let bb = self.hash(b as i32 + yi + 1);
+        let corner = |hash: usize, z: i32, dx: f64, dy: f64, dz: f64| {
+            self.gradient(self.hash(hash as i32 + z), dx, dy, dz)
+        };
+
+        let (u, v, w) = (smoothstep(dx), smoothstep(dy), smoothstep(dz));
+        lerp(
+            w,
+            lerp(
+                v,
+                lerp(
+                    u,
+                    corner(aa, zi, dx, dy, dz),
+                    corner(ba, zi, dx - 1.0, dy, dz),
+                ),
+                lerp(
+                    u,
+                    corner(ab, zi, dx, dy - 1.0, dz),
+                    corner(bb, zi, dx - 1.0, dy - 1.0, dz),
+                ),
+            ),
+            lerp(
+                v,
+                lerp(
+                    u,
+                    corner(aa, zi + 1, dx, dy, dz - 1.0),
+                    corner(ba, zi + 1, dx - 1.0, dy, dz - 1.0),
+                ),
+                lerp(
+                    u,
+                    corner(ab, zi + 1, dx, dy - 1.0, dz - 1.0),
+                    corner(bb, zi + 1, dx - 1.0, dy - 1.0, dz - 1.0),
+                ),
+            ),
+        )
+    }
+}
+
+/// Octaves of noise, the first one with a frequency of `2^first_octave` per block and each next
+/// one with twice the frequency and half the amplitude.
+pub struct PerlinNoise {
+    /// The octaves with their amplitude, None for the ones of amplitude 0.
+    octaves: Vec<Option<(ImprovedNoise, f64)>>,
+    lowest_input_factor: f64,
+    lowest_value_factor: f64,
+}
+
+impl PerlinNoise {
+    pub fn new(random: &mut Xoroshiro, first_octave: i32, amplitudes: &[f64]) -> Self {
+        let octaves = amplitudes
+            .iter()
+            .map(|amplitude| {
+                // Every octave uses the random numbers, so that the others do not change.
+                let noise = ImprovedNoise::new(random);
+                (*amplitude != 0.0).then_some((noise, *amplitude))
+            })
+            .collect();
+        let count = amplitudes.len() as i32;
+        PerlinNoise {
+            octaves,
+            lowest_input_factor: 2f64.powi(first_octave),
+            lowest_value_factor: 2f64.powi(count - 1) / (2f64.powi(count) - 1.0),
+        }
+    }
+
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let mut value = 0.0;
+        let mut input_factor = self.lowest_input_factor;
+        let mut value_factor = self.lowest_value_factor;
+        for octave in &self.octaves {
+            if let Some((noise, amplitude)) = octave {
+                let sample = noise.sample(
+                    wrap(x * input_factor),
+                    wrap(y * input_factor),
+                    wrap(z * input_factor),
+                );
+                value += amplitude * sample * value_factor;
+            }
+            input_factor *= 2.0;
+            value_factor /= 2.0;
+        }
+        value
+    }
+}
+
+/// Two `PerlinNoise` at slightly different scales, added together.
+pub struct NormalNoise {
+    first: PerlinNoise,
+    second: PerlinNoise,
+    value_factor: f64,
+}
+
+impl NormalNoise {
+    const INPUT_FACTOR: f64 = 1.018_126_888_217_522_7;
+
+    pub fn new(random: &mut Xoroshiro, first_octave: i32, amplitudes: &[f64]) -> Self {
+        let used = amplitudes.iter().enumerate().filter(|(_, a)| **a != 0.0);
+        let min = used.clone().map(|(i, _)| i).min().unwrap_or(0);
+        let max = used.map(|(i, _)| i).max().unwrap_or(0);
+        let expected_deviation = 0.1 * (1.0 + 1.0 / (max - min + 1) as f64);
+        NormalNoise {
+            first: PerlinNoise::new(random, first_octave, amplitudes),
+            second: PerlinNoise::new(random, first_octave, amplitudes),
+            value_factor: (1.0 / 6.0) / expected_deviation,
+        }
+    }
+
+    /// A noise of the world, from its seed and its name.
+    pub fn named(seed: i64, name: &str, first_octave: i32, amplitudes: &[f64]) -> Self {
+        Self::new(&mut Xoroshiro::named(seed, name), first_octave, amplitudes)
+    }
+
+    pub fn sample(&self, x: f64, y: f64, z: f64) -> f64 {
+        let second = self.second.sample(
+            x * Self::INPUT_FACTOR,
+            y * Self::INPUT_FACTOR,
+            z * Self::INPUT_FACTOR,
+        );
+        (self.first.sample(x, y, z) + second) * self.value_factor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_noise() {
+        let noise = NormalNoise::named(7, "test", -4, &[1.0, 1.0, 0.0, 1.0]);
+        let again = NormalNoise::named(7, "test", -4, &[1.0, 1.0, 0.0, 1.0]);
+        let mut sum = 0.0;
+        let mut max: f64 = 0.0;
+        for i in 0..2000 {
+            let (x, y, z) = (i as f64 * 3.7, (i % 50) as f64, -(i as f64) * 1.3);
+            let value = noise.sample(x, y, z);
+            assert_eq!(value, again.sample(x, y, z));
+            sum += value;
+            max = max.max(value.abs());
+        }
+        // Spread around 0, and continuous.
+        assert!((sum / 2000.0).abs() < 0.2, "{sum}");
+        assert!(max > 0.3 && max < 2.0, "{max}");
+        let step = (noise.sample(100.0, 5.0, 100.0) - noise.sample(100.1, 5.0, 100.0)).abs();
+        assert!(step < 0.05);
+
+        // The integer positions of one octave are 0, at the corners of the cells.
+        let improved = ImprovedNoise::new(&mut Xoroshiro::new(1));
+        let [x, y, z] = improved.offset;
+        assert!(
+            improved
+                .sample(x.ceil() - x, y.ceil() - y, z.ceil() - z)
+                .abs()
+                < 1e-9
+        );
+    }
+}
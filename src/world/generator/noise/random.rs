@@ -0,0 +1,88 @@
+//! The random numbers of the generator: Xoroshiro128++, like vanilla, and hashes of positions
+//! for the choices that must not depend on the order the chunks are generated in.
+
+use std::hash::Hasher;
+
+use twox_hash::XxHash64;
+
+/// The Stafford variant 13 of the 64 bits mixer, which spreads the bits of the seeds.
+fn mix(mut z: u64) -> u64 {
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
+
+pub struct Xoroshiro {
+    lo: u64,
+    hi: u64,
+}
+
+impl Xoroshiro {
+    pub fn new(seed: i64) -> Self {
+        let lo = seed as u64 ^ 0x6A09_E667_F3BC_C909;
+        let hi = lo.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        Xoroshiro {
+            lo: mix(lo),
+            hi: mix(hi),
+        }
+    }
+
+    /// A random source for one use of the world seed, like one noise.
+    pub fn named(seed: i64, name: &str) -> Self {
+        let mut hasher = XxHash64::with_seed(seed as u64);
+        hasher.write(name.as_bytes());
+        Self::new(hasher.finish() as i64)
+    }
+
+    pub fn next_long(&mut self) -> u64 {
+        let (lo, hi) = (self.lo, self.hi);
+        let result = lo.wrapping_add(hi).rotate_left(17).wrapping_add(lo);
+        let hi = hi ^ lo;
+        self.lo = lo.rotate_left(49) ^ hi ^ (hi << 21);
+        self.hi = hi.rotate_left(28);
+        result
+    }
+
+    /// A number from 0 to `bound`, excluded.
+    pub fn next_int(&mut self, bound: u32) -> u32 {
+        (((self.next_long() >> 32) * bound as u64) >> 32) as u32
+    }
+
+    /// A number from 0 to 1, excluded.
+    pub fn next_double(&mut self) -> f64 {
+        (self.next_long() >> 11) as f64 * f64::EPSILON / 2.0
+    }
+}
+
+/// A number from 0 to 1, excluded, which only depends on the seed and a position.
+pub fn at(seed: i64, x: i32, y: i32, z: i32) -> f64 {
+    let hash = (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+        ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+        ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+    (mix(hash ^ seed as u64) >> 11) as f64 * f64::EPSILON / 2.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random() {
+        let mut random = Xoroshiro::new(42);
+        let first: Vec<_> = (0..4).map(|_| random.next_long()).collect();
+        let mut again = Xoroshiro::new(42);
+        assert!(first.iter().all(|long| *long == again.next_long()));
+        assert_ne!(Xoroshiro::new(43).next_long(), first[0]);
+        assert_ne!(
+            Xoroshiro::named(42, "a").next_long(),
+            Xoroshiro::named(42, "b").next_long()
+        );
+
+        for _ in 0..1000 {
+            assert!(random.next_int(10) < 10);
+            assert!((0.0..1.0).contains(&random.next_double()));
+        }
+        assert_eq!(at(1, 2, 3, 4), at(1, 2, 3, 4));
+        assert_ne!(at(1, 2, 3, 4), at(1, 2, 3, 5));
+    }
+}
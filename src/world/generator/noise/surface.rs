@@ -0,0 +1,81 @@
+//! The surface rules: the blocks that replace the stone at the top of the ground, for each
+//! biome, and the bedrock at the bottom of the world.
+
+use crate::world::block;
+
+use super::random;
+
+/// The block states that the generator places.
+pub struct States {
+    pub stone: u32,
+    pub water: u32,
+    pub lava: u32,
+    pub bedrock: u32,
+    grass: u32,
+    dirt: u32,
+    coarse_dirt: u32,
+    podzol: u32,
+    sand: u32,
+    red_sand: u32,
+    gravel: u32,
+}
+
+impl States {
+    pub fn new() -> Self {
+        let state = |name| {
+            block::block(name)
+                .expect("The generated blocks are vanilla blocks")
+                .default_state
+        };
+        States {
+            stone: state("minecraft:stone"),
+            water: state("minecraft:water"),
+            lava: state("minecraft:lava"),
+            bedrock: state("minecraft:bedrock"),
+            grass: state("minecraft:grass_block"),
+            dirt: state("minecraft:dirt"),
+            coarse_dirt: state("minecraft:coarse_dirt"),
+            podzol: state("minecraft:podzol"),
+            sand: state("minecraft:sand"),
+            red_sand: state("minecraft:red_sand"),
+            gravel: state("minecraft:gravel"),
+        }
+    }
+
+    /// The top block and the blocks under it of a biome, given without its namespace. `noise`
+    /// from -1 to 1 makes patches of other blocks.
+    pub fn surface(&self, biome: &str, underwater: bool, noise: f64) -> (u32, u32) {
+        if underwater {
+            return match biome {
+                "warm_ocean" | "lukewarm_ocean" | "deep_lukewarm_ocean" | "river" | "beach" => {
+                    (self.sand, self.sand)
+                }
+                "frozen_river" | "stony_shore" => (self.gravel, self.gravel),
+                biome if biome.ends_with("ocean") => (self.gravel, self.gravel),
+                _ => (self.dirt, self.dirt),
+            };
+        }
+        match biome {
+            "desert" | "beach" | "snowy_beach" => (self.sand, self.sand),
+            "badlands" | "eroded_badlands" | "wooded_badlands" => (self.red_sand, self.red_sand),
+            "stony_shore" | "stony_peaks" | "jagged_peaks" | "frozen_peaks" => {
+                (self.stone, self.stone)
+            }
+            "windswept_gravelly_hills" if noise > 0.0 => (self.gravel, self.gravel),
+            "windswept_savanna" if noise > 0.1 => (self.coarse_dirt, self.dirt),
+            "old_growth_pine_taiga" | "old_growth_spruce_taiga" if noise > 0.0 => {
+                (self.podzol, self.dirt)
+            }
+            "old_growth_pine_taiga" | "old_growth_spruce_taiga" if noise < -0.3 => {
+                (self.coarse_dirt, self.dirt)
+            }
+            _ => (self.grass, self.dirt),
+        }
+    }
+
+    /// Whether a block is bedrock, at the bottom of the world or under the ceiling of the
+    /// Nether: always on the `layer` 0 at the edge, then less and less over the next four.
+    pub fn is_bedrock(seed: i64, x: i32, y: i32, z: i32, layer: i32) -> bool {
+        layer == 0 || (layer < 5 && random::at(seed, x, y, z) < (5 - layer) as f64 / 5.0)
+    }
+}
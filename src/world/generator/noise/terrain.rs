@@ -0,0 +1,214 @@
+//! The shape of the terrain: a density for each block, which is positive in the ground and
+//! negative in the air, like vanilla's density functions.
+//!
+//! The climate of a column gives its `offset`, how high the ground is, and its `factor`, how
+//! fast the density grows below the surface. The lower the factor, the more a 3D noise makes
+//! overhangs and floating pieces, like in the mountains. The caves are carved from the ground
+//! by two kinds of noise caves: large "cheese" caves, and long "spaghetti" tunnels.
+
+use super::climate::Climate;
+use super::perlin::NormalNoise;
+
+/// The water level of the oceans, and of the open spaces below it.
+pub const SEA_LEVEL: i32 = 63;
+
+/// The caves below it are filled with lava.
+pub const LAVA_LEVEL: i32 = -55;
+
+/// The height of the surface for each continentalness, in offset: blocks above the sea level
+/// divided by 128.
+const CONTINENTS: [(f64, f64); 11] = [
+    (-1.2, 0.04),
+    (-1.06, 0.04),
+    (-1.02, -0.27),
+    (-0.51, -0.27),
+    (-0.44, -0.15),
+    (-0.2, -0.12),
+    (-0.16, -0.02),
+    (-0.11, 0.0),
+    (0.03, 0.04),
+    (0.3, 0.1),
+    (1.0, 0.15),
+];
+
+/// Linear interpolation between points sorted by x, and their first or last value outside.
+fn spline(points: &[(f64, f64)], x: f64) -> f64 {
+    let next = points.partition_point(|(px, _)| *px <= x);
+    if next == 0 {
+        return points[0].1;
+    }
+    if next == points.len() {
+        return points[next - 1].1;
+    }
+    let ((x0, y0), (x1, y1)) = (points[next - 1], points[next]);
+    y0 + (x - x0) / (x1 - x0) * (y1 - y0)
+}
+
+fn clamped(value: f64) -> f64 {
+    value.clamp(0.0, 1.0)
+}
+
+/// The shape of the terrain in a column.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TerrainShape {
+    offset: f64,
+    factor: f64,
+}
+
+impl TerrainShape {
+    /// The shape for a climate. The amplified terrain has twice higher lands.
+    pub fn new(climate: &Climate, amplified: bool) -> Self {
+        let pv = climate.peaks_and_valleys();
+        let mut offset = spline(&CONTINENTS, climate.continentalness);
+
+        // The mountains, far from the coasts where the erosion is low.
+        let inland = clamped((climate.continentalness + 0.11) / 0.41);
+        let mountains = inland * clamped((0.45 - climate.erosion) / 1.45);
+        offset += mountains * (0.2 + pv.max(0.0) * 0.9);
+        offset += pv.min(0.0) * 0.04 * inland.max(0.3);
+
+        // The rivers, down to just below the sea level.
+        let river = clamped((-0.75 - pv) / 0.15);
+        if climate.continentalness > -0.19 && offset > -0.03 {
+            offset += (-0.03 - offset) * river;
+        }
+
+        let mut factor = 6.5 - 3.5 * mountains * (0.5 + pv.max(0.0) * 0.5);
+        if amplified && offset > 0.0 {
+            offset *= 2.0;
+            factor *= 0.8;
+        }
+        TerrainShape { offset, factor }
+    }
+
+    /// About the Y of the surface, without the 3D noise and the caves.
+    pub fn surface(&self) -> f64 {
+        SEA_LEVEL as f64 + 0.5 + self.offset * 128.0
+    }
+
+    /// The density of a block, before the 3D noise and the caves. It is 0 at the surface, and
+    /// grows 4 times faster in the ground than it decreases in the air.
+    fn density(&self, y: f64) -> f64 {
+        let density = (self.surface() - y) / 128.0 * self.factor;
+        if density > 0.0 {
+            density * 4.0
+        } else {
+            density
+        }
+    }
+}
+
+/// The 3D noises of the terrain and the caves, and the 2D ones of the aquifers.
+pub struct TerrainNoises {
+    base: NormalNoise,
+    cheese: NormalNoise,
+    spaghetti: [NormalNoise; 2],
+    aquifer: NormalNoise,
+    lava: NormalNoise,
+}
+
+/// The density of a block.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Density {
+    /// Without the caves: the blocks where it is not positive are above the ground.
+    pub terrain: f64,
+    /// With the caves: the blocks where it is positive are solid.
+    pub blocks: f64,
+}
+
+impl Density {
+    pub fn lerp(t: f64, a: Density, b: Density) -> Density {
+        Density {
+            terrain: a.terrain + t * (b.terrain - a.terrain),
+            blocks: a.blocks + t * (b.blocks - a.blocks),
+        }
+    }
+}
+
+impl TerrainNoises {
+    pub fn new(seed: i64) -> Self {
+        TerrainNoises {
+            base: NormalNoise::named(seed, "terrain", -7, &[1.0, 1.0, 1.0, 1.0]),
+            cheese: NormalNoise::named(
+                seed,
+                "cave_cheese",
+                -8,
+                &[0.5, 1.0, 2.0, 1.0, 2.0, 1.0, 0.0, 2.0, 0.0],
+            ),
+            spaghetti: [
+                NormalNoise::named(seed, "spaghetti_3d_1", -7, &[1.0]),
+                NormalNoise::named(seed, "spaghetti_3d_2", -7, &[1.0]),
+            ],
+            aquifer: NormalNoise::named(seed, "aquifer_fluid_level", -7, &[1.0, 1.0]),
+            lava: NormalNoise::named(seed, "aquifer_lava", -6, &[1.0]),
+        }
+    }
+
+    pub fn density(&self, shape: &TerrainShape, x: f64, y: f64, z: f64) -> Density {
+        let terrain = shape.density(y) + self.base.sample(x, y * 0.5, z) * 0.2;
+        if terrain <= 0.0 || y < (LAVA_LEVEL - 5) as f64 {
+            return Density {
+                terrain,
+                blocks: terrain,
+            };
+        }
+        // The caves rarely open to the surface.
+        let near_surface = (0.6 - terrain).max(0.0) * 4.0;
+        let cheese = (self.cheese.sample(x, y * 1.5, z) + 0.45) * 4.0 + near_surface;
+        let [a, b] = &self.spaghetti;
+        let spaghetti = (a.sample(x, y, z).abs().max(b.sample(x, y, z).abs()) - 0.07) * 8.0;
+        Density {
+            terrain,
+            blocks: terrain.min(cheese).min(spaghetti + near_surface),
+        }
+    }
+
+    /// The level of the water of the caves of a column, in the ground.
+    pub fn fluid_level(&self, x: f64, z: f64) -> i32 {
+        (-10.0 + self.aquifer.sample(x, 0.0, z) * 60.0).floor() as i32
+    }
+
+    /// Whether the caves of a column are filled with lava instead of water, deep enough.
+    pub fn is_lava(&self, x: f64, z: f64, y: i32) -> bool {
+        y <= LAVA_LEVEL || (y < -20 && self.lava.sample(x, 0.0, z) > 0.3)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn climate(continentalness: f64, erosion: f64, weirdness: f64) -> Climate {
+        Climate {
+            temperature: 0.0,
+            humidity: 0.0,
+            continentalness,
+            erosion,
+            weirdness,
+        }
+    }
+
+    #[test]
+    fn test_shapes() {
+        assert_eq!(spline(&CONTINENTS, -2.0), 0.04);
+        assert_eq!(spline(&CONTINENTS, 2.0), 0.15);
+        assert!((spline(&CONTINENTS, 0.165) - 0.07).abs() < 1e-9);
+
+        let surface = |c, e, w| TerrainShape::new(&climate(c, e, w), false).surface();
+        // Deep oceans, coasts, plains, then mountains.
+        assert!(surface(-0.7, 0.0, 0.3) < 40.0);
+        assert!((60.0..70.0).contains(&surface(-0.11, 0.2, 0.3)));
+        assert!((65.0..90.0).contains(&surface(0.2, 0.5, 0.3)));
+        assert!(surface(0.6, -0.9, 0.6) > 150.0);
+        // A river in the plains.
+        assert!(surface(0.2, 0.3, 0.0) < SEA_LEVEL as f64);
+
+        let amplified = TerrainShape::new(&climate(0.6, -0.9, 0.6), true).surface();
+        assert!(amplified > surface(0.6, -0.9, 0.6) + 100.0);
+
+        let noises = TerrainNoises::new(5);
+        let shape = TerrainShape::new(&climate(0.2, 0.5, 0.3), false);
+        assert!(noises.density(&shape, 0.0, 200.0, 0.0).blocks < 0.0);
+        assert!(noises.density(&shape, 0.0, -63.0, 0.0).terrain > 0.0);
+    }
+}
@@ -0,0 +1,106 @@
+//! The threads that generate the chunks. The generation takes long and uses the CPU, so it is
+//! kept off the tokio runtime: the chunks are asked for from any task, and are sent back when
+//! they are done. The threads are shared by the generators of every dimension.
+
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+
+use log::error;
+use tokio::sync::oneshot;
+
+use super::ChunkGenerator;
+use crate::world::chunk::Chunk;
+
+struct Job {
+    generator: Arc<dyn ChunkGenerator>,
+    x: i32,
+    z: i32,
+    done: oneshot::Sender<Chunk>,
+}
+
+pub struct WorkerPool {
+    jobs: Mutex<mpsc::Sender<Job>>,
+}
+
+impl WorkerPool {
+    /// The threads of the pool by default: one per core, but one for the server itself.
+    pub fn default_threads() -> usize {
+        thread::available_parallelism()
+            .map_or(1, |cores| cores.get().saturating_sub(1))
+            .max(1)
+    }
+
+    /// Starts `threads` threads, which stop when the pool is dropped.
+    pub fn new(threads: usize) -> Self {
+        let (sender, receiver) = mpsc::channel::<Job>();
+        let receiver = Arc::new(Mutex::new(receiver));
+        let threads = threads.max(1);
+        for i in 0..threads {
+            let receiver = Arc::clone(&receiver);
+            let spawned =
+                thread::Builder::new()
+                    .name(format!("Worldgen-{i}"))
+                    .spawn(move || loop {
+                        // The lock is released before the generation, for the other threads.
+                        let job = receiver.lock().unwrap().recv();
+                        let Ok(job) = job else {
+                            break;
+                        };
+                        // The chunk is not wanted anymore if the receiver is dropped.
+                        let _ = job.done.send(job.generator.generate(job.x, job.z));
+                    });
+            if let Err(e) = spawned {
+                error!("Failed to start a world generation thread: {e}");
+            }
+        }
+        WorkerPool {
+            jobs: Mutex::new(sender),
+        }
+    }
+
+    /// Queues the generation of a chunk. The chunks are generated in the order they are asked
+    /// for, and the receiver gets the chunk once it is done.
+    pub fn generate(
+        &self,
+        generator: &Arc<dyn ChunkGenerator>,
+        x: i32,
+        z: i32,
+    ) -> oneshot::Receiver<Chunk> {
+        let (done, receiver) = oneshot::channel();
+        let job = Job {
+            generator: Arc::clone(generator),
+            x,
+            z,
+            done,
+        };
+        // If every thread failed to start, the receiver gets an error.
+        let _ = self.jobs.lock().unwrap().send(job);
+        receiver
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::world::chunk::ChunkHeight;
+    use crate::world::generator::noise::{BiomeSource, NoiseGenerator, TerrainKind};
+
+    #[test]
+    fn test_pool() {
+        let generator: Arc<dyn ChunkGenerator> = Arc::new(NoiseGenerator::new(
+            8,
+            TerrainKind::Overworld,
+            BiomeSource::MultiNoise,
+            ChunkHeight::OVERWORLD,
+        ));
+        let pool = WorkerPool::new(3);
+        let chunks: Vec<_> = (0..6)
+            .map(|i| (i, pool.generate(&generator, i, -i)))
+            .collect();
+        for (i, receiver) in chunks {
+            let chunk = receiver.blocking_recv().unwrap();
+            assert_eq!(chunk, generator.generate(i, -i));
+        }
+        assert!(WorkerPool::default_threads() >= 1);
+    }
+}
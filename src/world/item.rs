@@ -0,0 +1,16 @@
+//! The item registry, generated at build time from the vanilla `registries.json` data report,
+//! with the IDs of the client for `consts::minecraft::PROTOCOL_VERSION`.
+
+include!(concat!(env!("OUT_DIR"), "/items.rs"));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_items() {
+        assert_eq!(ITEMS[0], "minecraft:air");
+        assert_eq!(ITEMS[1], "minecraft:stone");
+        assert_eq!(ITEMS[61], "minecraft:gravel");
+    }
+}
@@ -0,0 +1,447 @@
+//! 'level.dat', the metadata of a world: its seed, spawn, time, weather, game rules and how its
+//! dimensions are generated. The file is gzipped NBT, with everything in a 'Data' compound.
+//! Each save moves the previous file to 'level.dat_old', which is read if 'level.dat' is broken.
+//!
+//! See https://minecraft.wiki/w/Java_Edition_level_format#level.dat_format
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use log::warn;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+use crate::config::{Difficulty, Gamemode, Settings, WorlPreset};
+use crate::consts::minecraft::{DATA_VERSION, VERSION};
+use crate::nbt::binary;
+use crate::nbt::{from_tag, snbt, to_tag, Compound, NbtError, Tag};
+use crate::world::dimension::DimensionError;
+use crate::world::generator::flat::FlatSettings;
+
+pub const FILE_NAME: &str = "level.dat";
+pub const BACKUP_FILE_NAME: &str = "level.dat_old";
+const TEMPORARY_FILE_NAME: &str = "level.dat.tmp";
+
+/// The version of the Anvil format, in the lowercase 'version' entry.
+const ANVIL_VERSION: i32 = 19133;
+
+#[derive(Error, Debug)]
+pub enum LevelError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid level NBT: {0}")]
+    Nbt(#[from] NbtError),
+    #[error("No 'Data' compound in level.dat")]
+    MissingData,
+    #[error("Failed to save the chunks: {0}")]
+    Chunks(#[from] DimensionError),
+}
+
+/// The entries of the 'Data' compound. The missing ones take their default value, and the
+/// unknown ones are kept as they are when saving.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct LevelData {
+    #[serde(rename = "LevelName")]
+    pub level_name: String,
+    #[serde(rename = "DataVersion")]
+    pub data_version: i32,
+    #[serde(rename = "version")]
+    pub anvil_version: i32,
+    #[serde(rename = "Version")]
+    pub version: VersionInfo,
+    #[serde(rename = "GameType")]
+    pub game_type: i32,
+    #[serde(rename = "Difficulty")]
+    pub difficulty: i8,
+    #[serde(rename = "DifficultyLocked")]
+    pub difficulty_locked: bool,
+    pub hardcore: bool,
+    #[serde(rename = "allowCommands")]
+    pub allow_commands: bool,
+    #[serde(rename = "SpawnX")]
+    pub spawn_x: i32,
+    #[serde(rename = "SpawnY")]
+    pub spawn_y: i32,
+    #[serde(rename = "SpawnZ")]
+    pub spawn_z: i32,
+    #[serde(rename = "SpawnAngle")]
+    pub spawn_angle: f32,
+    /// The number of ticks since the world was created.
+    #[serde(rename = "Time")]
+    pub time: i64,
+    /// The time of day in ticks, 24000 per day. It stops with the game rule 'doDaylightCycle'.
+    #[serde(rename = "DayTime")]
+    pub day_time: i64,
+    /// When the world was last saved, in milliseconds since the Unix epoch.
+    #[serde(rename = "LastPlayed")]
+    pub last_played: i64,
+    pub raining: bool,
+    #[serde(rename = "rainTime")]
+    pub rain_time: i32,
+    pub thundering: bool,
+    #[serde(rename = "thunderTime")]
+    pub thunder_time: i32,
+    #[serde(rename = "clearWeatherTime")]
+    pub clear_weather_time: i32,
+    /// The game rules that were set, all as strings.
+    #[serde(rename = "GameRules")]
+    pub game_rules: BTreeMap<String, String>,
+    #[serde(rename = "WorldGenSettings")]
+    pub world_gen_settings: WorldGenSettings,
+    pub initialized: bool,
+    #[serde(rename = "ServerBrands")]
+    pub server_brands: Vec<String>,
+    #[serde(rename = "WasModded")]
+    pub was_modded: bool,
+    /// The 'Data' compound as it was read, for the entries not in this struct.
+    #[serde(skip)]
+    other: Compound,
+}
+
+/// The version of the game that last saved the world.
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default, rename_all = "PascalCase")]
+pub struct VersionInfo {
+    pub id: i32,
+    pub name: String,
+    pub series: String,
+    pub snapshot: bool,
+}
+
+impl VersionInfo {
+    pub fn current() -> Self {
+        VersionInfo {
+            id: DATA_VERSION,
+            name: VERSION.to_string(),
+            series: "main".to_string(),
+            snapshot: false,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct WorldGenSettings {
+    pub seed: i64,
+    /// Whether the structures are generated, 'generate-structures' in the properties.
+    pub generate_features: bool,
+    pub bonus_chest: bool,
+    /// The dimensions by ID, each with its type and its generator.
+    pub dimensions: Compound,
+}
+
+impl WorldGenSettings {
+    /// The settings of a new world, with the dimensions of its preset. The superflat worlds
+    /// use the JSON of 'generator-settings'.
+    pub fn new(
+        seed: i64,
+        generate_features: bool,
+        preset: WorlPreset,
+        generator_settings: &str,
+    ) -> Self {
+        let overworld = match preset {
+            WorlPreset::NORMAL => noise_generator("overworld", "overworld"),
+            WorlPreset::FLAT => format!(
+                r#"{{type:"minecraft:flat",settings:{}}}"#,
+                Tag::Compound(flat_settings(generator_settings).to_nbt())
+            ),
+            WorlPreset::LARGEBIOMES => noise_generator("large_biomes", "overworld"),
+            WorlPreset::AMPLIFIED => noise_generator("amplified", "overworld"),
+            WorlPreset::SINGLEBIOMESURFACE => r#"{type:"minecraft:noise",
+                settings:"minecraft:overworld",
+                biome_source:{type:"minecraft:fixed",biome:"minecraft:plains"}}"#
+                .to_string(),
+        };
+        let dimensions = [
+            ("minecraft:overworld", overworld),
+            ("minecraft:the_nether", noise_generator("nether", "nether")),
+            (
+                "minecraft:the_end",
+                r#"{type:"minecraft:noise",settings:"minecraft:end",
+                    biome_source:{type:"minecraft:the_end"}}"#
+                    .to_string(),
+            ),
+        ]
+        .into_iter()
+        .map(|(id, generator)| {
+            let dimension = format!(r#"{{type:"{id}",generator:{generator}}}"#);
+            let tag = snbt::parse(&dimension).expect("The generators are valid SNBT");
+            (id.to_string(), tag)
+        })
+        .collect();
+
+        WorldGenSettings {
+            seed,
+            generate_features,
+            bonus_chest: false,
+            dimensions,
+        }
+    }
+
+    /// The first 8 bytes of the SHA-256 digest of the seed, which the client is sent instead of
+    /// the seed for the biome blending.
+    pub fn hashed_seed(&self) -> i64 {
+        let digest = Sha256::digest(self.seed.to_le_bytes());
+        i64::from_le_bytes(digest[..8].try_into().unwrap())
+    }
+
+    /// Returns whether a dimension is superflat, which lowers the client's horizon.
+    pub fn is_flat(&self, dimension: &str) -> bool {
+        self.generator(dimension)
+            .and_then(|generator| generator.get("type"))
+            .and_then(Tag::as_str)
+            == Some("minecraft:flat")
+    }
+
+    /// Returns the generator of a dimension, like `{type:"minecraft:noise",...}`.
+    pub fn generator(&self, dimension: &str) -> Option<&Compound> {
+        self.dimensions
+            .get(dimension)?
+            .as_compound()?
+            .get("generator")?
+            .as_compound()
+    }
+}
+
+/// The superflat settings of 'generator-settings', or the default ones if it is empty or
+/// invalid.
+fn flat_settings(generator_settings: &str) -> FlatSettings {
+    match generator_settings.trim() {
+        "" | "{}" => FlatSettings::default(),
+        json => FlatSettings::from_json(json).unwrap_or_else(|e| {
+            warn!("Invalid generator-settings, using the default superflat layers: {e}");
+            FlatSettings::default()
+        }),
+    }
+}
+
+/// A noise generator with the biomes of a multi-noise preset.
+fn noise_generator(settings: &str, preset: &str) -> String {
+    format!(
+        r#"{{type:"minecraft:noise",settings:"minecraft:{settings}",
+            biome_source:{{type:"minecraft:multi_noise",preset:"minecraft:{preset}"}}}}"#
+    )
+}
+
+impl LevelData {
+    /// The metadata of a new world, from the properties. The seed is random if 'level-seed' is
+    /// empty.
+    pub fn new(config: &Settings) -> Self {
+        let seed = config.level_seed.unwrap_or_else(rand::random);
+        LevelData {
+            level_name: config
+                .level_name
+                .clone()
+                .unwrap_or_else(|| "world".to_string()),
+            data_version: DATA_VERSION,
+            anvil_version: ANVIL_VERSION,
+            version: VersionInfo::current(),
+            game_type: config.gamemode.id(),
+            // Hardcore worlds are always on hard.
+            difficulty: match config.hardcore {
+                true => Difficulty::HARD.id(),
+                false => config.difficulty.id(),
+            },
+            hardcore: config.hardcore,
+            spawn_y: 64,
+            world_gen_settings: WorldGenSettings::new(
+                seed,
+                config.generate_structures,
+                config.level_type,
+                &config.generator_settings,
+            ),
+            initialized: true,
+            server_brands: vec!["copper".to_string()],
+            ..Default::default()
+        }
+    }
+
+    /// Reads the 'level.dat' of a world, or its backup if it cannot be read.
+    /// Returns None if the world has neither.
+    pub fn load(dir: &Path) -> Result<Option<Self>, LevelError> {
+        let path = dir.join(FILE_NAME);
+        let backup = dir.join(BACKUP_FILE_NAME);
+        match Self::read(&path) {
+            Ok(level) => Ok(Some(level)),
+            Err(e) if backup.exists() => {
+                warn!(
+                    "Failed to read {}: {e}, reading {BACKUP_FILE_NAME} instead",
+                    path.display()
+                );
+                Self::read(&backup).map(Some)
+            }
+            Err(LevelError::Io(e)) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn read(path: &Path) -> Result<Self, LevelError> {
+        let (_, mut root) = binary::read_file(&fs::read(path)?)?;
+        let Some(Tag::Compound(data)) = root.remove("Data") else {
+            return Err(LevelError::MissingData);
+        };
+        let mut level: LevelData = from_tag(Tag::Compound(data.clone()))?;
+        level.other = data;
+        Ok(level)
+    }
+
+    /// Writes 'level.dat', as saved by the current version, after moving the previous one to
+    /// 'level.dat_old'.
+    pub fn save(&mut self, dir: &Path) -> Result<(), LevelError> {
+        self.data_version = DATA_VERSION;
+        self.anvil_version = ANVIL_VERSION;
+        self.version = VersionInfo::current();
+        self.last_played = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_millis() as i64);
+
+        let mut data = self.other.clone();
+        if let Tag::Compound(entries) = to_tag(self)? {
+            data.extend(entries);
+        }
+        let root = Compound::from([("Data".to_string(), Tag::Compound(data))]);
+        let bytes = binary::write_file("", &root)?;
+
+        // The new file is complete before it replaces the old one.
+        let temporary = dir.join(TEMPORARY_FILE_NAME);
+        let path = dir.join(FILE_NAME);
+        fs::write(&temporary, bytes)?;
+        if path.exists() {
+            fs::rename(&path, dir.join(BACKUP_FILE_NAME))?;
+        }
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+
+    pub fn gamemode(&self) -> Gamemode {
+        Gamemode::from_id(self.game_type).unwrap_or(Gamemode::SURVIVAL)
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        Difficulty::from_id(self.difficulty).unwrap_or(Difficulty::NORMAL)
+    }
+
+    /// Returns the value of a boolean game rule, or its default if it is not set.
+    pub fn game_rule_bool(&self, name: &str, default: bool) -> bool {
+        self.game_rules
+            .get(name)
+            .and_then(|value| value.parse().ok())
+            .unwrap_or(default)
+    }
+
+    /// Advances the time by one tick.
+    pub fn tick(&mut self) {
+        self.time += 1;
+        if self.game_rule_bool("doDaylightCycle", true) {
+            self.day_time += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn level() -> LevelData {
+        LevelData {
+            level_name: "world".to_string(),
+            game_type: Gamemode::CREATIVE.id(),
+            difficulty: Difficulty::HARD.id(),
+            spawn_x: -20,
+            spawn_y: 70,
+            time: 123_456,
+            day_time: 6000,
+            raining: true,
+            rain_time: 500,
+            game_rules: [("doDaylightCycle".to_string(), "false".to_string())].into(),
+            world_gen_settings: WorldGenSettings::new(-42, true, WorlPreset::FLAT, ""),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_save_and_load() -> Result<(), LevelError> {
+        let dir = TempDir::new()?;
+        assert!(LevelData::load(dir.path())?.is_none());
+
+        let mut level = level();
+        level.save(dir.path())?;
+        assert!(!dir.path().join(BACKUP_FILE_NAME).exists());
+        let mut loaded = LevelData::load(dir.path())?.unwrap();
+        assert_eq!(loaded.data_version, DATA_VERSION);
+        assert_eq!(loaded.gamemode(), Gamemode::CREATIVE);
+        assert_eq!(loaded.world_gen_settings.seed, -42);
+        assert_eq!(
+            loaded
+                .world_gen_settings
+                .generator("minecraft:overworld")
+                .unwrap()["type"],
+            Tag::from("minecraft:flat")
+        );
+        // The other entries are set from the file.
+        loaded.other.clear();
+        assert_eq!(loaded, level);
+
+        // The second save keeps the first as the backup.
+        level.time += 20;
+        level.save(dir.path())?;
+        assert_eq!(LevelData::load(dir.path())?.unwrap().time, 123_476);
+        assert_eq!(
+            LevelData::read(&dir.path().join(BACKUP_FILE_NAME))?.time,
+            123_456
+        );
+
+        // A broken level.dat falls back to the backup.
+        fs::write(dir.path().join(FILE_NAME), b"broken")?;
+        assert_eq!(LevelData::load(dir.path())?.unwrap().time, 123_456);
+        Ok(())
+    }
+
+    #[test]
+    fn test_unknown_entries_kept() -> Result<(), LevelError> {
+        let dir = TempDir::new()?;
+        let data = snbt::parse(
+            r#"{LevelName:"old",Time:5L,DataVersion:3465,
+                DragonFight:{DragonKilled:1b,Gateways:[I;1,2]},
+                WorldGenSettings:{seed:7L,dimensions:{}}}"#,
+        )?;
+        let root = Compound::from([("Data".to_string(), data)]);
+        fs::write(dir.path().join(FILE_NAME), binary::write_file("", &root)?)?;
+
+        let mut level = LevelData::load(dir.path())?.unwrap();
+        assert_eq!((level.level_name.as_str(), level.time), ("old", 5));
+        assert_eq!(level.world_gen_settings.seed, 7);
+        level.tick();
+        level.save(dir.path())?;
+
+        let (_, root) = binary::read_file(&fs::read(dir.path().join(FILE_NAME))?)?;
+        let data = root["Data"].as_compound().unwrap();
+        assert_eq!(data["Time"], Tag::Long(6));
+        assert_eq!(data["DayTime"], Tag::Long(1));
+        assert_eq!(data["DataVersion"], Tag::Int(DATA_VERSION));
+        assert_eq!(
+            data["DragonFight"],
+            snbt::parse("{DragonKilled:1b,Gateways:[I;1,2]}")?
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_presets() {
+        let settings = WorldGenSettings::new(1, true, WorlPreset::AMPLIFIED, "");
+        assert_eq!(settings.dimensions.len(), 3);
+        let overworld = settings.generator("minecraft:overworld").unwrap();
+        assert_eq!(overworld["settings"], Tag::from("minecraft:amplified"));
+        let end = settings.generator("minecraft:the_end").unwrap();
+        assert_eq!(
+            end["biome_source"].as_compound().unwrap()["type"],
+            Tag::from("minecraft:the_end")
+        );
+    }
+}
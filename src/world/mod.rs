@@ -0,0 +1,152 @@
+//! The worlds: their storage on disk and their chunks.
+
+pub mod biome;
+pub mod block;
+pub mod chunk;
+pub mod dimension;
+pub mod generator;
+pub mod item;
+pub mod level;
+pub mod paths;
+pub mod region;
+
+use std::sync::atomic::{AtomicI32, Ordering};
+use std::sync::{Arc, Mutex};
+
+use log::{info, warn};
+use once_cell::sync::{Lazy, OnceCell};
+
+use crate::config::Settings;
+use dimension::{Dimension, DimensionType, Location, DIMENSION_TYPES};
+use generator::pool::WorkerPool;
+use generator::ChunkGenerator;
+use level::{LevelData, LevelError};
+use paths::{LevelNameError, WorldPaths};
+use region::RegionStorage;
+
+/// The paths of the world, set at startup from 'level-name'.
+static PATHS: OnceCell<WorldPaths> = OnceCell::new();
+
+/// The metadata of the world, once it is loaded.
+pub static LEVEL: Lazy<Mutex<Option<LevelData>>> = Lazy::new(Mutex::default);
+
+/// The threads generating the chunks of every dimension.
+static WORKERS: Lazy<WorkerPool> = Lazy::new(|| WorkerPool::new(WorkerPool::default_threads()));
+
+/// The loaded dimensions, the Nether only if 'allow-nether' is on.
+static DIMENSIONS: OnceCell<Vec<Dimension>> = OnceCell::new();
+
+/// Held while the world is saved.
+static SAVING: Mutex<()> = Mutex::new(());
+
+/// The ID of the next entity, unique while the server runs.
+static NEXT_ENTITY_ID: AtomicI32 = AtomicI32::new(1);
+
+/// Sets the paths of the world from 'level-name', which is rejected if it leaves the server
+/// directory. It is read once, a change of 'level-name' needs a restart.
+pub fn init_paths(config: &Settings) -> Result<&'static WorldPaths, LevelNameError> {
+    let paths = WorldPaths::from_settings(config)?;
+    Ok(PATHS.get_or_init(|| paths))
+}
+
+/// Returns the paths of the world.
+///
+/// # Panics
+/// If they are not set yet with `init_paths`.
+pub fn paths() -> &'static WorldPaths {
+    PATHS.get().expect("The world paths are set at startup")
+}
+
+/// Loads the world's 'level.dat', or creates the world from the properties if there is none.
+pub fn load(config: &Settings) -> Result<(), LevelError> {
+    let dir = paths().root();
+    let level = match LevelData::load(dir)? {
+        Some(level) => level,
+        None => {
+            info!("No existing world data, creating new world");
+            let mut level = LevelData::new(config);
+            level.save(dir)?;
+            level
+        }
+    };
+    info!("Preparing level \"{}\"", level.level_name);
+    let mut dimensions = Vec::new();
+    for kind in DIMENSION_TYPES {
+        if kind.id == dimension::THE_NETHER.id && !config.allow_nether {
+            info!("The Nether is disabled by 'allow-nether'");
+            continue;
+        }
+        let regions = RegionStorage::new(&paths().region(kind.id), config.region_file_compression);
+        dimensions.push(Dimension::new(kind, chunk_generator(&level, kind), regions));
+    }
+    let _ = DIMENSIONS.set(dimensions);
+    *LEVEL.lock().unwrap() = Some(level);
+    Ok(())
+}
+
+/// The generator of a dimension from 'level.dat', None if it is not supported.
+fn chunk_generator(level: &LevelData, kind: &DimensionType) -> Option<Arc<dyn ChunkGenerator>> {
+    let Some(settings) = level.world_gen_settings.generator(kind.id) else {
+        warn!("The world has no generator for {}", kind.id);
+        return None;
+    };
+    match generator::from_nbt(settings, kind.height, level.world_gen_settings.seed) {
+        Ok(generator) => Some(Arc::from(generator)),
+        Err(e) => {
+            warn!("The new chunks of {} can't be generated: {e}", kind.id);
+            None
+        }
+    }
+}
+
+/// A loaded dimension, given its ID like "minecraft:the_nether". The Nether is not loaded if
+/// 'allow-nether' is off.
+pub fn dimension(id: &str) -> Option<&'static Dimension> {
+    DIMENSIONS
+        .get()?
+        .iter()
+        .find(|dimension| dimension.id() == id)
+}
+
+/// Returns an ID for a new entity, like a player who joins.
+pub fn next_entity_id() -> i32 {
+    NEXT_ENTITY_ID.fetch_add(1, Ordering::Relaxed)
+}
+
+/// Where the players spawn, in the overworld, at the middle of the spawn block.
+pub fn spawn() -> Location {
+    let (x, y, z) = match LEVEL.lock().unwrap().as_ref() {
+        Some(level) => (level.spawn_x, level.spawn_y, level.spawn_z),
+        None => (0, 64, 0),
+    };
+    Location {
+        dimension: dimension::OVERWORLD.id,
+        x: x as f64 + 0.5,
+        y: y as f64,
+        z: z as f64 + 0.5,
+    }
+}
+
+/// Saves the world's 'level.dat' and the chunks the players see, if the world is loaded.
+/// It may run off the main loop, and one save waits for the other.
+pub fn save() -> Result<(), LevelError> {
+    let _saving = SAVING.lock().unwrap();
+    for dimension in DIMENSIONS.get().into_iter().flatten() {
+        dimension.save_watched()?;
+    }
+    let level = LEVEL.lock().unwrap().clone();
+    match level {
+        Some(mut level) => level.save(paths().root()),
+        None => Ok(()),
+    }
+}
+
+/// Advances the world by one tick.
+pub fn tick() {
+    if let Some(level) = LEVEL.lock().unwrap().as_mut() {
+        level.tick();
+    }
+    for dimension in DIMENSIONS.get().into_iter().flatten() {
+        dimension.tick();
+    }
+}
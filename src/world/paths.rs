@@ -0,0 +1,203 @@
+//! The paths of a world's files, in the directory named by 'level-name'. Like vanilla, the
+//! overworld is at the root of the world, the Nether in 'DIM-1', the End in 'DIM1' and the other
+//! dimensions in 'dimensions/<namespace>/<path>'. Each dimension has its 'region', 'entities',
+//! 'poi' and 'data' folders, and the players are in the 'playerdata' folder of the world.
+
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use thiserror::Error;
+
+use crate::config::Settings;
+use crate::consts::minecraft::DIMENSIONS;
+
+/// The world directory when 'level-name' is empty.
+pub const DEFAULT_LEVEL_NAME: &str = "world";
+
+#[derive(Error, Debug, PartialEq)]
+pub enum LevelNameError {
+    #[error("The level name '{0}' must be a relative path")]
+    NotRelative(String),
+    #[error("The level name '{0}' must not leave the server directory")]
+    Traversal(String),
+    #[error("The level name '{0}' contains an invalid character")]
+    InvalidCharacter(String),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct WorldPaths {
+    root: PathBuf,
+}
+
+impl WorldPaths {
+    /// The paths of the world in the directory `level_name`, relative to the server directory.
+    /// It may be in subdirectories, but it must stay inside the server directory.
+    pub fn new(level_name: &str) -> Result<Self, LevelNameError> {
+        let error = |f: fn(String) -> LevelNameError| Err(f(level_name.to_string()));
+
+        // The backslashes and the colons are separators and drives on Windows.
+        if level_name
+            .chars()
+            .any(|c| c.is_control() || matches!(c, '\\' | ':'))
+        {
+            return error(LevelNameError::InvalidCharacter);
+        }
+        let path = Path::new(level_name);
+        for component in path.components() {
+            match component {
+                Component::Normal(_) | Component::CurDir => {}
+                Component::ParentDir => return error(LevelNameError::Traversal),
+                Component::RootDir | Component::Prefix(_) => {
+                    return error(LevelNameError::NotRelative)
+                }
+            }
+        }
+        let root: PathBuf = path
+            .components()
+            .filter(|component| *component != Component::CurDir)
+            .collect();
+        Ok(WorldPaths {
+            // Only made of '.' components.
+            root: match root.as_os_str().is_empty() {
+                true => PathBuf::from(DEFAULT_LEVEL_NAME),
+                false => root,
+            },
+        })
+    }
+
+    /// The paths of the world of the properties.
+    pub fn from_settings(config: &Settings) -> Result<Self, LevelNameError> {
+        Self::new(config.level_name.as_deref().unwrap_or(DEFAULT_LEVEL_NAME))
+    }
+
+    /// The world directory.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// The directory of a dimension, given its ID like "minecraft:the_nether".
+    pub fn dimension(&self, dimension: &str) -> PathBuf {
+        match dimension {
+            "minecraft:overworld" => self.root.clone(),
+            "minecraft:the_nether" => self.root.join("DIM-1"),
+            "minecraft:the_end" => self.root.join("DIM1"),
+            _ => {
+                let (namespace, path) = dimension
+                    .split_once(':')
+                    .unwrap_or(("minecraft", dimension));
+                self.root.join("dimensions").join(namespace).join(path)
+            }
+        }
+    }
+
+    /// The region files of the chunks of a dimension.
+    pub fn region(&self, dimension: &str) -> PathBuf {
+        self.dimension(dimension).join("region")
+    }
+
+    /// The region files of the entities of a dimension.
+    pub fn entities(&self, dimension: &str) -> PathBuf {
+        self.dimension(dimension).join("entities")
+    }
+
+    /// The region files of the points of interest of a dimension, like beds and workstations.
+    pub fn poi(&self, dimension: &str) -> PathBuf {
+        self.dimension(dimension).join("poi")
+    }
+
+    /// The saved data of a dimension, like the maps and the raids.
+    pub fn data(&self, dimension: &str) -> PathBuf {
+        self.dimension(dimension).join("data")
+    }
+
+    /// The data of the players, one '<uuid>.dat' file each.
+    pub fn playerdata(&self) -> PathBuf {
+        self.root.join("playerdata")
+    }
+
+    /// Creates the directories of the world and of the vanilla dimensions, if they are missing.
+    pub fn create_dirs(&self) -> io::Result<()> {
+        fs::create_dir_all(self.playerdata())?;
+        for dimension in DIMENSIONS {
+            for dir in [
+                self.region(dimension),
+                self.entities(dimension),
+                self.poi(dimension),
+                self.data(dimension),
+            ] {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dimensions() {
+        let paths = WorldPaths::new("worlds/survival").unwrap();
+        assert_eq!(paths.root(), Path::new("worlds/survival"));
+        assert_eq!(
+            paths.region("minecraft:overworld"),
+            Path::new("worlds/survival/region")
+        );
+        assert_eq!(
+            paths.poi("minecraft:the_nether"),
+            Path::new("worlds/survival/DIM-1/poi")
+        );
+        assert_eq!(
+            paths.entities("minecraft:the_end"),
+            Path::new("worlds/survival/DIM1/entities")
+        );
+        assert_eq!(
+            paths.data("copper:mining"),
+            Path::new("worlds/survival/dimensions/copper/mining/data")
+        );
+        assert_eq!(paths.playerdata(), Path::new("worlds/survival/playerdata"));
+        assert_eq!(
+            WorldPaths::new("./world/.").unwrap().root(),
+            Path::new("world")
+        );
+        assert_eq!(WorldPaths::new(".").unwrap().root(), Path::new("world"));
+    }
+
+    #[test]
+    fn test_invalid_names() {
+        for name in ["..", "../world", "world/../../other", "a/.."] {
+            assert_eq!(
+                WorldPaths::new(name),
+                Err(LevelNameError::Traversal(name.to_string()))
+            );
+        }
+        assert!(matches!(
+            WorldPaths::new("/etc"),
+            Err(LevelNameError::NotRelative(_))
+        ));
+        for name in ["..\\world", "C:world", "world\n"] {
+            assert!(matches!(
+                WorldPaths::new(name),
+                Err(LevelNameError::InvalidCharacter(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_create_dirs() -> io::Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let paths = WorldPaths {
+            root: dir.path().to_path_buf(),
+        };
+        paths.create_dirs()?;
+        // Twice, since the directories may already exist.
+        paths.create_dirs()?;
+        assert!(dir.path().join("DIM-1/region").is_dir());
+        assert!(dir.path().join("DIM1/poi").is_dir());
+        assert!(dir.path().join("entities").is_dir());
+        assert!(dir.path().join("playerdata").is_dir());
+        Ok(())
+    }
+}
@@ -0,0 +1,139 @@
+//! The LZ4 block streams of lz4-java, which vanilla writes with 'region-file-compression=lz4'.
+//! The data is split in blocks of at most 64 KiB, each with a 21-byte header: the magic
+//! "LZ4Block", a token with the method and the block size, the compressed and original lengths
+//! and a checksum, little-endian. An empty block ends the stream.
+
+use std::hash::Hasher;
+
+use twox_hash::XxHash32;
+
+use super::RegionError;
+
+const MAGIC: &[u8; 8] = b"LZ4Block";
+const HEADER_SIZE: usize = 21;
+const METHOD_RAW: u8 = 0x10;
+const METHOD_LZ4: u8 = 0x20;
+/// The blocks hold at most `1 << (level + 10)` bytes, 64 KiB at the level used by vanilla.
+const COMPRESSION_LEVEL: u8 = 6;
+const BLOCK_SIZE: usize = 1 << (COMPRESSION_LEVEL + 10);
+const CHECKSUM_SEED: u32 = 0x9747_B28C;
+
+/// The XXH32 of the original data, truncated to 28 bits like lz4-java.
+fn checksum(data: &[u8]) -> u32 {
+    let mut hasher = XxHash32::with_seed(CHECKSUM_SEED);
+    hasher.write(data);
+    hasher.finish() as u32 & 0x0FFF_FFFF
+}
+
+fn write_header(out: &mut Vec<u8>, method: u8, compressed: usize, original: usize, check: u32) {
+    out.extend_from_slice(MAGIC);
+    out.push(method | COMPRESSION_LEVEL);
+    out.extend_from_slice(&(compressed as u32).to_le_bytes());
+    out.extend_from_slice(&(original as u32).to_le_bytes());
+    out.extend_from_slice(&check.to_le_bytes());
+}
+
+pub fn compress(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() / 2 + HEADER_SIZE * 2);
+    for block in data.chunks(BLOCK_SIZE) {
+        let compressed = lz4_flex::block::compress(block);
+        // Like lz4-java, the blocks that do not shrink are stored as they are.
+        let (method, payload) = if compressed.len() < block.len() {
+            (METHOD_LZ4, &compressed[..])
+        } else {
+            (METHOD_RAW, block)
+        };
+        write_header(
+            &mut out,
+            method,
+            payload.len(),
+            block.len(),
+            checksum(block),
+        );
+        out.extend_from_slice(payload);
+    }
+    write_header(&mut out, METHOD_RAW, 0, 0, 0);
+    out
+}
+
+pub fn decompress(mut data: &[u8]) -> Result<Vec<u8>, RegionError> {
+    let error = |message: &str| RegionError::Lz4(message.to_string());
+    let mut out = Vec::new();
+
+    loop {
+        let header = data
+            .get(..HEADER_SIZE)
+            .ok_or_else(|| error("truncated block header"))?;
+        if &header[..8] != MAGIC {
+            return Err(error("invalid block magic"));
+        }
+        let method = header[8] & 0xF0;
+        let max_size = 1usize << ((header[8] & 0x0F) + 10);
+        let int = |i: usize| u32::from_le_bytes(header[i..i + 4].try_into().unwrap()) as usize;
+        let (compressed, original, check) = (int(9), int(13), int(17) as u32);
+
+        if original == 0 {
+            // The end of the stream.
+            return match compressed {
+                0 => Ok(out),
+                _ => Err(error("invalid empty block")),
+            };
+        }
+        let payload = data
+            .get(HEADER_SIZE..HEADER_SIZE + compressed)
+            .ok_or_else(|| error("truncated block"))?;
+        if original > max_size {
+            return Err(error("block larger than its size"));
+        }
+
+        let start = out.len();
+        match method {
+            METHOD_RAW if compressed == original => out.extend_from_slice(payload),
+            METHOD_LZ4 => {
+                out.resize(start + original, 0);
+                let len = lz4_flex::block::decompress_into(payload, &mut out[start..])
+                    .map_err(|e| RegionError::Lz4(e.to_string()))?;
+                if len != original {
+                    return Err(error("block shorter than its length"));
+                }
+            }
+            _ => return Err(error("invalid block method")),
+        }
+        if checksum(&out[start..]) != check {
+            return Err(error("block checksum mismatch"));
+        }
+
+        data = &data[HEADER_SIZE + compressed..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn test_round_trip() {
+        let mut rng = rand::thread_rng();
+        // Compressible data over several blocks, and random data stored raw.
+        let repeated: Vec<u8> = (0..200_000).map(|i| (i % 7) as u8).collect();
+        let random: Vec<u8> = (0..1000).map(|_| rng.gen()).collect();
+
+        for data in [&repeated[..], &random[..], &[]] {
+            let compressed = compress(data);
+            assert_eq!(decompress(&compressed).unwrap(), data);
+        }
+        assert!(compress(&repeated).len() < repeated.len() / 10);
+        assert_eq!(compress(&random)[8], METHOD_RAW | COMPRESSION_LEVEL);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut data = compress(b"some chunk data, some chunk data");
+        assert!(decompress(&data[..data.len() - 1]).is_err());
+        // The checksum.
+        data[17] ^= 1;
+        assert!(matches!(decompress(&data), Err(RegionError::Lz4(_))));
+        assert!(decompress(b"LZ4Blocc").is_err());
+    }
+}
@@ -0,0 +1,626 @@
+//! Anvil region files, 'r.<x>.<z>.mca', holding 32×32 chunks each.
+//! The file starts with two 4 KiB tables: the location of each chunk, as its first sector and
+//! its number of sectors, and the time it was last saved. A chunk is stored as its length, its
+//! compression and its compressed NBT, padded to whole 4 KiB sectors. The chunks needing more
+//! than 255 sectors are stored in 'c.<x>.<z>.mcc' files next to the region instead.
+//!
+//! See https://minecraft.wiki/w/Region_file_format
+
+mod lz4;
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use flate2::read::{GzDecoder, ZlibDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use log::warn;
+use thiserror::Error;
+
+use crate::nbt::{binary, Compound, NbtError};
+
+pub const SECTOR_SIZE: usize = 4096;
+/// The location and the timestamp tables.
+const HEADER_SECTORS: usize = 2;
+const CHUNKS: usize = 32 * 32;
+/// The most sectors a chunk can take in the region file, the larger ones are external.
+const MAX_SECTORS: usize = 255;
+/// Set on the compression of the chunks stored in '.mcc' files.
+const EXTERNAL_FLAG: u8 = 0x80;
+/// The length and the compression of a chunk.
+const CHUNK_HEADER_SIZE: usize = 5;
+/// The most region files kept open by a `RegionStorage`, like vanilla.
+const MAX_OPEN_REGIONS: usize = 256;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Compression {
+    Gzip,
+    Zlib,
+    None,
+    Lz4,
+}
+
+impl Compression {
+    /// The ID of the compression in the region files.
+    pub fn id(self) -> u8 {
+        match self {
+            Compression::Gzip => 1,
+            Compression::Zlib => 2,
+            Compression::None => 3,
+            Compression::Lz4 => 4,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        match id {
+            1 => Some(Compression::Gzip),
+            2 => Some(Compression::Zlib),
+            3 => Some(Compression::None),
+            4 => Some(Compression::Lz4),
+            _ => None,
+        }
+    }
+
+    /// Parses 'region-file-compression' in 'server.properties': deflate, lz4 or none.
+    pub fn from_property(value: &str) -> Option<Self> {
+        match value {
+            "deflate" => Some(Compression::Zlib),
+            "lz4" => Some(Compression::Lz4),
+            "none" => Some(Compression::None),
+            _ => None,
+        }
+    }
+
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        Ok(match self {
+            Compression::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            Compression::Zlib => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data)?;
+                encoder.finish()?
+            }
+            Compression::None => data.to_vec(),
+            Compression::Lz4 => lz4::compress(data),
+        })
+    }
+
+    fn decompress(self, data: &[u8]) -> Result<Vec<u8>, RegionError> {
+        let mut result = Vec::new();
+        match self {
+            Compression::Gzip => {
+                GzDecoder::new(data).read_to_end(&mut result)?;
+            }
+            Compression::Zlib => {
+                ZlibDecoder::new(data).read_to_end(&mut result)?;
+            }
+            Compression::None => result.extend_from_slice(data),
+            Compression::Lz4 => result = lz4::decompress(data)?,
+        }
+        Ok(result)
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum RegionError {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Invalid chunk NBT: {0}")]
+    Nbt(#[from] NbtError),
+    #[error("Unknown chunk compression {0}")]
+    UnknownCompression(u8),
+    #[error("Invalid chunk length {0}")]
+    InvalidLength(i32),
+    #[error("Invalid LZ4 chunk data: {0}")]
+    Lz4(String),
+}
+
+/// The name of the region file holding a chunk.
+pub fn file_name(chunk_x: i32, chunk_z: i32) -> String {
+    format!("r.{}.{}.mca", chunk_x >> 5, chunk_z >> 5)
+}
+
+/// The index of a chunk in the tables of its region.
+fn index(chunk_x: i32, chunk_z: i32) -> usize {
+    ((chunk_x & 31) + (chunk_z & 31) * 32) as usize
+}
+
+/// The seconds since the Unix epoch, as stored in the timestamp table.
+pub fn timestamp() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32)
+}
+
+/// A region file. The chunks are given by their coordinates in the world, the region only uses
+/// their lowest 5 bits.
+pub struct RegionFile {
+    path: PathBuf,
+    file: File,
+    /// The location of each chunk: its first sector << 8 | its number of sectors, or 0 if it
+    /// is not saved.
+    locations: [u32; CHUNKS],
+    timestamps: [u32; CHUNKS],
+    /// Whether each sector of the file is taken.
+    used: Vec<bool>,
+}
+
+impl RegionFile {
+    /// Opens a region file, creating it if it does not exist.
+    pub fn open(path: &Path) -> Result<Self, RegionError> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)?;
+
+        let header_size = (HEADER_SECTORS * SECTOR_SIZE) as u64;
+        if file.metadata()?.len() < header_size {
+            // New, or truncated: the missing entries are absent chunks.
+            file.set_len(header_size)?;
+        }
+        let mut header = vec![0; HEADER_SECTORS * SECTOR_SIZE];
+        file.seek(SeekFrom::Start(0))?;
+        file.read_exact(&mut header)?;
+
+        let sectors = file.metadata()?.len().div_ceil(SECTOR_SIZE as u64) as usize;
+        let mut region = Self {
+            path: path.to_path_buf(),
+            file,
+            locations: [0; CHUNKS],
+            timestamps: [0; CHUNKS],
+            used: vec![false; sectors],
+        };
+        region.used[..HEADER_SECTORS].fill(true);
+
+        let entry = |table: usize, i: usize| {
+            let start = table * SECTOR_SIZE + i * 4;
+            u32::from_be_bytes(header[start..start + 4].try_into().unwrap())
+        };
+        for i in 0..CHUNKS {
+            let location = entry(0, i);
+            let (offset, count) = split_location(location);
+            if location == 0 {
+                continue;
+            }
+            if offset < HEADER_SECTORS || count == 0 || offset + count > sectors {
+                warn!(
+                    "Ignoring the chunk {i} of '{}': its sectors are out of the file",
+                    path.display()
+                );
+                continue;
+            }
+            region.locations[i] = location;
+            region.timestamps[i] = entry(1, i);
+            region.used[offset..offset + count].fill(true);
+        }
+
+        Ok(region)
+    }
+
+    #[cfg(test)]
+    pub fn has_chunk(&self, chunk_x: i32, chunk_z: i32) -> bool {
+        self.locations[index(chunk_x, chunk_z)] != 0
+    }
+
+    /// When the chunk was last saved, in seconds since the Unix epoch, or 0 if it is not saved.
+    #[cfg(test)]
+    pub fn timestamp(&self, chunk_x: i32, chunk_z: i32) -> u32 {
+        self.timestamps[index(chunk_x, chunk_z)]
+    }
+
+    /// Reads the NBT of a chunk, None if it is not saved.
+    pub fn read_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<Compound>, RegionError> {
+        let Some(data) = self.read_chunk_data(chunk_x, chunk_z)? else {
+            return Ok(None);
+        };
+        let (_, compound, _) = binary::read_named(&data)?;
+        Ok(Some(compound))
+    }
+
+    /// Reads the decompressed NBT data of a chunk, None if it is not saved.
+    pub fn read_chunk_data(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<Vec<u8>>, RegionError> {
+        let (offset, count) = split_location(self.locations[index(chunk_x, chunk_z)]);
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let mut sectors = vec![0; count * SECTOR_SIZE];
+        self.file
+            .seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.read_exact(&mut sectors)?;
+
+        // The length counts the compression byte.
+        let length = i32::from_be_bytes(sectors[..4].try_into().unwrap());
+        if length < 1 || length as usize > sectors.len() - 4 {
+            return Err(RegionError::InvalidLength(length));
+        }
+        let kind = sectors[4];
+        let compression = Compression::from_id(kind & !EXTERNAL_FLAG)
+            .ok_or(RegionError::UnknownCompression(kind & !EXTERNAL_FLAG))?;
+
+        let data = if kind & EXTERNAL_FLAG != 0 {
+            compression.decompress(&fs::read(self.external_path(chunk_x, chunk_z))?)?
+        } else {
+            compression.decompress(&sectors[CHUNK_HEADER_SIZE..4 + length as usize])?
+        };
+        Ok(Some(data))
+    }
+
+    /// Writes a chunk, in new sectors so that a crash while writing leaves the previous version
+    /// in place. Its previous sectors are freed for the next chunks.
+    pub fn write_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk: &Compound,
+        compression: Compression,
+        timestamp: u32,
+    ) -> Result<(), RegionError> {
+        let data = compression.compress(&binary::write_named("", chunk)?)?;
+        let external_path = self.external_path(chunk_x, chunk_z);
+        let external = CHUNK_HEADER_SIZE + data.len() > MAX_SECTORS * SECTOR_SIZE;
+
+        let mut sectors = Vec::with_capacity(CHUNK_HEADER_SIZE + data.len());
+        if external {
+            let temporary = external_path.with_extension("mcc.tmp");
+            fs::write(&temporary, &data)?;
+            fs::rename(&temporary, &external_path)?;
+            sectors.extend_from_slice(&1i32.to_be_bytes());
+            sectors.push(compression.id() | EXTERNAL_FLAG);
+        } else {
+            sectors.extend_from_slice(&(data.len() as i32 + 1).to_be_bytes());
+            sectors.push(compression.id());
+            sectors.extend_from_slice(&data);
+        }
+        sectors.resize(sectors.len().next_multiple_of(SECTOR_SIZE), 0);
+
+        let count = sectors.len() / SECTOR_SIZE;
+        let offset = self.allocate(count);
+        self.file
+            .seek(SeekFrom::Start((offset * SECTOR_SIZE) as u64))?;
+        self.file.write_all(&sectors)?;
+
+        let i = index(chunk_x, chunk_z);
+        let previous = self.locations[i];
+        self.locations[i] = ((offset as u32) << 8) | count as u32;
+        self.timestamps[i] = timestamp;
+        self.write_header_entry(i)?;
+
+        let (previous_offset, previous_count) = split_location(previous);
+        self.used[previous_offset..previous_offset + previous_count].fill(false);
+        if !external {
+            match fs::remove_file(&external_path) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e.into()),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes the location and the timestamp of a chunk.
+    fn write_header_entry(&mut self, i: usize) -> io::Result<()> {
+        self.file.seek(SeekFrom::Start((i * 4) as u64))?;
+        self.file.write_all(&self.locations[i].to_be_bytes())?;
+        self.file
+            .seek(SeekFrom::Start((SECTOR_SIZE + i * 4) as u64))?;
+        self.file.write_all(&self.timestamps[i].to_be_bytes())
+    }
+
+    /// Takes the first `count` free sectors in a row, at the end of the file if there are none.
+    fn allocate(&mut self, count: usize) -> usize {
+        let mut free = 0;
+        for sector in 0..self.used.len() {
+            if self.used[sector] {
+                free = 0;
+                continue;
+            }
+            free += 1;
+            if free == count {
+                let start = sector + 1 - count;
+                self.used[start..=sector].fill(true);
+                return start;
+            }
+        }
+
+        // The free sectors at the end of the file are reused too.
+        let start = self.used.len() - free;
+        self.used.resize(start + count, true);
+        self.used[start..].fill(true);
+        start
+    }
+
+    /// The file of the chunk if it is too large for the region, named after its coordinates in
+    /// the world.
+    fn external_path(&self, chunk_x: i32, chunk_z: i32) -> PathBuf {
+        self.path
+            .with_file_name(format!("c.{chunk_x}.{chunk_z}.mcc"))
+    }
+}
+
+/// Returns the first sector and the number of sectors of a location.
+fn split_location(location: u32) -> (usize, usize) {
+    ((location >> 8) as usize, (location & 0xFF) as usize)
+}
+
+/// The region files of a dimension, opened when their chunks are needed.
+pub struct RegionStorage {
+    dir: PathBuf,
+    compression: Compression,
+    regions: HashMap<(i32, i32), RegionFile>,
+}
+
+impl RegionStorage {
+    /// `compression` is used to write the chunks, they are read in any compression.
+    pub fn new(dir: &Path, compression: Compression) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            compression,
+            regions: HashMap::new(),
+        }
+    }
+
+    /// Reads a chunk, None if it was never saved.
+    pub fn read_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+    ) -> Result<Option<Compound>, RegionError> {
+        match self.region(chunk_x, chunk_z, false)? {
+            Some(region) => region.read_chunk(chunk_x, chunk_z),
+            None => Ok(None),
+        }
+    }
+
+    pub fn write_chunk(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        chunk: &Compound,
+    ) -> Result<(), RegionError> {
+        let compression = self.compression;
+        let region = self
+            .region(chunk_x, chunk_z, true)?
+            .expect("The region is created");
+        region.write_chunk(chunk_x, chunk_z, chunk, compression, timestamp())
+    }
+
+    /// Returns the region of a chunk, opening it if needed. It is only created if `create` is
+    /// true, and None otherwise.
+    fn region(
+        &mut self,
+        chunk_x: i32,
+        chunk_z: i32,
+        create: bool,
+    ) -> Result<Option<&mut RegionFile>, RegionError> {
+        let key = (chunk_x >> 5, chunk_z >> 5);
+        if !self.regions.contains_key(&key) {
+            let path = self.dir.join(file_name(chunk_x, chunk_z));
+            if !create && !path.exists() {
+                return Ok(None);
+            }
+            fs::create_dir_all(&self.dir)?;
+            if self.regions.len() >= MAX_OPEN_REGIONS {
+                let closed = *self.regions.keys().next().unwrap();
+                self.regions.remove(&closed);
+            }
+            self.regions.insert(key, RegionFile::open(&path)?);
+        }
+        Ok(self.regions.get_mut(&key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::nbt::Tag;
+    use rand::Rng;
+    use tempfile::TempDir;
+
+    /// Copies the fixture regions, built to the vanilla layout, to a temporary directory.
+    fn fixtures() -> TempDir {
+        let dir = TempDir::new().unwrap();
+        let fixtures = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/region");
+        for entry in fs::read_dir(fixtures).unwrap() {
+            let path = entry.unwrap().path();
+            if matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("mca" | "mcc")
+            ) {
+                fs::copy(&path, dir.path().join(path.file_name().unwrap())).unwrap();
+            }
+        }
+        dir
+    }
+
+    fn chunk(x: i32, z: i32) -> Compound {
+        Compound::from([
+            ("DataVersion".to_string(), Tag::Int(3955)),
+            ("xPos".to_string(), Tag::Int(x)),
+            ("zPos".to_string(), Tag::Int(z)),
+            ("Status".to_string(), Tag::from("minecraft:full")),
+        ])
+    }
+
+    #[test]
+    fn test_read_fixture() {
+        let dir = fixtures();
+        let mut region = RegionFile::open(&dir.path().join("r.0.0.mca")).unwrap();
+
+        // Zlib, gzip, uncompressed, LZ4 and external.
+        for x in 0..5 {
+            let chunk = region.read_chunk(x, 0).unwrap().unwrap();
+            assert_eq!(chunk["xPos"], Tag::Int(x), "chunk {x}");
+            assert_eq!(chunk["zPos"], Tag::Int(0));
+            assert_eq!(chunk["Status"], Tag::from("minecraft:full"));
+            assert_eq!(region.timestamp(x, 0), 1_725_000_000 + x as u32);
+        }
+        assert_eq!(
+            region.read_chunk(4, 0).unwrap().unwrap()["Padding"],
+            Tag::from("x".repeat(100))
+        );
+        assert!(region.read_chunk(5, 0).unwrap().is_none());
+        assert!(!region.has_chunk(31, 31));
+    }
+
+    /// Reads a region generated by a vanilla 1.21.1 server, see `fixtures/region/vanilla`.
+    #[test]
+    #[ignore = "needs fixtures/region/vanilla/r.0.0.mca from a vanilla 1.21.1 server"]
+    fn test_read_vanilla_region() {
+        use crate::world::block;
+        use crate::world::chunk::{Chunk, ChunkHeight, VanillaRegistry};
+
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/region/vanilla");
+        let dir = TempDir::new().unwrap();
+        fs::copy(path.join("r.0.0.mca"), dir.path().join("r.0.0.mca")).unwrap();
+        let mut region = RegionFile::open(&dir.path().join("r.0.0.mca")).unwrap();
+
+        let bedrock = block::block("minecraft:bedrock").unwrap().default_state;
+        let mut full = 0;
+        for (x, z) in (0..32).flat_map(|x| (0..32).map(move |z| (x, z))) {
+            let Some(nbt) = region.read_chunk(x, z).unwrap() else {
+                continue;
+            };
+            assert_eq!(nbt["DataVersion"], Tag::Int(3955), "chunk {x} {z}");
+            assert_eq!(nbt["yPos"], Tag::Int(-4));
+            assert!(region.timestamp(x, z) > 0);
+            if nbt["Status"] != Tag::from("minecraft:full") {
+                continue;
+            }
+            full += 1;
+            let chunk = Chunk::from_nbt(nbt, ChunkHeight::OVERWORLD, &VanillaRegistry).unwrap();
+            assert_eq!((chunk.x, chunk.z), (x, z));
+            // The overworld is generated with a floor of bedrock, and never reaches the top.
+            for (bx, bz) in (0..16).flat_map(|bx| (0..16).map(move |bz| (bx, bz))) {
+                assert_eq!(chunk.block(bx, -64, bz), bedrock, "chunk {x} {z}");
+                assert!(block::is_air(chunk.block(bx, 319, bz)));
+            }
+        }
+        // The spawn chunks around 0 0 are always generated.
+        assert!(full >= 100, "only {full} full chunks");
+    }
+
+    #[test]
+    fn test_write_and_reuse() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("r.-1.0.mca");
+        let mut region = RegionFile::open(&path).unwrap();
+
+        for compression in [
+            Compression::Gzip,
+            Compression::Zlib,
+            Compression::None,
+            Compression::Lz4,
+        ] {
+            region
+                .write_chunk(-1, 0, &chunk(-1, 0), compression, 42)
+                .unwrap();
+            region
+                .write_chunk(-32, 31, &chunk(-32, 31), compression, 43)
+                .unwrap();
+            assert_eq!(region.read_chunk(-1, 0).unwrap(), Some(chunk(-1, 0)));
+        }
+
+        // Rewriting alternates between two places, so the file stops growing.
+        let len = fs::metadata(&path).unwrap().len();
+        assert_eq!(len % SECTOR_SIZE as u64, 0);
+        for _ in 0..10 {
+            region
+                .write_chunk(-1, 0, &chunk(-1, 0), Compression::Zlib, 44)
+                .unwrap();
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), len);
+
+        // Everything is read back after reopening.
+        let mut region = RegionFile::open(&path).unwrap();
+        assert_eq!(region.read_chunk(-1, 0).unwrap(), Some(chunk(-1, 0)));
+        assert_eq!(region.read_chunk(-32, 31).unwrap(), Some(chunk(-32, 31)));
+        assert_eq!(region.timestamp(-1, 0), 44);
+        assert_eq!(region.timestamp(-32, 31), 43);
+    }
+
+    #[test]
+    fn test_external_chunk() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut region = RegionFile::open(&path).unwrap();
+
+        // Random longs do not compress: 1.6 MB, more than 255 sectors.
+        let mut rng = rand::thread_rng();
+        let mut large = chunk(1, 2);
+        large.insert(
+            "Noise".to_string(),
+            Tag::LongArray((0..200_000).map(|_| rng.gen()).collect()),
+        );
+        region
+            .write_chunk(1, 2, &large, Compression::Zlib, 1)
+            .unwrap();
+        let external = dir.path().join("c.1.2.mcc");
+        assert!(external.exists());
+        assert_eq!(fs::metadata(&path).unwrap().len(), 3 * SECTOR_SIZE as u64);
+        assert_eq!(region.read_chunk(1, 2).unwrap(), Some(large));
+
+        // Smaller again, back in the region.
+        region
+            .write_chunk(1, 2, &chunk(1, 2), Compression::Zlib, 2)
+            .unwrap();
+        assert!(!external.exists());
+        assert_eq!(region.read_chunk(1, 2).unwrap(), Some(chunk(1, 2)));
+    }
+
+    #[test]
+    fn test_corrupted() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("r.0.0.mca");
+        let mut header = vec![0; HEADER_SECTORS * SECTOR_SIZE];
+        // A chunk past the end of the file, and one with an unknown compression.
+        header[..4].copy_from_slice(&((100 << 8) | 1u32).to_be_bytes());
+        header[4..8].copy_from_slice(&((2 << 8) | 1u32).to_be_bytes());
+        let mut sector = vec![0; SECTOR_SIZE];
+        sector[..5].copy_from_slice(&[0, 0, 0, 2, 9]);
+        header.extend_from_slice(&sector);
+        fs::write(&path, header).unwrap();
+
+        let mut region = RegionFile::open(&path).unwrap();
+        assert!(!region.has_chunk(0, 0));
+        assert!(matches!(
+            region.read_chunk(1, 0),
+            Err(RegionError::UnknownCompression(9))
+        ));
+    }
+
+    #[test]
+    fn test_storage() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = RegionStorage::new(&dir.path().join("region"), Compression::Lz4);
+
+        assert!(storage.read_chunk(100, -100).unwrap().is_none());
+        assert!(!dir.path().join("region").exists());
+
+        storage.write_chunk(100, -100, &chunk(100, -100)).unwrap();
+        assert!(dir.path().join("region/r.3.-4.mca").exists());
+        assert_eq!(
+            storage.read_chunk(100, -100).unwrap(),
+            Some(chunk(100, -100))
+        );
+        assert_eq!(
+            Compression::from_property("deflate"),
+            Some(Compression::Zlib)
+        );
+        assert_eq!(file_name(-1, 31), "r.-1.0.mca");
+    }
+}